@@ -1,10 +1,12 @@
 use heck::*;
 use std::collections::{BTreeSet, HashMap, HashSet};
 use std::mem;
+use witx_bindgen_gen_core::witx2::abi::program::Program;
 use witx_bindgen_gen_core::witx2::abi::{
-    Bindgen, Bitcast, Direction, Instruction, LiftLower, WasmType, WitxInstruction,
+    Bindgen, Bitcast, Direction, Instruction, LiftLower, MultiValue, StringEncoding, WasmType,
+    WitxInstruction,
 };
-use witx_bindgen_gen_core::{witx2::*, Files, Generator, Ns};
+use witx_bindgen_gen_core::{line_directives, witx2::*, Files, Generator, Ns, Origin};
 
 #[derive(Default)]
 pub struct C {
@@ -31,17 +33,35 @@ pub struct C {
     // at the end.
     types: HashMap<TypeId, witx_bindgen_gen_core::Source>,
 
+    // `#define`s for constants, and where they're defined, printed after all
+    // types.
+    consts: Vec<(Option<Origin>, String)>,
+
+    // Where the item currently being generated was defined, if source maps
+    // are enabled.
+    origin: Option<Origin>,
+
     needs_string: bool,
 }
 
 struct Func {
     src: Source,
+    origin: Option<Origin>,
 }
 
 #[derive(Default, Debug, Clone)]
 #[cfg_attr(feature = "structopt", derive(structopt::StructOpt))]
 pub struct Opts {
-    // ...
+    /// The maximum number of results returned natively with wasm multi-value,
+    /// or `disabled` to always use a return pointer for more than one result.
+    /// This must match the setting used by the other side, and the generated
+    /// code must be compiled with clang's multi-value ABI (`-mmultivalue
+    /// -Xclang -target-abi -Xclang experimental-mv`).
+    #[cfg_attr(
+        feature = "structopt",
+        structopt(long = "multi-value", default_value = "disabled")
+    )]
+    pub multi_value: MultiValue,
 }
 
 impl Opts {
@@ -104,6 +124,7 @@ impl C {
             func.name.to_snake_case()
         );
         self.names.insert(&name).expect("duplicate symbols");
+        self.docs(&func.docs);
         let start = self.src.header.len();
 
         let ret = self.classify_ret(iface, func);
@@ -159,6 +180,26 @@ impl C {
         }
     }
 
+    /// Returns the C return type of the raw wasm function `name`.
+    ///
+    /// Multiple results are returned as a struct, which clang's multi-value
+    /// ABI returns as one wasm value per field, so the struct is defined here
+    /// in the C file as `{name}_ret_t`.
+    fn wasm_results(&mut self, name: &str, results: &[WasmType]) -> String {
+        match results.len() {
+            0 => "void".to_string(),
+            1 => wasm_type(results[0]).to_string(),
+            _ => {
+                self.src.c("typedef struct {\n");
+                for (i, result) in results.iter().enumerate() {
+                    self.src.c(&format!("{} f{};\n", wasm_type(*result), i));
+                }
+                self.src.c(&format!("}} {}_ret_t;\n", name));
+                format!("{}_ret_t", name)
+            }
+        }
+    }
+
     fn is_arg_by_pointer(&self, iface: &Interface, ty: &Type) -> bool {
         match ty {
             Type::Id(id) => match &iface.types[*id].kind {
@@ -169,9 +210,11 @@ impl C {
                 | TypeDefKind::ConstPointer(_)
                 | TypeDefKind::Record(_)
                 | TypeDefKind::List(_)
+                | TypeDefKind::Array(..)
                 | TypeDefKind::PushBuffer(_)
                 | TypeDefKind::PullBuffer(_) => true,
             },
+            Type::String => true,
             _ => false,
         }
     }
@@ -224,6 +267,12 @@ impl C {
             Type::F32 => self.src.h("float"),
             Type::F64 => self.src.h("double"),
             Type::Usize => self.src.h("size_t"),
+            Type::Bool => self.src.h("bool"),
+            Type::String => {
+                self.print_namespace(iface);
+                self.src.h("string_t");
+                self.needs_string = true;
+            }
             Type::Handle(id) => {
                 self.print_namespace(iface);
                 self.src.h(&iface.resources[*id].name.to_snake_case());
@@ -239,10 +288,7 @@ impl C {
                 }
                 match &ty.kind {
                     TypeDefKind::Type(t) => self.print_ty(iface, t),
-                    TypeDefKind::Variant(v) => {
-                        if v.is_bool() {
-                            return self.src.h("bool");
-                        }
+                    TypeDefKind::Variant(_) => {
                         self.public_anonymous_types.insert(*id);
                         self.private_anonymous_types.remove(id);
                         self.print_namespace(iface);
@@ -258,13 +304,9 @@ impl C {
                         self.print_ty(iface, t);
                         self.src.h("*");
                     }
-                    TypeDefKind::List(Type::Char) => {
-                        self.print_namespace(iface);
-                        self.src.h("string_t");
-                        self.needs_string = true;
-                    }
                     TypeDefKind::Record(_)
                     | TypeDefKind::List(_)
+                    | TypeDefKind::Array(..)
                     | TypeDefKind::PushBuffer(_)
                     | TypeDefKind::PullBuffer(_) => {
                         self.public_anonymous_types.insert(*id);
@@ -293,6 +335,8 @@ impl C {
             Type::F32 => self.src.h("f32"),
             Type::F64 => self.src.h("f64"),
             Type::Usize => self.src.h("usize"),
+            Type::Bool => self.src.h("bool"),
+            Type::String => self.src.h("string"),
             Type::Handle(id) => self.src.h(&iface.resources[*id].name.to_snake_case()),
             Type::Id(id) => {
                 let ty = &iface.types[*id];
@@ -325,8 +369,6 @@ impl C {
                                 Some(t) => self.print_ty_name(iface, t),
                                 None => self.src.h("void"),
                             }
-                        } else if v.is_bool() {
-                            self.src.h("bool");
                         } else {
                             unimplemented!();
                         }
@@ -339,11 +381,14 @@ impl C {
                         self.src.h("const_ptr_ ");
                         self.print_ty_name(iface, t);
                     }
-                    TypeDefKind::List(Type::Char) => self.src.h("string"),
                     TypeDefKind::List(t) => {
                         self.src.h("list_");
                         self.print_ty_name(iface, t);
                     }
+                    TypeDefKind::Array(t, len) => {
+                        self.src.h(&format!("array{}_", len));
+                        self.print_ty_name(iface, t);
+                    }
                     TypeDefKind::PushBuffer(t) => {
                         self.src.h("push_buffer_");
                         self.print_ty_name(iface, t);
@@ -416,6 +461,12 @@ impl C {
                 self.src.h("size_t len;\n");
                 self.src.h("}");
             }
+            TypeDefKind::Array(t, len) => {
+                self.src.h("struct {\n");
+                self.print_ty(iface, t);
+                self.src.h(&format!(" data[{}];\n", len));
+                self.src.h("}");
+            }
             TypeDefKind::PushBuffer(t) | TypeDefKind::PullBuffer(t) => {
                 self.src.h("struct {\n");
                 self.src.h("int32_t is_handle;\n");
@@ -524,20 +575,23 @@ impl C {
                     self.free(iface, t, "&ptr->ptr[i]");
                     self.src.c("}\n");
                 }
-                let (size, align) = if *t == Type::Char {
-                    (1, 1)
-                } else {
-                    (self.sizes.size(t), self.sizes.align(t))
-                };
                 self.src.c(&format!(
                     "canonical_abi_free(ptr->ptr, ptr->len * {}, {});\n",
-                    size, align,
+                    self.sizes.size(t),
+                    self.sizes.align(t),
                 ));
             }
 
+            TypeDefKind::Array(t, len) => {
+                self.src
+                    .c(&format!("for (size_t i = 0; i < {}; i++) {{\n", len));
+                self.free(iface, t, "&ptr->data[i]");
+                self.src.c("}\n");
+            }
+
             TypeDefKind::Variant(v) => {
                 self.src.c("switch (ptr->tag) {\n");
-                for (i, case) in v.cases.iter().enumerate() {
+                for case in v.cases.iter() {
                     let case_ty = match &case.ty {
                         Some(ty) => ty,
                         None => continue,
@@ -545,7 +599,7 @@ impl C {
                     if !self.owns_anything(iface, case_ty) {
                         continue;
                     }
-                    self.src.c(&format!("case {}: {{\n", i));
+                    self.src.c(&format!("case {}: {{\n", case.discriminant));
                     let expr = if v.as_option().is_some() {
                         String::from("&ptr->val")
                     } else {
@@ -569,13 +623,14 @@ impl C {
     fn owns_anything(&self, iface: &Interface, ty: &Type) -> bool {
         let id = match ty {
             Type::Id(id) => *id,
-            Type::Handle(_) => return true,
+            Type::Handle(_) | Type::String => return true,
             _ => return false,
         };
         match &iface.types[id].kind {
             TypeDefKind::Type(t) => self.owns_anything(iface, t),
             TypeDefKind::Record(r) => r.fields.iter().any(|t| self.owns_anything(iface, &t.ty)),
             TypeDefKind::List(_) => true,
+            TypeDefKind::Array(t, _) => self.owns_anything(iface, t),
             TypeDefKind::PushBuffer(_) | TypeDefKind::PullBuffer(_) => false,
             TypeDefKind::Pointer(_) | TypeDefKind::ConstPointer(_) => false,
             TypeDefKind::Variant(v) => v
@@ -598,16 +653,59 @@ impl C {
         self.src.c(");\n");
     }
 
+    /// Returns whether `ty` is represented with a 64-bit integer.
+    fn is_64bit(&self, iface: &Interface, ty: &Type) -> bool {
+        match ty {
+            Type::U64 | Type::S64 => true,
+            Type::Id(id) => match &iface.types[*id].kind {
+                TypeDefKind::Type(t) => self.is_64bit(iface, t),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Prints `docs` as a Doxygen comment in the header.
     fn docs(&mut self, docs: &Docs) {
-        let docs = match &docs.contents {
-            Some(docs) => docs,
-            None => return,
-        };
-        for line in docs.trim().lines() {
-            self.src.h("// ");
-            self.src.h(line);
-            self.src.h("\n");
+        let mut paragraphs = Vec::new();
+        if let Some(description) = &docs.description {
+            paragraphs.push(description.clone());
+        }
+        let mut tags = Vec::new();
+        for (name, text) in docs.params.iter() {
+            tags.push(format!("@param {} {}", name.to_snake_case(), text));
+        }
+        if let Some(text) = &docs.returns {
+            tags.push(format!("@return {}", text));
+        }
+        if let Some(text) = &docs.errors {
+            tags.push(format!("@par Errors\n{}", text));
+        }
+        if let Some(text) = &docs.deprecated {
+            tags.push(format!("@deprecated {}", text));
+        }
+        if let Some(text) = &docs.since {
+            tags.push(format!("@since {}", text));
         }
+        if !tags.is_empty() {
+            paragraphs.push(tags.join("\n"));
+        }
+        if paragraphs.is_empty() {
+            return;
+        }
+        self.src.h("/**\n");
+        for line in paragraphs.join("\n\n").lines() {
+            // Docs can't end the comment early.
+            let line = line.trim_end().replace("*/", "* /");
+            if line.is_empty() {
+                self.src.h(" *\n");
+            } else {
+                self.src.h(" * ");
+                self.src.h(&line);
+                self.src.h("\n");
+            }
+        }
+        self.src.h(" */\n");
     }
 }
 
@@ -615,6 +713,11 @@ impl Return {
     fn return_single(&mut self, iface: &Interface, ty: &Type, orig_ty: &Type) {
         let id = match ty {
             Type::Id(id) => *id,
+            // strings, like lists, always go to return pointers
+            Type::String => {
+                self.retptrs.push(*orig_ty);
+                return;
+            }
             _ => {
                 self.scalar = Some(Scalar::Type(*orig_ty));
                 return;
@@ -631,17 +734,18 @@ impl Return {
             // record returns may become many return pointers with tuples
             TypeDefKind::Record(_) => self.splat_tuples(iface, ty, orig_ty),
 
-            // other records/lists/buffers always go to return pointers
-            TypeDefKind::List(_) | TypeDefKind::PushBuffer(_) | TypeDefKind::PullBuffer(_) => {
-                self.retptrs.push(*orig_ty)
-            }
+            // other records/lists/arrays/buffers always go to return pointers
+            TypeDefKind::List(_)
+            | TypeDefKind::Array(..)
+            | TypeDefKind::PushBuffer(_)
+            | TypeDefKind::PullBuffer(_) => self.retptrs.push(*orig_ty),
 
             // pointers are scalars
             TypeDefKind::Pointer(_) | TypeDefKind::ConstPointer(_) => {
                 self.scalar = Some(Scalar::Type(*orig_ty));
             }
 
-            // Enums are scalars (this includes bools)
+            // Enums are scalars
             TypeDefKind::Variant(v) if v.is_enum() => {
                 self.scalar = Some(Scalar::Type(*orig_ty));
             }
@@ -703,12 +807,22 @@ impl Return {
 }
 
 impl Generator for C {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            async_functions: false,
+            anonymous_records: false,
+            anonymous_variants: false,
+            ..Capabilities::default()
+        }
+    }
+
     fn preprocess_one(&mut self, iface: &Interface, dir: Direction) {
         self.sizes.fill(dir, iface);
         self.in_import = dir == Direction::Import;
 
-        for func in iface.functions.iter() {
-            let sig = iface.wasm_signature(dir, func);
+        let getters = iface.globals.iter().map(|g| g.getter()).collect::<Vec<_>>();
+        for func in iface.functions.iter().chain(&getters) {
+            let sig = iface.wasm_signature(dir, self.opts.multi_value, func);
             if let Some(results) = sig.retptr {
                 self.i64_return_pointer_area_size =
                     self.i64_return_pointer_area_size.max(results.len());
@@ -716,6 +830,10 @@ impl Generator for C {
         }
     }
 
+    fn origin(&mut self, origin: Option<Origin>) {
+        self.origin = origin;
+    }
+
     fn type_record(
         &mut self,
         iface: &Interface,
@@ -738,13 +856,16 @@ impl Generator for C {
             self.src.h(&name.to_snake_case());
             self.src.h("_t;\n");
 
-            for (i, field) in record.fields.iter().enumerate() {
+            for field in record.fields.iter() {
+                // Shifts past the sign bit of an `int` are undefined.
+                let one = if field.bit < 31 { "1" } else { "1ULL" };
                 self.src.h(&format!(
-                    "#define {}_{}_{} (1 << {})\n",
+                    "#define {}_{}_{} ({} << {})\n",
                     iface.name.to_shouty_snake_case(),
                     name.to_shouty_snake_case(),
                     field.name.to_shouty_snake_case(),
-                    i,
+                    one,
+                    field.bit,
                 ));
             }
         } else {
@@ -779,12 +900,7 @@ impl Generator for C {
         let prev = mem::take(&mut self.src.header);
         self.docs(docs);
         self.names.insert(&name.to_snake_case()).unwrap();
-        if variant.is_bool() {
-            self.src.h("typedef bool ");
-            self.print_namespace(iface);
-            self.src.h(&name.to_snake_case());
-            self.src.h("_t;\n");
-        } else if variant.is_enum() {
+        if variant.is_enum() {
             self.src.h("typedef ");
             self.src.h(int_repr(variant.tag));
             self.src.h(" ");
@@ -818,13 +934,13 @@ impl Generator for C {
             self.src.h(&name.to_snake_case());
             self.src.h("_t;\n");
         }
-        for (i, case) in variant.cases.iter().enumerate() {
+        for case in variant.cases.iter() {
             self.src.h(&format!(
                 "#define {}_{}_{} {}\n",
                 iface.name.to_shouty_snake_case(),
                 name.to_shouty_snake_case(),
                 case.name.to_shouty_snake_case(),
-                i,
+                case.discriminant,
             ));
         }
 
@@ -852,17 +968,33 @@ impl Generator for C {
     fn type_list(&mut self, iface: &Interface, id: TypeId, name: &str, ty: &Type, docs: &Docs) {
         let prev = mem::take(&mut self.src.header);
         self.docs(docs);
-        if *ty == Type::Char {
-            self.src.h("typedef ");
-            self.print_namespace(iface);
-            self.src.h("string_t ");
-        } else {
-            self.src.h("typedef struct {\n");
-            self.print_ty(iface, ty);
-            self.src.h(" *ptr;\n");
-            self.src.h("size_t len;\n");
-            self.src.h("} ");
-        }
+        self.src.h("typedef struct {\n");
+        self.print_ty(iface, ty);
+        self.src.h(" *ptr;\n");
+        self.src.h("size_t len;\n");
+        self.src.h("} ");
+        self.print_namespace(iface);
+        self.src.h(&name.to_snake_case());
+        self.src.h("_t;\n");
+        self.types
+            .insert(id, mem::replace(&mut self.src.header, prev));
+    }
+
+    fn type_array(
+        &mut self,
+        iface: &Interface,
+        id: TypeId,
+        name: &str,
+        ty: &Type,
+        len: u32,
+        docs: &Docs,
+    ) {
+        let prev = mem::take(&mut self.src.header);
+        self.docs(docs);
+        self.src.h("typedef struct {\n");
+        self.print_ty(iface, ty);
+        self.src.h(&format!(" data[{}];\n", len));
+        self.src.h("} ");
         self.print_namespace(iface);
         self.src.h(&name.to_snake_case());
         self.src.h("_t;\n");
@@ -920,28 +1052,90 @@ impl Generator for C {
             .insert(id, mem::replace(&mut self.src.header, prev));
     }
 
+    fn const_(
+        &mut self,
+        iface: &Interface,
+        name: &str,
+        ty: &Type,
+        value: &ConstValue,
+        docs: &Docs,
+    ) {
+        let prev = mem::take(&mut self.src.header);
+        self.docs(docs);
+        let docs = mem::replace(&mut self.src.header, prev);
+
+        let value = match value {
+            ConstValue::Bool(b) => b.to_string(),
+            ConstValue::Unsigned(n) if self.is_64bit(iface, ty) => format!("{}ULL", n),
+            ConstValue::Unsigned(n) => format!("{}U", n),
+            // The most negative integer can't be written as a literal in C
+            // since the literal is negated after it's typed.
+            ConstValue::Signed(i64::MIN) => "(-9223372036854775807LL - 1)".to_string(),
+            ConstValue::Signed(n) if self.is_64bit(iface, ty) => format!("({}LL)", n),
+            ConstValue::Signed(n) => format!("({})", n),
+            ConstValue::Float(n) if *n < 0.0 => format!("({:?})", n),
+            ConstValue::Float(n) => format!("{:?}", n),
+            ConstValue::Char(c) => format!("{}", *c as u32),
+            ConstValue::String(s) => c_string(s),
+            ConstValue::Enum { ty, case } => format!(
+                "{}_{}_{}",
+                iface.name.to_shouty_snake_case(),
+                iface.types[*ty]
+                    .name
+                    .as_ref()
+                    .unwrap()
+                    .to_shouty_snake_case(),
+                case.to_shouty_snake_case(),
+            ),
+            ConstValue::Flags { ty, flags } => {
+                if flags.is_empty() {
+                    "0".to_string()
+                } else {
+                    let name = iface.types[*ty].name.as_ref().unwrap();
+                    let flags = flags
+                        .iter()
+                        .map(|f| {
+                            format!(
+                                "{}_{}_{}",
+                                iface.name.to_shouty_snake_case(),
+                                name.to_shouty_snake_case(),
+                                f.to_shouty_snake_case(),
+                            )
+                        })
+                        .collect::<Vec<_>>();
+                    format!("({})", flags.join(" | "))
+                }
+            }
+        };
+        let define = format!(
+            "{}#define {}_{} {}\n",
+            &docs[..],
+            iface.name.to_shouty_snake_case(),
+            name.to_shouty_snake_case(),
+            value,
+        );
+        self.consts.push((self.origin.clone(), define));
+    }
+
     fn import(&mut self, iface: &Interface, func: &Function) {
         assert!(!func.is_async, "async not supported yet");
         let prev = mem::take(&mut self.src);
-        let sig = iface.wasm_signature(Direction::Import, func);
+        let sig = iface.wasm_signature(Direction::Import, self.opts.multi_value, func);
 
         // In the private C file, print a function declaration which is the
         // actual wasm import that we'll be calling, and this has the raw wasm
         // signature.
-        self.src.c(&format!(
-            "__attribute__((import_module(\"{}\"), import_name(\"{}\")))\n",
-            iface.name, func.name
-        ));
         let import_name = self.names.tmp(&format!(
             "__wasm_import_{}_{}",
             iface.name.to_snake_case(),
             func.name.to_snake_case()
         ));
-        match sig.results.len() {
-            0 => self.src.c("void"),
-            1 => self.src.c(wasm_type(sig.results[0])),
-            _ => unimplemented!("multi-value return not supported"),
-        }
+        let ret = self.wasm_results(&import_name, &sig.results);
+        self.src.c(&format!(
+            "__attribute__((import_module(\"{}\"), import_name(\"{}\")))\n",
+            iface.name, func.name
+        ));
+        self.src.c(&ret);
         self.src.c(" ");
         self.src.c(&import_name);
         self.src.c("(");
@@ -962,6 +1156,17 @@ impl Generator for C {
         self.src.c(&c_sig.sig);
         self.src.c(" {\n");
 
+        let mut program = Program::new(
+            iface,
+            Direction::Import,
+            LiftLower::LowerArgsLiftResults,
+            StringEncoding::Utf8,
+            self.opts.multi_value,
+            func,
+            &self.sizes,
+        );
+        program.optimize(iface, &self.sizes);
+
         let mut f = FunctionBindgen::new(self, c_sig, &import_name);
         for (pointer, param) in f.sig.params.iter() {
             f.locals.insert(param).unwrap();
@@ -975,12 +1180,7 @@ impl Generator for C {
         for ptr in f.sig.retptrs.iter() {
             f.locals.insert(ptr).unwrap();
         }
-        iface.call(
-            Direction::Import,
-            LiftLower::LowerArgsLiftResults,
-            func,
-            &mut f,
-        );
+        program.emit(iface, func, &mut f);
 
         let FunctionBindgen { src, .. } = f;
 
@@ -991,13 +1191,16 @@ impl Generator for C {
         self.funcs
             .entry(iface.name.to_string())
             .or_insert(Vec::new())
-            .push(Func { src });
+            .push(Func {
+                src,
+                origin: self.origin.clone(),
+            });
     }
 
     fn export(&mut self, iface: &Interface, func: &Function) {
         assert!(!func.is_async, "async not supported yet");
         let prev = mem::take(&mut self.src);
-        let sig = iface.wasm_signature(Direction::Export, func);
+        let sig = iface.wasm_signature(Direction::Export, self.opts.multi_value, func);
 
         // Print the actual header for this function into the header file, and
         // it's what we'll be calling.
@@ -1005,22 +1208,30 @@ impl Generator for C {
 
         // Generate, in the C source file, the raw wasm signature that has the
         // canonical ABI.
-        self.src.c(&format!(
-            "__attribute__((export_name(\"{}\")))\n",
-            func.name
-        ));
         let import_name = self.names.tmp(&format!(
             "__wasm_export_{}_{}",
             iface.name.to_snake_case(),
             func.name.to_snake_case()
         ));
+        let ret = self.wasm_results(&import_name, &sig.results);
+        self.src.c(&format!(
+            "__attribute__((export_name(\"{}\")))\n",
+            func.name
+        ));
+
+        let mut program = Program::new(
+            iface,
+            Direction::Export,
+            LiftLower::LiftArgsLowerResults,
+            StringEncoding::Utf8,
+            self.opts.multi_value,
+            func,
+            &self.sizes,
+        );
+        program.optimize(iface, &self.sizes);
 
         let mut f = FunctionBindgen::new(self, c_sig, &import_name);
-        match sig.results.len() {
-            0 => f.gen.src.c("void"),
-            1 => f.gen.src.c(wasm_type(sig.results[0])),
-            _ => unimplemented!("multi-value return not supported"),
-        }
+        f.gen.src.c(&ret);
         f.gen.src.c(" ");
         f.gen.src.c(&import_name);
         f.gen.src.c("(");
@@ -1038,12 +1249,7 @@ impl Generator for C {
         f.gen.src.c(") {\n");
 
         // Perform all lifting/lowering and append it to our src.
-        iface.call(
-            Direction::Export,
-            LiftLower::LiftArgsLowerResults,
-            func,
-            &mut f,
-        );
+        program.emit(iface, func, &mut f);
         let FunctionBindgen { src, .. } = f;
         self.src.c(&src);
         self.src.c("}\n");
@@ -1052,10 +1258,16 @@ impl Generator for C {
         self.funcs
             .entry(iface.name.to_string())
             .or_insert(Vec::new())
-            .push(Func { src });
+            .push(Func {
+                src,
+                origin: self.origin.clone(),
+            });
     }
 
     fn finish_one(&mut self, iface: &Interface, files: &mut Files) {
+        // Items are printed in a different order than they're generated in,
+        // so their origins are recorded here as they're printed.
+        let source_maps = files.source_maps();
         self.src.h(&format!(
             "\
                 #ifndef __BINDINGS_{0}_H
@@ -1080,7 +1292,10 @@ impl Generator for C {
 
         self.print_intrinsics();
 
-        for (_, resource) in iface.resources.iter() {
+        for (id, resource) in iface.resources.iter() {
+            if source_maps {
+                self.src.set_origin(Origin::resource(iface, id));
+            }
             let ns = iface.name.to_snake_case();
             let name = resource.name.to_snake_case();
             self.src.h(&format!(
@@ -1160,13 +1375,19 @@ impl Generator for C {
             }
         }
 
+        if source_maps {
+            self.src.set_origin(None);
+        }
+
         // Continuously generate anonymous types while we continue to find more
         //
         // First we take care of the public set of anonymous types. This will
         // iteratively print them and also remove any references from the
         // private set if we happen to also reference them.
+        let mut public_types = HashSet::new();
         while !self.public_anonymous_types.is_empty() {
             for ty in mem::take(&mut self.public_anonymous_types) {
+                public_types.insert(ty);
                 self.print_anonymous_type(iface, ty);
             }
         }
@@ -1174,11 +1395,15 @@ impl Generator for C {
         // Next we take care of private types. To do this we have basically the
         // same loop as above, after we switch the sets. We record, however,
         // all private types in a local set here to later determine if the type
-        // needs to be in the C file or the H file.
+        // needs to be in the C file or the H file. Private types may refer to
+        // public ones, which are skipped since they're already in the H file.
         let mut private_types = HashSet::new();
         self.public_anonymous_types = mem::take(&mut self.private_anonymous_types);
         while !self.public_anonymous_types.is_empty() {
             for ty in mem::take(&mut self.public_anonymous_types) {
+                if public_types.contains(&ty) {
+                    continue;
+                }
                 private_types.insert(ty);
                 self.print_anonymous_type(iface, ty);
             }
@@ -1226,6 +1451,9 @@ impl Generator for C {
         // topological order, so we
         for id in iface.topological_types() {
             if let Some(ty) = self.types.get(&id) {
+                if source_maps {
+                    self.src.set_origin(Origin::type_(iface, id));
+                }
                 if private_types.contains(&id) {
                     self.src.c(ty);
                 } else {
@@ -1235,6 +1463,16 @@ impl Generator for C {
             }
         }
 
+        for (origin, define) in mem::take(&mut self.consts) {
+            if source_maps {
+                self.src.set_origin(origin);
+            }
+            self.src.h(&define);
+        }
+        if source_maps {
+            self.src.set_origin(None);
+        }
+
         if self.i64_return_pointer_area_size > 0 {
             self.src.c(&format!(
                 "static int64_t RET_AREA[{}];\n",
@@ -1244,10 +1482,16 @@ impl Generator for C {
 
         for (_module, funcs) in mem::take(&mut self.funcs) {
             for func in funcs {
+                if source_maps {
+                    self.src.set_origin(func.origin);
+                }
                 self.src.h(&func.src.header);
                 self.src.c(&func.src.src);
             }
         }
+        if source_maps {
+            self.src.set_origin(None);
+        }
 
         self.src.h("\
         #ifdef __cplusplus
@@ -1256,8 +1500,20 @@ impl Generator for C {
         ");
         self.src.h("#endif\n");
 
-        files.push(&format!("{}.c", iface.name), self.src.src.as_bytes());
-        files.push(&format!("{}.h", iface.name), self.src.header.as_bytes());
+        let c = format!("{}.c", iface.name);
+        let h = format!("{}.h", iface.name);
+        if source_maps {
+            let src = &self.src.src;
+            files.push(&c, line_directives(&c, src, &src.line_origins()).as_bytes());
+            let header = &self.src.header;
+            files.push(
+                &h,
+                line_directives(&h, header, &header.line_origins()).as_bytes(),
+            );
+        } else {
+            files.push(&c, self.src.src.as_bytes());
+            files.push(&h, self.src.header.as_bytes());
+        }
     }
 }
 
@@ -1369,6 +1625,10 @@ impl Bindgen for FunctionBindgen<'_> {
         iface.all_bits_valid(ty)
     }
 
+    fn is_record_canonical(&self, iface: &Interface, ty: TypeId) -> bool {
+        iface.all_bits_valid(&Type::Id(ty))
+    }
+
     fn emit(
         &mut self,
         iface: &Interface,
@@ -1425,6 +1685,13 @@ impl Bindgen for FunctionBindgen<'_> {
                 results.push(format!("(int32_t) ({})", operands[0]));
             }
 
+            Instruction::BoolFromI32 => {
+                results.push(format!("(bool) ({})", operands[0]));
+            }
+            Instruction::I32FromBool => {
+                results.push(format!("(int32_t) ({})", operands[0]));
+            }
+
             Instruction::Bitcasts { casts } => {
                 for (cast, op) in casts.iter().zip(operands) {
                     let op = op;
@@ -1475,6 +1742,64 @@ impl Bindgen for FunctionBindgen<'_> {
                 ));
             }
 
+            // Arrays are wrapped in a struct so they can be assigned and
+            // returned like any other value.
+            Instruction::ArrayLower { len, .. } => {
+                let op = &operands[0];
+                for i in 0..*len {
+                    results.push(format!("({}).data[{}]", op, i));
+                }
+            }
+            Instruction::ArrayLift { ty, .. } => {
+                let name = self.gen.type_string(iface, &Type::Id(*ty));
+                let mut result = format!("({}) {{ {{\n", name);
+                for op in operands {
+                    result.push_str(&format!("{},\n", op));
+                }
+                result.push_str("} }");
+                results.push(result);
+            }
+            // Arrays in memory have the same layout as the struct they're
+            // wrapped in, which is copied in and out of memory whole, so the
+            // block for each element isn't needed.
+            Instruction::ArrayLowerMemory { ty, realloc, .. } => {
+                let _body = self.blocks.pop().unwrap();
+                match realloc {
+                    // The callee only borrows the array.
+                    None => results.push(format!("(int32_t) &({})", operands[0])),
+                    Some(realloc) => {
+                        let name = self.gen.type_string(iface, &Type::Id(*ty));
+                        let ptr = self.locals.tmp("ptr");
+                        let size = self.gen.sizes.size(&Type::Id(*ty));
+                        let align = self.gen.sizes.align(&Type::Id(*ty));
+                        self.src.push_str(&format!(
+                            "{} *{} = {}(NULL, 0, {}, {});\n",
+                            name, ptr, realloc, align, size,
+                        ));
+                        self.src.push_str(&format!("*{} = {};\n", ptr, operands[0]));
+                        results.push(format!("(int32_t) {}", ptr));
+                    }
+                }
+            }
+            Instruction::ArrayLiftMemory { ty, free, .. } => {
+                let _body = self.blocks.pop().unwrap();
+                let name = self.gen.type_string(iface, &Type::Id(*ty));
+                let array = self.locals.tmp("array");
+                self.src.push_str(&format!(
+                    "{} {} = *({}*) ({});\n",
+                    name, array, name, operands[0],
+                ));
+                if let Some(free) = free {
+                    let size = self.gen.sizes.size(&Type::Id(*ty));
+                    let align = self.gen.sizes.align(&Type::Id(*ty));
+                    self.src.push_str(&format!(
+                        "{}((void*) ({}), {}, {});\n",
+                        free, operands[0], size, align,
+                    ));
+                }
+                results.push(array);
+            }
+
             Instruction::RecordLower { record, .. } => {
                 if record.is_tuple() {
                     let op = &operands[0];
@@ -1497,6 +1822,14 @@ impl Bindgen for FunctionBindgen<'_> {
                 result.push_str("}");
                 results.push(result);
             }
+            Instruction::RecordCanonLower { ty, offset, .. } => {
+                let name = self.gen.type_string(iface, &Type::Id(*ty));
+                self.store(&name, *offset, operands);
+            }
+            Instruction::RecordCanonLift { ty, offset, .. } => {
+                let name = self.gen.type_string(iface, &Type::Id(*ty));
+                self.load(&name, *offset, operands, results);
+            }
 
             // TODO: checked
             Instruction::FlagsLower { record, .. } | Instruction::FlagsLift { record, .. } => {
@@ -1552,10 +1885,11 @@ impl Bindgen for FunctionBindgen<'_> {
 
                 self.src
                     .push_str(&format!("switch ((int32_t) {}) {{\n", expr_to_match));
-                for (i, ((case, (block, block_results)), payload)) in
-                    variant.cases.iter().zip(blocks).zip(payloads).enumerate()
+                for ((case, (block, block_results)), payload) in
+                    variant.cases.iter().zip(blocks).zip(payloads)
                 {
-                    self.src.push_str(&format!("case {}: {{\n", i));
+                    self.src
+                        .push_str(&format!("case {}: {{\n", case.discriminant as i32));
                     if let Some(ty) = &case.ty {
                         if !self.gen.is_empty_type(iface, ty) {
                             let ty = self.gen.type_string(iface, ty);
@@ -1597,10 +1931,9 @@ impl Bindgen for FunctionBindgen<'_> {
                     .push_str(&format!("{}.tag = {};\n", result, operands[0]));
                 self.src
                     .push_str(&format!("switch ((int32_t) {}.tag) {{\n", result));
-                for (i, (case, (block, block_results))) in
-                    variant.cases.iter().zip(blocks).enumerate()
-                {
-                    self.src.push_str(&format!("case {}: {{\n", i));
+                for (case, (block, block_results)) in variant.cases.iter().zip(blocks) {
+                    self.src
+                        .push_str(&format!("case {}: {{\n", case.discriminant as i32));
                     self.src.push_str(&block);
 
                     if case.ty.is_some() {
@@ -1620,16 +1953,25 @@ impl Bindgen for FunctionBindgen<'_> {
                 results.push(result);
             }
 
+            Instruction::StringLower { .. } => {
+                results.push(format!("(int32_t) ({}).ptr", operands[0]));
+                results.push(format!("(int32_t) ({}).len", operands[0]));
+            }
+            Instruction::StringLift { .. } => {
+                let string_name = self.gen.type_string(iface, &Type::String);
+                results.push(format!(
+                    "({}) {{ (char*)({}), (size_t)({}) }}",
+                    string_name, operands[0], operands[1]
+                ));
+            }
+
             Instruction::ListCanonLower { .. } => {
                 results.push(format!("(int32_t) ({}).ptr", operands[0]));
                 results.push(format!("(int32_t) ({}).len", operands[0]));
             }
             Instruction::ListCanonLift { element, ty, .. } => {
                 let list_name = self.gen.type_string(iface, &Type::Id(*ty));
-                let elem_name = match element {
-                    Type::Char => "char".into(),
-                    _ => self.gen.type_string(iface, element),
-                };
+                let elem_name = self.gen.type_string(iface, element);
                 results.push(format!(
                     "({}) {{ ({}*)({}), (size_t)({}) }}",
                     list_name, elem_name, operands[0], operands[1]
@@ -1645,10 +1987,7 @@ impl Bindgen for FunctionBindgen<'_> {
             Instruction::ListLift { element, ty, .. } => {
                 let _body = self.blocks.pop().unwrap();
                 let list_name = self.gen.type_string(iface, &Type::Id(*ty));
-                let elem_name = match element {
-                    Type::Char => "char".into(),
-                    _ => self.gen.type_string(iface, element),
-                };
+                let elem_name = self.gen.type_string(iface, element);
                 results.push(format!(
                     "({}) {{ ({}*)({}), (size_t)({}) }}",
                     list_name, elem_name, operands[0], operands[1]
@@ -1679,7 +2018,12 @@ impl Bindgen for FunctionBindgen<'_> {
                         self.src.push_str(&format!(" {} = ", ret));
                         results.push(ret);
                     }
-                    _ => unimplemented!(),
+                    n => {
+                        let ret = self.locals.tmp("ret");
+                        self.src
+                            .push_str(&format!("{}_ret_t {} = ", self.func_to_call, ret));
+                        results.extend((0..n).map(|i| format!("{}.f{}", ret, i)));
+                    }
                 }
                 self.src.push_str(self.func_to_call);
                 self.src.push_str("(");
@@ -1848,12 +2192,15 @@ impl Bindgen for FunctionBindgen<'_> {
                         .push_str(&format!("return {}.tag ? {0}.val.err : -1;\n", variant,));
                 }
             },
-            Instruction::Return { amt, .. } => {
-                assert!(*amt <= 1);
-                if *amt == 1 {
-                    self.src.push_str(&format!("return {};\n", operands[0]));
-                }
-            }
+            Instruction::Return { amt, .. } => match amt {
+                0 => {}
+                1 => self.src.push_str(&format!("return {};\n", operands[0])),
+                _ => self.src.push_str(&format!(
+                    "return ({}_ret_t){{ {} }};\n",
+                    self.func_to_call,
+                    operands.join(", ")
+                )),
+            },
 
             Instruction::I32Load { offset } => self.load("int32_t", *offset, operands, results),
             Instruction::I64Load { offset } => self.load("int64_t", *offset, operands, results),
@@ -1915,6 +2262,10 @@ impl Source {
     fn h(&mut self, s: &str) {
         self.header.push_str(s);
     }
+    fn set_origin(&mut self, origin: Option<Origin>) {
+        self.header.set_origin(origin.clone());
+        self.src.set_origin(origin);
+    }
 }
 
 fn wasm_type(ty: WasmType) -> &'static str {
@@ -1942,3 +2293,19 @@ fn case_field_name(case: &Case) -> String {
         case.name.to_snake_case()
     }
 }
+
+/// Renders `s` as a C string literal, escaping everything that isn't
+/// printable ASCII as octal byte escapes.
+fn c_string(s: &str) -> String {
+    let mut ret = String::from("\"");
+    for b in s.bytes() {
+        match b {
+            b'"' => ret.push_str("\\\""),
+            b'\\' => ret.push_str("\\\\"),
+            b' '..=b'~' => ret.push(b as char),
+            _ => ret.push_str(&format!("\\{:03o}", b)),
+        }
+    }
+    ret.push('"');
+    ret
+}
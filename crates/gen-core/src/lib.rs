@@ -8,10 +8,21 @@ use witx2::*;
 // pub use witx;
 pub use witx2;
 mod ns;
+mod origin;
+#[cfg(feature = "plugin")]
+pub mod plugin;
 
 pub use ns::Ns;
+pub use origin::*;
 
 pub trait Generator {
+    /// The features of interfaces this generator supports, which interfaces
+    /// are checked against with [`Interface::validate`] before generating
+    /// bindings.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+
     fn preprocess_all(&mut self, imports: &[Interface], exports: &[Interface]) {
         drop((imports, exports));
     }
@@ -20,6 +31,15 @@ pub trait Generator {
         drop((iface, dir));
     }
 
+    /// Called before each item of an interface is generated with where the
+    /// item was defined, and with `None` before [`Generator::finish_one`].
+    ///
+    /// This is only called if [`Files::source_maps`] is enabled, for
+    /// generators to record with [`Source::set_origin`].
+    fn origin(&mut self, origin: Option<Origin>) {
+        drop(origin);
+    }
+
     fn type_record(
         &mut self,
         iface: &Interface,
@@ -39,6 +59,15 @@ pub trait Generator {
     fn type_resource(&mut self, iface: &Interface, ty: ResourceId);
     fn type_alias(&mut self, iface: &Interface, id: TypeId, name: &str, ty: &Type, docs: &Docs);
     fn type_list(&mut self, iface: &Interface, id: TypeId, name: &str, ty: &Type, docs: &Docs);
+    fn type_array(
+        &mut self,
+        iface: &Interface,
+        id: TypeId,
+        name: &str,
+        ty: &Type,
+        len: u32,
+        docs: &Docs,
+    );
     fn type_pointer(
         &mut self,
         iface: &Interface,
@@ -65,10 +94,20 @@ pub trait Generator {
         ty: &Type,
         docs: &Docs,
     );
-    // fn const_(&mut self, iface: &Interface, name: &str, ty: &str, val: u64, docs: &Docs);
+    fn const_(&mut self, iface: &Interface, name: &str, ty: &Type, value: &ConstValue, docs: &Docs);
     fn import(&mut self, iface: &Interface, func: &Function);
     fn export(&mut self, iface: &Interface, func: &Function);
 
+    /// Generates bindings for a global, which by default are the bindings of
+    /// its getter function from [`Global::getter`].
+    fn global(&mut self, iface: &Interface, global: &Global, dir: Direction) {
+        let getter = global.getter();
+        match dir {
+            Direction::Import => self.import(iface, &getter),
+            Direction::Export => self.export(iface, &getter),
+        }
+    }
+
     fn finish_one(&mut self, iface: &Interface, files: &mut Files);
 
     fn finish_all(&mut self, files: &mut Files) {
@@ -77,6 +116,7 @@ pub trait Generator {
 
     fn generate_one(&mut self, iface: &Interface, dir: Direction, files: &mut Files) {
         self.preprocess_one(iface, dir);
+        let source_maps = files.source_maps();
 
         for (id, ty) in iface.types.iter() {
             // assert!(ty.foreign_module.is_none()); // TODO
@@ -84,12 +124,16 @@ pub trait Generator {
                 Some(name) => name,
                 None => continue,
             };
+            if source_maps {
+                self.origin(Origin::type_(iface, id));
+            }
             match &ty.kind {
                 TypeDefKind::Record(record) => self.type_record(iface, id, name, record, &ty.docs),
                 TypeDefKind::Variant(variant) => {
                     self.type_variant(iface, id, name, variant, &ty.docs)
                 }
                 TypeDefKind::List(t) => self.type_list(iface, id, name, t, &ty.docs),
+                TypeDefKind::Array(t, len) => self.type_array(iface, id, name, t, *len, &ty.docs),
                 TypeDefKind::PushBuffer(t) => self.type_push_buffer(iface, id, name, t, &ty.docs),
                 TypeDefKind::PullBuffer(t) => self.type_pull_buffer(iface, id, name, t, &ty.docs),
                 TypeDefKind::Type(t) => self.type_alias(iface, id, name, t, &ty.docs),
@@ -101,20 +145,39 @@ pub trait Generator {
         }
 
         for (id, _resource) in iface.resources.iter() {
+            if source_maps {
+                self.origin(Origin::resource(iface, id));
+            }
             self.type_resource(iface, id);
         }
 
-        // for c in module.constants() {
-        //     self.const_(&c.name, &c.ty, c.value, &c.docs);
-        // }
+        for c in iface.constants.iter() {
+            if source_maps {
+                self.origin(Origin::new(iface, None, &c.name, c.span));
+            }
+            self.const_(iface, &c.name, &c.ty, &c.value, &c.docs);
+        }
 
         for f in iface.functions.iter() {
+            if source_maps {
+                self.origin(Origin::function(iface, f));
+            }
             match dir {
                 Direction::Import => self.import(iface, &f),
                 Direction::Export => self.export(iface, &f),
             }
         }
 
+        for g in iface.globals.iter() {
+            if source_maps {
+                self.origin(Origin::new(iface, None, &g.name, g.span));
+            }
+            self.global(iface, g, dir);
+        }
+
+        if source_maps {
+            self.origin(None);
+        }
         self.finish_one(iface, files)
     }
 
@@ -150,7 +213,7 @@ pub struct TypeInfo {
     /// result of a function.
     pub result: bool,
 
-    /// Whether or not this type (transitively) has a list.
+    /// Whether or not this type (transitively) has a list or a string.
     pub has_list: bool,
 
     /// Whether or not this type (transitively) has a handle.
@@ -188,6 +251,15 @@ impl Types {
             }
             self.maybe_set_preview1_dtor(iface, f);
         }
+        // Constants are handed out to users much like function results are,
+        // so their types need to be available in the same way.
+        for c in iface.constants.iter() {
+            self.set_param_result_ty(iface, &c.ty, false, true);
+        }
+        // Globals are read through getters, so they're results as well.
+        for g in iface.globals.iter() {
+            self.set_param_result_ty(iface, &g.ty, false, true);
+        }
     }
 
     fn maybe_set_preview1_dtor(&mut self, iface: &Interface, f: &Function) {
@@ -275,9 +347,10 @@ impl Types {
                 info = self.type_info(iface, ty);
                 info.has_pull_buffer = true;
             }
-            TypeDefKind::ConstPointer(ty) | TypeDefKind::Pointer(ty) | TypeDefKind::Type(ty) => {
-                info = self.type_info(iface, ty)
-            }
+            TypeDefKind::ConstPointer(ty)
+            | TypeDefKind::Pointer(ty)
+            | TypeDefKind::Array(ty, _)
+            | TypeDefKind::Type(ty) => info = self.type_info(iface, ty),
         }
         self.type_info.insert(ty, info);
         return info;
@@ -287,6 +360,7 @@ impl Types {
         let mut info = TypeInfo::default();
         match ty {
             Type::Handle(_) => info.has_handle = true,
+            Type::String => info.has_list = true,
             Type::Id(id) => return self.type_id_info(iface, *id),
             _ => {}
         }
@@ -308,6 +382,7 @@ impl Types {
                 }
             }
             TypeDefKind::List(ty)
+            | TypeDefKind::Array(ty, _)
             | TypeDefKind::PushBuffer(ty)
             | TypeDefKind::PullBuffer(ty)
             | TypeDefKind::Pointer(ty)
@@ -335,9 +410,49 @@ impl Types {
 #[derive(Default)]
 pub struct Files {
     files: BTreeMap<String, Vec<u8>>,
+    /// The origin of each line of the files pushed with
+    /// [`Files::push_source`], if source maps are enabled.
+    origins: Option<BTreeMap<String, Vec<Option<Origin>>>>,
 }
 
 impl Files {
+    /// Enables source maps, which map the lines of generated files back to
+    /// the items in `*.witx` files that they were generated from.
+    ///
+    /// For every file pushed with [`Files::push_source`], a [source map]
+    /// named after the file with a `.map` extension added is also generated.
+    /// Generators may also use [`Files::source_maps`] to decide whether to
+    /// add language-specific markers to their output, such as with
+    /// [`line_directives`].
+    ///
+    /// [source map]: https://sourcemaps.info/spec.html
+    pub fn enable_source_maps(&mut self) {
+        self.origins.get_or_insert_with(BTreeMap::new);
+    }
+
+    /// Returns whether source maps are enabled.
+    pub fn source_maps(&self) -> bool {
+        self.origins.is_some()
+    }
+
+    /// Same as [`Files::push`], except that where the lines of `src` were
+    /// generated from is recorded in the source map of `name`, if source
+    /// maps are enabled.
+    pub fn push_source(&mut self, name: &str, src: &Source) {
+        if let Some(origins) = &mut self.origins {
+            let lines = origins.entry(name.to_string()).or_insert_with(Vec::new);
+            let prev = self
+                .files
+                .get(name)
+                .map(|f| f.iter().filter(|b| **b == b'\n').count());
+            lines.resize(prev.unwrap_or(0), None);
+            lines.extend(src.line_origins());
+            let map = origin::source_map(name, lines);
+            self.files.insert(format!("{}.map", name), map.into_bytes());
+        }
+        self.push(name, src.as_bytes());
+    }
+
     pub fn push(&mut self, name: &str, contents: &[u8]) {
         match self.files.entry(name.to_owned()) {
             Entry::Vacant(entry) => {
@@ -362,9 +477,85 @@ pub fn load(path: impl AsRef<Path>) -> Result<Interface> {
 pub struct Source {
     s: String,
     indent: usize,
+    /// The lines at which the origin of this source changes, and what it
+    /// changes to.
+    origins: Vec<(usize, Option<Origin>)>,
+    /// The number of lines in `s` up to the byte offset `lines.0`, so lines
+    /// don't need to be counted from the start every time.
+    lines: (usize, usize),
 }
 
 impl Source {
+    /// Records that the code pushed from now on was generated from `origin`,
+    /// until the origin is changed again.
+    ///
+    /// Origins are tracked per line, so if something has already been pushed
+    /// to the current line it keeps its origin and `origin` starts at the
+    /// next line.
+    pub fn set_origin(&mut self, origin: Option<Origin>) {
+        // The string may have been changed through `as_mut_string` since
+        // lines were last counted, in which case they're counted again.
+        let (counted, lines) = match self.s.get(self.lines.0..) {
+            Some(_) => self.lines,
+            None => (0, 0),
+        };
+        let line = lines + self.s[counted..].matches('\n').count();
+        self.lines = (self.s.len(), line);
+        // Only indentation may have been pushed to the current line so far.
+        let current = &self.s[self.s.rfind('\n').map(|i| i + 1).unwrap_or(0)..];
+        let line = if current.trim().is_empty() {
+            line
+        } else {
+            line + 1
+        };
+        match self.origins.last_mut() {
+            Some(last) if last.0 == line => last.1 = origin,
+            _ => self.origins.push((line, origin)),
+        }
+    }
+
+    /// Pushes the contents of `src` as with [`Source::push_str`], along with
+    /// the origins recorded in it.
+    ///
+    /// Lines of `src` before its first origin keep the current origin, and
+    /// the current origin still applies to whatever is pushed afterwards.
+    pub fn append(&mut self, src: &Source) {
+        let origin = self.origin().cloned();
+        let base = self.s.matches('\n').count();
+        self.push_str(&src.s);
+        for (line, origin) in src.origins.iter() {
+            let line = base + line;
+            match self.origins.last_mut() {
+                Some(last) if last.0 == line => last.1 = origin.clone(),
+                _ => self.origins.push((line, origin.clone())),
+            }
+        }
+        if !src.origins.is_empty() {
+            self.set_origin(origin);
+        }
+    }
+
+    /// Returns the origin that code pushed now is recorded as coming from.
+    pub fn origin(&self) -> Option<&Origin> {
+        self.origins.last().and_then(|(_, o)| o.as_ref())
+    }
+
+    /// Returns where each line of this source was generated from, as
+    /// recorded with [`Source::set_origin`].
+    pub fn line_origins(&self) -> Vec<Option<Origin>> {
+        let mut ret = vec![None; self.s.matches('\n').count() + 1];
+        for (i, (line, origin)) in self.origins.iter().enumerate() {
+            let end = match self.origins.get(i + 1) {
+                Some((next, _)) => *next,
+                None => ret.len(),
+            };
+            for slot in ret[*line..end].iter_mut() {
+                *slot = origin.clone();
+            }
+        }
+        ret
+    }
+
     pub fn push_str(&mut self, src: &str) {
         let lines = src.lines().collect::<Vec<_>>();
         for (i, line) in lines.iter().enumerate() {
@@ -425,7 +616,7 @@ impl From<Source> for String {
 
 #[cfg(test)]
 mod tests {
-    use super::{Generator, Source};
+    use super::{Generator, Origin, Source};
 
     #[test]
     fn simple_append() {
@@ -473,6 +664,66 @@ mod tests {
         assert_eq!(s.s, "function() {\n  x\n}");
     }
 
+    #[test]
+    fn origins() {
+        let origin = |name: &str| {
+            Some(Origin {
+                file: "a.witx".into(),
+                line: 1,
+                col: 1,
+                name: name.to_string(),
+            })
+        };
+        let mut s = Source::default();
+        s.push_str("a\n");
+        s.set_origin(origin("x"));
+        s.push_str("b {\nc\n");
+        s.set_origin(origin("y"));
+        s.set_origin(origin("z"));
+        s.push_str("d");
+        assert_eq!(s.origin(), origin("z").as_ref());
+        s.set_origin(None);
+        s.push_str("\ne\n");
+        assert_eq!(
+            s.line_origins(),
+            [None, origin("x"), origin("x"), origin("z"), None, None]
+        );
+    }
+
+    #[test]
+    fn append() {
+        let origin = |name: &str| {
+            Some(Origin {
+                file: "a.witx".into(),
+                line: 1,
+                col: 1,
+                name: name.to_string(),
+            })
+        };
+        let mut body = Source::default();
+        body.set_origin(origin("f"));
+        body.push_str("function() {\nx\n}");
+        let mut s = Source::default();
+        s.set_origin(origin("a"));
+        s.push_str("a\n");
+        s.push_str("b = ");
+        s.append(&body);
+        s.push_str(";\nc\n");
+        assert_eq!(s.s, "a\nb = function() {\n  x\n};\nc\n");
+        assert_eq!(s.origin(), origin("a").as_ref());
+        assert_eq!(
+            s.line_origins(),
+            [
+                origin("a"),
+                origin("f"),
+                origin("f"),
+                origin("f"),
+                origin("a"),
+                origin("a")
+            ]
+        );
+    }
+
     #[test]
     fn generator_is_object_safe() {
         fn _assert(_: &dyn Generator) {}
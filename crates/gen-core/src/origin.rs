@@ -0,0 +1,266 @@
+use std::fmt::Write;
+use std::path::PathBuf;
+use witx2::*;
+
+/// The item in a `*.witx` file that some generated code was generated from.
+///
+/// These are recorded with [`Source::set_origin`](crate::Source::set_origin)
+/// and used to map generated files back to the items which produced them,
+/// see [`Files::enable_source_maps`](crate::Files::enable_source_maps).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Origin {
+    /// The file the item was defined in.
+    pub file: PathBuf,
+    /// The 1-based line of the item's name in `file`.
+    pub line: u32,
+    /// The 1-based column of the item's name in `file`.
+    pub col: u32,
+    /// The name of the item.
+    pub name: String,
+}
+
+impl Origin {
+    /// Returns the origin of an item named `name` at `span` in the file of
+    /// `module`, as with [`Interface::source`].
+    ///
+    /// Returns `None` if the file isn't known.
+    pub fn new(iface: &Interface, module: Option<&str>, name: &str, span: Span) -> Option<Origin> {
+        let file = iface.source(module)?;
+        let (line, col) = file.line_col(span.start);
+        Some(Origin {
+            file: file.path.clone(),
+            line,
+            col,
+            name: name.to_string(),
+        })
+    }
+
+    /// Returns the origin of the named type `id`.
+    pub fn type_(iface: &Interface, id: TypeId) -> Option<Origin> {
+        let ty = &iface.types[id];
+        let module = ty.foreign_module.as_deref();
+        Origin::new(iface, module, ty.name.as_deref()?, ty.span)
+    }
+
+    pub fn resource(iface: &Interface, id: ResourceId) -> Option<Origin> {
+        let resource = &iface.resources[id];
+        let module = resource.foreign_module.as_deref();
+        Origin::new(iface, module, &resource.name, resource.span)
+    }
+
+    pub fn function(iface: &Interface, func: &Function) -> Option<Origin> {
+        Origin::new(iface, None, &func.name, func.span)
+    }
+}
+
+/// Returns a [source map] for the file `name`, where each line has the
+/// origin at the same index of `lines`.
+///
+/// [source map]: https://sourcemaps.info/spec.html
+pub(crate) fn source_map(name: &str, lines: &[Option<Origin>]) -> String {
+    let mut sources = Vec::<&PathBuf>::new();
+    let mut names = Vec::<&str>::new();
+    let mut mappings = String::new();
+    // Every field but the generated column is relative to its value in the
+    // previous segment, which is the one at the start of the previous line
+    // with an origin.
+    let mut prev = [0i64; 4];
+    for (i, origin) in lines.iter().enumerate() {
+        if i > 0 {
+            mappings.push(';');
+        }
+        let origin = match origin {
+            Some(origin) => origin,
+            None => continue,
+        };
+        let source = match sources.iter().position(|s| **s == origin.file) {
+            Some(i) => i,
+            None => {
+                sources.push(&origin.file);
+                sources.len() - 1
+            }
+        };
+        let name = match names.iter().position(|n| *n == origin.name) {
+            Some(i) => i,
+            None => {
+                names.push(&origin.name);
+                names.len() - 1
+            }
+        };
+        let segment = [
+            source as i64,
+            i64::from(origin.line) - 1,
+            i64::from(origin.col) - 1,
+            name as i64,
+        ];
+        vlq(&mut mappings, 0);
+        for (cur, prev) in segment.iter().zip(prev.iter_mut()) {
+            vlq(&mut mappings, cur - *prev);
+            *prev = *cur;
+        }
+    }
+
+    let mut map = String::new();
+    map.push_str("{\"version\":3,\"file\":");
+    json_string(&mut map, name.rsplit('/').next().unwrap());
+    map.push_str(",\"sources\":[");
+    for (i, source) in sources.iter().enumerate() {
+        if i > 0 {
+            map.push(',');
+        }
+        json_string(&mut map, &source.display().to_string());
+    }
+    map.push_str("],\"names\":[");
+    for (i, name) in names.iter().enumerate() {
+        if i > 0 {
+            map.push(',');
+        }
+        json_string(&mut map, name);
+    }
+    map.push_str("],\"mappings\":");
+    json_string(&mut map, &mappings);
+    map.push_str("}\n");
+    map
+}
+
+/// Appends `value` as a base64 VLQ, as used by source maps.
+fn vlq(dst: &mut String, value: i64) {
+    const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut value = if value < 0 {
+        ((-value) << 1) | 1
+    } else {
+        value << 1
+    };
+    loop {
+        let mut digit = value & 0b11111;
+        value >>= 5;
+        if value > 0 {
+            digit |= 0b100000;
+        }
+        dst.push(BASE64[digit as usize] as char);
+        if value == 0 {
+            break;
+        }
+    }
+}
+
+fn json_string(dst: &mut String, s: &str) {
+    dst.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => dst.push_str("\\\""),
+            '\\' => dst.push_str("\\\\"),
+            c if (c as u32) < 0x20 => write!(dst, "\\u{:04x}", c as u32).unwrap(),
+            c => dst.push(c),
+        }
+    }
+    dst.push('"');
+}
+
+/// Returns `contents` with C `#line` directives attributing its lines, whose
+/// origins are given by `lines`, to the items in `*.witx` files that they
+/// were generated from so compiler errors point there.
+///
+/// The compiler advances the line number of each directive with every line,
+/// so one is emitted before each line with an origin. Lines without an origin
+/// are attributed back to `name`, the file that `contents` are written to.
+pub fn line_directives(name: &str, contents: &str, lines: &[Option<Origin>]) -> String {
+    let mut ret = String::new();
+    let mut attributed = false;
+    // The 1-based line number in the returned string of the next line.
+    let mut line = 1;
+    for (i, text) in contents.split_inclusive('\n').enumerate() {
+        match lines.get(i).and_then(|o| o.as_ref()) {
+            Some(o) => {
+                let file = o.file.display().to_string();
+                writeln!(ret, "#line {} {}", o.line, c_string(&file)).unwrap();
+                line += 1;
+                attributed = true;
+            }
+            None if attributed => {
+                writeln!(ret, "#line {} {}", line + 1, c_string(name)).unwrap();
+                line += 1;
+                attributed = false;
+            }
+            None => {}
+        }
+        ret.push_str(text);
+        line += 1;
+    }
+    ret
+}
+
+/// Quotes `s` as a C string literal.
+fn c_string(s: &str) -> String {
+    let mut ret = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            '\n' => ret.push_str("\\n"),
+            c => ret.push(c),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn origin(line: u32, col: u32, name: &str) -> Option<Origin> {
+        Some(Origin {
+            file: "a.witx".into(),
+            line,
+            col,
+            name: name.to_string(),
+        })
+    }
+
+    #[test]
+    fn vlq() {
+        let encode = |value| {
+            let mut s = String::new();
+            super::vlq(&mut s, value);
+            s
+        };
+        assert_eq!(encode(0), "A");
+        assert_eq!(encode(1), "C");
+        assert_eq!(encode(-1), "D");
+        assert_eq!(encode(16), "gB");
+        assert_eq!(encode(-100), "pG");
+    }
+
+    #[test]
+    fn source_map() {
+        let lines = [
+            None,
+            origin(3, 1, "foo"),
+            origin(3, 1, "foo"),
+            origin(1, 5, "bar"),
+        ];
+        assert_eq!(
+            super::source_map("out/x.js", &lines),
+            "{\"version\":3,\"file\":\"x.js\",\"sources\":[\"a.witx\"],\
+             \"names\":[\"foo\",\"bar\"],\"mappings\":\";AAEAA;AAAAA;AAFIC\"}\n",
+        );
+    }
+
+    #[test]
+    fn line_directives() {
+        let lines = [None, origin(3, 1, "foo"), origin(3, 1, "foo"), None];
+        assert_eq!(
+            super::line_directives("x.c", "a\nb\nc\nd\n", &lines),
+            "a\n#line 3 \"a.witx\"\nb\n#line 3 \"a.witx\"\nc\n#line 7 \"x.c\"\nd\n",
+        );
+        let lines = [Some(Origin {
+            file: r#"C:\dir\"a".witx"#.into(),
+            ..origin(1, 1, "foo").unwrap()
+        })];
+        assert_eq!(
+            super::line_directives("x.c", "a\n", &lines),
+            "#line 1 \"C:\\\\dir\\\\\\\"a\\\".witx\"\na\n",
+        );
+    }
+}
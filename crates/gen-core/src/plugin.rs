@@ -0,0 +1,281 @@
+//! Generators which run outside of this process, so that bindings can be
+//! generated for languages without a generator in this repository.
+//!
+//! A plugin is any program which reads a [`Request`] as JSON from its stdin
+//! and writes a [`Response`] as JSON to its stdout before exiting
+//! successfully. Interfaces are described in the format of [`witx2::json`],
+//! and plugins should check [`Request::version`] against the version of that
+//! format they understand. Anything a plugin writes to stderr is passed
+//! through, and a plugin fails by exiting with a non-zero status.
+
+use crate::{Files, Generator};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::io::Write;
+use std::path::{Component, Path};
+use std::process::{Command, Stdio};
+use witx2::*;
+
+/// What a plugin is asked to generate bindings for.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Request {
+    /// The version of [`witx2::json`] that interfaces are described with.
+    pub version: u32,
+    /// The arguments the plugin was configured with.
+    pub args: Vec<String>,
+    pub imports: Vec<json::Interface>,
+    pub exports: Vec<json::Interface>,
+}
+
+/// The files a plugin generated, keyed by their name.
+///
+/// Names are paths relative to the output directory, such as `src/lib.rs`,
+/// and can't contain `..` components.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Response {
+    pub files: BTreeMap<String, String>,
+}
+
+/// A generator which runs a plugin.
+///
+/// Failing to run the plugin, or the plugin failing, panics when generating
+/// bindings as with any other generator. Use [`Plugin::run`] to handle errors
+/// instead.
+pub struct Plugin {
+    program: OsString,
+    args: Vec<String>,
+}
+
+impl Plugin {
+    /// Creates a generator which runs `program`, either a path or the name of
+    /// a program to look for in `PATH`.
+    pub fn new(program: impl Into<OsString>) -> Plugin {
+        Plugin {
+            program: program.into(),
+            args: Vec::new(),
+        }
+    }
+
+    /// Adds an argument which is passed to the plugin in [`Request::args`].
+    pub fn arg(&mut self, arg: impl Into<String>) -> &mut Plugin {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Runs the plugin to generate bindings for `imports` and `exports`.
+    pub fn run(&self, imports: &[Interface], exports: &[Interface]) -> Result<Files> {
+        let program = self.program.to_string_lossy();
+        let request = Request {
+            version: json::VERSION,
+            args: self.args.clone(),
+            imports: imports.iter().map(json::Interface::new).collect(),
+            exports: exports.iter().map(json::Interface::new).collect(),
+        };
+        let mut child = Command::new(&self.program)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("failed to run plugin `{}`", program))?;
+        // Write the request from another thread so that a plugin which
+        // starts writing its response early can't deadlock with us.
+        let mut stdin = child.stdin.take().unwrap();
+        let writer = std::thread::spawn(move || -> std::io::Result<()> {
+            serde_json::to_writer(&mut stdin, &request)?;
+            stdin.flush()
+        });
+        let output = child
+            .wait_with_output()
+            .with_context(|| format!("failed to run plugin `{}`", program))?;
+        if !output.status.success() {
+            bail!("plugin `{}` failed: {}", program, output.status);
+        }
+        // The plugin may exit successfully without reading all of its input,
+        // so only its output is checked.
+        drop(writer.join());
+        let response: Response = serde_json::from_slice(&output.stdout)
+            .with_context(|| format!("plugin `{}` returned an invalid response", program))?;
+        let mut files = Files::default();
+        for (name, contents) in response.files.iter() {
+            // Files are written relative to an output directory, which they
+            // mustn't be able to escape.
+            let relative = Path::new(name)
+                .components()
+                .all(|c| matches!(c, Component::Normal(_)));
+            if name.is_empty() || !relative {
+                bail!(
+                    "plugin `{}` returned a file with an invalid name `{}`",
+                    program,
+                    name
+                );
+            }
+            files.push(name, contents.as_bytes());
+        }
+        Ok(files)
+    }
+}
+
+// The plugin is given every interface at once in `generate_all`, so none of
+// the per-item callbacks are used.
+impl Generator for Plugin {
+    fn generate_all(&mut self, imports: &[Interface], exports: &[Interface], files: &mut Files) {
+        let generated = self
+            .run(imports, exports)
+            .unwrap_or_else(|e| panic!("{:?}", e));
+        for (name, contents) in generated.iter() {
+            files.push(name, contents);
+        }
+    }
+
+    fn type_record(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _record: &Record,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_variant(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _variant: &Variant,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_resource(&mut self, _iface: &Interface, _ty: ResourceId) {}
+
+    fn type_alias(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_list(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_array(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _len: u32,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_pointer(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _const_: bool,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_builtin(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_push_buffer(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_pull_buffer(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn const_(
+        &mut self,
+        _iface: &Interface,
+        _name: &str,
+        _ty: &Type,
+        _value: &ConstValue,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn import(&mut self, _iface: &Interface, _func: &Function) {}
+
+    fn export(&mut self, _iface: &Interface, _func: &Function) {}
+
+    fn finish_one(&mut self, _iface: &Interface, _files: &mut Files) {}
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Returns a plugin which runs `script` with `sh`.
+    fn sh(script: &str) -> Plugin {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let dir = std::env::temp_dir().join(format!("witx-bindgen-plugin-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join(format!("{}.sh", NEXT.fetch_add(1, Ordering::SeqCst)));
+        std::fs::write(&file, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o755)).unwrap();
+        Plugin::new(file)
+    }
+
+    #[test]
+    fn run() -> Result<()> {
+        let iface = Interface::parse("foo", "x: function()")?;
+        let mut plugin = sh(r#"grep -q '"name":"foo"' && echo '{"files":{"foo.txt":"hi"}}'"#);
+        plugin.arg("--bar");
+        let files = plugin.run(&[iface], &[])?;
+        let files = files.iter().collect::<Vec<_>>();
+        assert_eq!(files, [("foo.txt", &b"hi"[..])]);
+        Ok(())
+    }
+
+    #[test]
+    fn errors() {
+        let err = |plugin: Plugin| format!("{:#}", plugin.run(&[], &[]).err().unwrap());
+        assert!(err(sh("exit 3")).contains("failed: exit status: 3"));
+        assert!(err(sh("echo nope")).contains("invalid response"));
+        assert!(err(Plugin::new("/nonexistent")).contains("failed to run plugin"));
+        for name in ["/etc/passwd", "../foo.txt", "a/../../b", ""].iter() {
+            let script = format!(r#"echo '{{"files":{{"{}":"hi"}}}}'"#, name);
+            assert!(err(sh(&script)).contains("invalid name"), "{}", name);
+        }
+    }
+}
@@ -2,9 +2,10 @@ use heck::*;
 use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::mem;
 use witx_bindgen_gen_core::witx2::abi::{
-    Bindgen, Bitcast, Direction, Instruction, LiftLower, WasmType, WitxInstruction,
+    Bindgen, Bitcast, Direction, Instruction, LiftLower, MultiValue, StringEncoding, WasmType,
+    WitxInstruction,
 };
-use witx_bindgen_gen_core::{witx2::*, Files, Generator};
+use witx_bindgen_gen_core::{witx2::*, Files, Generator, Origin};
 
 #[derive(Default)]
 pub struct Js {
@@ -23,6 +24,9 @@ pub struct Js {
     needs_ty_result: bool,
     needs_ty_push_buffer: bool,
     needs_ty_pull_buffer: bool,
+    // Where the item currently being generated was defined, if source maps
+    // are enabled.
+    origin: Option<Origin>,
 }
 
 #[derive(Default)]
@@ -42,6 +46,23 @@ struct Exports {
 pub struct Opts {
     #[cfg_attr(feature = "structopt", structopt(long = "no-typescript"))]
     pub no_typescript: bool,
+
+    /// The encoding of strings in linear memory: `utf8`, `utf16` or
+    /// `latin1+utf16`. This must match the encoding used by the other side.
+    #[cfg_attr(
+        feature = "structopt",
+        structopt(long = "string-encoding", default_value = "utf8")
+    )]
+    pub string_encoding: StringEncoding,
+
+    /// The maximum number of results returned natively with wasm multi-value,
+    /// or `disabled` to always use a return pointer for more than one result.
+    /// This must match the setting used by the other side.
+    #[cfg_attr(
+        feature = "structopt",
+        structopt(long = "multi-value", default_value = "disabled")
+    )]
+    pub multi_value: MultiValue,
 }
 
 impl Opts {
@@ -64,6 +85,8 @@ enum Intrinsic {
     ValidateF64,
     ValidateGuestChar,
     ValidateHostChar,
+    ValidateGuestBool,
+    ValidateHostBool,
     ValidateFlags,
     ValidateFlags64,
     I32ToF32,
@@ -73,6 +96,11 @@ enum Intrinsic {
     Utf8Decoder,
     Utf8Encode,
     Utf8EncodedLen,
+    Utf16Decoder,
+    Utf16Encode,
+    CompactUtf16Decode,
+    CompactUtf16Encode,
+    CompactUtf16EncodedLen,
     Slab,
     Promises,
     WithCurrentPromise,
@@ -91,6 +119,8 @@ impl Intrinsic {
             Intrinsic::ValidateF64 => "validate_f64",
             Intrinsic::ValidateGuestChar => "validate_guest_char",
             Intrinsic::ValidateHostChar => "validate_host_char",
+            Intrinsic::ValidateGuestBool => "validate_guest_bool",
+            Intrinsic::ValidateHostBool => "validate_host_bool",
             Intrinsic::ValidateFlags => "validate_flags",
             Intrinsic::ValidateFlags64 => "validate_flags64",
             Intrinsic::F32ToI32 => "f32ToI32",
@@ -100,6 +130,11 @@ impl Intrinsic {
             Intrinsic::Utf8Decoder => "UTF8_DECODER",
             Intrinsic::Utf8Encode => "utf8_encode",
             Intrinsic::Utf8EncodedLen => "UTF8_ENCODED_LEN",
+            Intrinsic::Utf16Decoder => "UTF16_DECODER",
+            Intrinsic::Utf16Encode => "utf16_encode",
+            Intrinsic::CompactUtf16Decode => "compact_utf16_decode",
+            Intrinsic::CompactUtf16Encode => "compact_utf16_encode",
+            Intrinsic::CompactUtf16EncodedLen => "COMPACT_UTF16_ENCODED_LEN",
             Intrinsic::Slab => "Slab",
             Intrinsic::Promises => "PROMISES",
             Intrinsic::WithCurrentPromise => "with_current_promise",
@@ -125,6 +160,19 @@ impl Js {
         }
     }
 
+    /// Returns whether `ty` is represented with a `bigint` in JS.
+    fn is_bigint(&self, iface: &Interface, ty: &Type) -> bool {
+        match ty {
+            Type::U64 | Type::S64 => true,
+            Type::Id(id) => match &iface.types[*id].kind {
+                TypeDefKind::Type(t) => self.is_bigint(iface, t),
+                TypeDefKind::Record(r) if r.is_flags() => iface.flags_repr(r) == Some(Int::U64),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
     fn array_ty(&self, iface: &Interface, ty: &Type) -> Option<&'static str> {
         match ty {
             Type::U8 | Type::CChar => Some("Uint8Array"),
@@ -137,7 +185,7 @@ impl Js {
             Type::S64 => Some("BigInt64Array"),
             Type::F32 => Some("Float32Array"),
             Type::F64 => Some("Float64Array"),
-            Type::Char => None,
+            Type::Char | Type::Bool | Type::String => None,
             Type::Handle(_) => None,
             Type::Id(id) => match &iface.types[*id].kind {
                 TypeDefKind::Type(t) => self.array_ty(iface, t),
@@ -159,7 +207,8 @@ impl Js {
             | Type::F32
             | Type::F64 => self.src.ts("number"),
             Type::U64 | Type::S64 => self.src.ts("bigint"),
-            Type::Char => self.src.ts("string"),
+            Type::Char | Type::String => self.src.ts("string"),
+            Type::Bool => self.src.ts("boolean"),
             Type::Handle(id) => self.src.ts(&iface.resources[*id].name.to_camel_case()),
             Type::Id(id) => {
                 let ty = &iface.types[*id];
@@ -170,7 +219,6 @@ impl Js {
                     TypeDefKind::Type(t) => self.print_ty(iface, t),
                     TypeDefKind::Record(r) if r.is_tuple() => self.print_tuple(iface, r),
                     TypeDefKind::Record(_) => panic!("anonymous record"),
-                    TypeDefKind::Variant(v) if v.is_bool() => self.src.ts("boolean"),
                     TypeDefKind::Variant(v) => {
                         if self.is_nullable_option(iface, v) {
                             self.print_ty(iface, v.cases[1].ty.as_ref().unwrap());
@@ -197,7 +245,7 @@ impl Js {
                             panic!("anonymous variant");
                         }
                     }
-                    TypeDefKind::List(v) => self.print_list(iface, v),
+                    TypeDefKind::List(v) | TypeDefKind::Array(v, _) => self.print_list(iface, v),
                     TypeDefKind::PushBuffer(v) => self.print_buffer(iface, true, v),
                     TypeDefKind::PullBuffer(v) => self.print_buffer(iface, false, v),
                     TypeDefKind::Pointer(_) | TypeDefKind::ConstPointer(_) => {
@@ -212,12 +260,8 @@ impl Js {
         match self.array_ty(iface, ty) {
             Some(ty) => self.src.ts(ty),
             None => {
-                if let Type::Char = ty {
-                    self.src.ts("string");
-                } else {
-                    self.print_ty(iface, ty);
-                    self.src.ts("[]");
-                }
+                self.print_ty(iface, ty);
+                self.src.ts("[]");
             }
         }
     }
@@ -252,13 +296,57 @@ impl Js {
     }
 
     fn docs(&mut self, docs: &Docs) {
-        let docs = match &docs.contents {
-            Some(docs) => docs,
-            None => return,
-        };
-        for line in docs.lines() {
-            self.src.ts(&format!("// {}\n", line));
+        let mut lines = Vec::new();
+        if let Some(description) = &docs.description {
+            lines.extend(description.lines().map(|l| l.to_string()));
+        }
+        if let Some(errors) = &docs.errors {
+            if !lines.is_empty() {
+                lines.push(String::new());
+            }
+            let mut errors = errors.lines();
+            lines.push(format!("Errors: {}", errors.next().unwrap_or("")));
+            lines.extend(errors.map(|l| l.to_string()));
+        }
+        let mut tags = Vec::new();
+        for (name, text) in docs.params.iter() {
+            let name = name.to_mixed_case();
+            tags.push((format!("@param {} -", to_js_ident(&name)), text));
         }
+        if let Some(text) = &docs.returns {
+            tags.push(("@returns".to_string(), text));
+        }
+        if let Some(text) = &docs.since {
+            tags.push(("@since".to_string(), text));
+        }
+        if let Some(text) = &docs.deprecated {
+            tags.push(("@deprecated".to_string(), text));
+        }
+        if !lines.is_empty() && !tags.is_empty() {
+            lines.push(String::new());
+        }
+        for (tag, text) in tags {
+            let mut text = text.lines();
+            match text.next() {
+                Some(first) => lines.push(format!("{} {}", tag, first)),
+                None => lines.push(tag),
+            }
+            lines.extend(text.map(|l| l.to_string()));
+        }
+        if lines.is_empty() {
+            return;
+        }
+        self.src.ts("/**\n");
+        for line in lines {
+            // Docs can't end the comment early.
+            let line = line.trim_end().replace("*/", "* /");
+            if line.is_empty() {
+                self.src.ts(" *\n");
+            } else {
+                self.src.ts(&format!(" * {}\n", line));
+            }
+        }
+        self.src.ts(" */\n");
     }
 
     fn ts_func(&mut self, iface: &Interface, func: &Function) {
@@ -356,11 +444,23 @@ impl Js {
 }
 
 impl Generator for Js {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            anonymous_records: false,
+            anonymous_variants: false,
+            ..Capabilities::default()
+        }
+    }
+
     fn preprocess_one(&mut self, iface: &Interface, dir: Direction) {
         self.sizes.fill(dir, iface);
         self.in_import = dir == Direction::Import;
     }
 
+    fn origin(&mut self, origin: Option<Origin>) {
+        self.origin = origin;
+    }
+
     fn type_record(
         &mut self,
         iface: &Interface,
@@ -389,20 +489,21 @@ impl Generator for Js {
                 ""
             };
             let name = name.to_shouty_snake_case();
-            for (i, field) in record.fields.iter().enumerate() {
+            for field in record.fields.iter() {
+                let bit = field.bit;
                 let field = field.name.to_shouty_snake_case();
                 self.src.js(&format!(
                     "export const {}_{} = {}{};\n",
                     name,
                     field,
-                    1u64 << i,
+                    1u64 << bit,
                     suffix,
                 ));
                 self.src.ts(&format!(
                     "export const {}_{} = {}{};\n",
                     name,
                     field,
-                    1u64 << i,
+                    1u64 << bit,
                     suffix,
                 ));
             }
@@ -428,12 +529,7 @@ impl Generator for Js {
         docs: &Docs,
     ) {
         self.docs(docs);
-        if variant.is_bool() {
-            self.src.ts(&format!(
-                "export type {} = boolean;\n",
-                name.to_camel_case(),
-            ));
-        } else if self.is_nullable_option(iface, variant) {
+        if self.is_nullable_option(iface, variant) {
             self.src
                 .ts(&format!("export type {} = ", name.to_camel_case()));
             self.print_ty(iface, variant.cases[1].ty.as_ref().unwrap());
@@ -441,10 +537,10 @@ impl Generator for Js {
         } else if variant.is_enum() {
             self.src
                 .ts(&format!("export enum {} {{\n", name.to_camel_case()));
-            for (i, case) in variant.cases.iter().enumerate() {
+            for case in variant.cases.iter() {
                 self.docs(&case.docs);
                 let name = case.name.to_camel_case();
-                self.src.ts(&format!("{} = {},\n", name, i));
+                self.src.ts(&format!("{} = {},\n", name, case.discriminant));
             }
             self.src.ts("}\n");
 
@@ -452,10 +548,12 @@ impl Generator for Js {
                 "export const {} = Object.freeze({{\n",
                 name.to_camel_case()
             ));
-            for (i, case) in variant.cases.iter().enumerate() {
+            for case in variant.cases.iter() {
                 let name = case.name.to_camel_case();
-                self.src.js(&format!("{}: \"{}\",\n", i, name));
-                self.src.js(&format!("\"{}\": {},\n", name, i));
+                self.src
+                    .js(&format!("{}: \"{}\",\n", case.discriminant, name));
+                self.src
+                    .js(&format!("\"{}\": {},\n", name, case.discriminant));
             }
             self.src.js("});\n");
         } else {
@@ -510,6 +608,18 @@ impl Generator for Js {
         self.src.ts(";\n");
     }
 
+    fn type_array(
+        &mut self,
+        iface: &Interface,
+        id: TypeId,
+        name: &str,
+        ty: &Type,
+        _len: u32,
+        docs: &Docs,
+    ) {
+        self.type_list(iface, id, name, ty, docs);
+    }
+
     fn type_pointer(
         &mut self,
         iface: &Interface,
@@ -556,10 +666,66 @@ impl Generator for Js {
         self.src.ts(";\n");
     }
 
+    fn const_(
+        &mut self,
+        iface: &Interface,
+        name: &str,
+        ty: &Type,
+        value: &ConstValue,
+        docs: &Docs,
+    ) {
+        self.docs(docs);
+        let name = name.to_shouty_snake_case();
+        self.src.ts(&format!("export const {}: ", name));
+        self.print_ty(iface, ty);
+        self.src.ts(";\n");
+
+        let value = match value {
+            ConstValue::Bool(b) => b.to_string(),
+            ConstValue::Unsigned(n) if self.is_bigint(iface, ty) => format!("{}n", n),
+            ConstValue::Signed(n) if self.is_bigint(iface, ty) => format!("{}n", n),
+            ConstValue::Unsigned(n) => n.to_string(),
+            ConstValue::Signed(n) => n.to_string(),
+            // `Debug` escapes are all understood by JS as well.
+            ConstValue::Float(n) => format!("{:?}", n),
+            ConstValue::Char(c) => format!("{:?}", c.to_string()),
+            ConstValue::String(s) => format!("{:?}", s),
+            ConstValue::Enum { ty, case } => {
+                let name = iface.types[*ty].name.as_ref().unwrap();
+                format!("{}.{}", name.to_camel_case(), case.to_camel_case())
+            }
+            ConstValue::Flags { ty, flags } => {
+                let name = iface.types[*ty].name.as_ref().unwrap();
+                if flags.is_empty() {
+                    if self.is_bigint(iface, &Type::Id(*ty)) {
+                        "0n".to_string()
+                    } else {
+                        "0".to_string()
+                    }
+                } else {
+                    flags
+                        .iter()
+                        .map(|f| {
+                            format!(
+                                "{}_{}",
+                                name.to_shouty_snake_case(),
+                                f.to_shouty_snake_case()
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join(" | ")
+                }
+            }
+        };
+        self.src
+            .js(&format!("export const {} = {};\n", name, value));
+    }
+
     fn import(&mut self, iface: &Interface, func: &Function) {
         let prev = mem::take(&mut self.src);
+        self.src.js.set_origin(self.origin.clone());
 
-        let sig = iface.wasm_signature(Direction::Import, func);
+        let sig = iface.wasm_signature(Direction::Import, self.opts.multi_value, func);
         let params = (0..sig.params.len())
             .map(|i| format!("arg{}", i))
             .collect::<Vec<_>>();
@@ -571,6 +737,8 @@ impl Generator for Js {
         iface.call(
             Direction::Import,
             LiftLower::LiftArgsLowerResults,
+            f.gen.opts.string_encoding,
+            f.gen.opts.multi_value,
             func,
             &mut f,
         );
@@ -629,6 +797,7 @@ impl Generator for Js {
 
     fn export(&mut self, iface: &Interface, func: &Function) {
         let prev = mem::take(&mut self.src);
+        self.src.js.set_origin(self.origin.clone());
 
         let mut params = func
             .params
@@ -670,6 +839,8 @@ impl Generator for Js {
         iface.call(
             Direction::Export,
             LiftLower::LowerArgsLiftResults,
+            f.gen.opts.string_encoding,
+            f.gen.opts.multi_value,
             func,
             &mut f,
         );
@@ -758,12 +929,10 @@ impl Generator for Js {
                 .iter()
                 .chain(funcs.resource_funcs.values().flat_map(|v| v))
             {
-                self.src.js(&format!(
-                    "imports[\"{}\"][\"{}\"] = {};\n",
-                    module,
-                    name,
-                    src.js.trim(),
-                ));
+                self.src
+                    .js(&format!("imports[\"{}\"][\"{}\"] = ", module, name));
+                self.src.js.append(&src.js);
+                self.src.js(";\n");
             }
 
             for (_, src) in funcs.freestanding_funcs.iter() {
@@ -975,7 +1144,7 @@ impl Generator for Js {
             self.src.js("}\n");
 
             for func in exports.freestanding_funcs.iter() {
-                self.src.js(&func.js);
+                self.src.js.append(&func.js);
                 self.src.ts(&func.ts);
             }
             self.src.ts("}\n");
@@ -1047,7 +1216,7 @@ impl Generator for Js {
 
                 if let Some(funcs) = exports.resource_funcs.get(&ty) {
                     for func in funcs {
-                        self.src.js(&func.js);
+                        self.src.js.append(&func.js);
                         self.src.ts(&func.ts);
                     }
                 }
@@ -1101,14 +1270,21 @@ impl Generator for Js {
             self.src.js(" } from './intrinsics.js';\n");
         }
 
-        self.src.js(&imports.js);
+        self.src.js.append(&imports.js);
         self.src.ts(&imports.ts);
-        self.src.js(&exports.js);
+        self.src.js.append(&exports.js);
         self.src.ts(&exports.ts);
 
-        let src = mem::take(&mut self.src);
+        let mut src = mem::take(&mut self.src);
         let name = iface.name.to_snake_case();
-        files.push(&format!("{}.js", name), src.js.as_bytes());
+        if files.source_maps() {
+            // `Files::push_source` writes the map next to the file.
+            if !src.js.ends_with('\n') {
+                src.js("\n");
+            }
+            src.js(&format!("//# sourceMappingURL={}.js.map\n", name));
+        }
+        files.push_source(&format!("{}.js", name), &src.js);
         if !self.opts.no_typescript {
             files.push(&format!("{}.d.ts", name), src.ts.as_bytes());
         }
@@ -1345,6 +1521,17 @@ impl Bindgen for FunctionBindgen<'_> {
                 results.push(format!("{}({})", validate, operands[0]));
             }
 
+            // Booleans from the host must actually be booleans, and values
+            // coming from wasm must be either 0 or 1.
+            Instruction::I32FromBool => {
+                let validate = self.gen.intrinsic(Intrinsic::ValidateHostBool);
+                results.push(format!("{}({})", validate, operands[0]));
+            }
+            Instruction::BoolFromI32 => {
+                let validate = self.gen.intrinsic(Intrinsic::ValidateGuestBool);
+                results.push(format!("{}({})", validate, operands[0]));
+            }
+
             Instruction::Bitcasts { casts } => {
                 for (cast, op) in casts.iter().zip(operands) {
                     match cast {
@@ -1428,6 +1615,115 @@ impl Bindgen for FunctionBindgen<'_> {
                 ));
             }
 
+            Instruction::ArrayLower { len, .. } => {
+                // Arrays are lowered with destructuring assignment after
+                // checking the length, since extra elements would otherwise
+                // be ignored and missing ones would be `undefined`.
+                let tmp = self.tmp();
+                self.src
+                    .js(&format!("const array{} = {};\n", tmp, operands[0]));
+                self.src
+                    .js(&format!("if (array{}.length !== {})\n", tmp, len));
+                self.src.js(&format!(
+                    "throw new RangeError(\"expected an array of {} elements\");\n",
+                    len,
+                ));
+                let mut expr = "const [".to_string();
+                for i in 0..*len {
+                    if i > 0 {
+                        expr.push_str(", ");
+                    }
+                    let name = format!("array{}_{}", tmp, i);
+                    expr.push_str(&name);
+                    results.push(name);
+                }
+                self.src.js(&format!("{}] = array{};\n", expr, tmp));
+            }
+            Instruction::ArrayLift { element, .. } => {
+                let elements = operands.join(", ");
+                match self.gen.array_ty(iface, element) {
+                    Some(ty) => results.push(format!("new {}([{}])", ty, elements)),
+                    None => results.push(format!("[{}]", elements)),
+                }
+            }
+            Instruction::ArrayLowerMemory {
+                element,
+                len,
+                realloc,
+                ..
+            } => {
+                let realloc = realloc.unwrap();
+                let (body, body_results) = self.blocks.pop().unwrap();
+                assert!(body_results.is_empty());
+                let tmp = self.tmp();
+                let array = format!("array{}", tmp);
+                let result = format!("result{}", tmp);
+                self.needs_realloc = Some(realloc.to_string());
+                let size = self.gen.sizes.size(element);
+                let align = self.gen.sizes.align(element);
+
+                // The length is checked as with `ArrayLower`, and then the
+                // elements are written to memory as with lists.
+                self.src
+                    .js(&format!("const {} = {};\n", array, operands[0]));
+                self.src.js(&format!("if ({}.length !== {})\n", array, len));
+                self.src.js(&format!(
+                    "throw new RangeError(\"expected an array of {} elements\");\n",
+                    len,
+                ));
+                self.src.js(&format!(
+                    "const {} = realloc(0, 0, {}, {});\n",
+                    result,
+                    align,
+                    size * *len as usize,
+                ));
+                self.src
+                    .js(&format!("for (let i = 0; i < {}; i++) {{\n", len));
+                self.src.js(&format!("const e = {}[i];\n", array));
+                self.src
+                    .js(&format!("const base = {} + i * {};\n", result, size));
+                self.src.js(&body);
+                self.src.js("}\n");
+
+                results.push(result);
+            }
+            Instruction::ArrayLiftMemory {
+                element, len, free, ..
+            } => {
+                let (body, body_results) = self.blocks.pop().unwrap();
+                let tmp = self.tmp();
+                let size = self.gen.sizes.size(element);
+                let align = self.gen.sizes.align(element);
+                let base = format!("base{}", tmp);
+                self.src.js(&format!("const {} = {};\n", base, operands[0]));
+                let result = format!("result{}", tmp);
+                self.src.js(&format!("const {} = [];\n", result));
+
+                self.src
+                    .js(&format!("for (let i = 0; i < {}; i++) {{\n", len));
+                self.src
+                    .js(&format!("const base = {} + i * {};\n", base, size));
+                self.src.js(&body);
+                assert_eq!(body_results.len(), 1);
+                self.src
+                    .js(&format!("{}.push({});\n", result, body_results[0]));
+                self.src.js("}\n");
+
+                if let Some(free) = free {
+                    self.needs_free = Some(free.to_string());
+                    self.src.js(&format!(
+                        "free({}, {}, {});\n",
+                        base,
+                        size * *len as usize,
+                        align,
+                    ));
+                }
+                match self.gen.array_ty(iface, element) {
+                    Some(ty) => results.push(format!("new {}({})", ty, result)),
+                    None => results.push(result),
+                }
+            }
+
             Instruction::RecordLower { record, .. } => {
                 if record.is_tuple() {
                     // Tuples are represented as an array, sowe can use
@@ -1513,11 +1809,7 @@ impl Bindgen for FunctionBindgen<'_> {
                 self.src
                     .js(&format!("const variant{} = {};\n", tmp, operands[0]));
 
-                if result_types.len() == 1
-                    && variant.is_enum()
-                    && name.is_some()
-                    && !variant.is_bool()
-                {
+                if result_types.len() == 1 && variant.is_enum() && name.is_some() {
                     let name = name.unwrap().to_camel_case();
                     self.src
                         .js(&format!("if (!(variant{} in {}))\n", tmp, name));
@@ -1537,8 +1829,7 @@ impl Bindgen for FunctionBindgen<'_> {
                     results.push(format!("variant{}_{}", tmp, i));
                 }
 
-                let expr_to_match = if variant.is_bool()
-                    || self.gen.is_nullable_option(iface, variant)
+                let expr_to_match = if self.gen.is_nullable_option(iface, variant)
                     || (variant.is_enum() && name.is_some())
                 {
                     format!("variant{}", tmp)
@@ -1548,12 +1839,8 @@ impl Bindgen for FunctionBindgen<'_> {
 
                 self.src.js(&format!("switch ({}) {{\n", expr_to_match));
                 let mut use_default = true;
-                for (i, (case, (block, block_results))) in
-                    variant.cases.iter().zip(blocks).enumerate()
-                {
-                    if variant.is_bool() {
-                        self.src.js(&format!("case {}: {{\n", case.name.as_str()));
-                    } else if self.gen.is_nullable_option(iface, variant) {
+                for (case, (block, block_results)) in variant.cases.iter().zip(blocks) {
+                    if self.gen.is_nullable_option(iface, variant) {
                         if case.ty.is_none() {
                             self.src.js("case null: {\n");
                         } else {
@@ -1562,7 +1849,7 @@ impl Bindgen for FunctionBindgen<'_> {
                             use_default = false;
                         }
                     } else if variant.is_enum() && name.is_some() {
-                        self.src.js(&format!("case {}: {{\n", i));
+                        self.src.js(&format!("case {}: {{\n", case.discriminant));
                         self.src.js(&format!("const e = variant{};\n", tmp));
                     } else {
                         self.src
@@ -1582,9 +1869,7 @@ impl Bindgen for FunctionBindgen<'_> {
                 if use_default {
                     let variant_name = name.map(|s| s.to_camel_case());
                     let variant_name = variant_name.as_deref().unwrap_or_else(|| {
-                        if variant.is_bool() {
-                            "bool"
-                        } else if variant.as_expected().is_some() {
+                        if variant.as_expected().is_some() {
                             "expected"
                         } else if variant.as_option().is_some() {
                             "option"
@@ -1608,7 +1893,7 @@ impl Bindgen for FunctionBindgen<'_> {
                     .collect::<Vec<_>>();
 
                 let tmp = self.tmp();
-                if variant.is_enum() && name.is_some() && !variant.is_bool() {
+                if variant.is_enum() && name.is_some() {
                     let name = name.unwrap().to_camel_case();
                     self.src
                         .js(&format!("const tag{} = {};\n", tmp, operands[0]));
@@ -1623,17 +1908,11 @@ impl Bindgen for FunctionBindgen<'_> {
 
                 self.src.js(&format!("let variant{};\n", tmp));
                 self.src.js(&format!("switch ({}) {{\n", operands[0]));
-                for (i, (case, (block, block_results))) in
-                    variant.cases.iter().zip(blocks).enumerate()
-                {
-                    self.src.js(&format!("case {}: {{\n", i));
+                for (case, (block, block_results)) in variant.cases.iter().zip(blocks) {
+                    self.src.js(&format!("case {}: {{\n", case.discriminant));
                     self.src.js(&block);
 
-                    if variant.is_bool() {
-                        assert!(block_results.is_empty());
-                        self.src
-                            .js(&format!("variant{} = {};\n", tmp, case.name.as_str()));
-                    } else if variant.is_enum() && name.is_some() {
+                    if variant.is_enum() && name.is_some() {
                         assert!(block_results.is_empty());
                         self.src.js(&format!("variant{} = tag{0};\n", tmp));
                     } else if self.gen.is_nullable_option(iface, variant) {
@@ -1660,9 +1939,7 @@ impl Bindgen for FunctionBindgen<'_> {
                 }
                 let variant_name = name.map(|s| s.to_camel_case());
                 let variant_name = variant_name.as_deref().unwrap_or_else(|| {
-                    if variant.is_bool() {
-                        "bool"
-                    } else if variant.as_expected().is_some() {
+                    if variant.as_expected().is_some() {
                         "expected"
                     } else if variant.as_option().is_some() {
                         "option"
@@ -1689,8 +1966,62 @@ impl Bindgen for FunctionBindgen<'_> {
                 self.needs_realloc = Some(realloc.to_string());
                 let tmp = self.tmp();
 
-                match element {
-                    Type::Char => {
+                let size = self.gen.sizes.size(element);
+                let align = self.gen.sizes.align(element);
+                self.src
+                    .js(&format!("const val{} = {};\n", tmp, operands[0]));
+                self.src.js(&format!("const len{} = val{0}.length;\n", tmp));
+                self.src.js(&format!(
+                    "const ptr{} = realloc(0, 0, {}, len{0} * {});\n",
+                    tmp, align, size,
+                ));
+                // TODO: this is the wrong endianness
+                self.src.js(&format!(
+                    "(new Uint8Array(memory.buffer, ptr{}, len{0} * {})).set(new Uint8Array(val{0}.buffer));\n",
+                    tmp, size,
+                ));
+                results.push(format!("ptr{}", tmp));
+                results.push(format!("len{}", tmp));
+            }
+            Instruction::ListCanonLift { element, free, .. } => {
+                self.needs_memory = true;
+                let tmp = self.tmp();
+                self.src
+                    .js(&format!("const ptr{} = {};\n", tmp, operands[0]));
+                self.src
+                    .js(&format!("const len{} = {};\n", tmp, operands[1]));
+                // TODO: this is the wrong endianness
+                let array_ty = self.gen.array_ty(iface, element).unwrap();
+                let result = format!(
+                    "new {}(memory.buffer.slice(ptr{}, ptr{1} + len{1} * {}))",
+                    array_ty,
+                    tmp,
+                    self.gen.sizes.size(element),
+                );
+                let align = self.gen.sizes.align(element);
+                match free {
+                    Some(free) => {
+                        self.needs_free = Some(free.to_string());
+                        self.src.js(&format!("const list{} = {};\n", tmp, result));
+                        self.src
+                            .js(&format!("free(ptr{}, len{0}, {});\n", tmp, align));
+                        results.push(format!("list{}", tmp));
+                    }
+                    None => results.push(result),
+                }
+            }
+
+            Instruction::StringLower { realloc, encoding } => {
+                // Like lists, strings are only lowered when they're passed
+                // into wasm, so `realloc` should always be `Some`.
+                let realloc = realloc.unwrap();
+                self.gen.needs_get_export = true;
+                self.needs_memory = true;
+                self.needs_realloc = Some(realloc.to_string());
+                let tmp = self.tmp();
+
+                match encoding {
+                    StringEncoding::Utf8 => {
                         let encode = self.gen.intrinsic(Intrinsic::Utf8Encode);
                         self.src.js(&format!(
                             "const ptr{} = {}({}, realloc, memory);\n",
@@ -1700,55 +2031,71 @@ impl Bindgen for FunctionBindgen<'_> {
                         self.src
                             .js(&format!("const len{} = {};\n", tmp, encoded_len));
                     }
-                    _ => {
-                        let size = self.gen.sizes.size(element);
-                        let align = self.gen.sizes.align(element);
+                    // JS strings are already UTF-16 so their length is the
+                    // number of code units.
+                    StringEncoding::Utf16 => {
+                        let encode = self.gen.intrinsic(Intrinsic::Utf16Encode);
                         self.src
                             .js(&format!("const val{} = {};\n", tmp, operands[0]));
-                        self.src.js(&format!("const len{} = val{0}.length;\n", tmp));
                         self.src.js(&format!(
-                            "const ptr{} = realloc(0, 0, {}, len{0} * {});\n",
-                            tmp, align, size,
+                            "const ptr{0} = {1}(val{0}, realloc, memory);\n",
+                            tmp, encode,
                         ));
-                        // TODO: this is the wrong endianness
+                        self.src
+                            .js(&format!("const len{0} = val{0}.length;\n", tmp));
+                    }
+                    StringEncoding::CompactUtf16 => {
+                        let encode = self.gen.intrinsic(Intrinsic::CompactUtf16Encode);
                         self.src.js(&format!(
-                            "(new Uint8Array(memory.buffer, ptr{}, len{0} * {})).set(new Uint8Array(val{0}.buffer));\n",
-                            tmp, size,
+                            "const ptr{} = {}({}, realloc, memory);\n",
+                            tmp, encode, operands[0],
                         ));
+                        let encoded_len = self.gen.intrinsic(Intrinsic::CompactUtf16EncodedLen);
+                        self.src
+                            .js(&format!("const len{} = {};\n", tmp, encoded_len));
                     }
-                };
+                }
                 results.push(format!("ptr{}", tmp));
                 results.push(format!("len{}", tmp));
             }
-            Instruction::ListCanonLift { element, free, .. } => {
+            Instruction::StringLift { free, encoding } => {
                 self.needs_memory = true;
                 let tmp = self.tmp();
                 self.src
                     .js(&format!("const ptr{} = {};\n", tmp, operands[0]));
                 self.src
                     .js(&format!("const len{} = {};\n", tmp, operands[1]));
-                let (result, align) = match element {
-                    Type::Char => {
+                // The size and alignment of the string's allocation, used to
+                // free it.
+                let (result, size, align) = match encoding {
+                    StringEncoding::Utf8 => {
                         let decoder = self.gen.intrinsic(Intrinsic::Utf8Decoder);
                         (
                             format!(
                                 "{}.decode(new Uint8Array(memory.buffer, ptr{}, len{1}))",
                                 decoder, tmp,
                             ),
-                            1,
+                            format!("len{}", tmp),
+                            "1".to_string(),
                         )
                     }
-                    _ => {
-                        // TODO: this is the wrong endianness
-                        let array_ty = self.gen.array_ty(iface, element).unwrap();
+                    StringEncoding::Utf16 => {
+                        let decoder = self.gen.intrinsic(Intrinsic::Utf16Decoder);
                         (
                             format!(
-                                "new {}(memory.buffer.slice(ptr{}, ptr{1} + len{1} * {}))",
-                                array_ty,
-                                tmp,
-                                self.gen.sizes.size(element),
+                                "{}.decode(new Uint16Array(memory.buffer, ptr{}, len{1}))",
+                                decoder, tmp,
                             ),
-                            self.gen.sizes.align(element),
+                            format!("len{} * 2", tmp),
+                            "2".to_string(),
+                        )
+                    }
+                    StringEncoding::CompactUtf16 => {
+                        let decode = self.gen.intrinsic(Intrinsic::CompactUtf16Decode);
+                        (
+                            format!("{}(memory, ptr{}, len{1})", decode, tmp),
+                            format!("len{0} < 0 ? (len{0} & 0x7fffffff) * 2 : len{0}", tmp),
+                            format!("len{} < 0 ? 2 : 1", tmp),
                         )
                     }
                 };
@@ -1757,7 +2104,7 @@ impl Bindgen for FunctionBindgen<'_> {
                         self.needs_free = Some(free.to_string());
                         self.src.js(&format!("const list{} = {};\n", tmp, result));
                         self.src
-                            .js(&format!("free(ptr{}, len{0}, {});\n", tmp, align));
+                            .js(&format!("free(ptr{}, {}, {});\n", tmp, size, align));
                         results.push(format!("list{}", tmp));
                     }
                     None => results.push(result),
@@ -2284,6 +2631,22 @@ impl Js {
                 }
             "),
 
+            Intrinsic::ValidateGuestBool => self.src.js("
+                export function validate_guest_bool(i) {
+                    if (i !== 0 && i !== 1) \
+                        throw new RangeError(`invalid variant discriminant for bool`);
+                    return i === 1;
+                }
+            "),
+
+            Intrinsic::ValidateHostBool => self.src.js("
+                export function validate_host_bool(b) {
+                    if (typeof b !== 'boolean') \
+                        throw new TypeError(`must be a boolean`);
+                    return b ? 1 : 0;
+                }
+            "),
+
             Intrinsic::ValidateFlags => self.src.js("
                 export function validate_flags(flags, mask) {
                     if (!Number.isInteger(flags)) \
@@ -2367,6 +2730,85 @@ impl Js {
                 }
             "),
 
+            Intrinsic::Utf16Decoder => self
+                .src
+                .js("export const UTF16_DECODER = new TextDecoder('utf-16le');\n"),
+
+            Intrinsic::Utf16Encode => self.src.js("
+                export function utf16_encode(s, realloc, memory) {
+                    if (typeof s !== 'string') \
+                        throw new TypeError('expected a string');
+
+                    if (s.length === 0)
+                        return 2;
+
+                    const ptr = realloc(0, 0, 2, s.length * 2);
+                    const units = new Uint16Array(memory.buffer, ptr, s.length);
+                    for (let i = 0; i < s.length; i++)
+                        units[i] = s.charCodeAt(i);
+                    return ptr;
+                }
+            "),
+
+            Intrinsic::CompactUtf16EncodedLen => {
+                self.src.js("export let COMPACT_UTF16_ENCODED_LEN = 0;\n")
+            }
+
+            // Strings are stored as Latin-1 if all of their code units fit in
+            // a byte, and as UTF-16 with the high bit of their length set
+            // otherwise.
+            Intrinsic::CompactUtf16Encode => self.src.js("
+                export function compact_utf16_encode(s, realloc, memory) {
+                    if (typeof s !== 'string') \
+                        throw new TypeError('expected a string');
+
+                    COMPACT_UTF16_ENCODED_LEN = s.length;
+                    if (s.length === 0)
+                        return 1;
+
+                    let latin1 = true;
+                    for (let i = 0; i < s.length; i++) {
+                        if (s.charCodeAt(i) > 0xff) {
+                            latin1 = false;
+                            break;
+                        }
+                    }
+
+                    if (latin1) {
+                        const ptr = realloc(0, 0, 1, s.length);
+                        const bytes = new Uint8Array(memory.buffer, ptr, s.length);
+                        for (let i = 0; i < s.length; i++)
+                            bytes[i] = s.charCodeAt(i);
+                        return ptr;
+                    }
+
+                    const ptr = realloc(0, 0, 2, s.length * 2);
+                    const units = new Uint16Array(memory.buffer, ptr, s.length);
+                    for (let i = 0; i < s.length; i++)
+                        units[i] = s.charCodeAt(i);
+                    COMPACT_UTF16_ENCODED_LEN = s.length | 0x80000000;
+                    return ptr;
+                }
+            "),
+
+            Intrinsic::CompactUtf16Decode => self.src.js("
+                const COMPACT_UTF16_DECODER = new TextDecoder('utf-16le');
+
+                export function compact_utf16_decode(memory, ptr, len) {
+                    if (len & 0x80000000) {
+                        const units = new Uint16Array(memory.buffer, ptr, len & 0x7fffffff);
+                        return COMPACT_UTF16_DECODER.decode(units);
+                    }
+                    // Note that the `latin1` `TextDecoder` is actually
+                    // windows-1252, so bytes are mapped to code points here.
+                    const bytes = new Uint8Array(memory.buffer, ptr, len);
+                    let s = '';
+                    for (let i = 0; i < bytes.length; i += 4096)
+                        s += String.fromCharCode(...bytes.subarray(i, i + 4096));
+                    return s;
+                }
+            "),
+
             Intrinsic::Slab => self.src.js("
                 export class Slab {
                     constructor() {
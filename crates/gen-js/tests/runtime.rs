@@ -2,11 +2,49 @@ use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
+use witx_bindgen_gen_core::witx2::abi::{MultiValue, StringEncoding};
 use witx_bindgen_gen_core::Generator;
+use witx_bindgen_gen_js::Opts;
 
 test_helpers::runtime_tests!("ts");
+test_helpers::runtime_tests_string_encodings!("ts");
+test_helpers::runtime_tests_multi_value!("ts");
 
 fn execute(name: &str, wasm: &Path, ts: &Path, imports: &Path, exports: &Path) {
+    execute_opts(name, wasm, ts, imports, exports, Opts::default())
+}
+
+fn execute_string_encoding(
+    name: &str,
+    wasm: &Path,
+    ts: &Path,
+    imports: &Path,
+    exports: &Path,
+    string_encoding: StringEncoding,
+) {
+    let opts = Opts {
+        string_encoding,
+        ..Default::default()
+    };
+    execute_opts(name, wasm, ts, imports, exports, opts)
+}
+
+fn execute_multi_value(
+    name: &str,
+    wasm: &Path,
+    ts: &Path,
+    imports: &Path,
+    exports: &Path,
+    multi_value: MultiValue,
+) {
+    let opts = Opts {
+        multi_value,
+        ..Default::default()
+    };
+    execute_opts(name, wasm, ts, imports, exports, opts)
+}
+
+fn execute_opts(name: &str, wasm: &Path, ts: &Path, imports: &Path, exports: &Path, opts: Opts) {
     let mut dir = PathBuf::from(env!("OUT_DIR"));
     dir.push(name);
     drop(fs::remove_dir_all(&dir));
@@ -17,8 +55,7 @@ fn execute(name: &str, wasm: &Path, ts: &Path, imports: &Path, exports: &Path) {
     let imports = witx_bindgen_gen_core::witx2::Interface::parse_file(imports).unwrap();
     let exports = witx_bindgen_gen_core::witx2::Interface::parse_file(exports).unwrap();
     let mut files = Default::default();
-    witx_bindgen_gen_js::Opts::default()
-        .build()
+    opts.build()
         .generate_all(&[imports], &[exports], &mut files);
     for (file, contents) in files.iter() {
         fs::write(dir.join(file), contents).unwrap();
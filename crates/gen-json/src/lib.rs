@@ -0,0 +1,155 @@
+use witx2::*;
+use witx_bindgen_gen_core::{Files, Generator};
+
+/// Generates a JSON description of each interface in the format defined by
+/// `witx2::json`.
+#[derive(Default)]
+pub struct Json {
+    opts: Opts,
+}
+
+#[derive(Default, Debug, Clone)]
+#[cfg_attr(feature = "structopt", derive(structopt::StructOpt))]
+pub struct Opts {
+    /// Emits JSON without any whitespace instead of pretty-printing it.
+    #[cfg_attr(feature = "structopt", structopt(long))]
+    pub compact: bool,
+}
+
+impl Opts {
+    pub fn build(&self) -> Json {
+        let mut r = Json::new();
+        r.opts = self.clone();
+        r
+    }
+}
+
+impl Json {
+    pub fn new() -> Json {
+        Json::default()
+    }
+}
+
+// Everything about an interface is described at once in `finish_one`, so
+// none of the per-item callbacks need to do anything.
+impl Generator for Json {
+    fn type_record(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _record: &Record,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_variant(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _variant: &Variant,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_resource(&mut self, _iface: &Interface, _ty: ResourceId) {}
+
+    fn type_alias(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_list(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_array(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _len: u32,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_pointer(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _const_: bool,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_builtin(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_push_buffer(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_pull_buffer(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn const_(
+        &mut self,
+        _iface: &Interface,
+        _name: &str,
+        _ty: &Type,
+        _value: &ConstValue,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn import(&mut self, _iface: &Interface, _func: &Function) {}
+
+    fn export(&mut self, _iface: &Interface, _func: &Function) {}
+
+    fn finish_one(&mut self, iface: &Interface, files: &mut Files) {
+        let json = json::Interface::new(iface);
+        let mut contents = if self.opts.compact {
+            serde_json::to_string(&json)
+        } else {
+            serde_json::to_string_pretty(&json)
+        }
+        .unwrap();
+        contents.push('\n');
+        files.push(&format!("{}.json", iface.name), contents.as_bytes());
+    }
+}
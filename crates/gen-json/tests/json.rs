@@ -0,0 +1,110 @@
+use serde_json::json;
+use std::fs;
+use std::path::Path;
+use witx2::abi::Direction;
+use witx2::json;
+use witx_bindgen_gen_core::{Files, Generator};
+
+fn generate(iface: &witx2::Interface, dir: Direction) -> json::Interface {
+    let mut files = Files::default();
+    let mut gen = witx_bindgen_gen_json::Opts::default().build();
+    gen.generate_one(iface, dir, &mut files);
+    let (name, contents) = files.iter().next().unwrap();
+    assert_eq!(name, format!("{}.json", iface.name));
+    serde_json::from_slice(contents).unwrap()
+}
+
+#[test]
+fn corpus() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../witx2/tests/ui");
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|s| s.to_str()) != Some("witx") {
+            continue;
+        }
+        let iface = witx2::Interface::parse_file(&path).unwrap();
+        let json = generate(&iface, Direction::Import);
+        assert_eq!(json, json::Interface::new(&iface), "{:?}", path);
+        assert_eq!(json, generate(&iface, Direction::Export), "{:?}", path);
+
+        assert_eq!(json.version, json::VERSION);
+        assert_eq!(json.types.len(), iface.types.len());
+        let mut sizes = witx2::SizeAlign::default();
+        sizes.fill(Direction::Export, &iface);
+        for ((id, _), ty) in iface.types.iter().zip(&json.types) {
+            let id = witx2::Type::Id(id);
+            assert_eq!(ty.layout.export.size, sizes.size(&id));
+            assert_eq!(ty.layout.export.align, sizes.align(&id));
+        }
+    }
+}
+
+#[test]
+fn format() {
+    let iface = witx2::Interface::parse(
+        "foo",
+        "
+            /// A point
+            record point { x: u32, y: u64 }
+            resource r { get: function() -> point }
+        ",
+    )
+    .unwrap();
+    let json = serde_json::to_value(json::Interface::new(&iface)).unwrap();
+    assert_eq!(
+        json,
+        json!({
+            "version": 6,
+            "name": "foo",
+            "resources": [{ "name": "r" }],
+            "types": [
+                {
+                    "name": "point",
+                    "docs": "A point\n",
+                    "kind": {
+                        "record": {
+                            "kind": "other",
+                            "fields": [
+                                { "name": "x", "ty": "u32" },
+                                { "name": "y", "ty": "u64" },
+                            ],
+                        },
+                    },
+                    "layout": {
+                        "import": { "size": 16, "align": 8 },
+                        "export": { "size": 16, "align": 8 },
+                    },
+                },
+                {
+                    "kind": { "type": { "handle": 0 } },
+                    "layout": {
+                        "import": { "size": 4, "align": 4 },
+                        "export": { "size": 4, "align": 4 },
+                    },
+                },
+            ],
+            "functions": [{
+                "name": "r::get",
+                "kind": { "method": { "resource": 0, "name": "get" } },
+                "abi": "canonical",
+                "async": false,
+                "params": [{ "name": "self", "ty": { "handle": 0 } }],
+                "results": [{ "name": "", "ty": { "id": 0 } }],
+                "wasm_signature": {
+                    "import": {
+                        "params": ["i32", "i32"],
+                        "results": [],
+                        "retptr": ["i32", "i64"],
+                    },
+                    "export": {
+                        "params": ["i32"],
+                        "results": ["i32"],
+                        "retptr": ["i32", "i64"],
+                    },
+                },
+            }],
+            "globals": [],
+            "constants": [],
+        })
+    );
+}
@@ -13,6 +13,7 @@ pub struct Markdown {
     hrefs: HashMap<String, String>,
     funcs: usize,
     types: usize,
+    consts: usize,
 }
 
 #[derive(Default, Debug, Clone)]
@@ -49,6 +50,8 @@ impl Markdown {
             Type::Char => self.src.push_str("`char`"),
             Type::CChar => self.src.push_str("`c_char`"),
             Type::Usize => self.src.push_str("`usize`"),
+            Type::Bool => self.src.push_str("`bool`"),
+            Type::String => self.src.push_str("`string`"),
             Type::Handle(id) => {
                 self.src.push_str("handle<");
                 self.src.push_str(&iface.resources[*id].name);
@@ -80,9 +83,7 @@ impl Markdown {
                         self.src.push_str(")");
                     }
                     TypeDefKind::Variant(v) => {
-                        if v.is_bool() {
-                            self.src.push_str("`bool`");
-                        } else if let Some(t) = v.as_option() {
+                        if let Some(t) = v.as_option() {
                             self.src.push_str("option<");
                             self.print_ty(iface, t, false);
                             self.src.push_str(">");
@@ -102,12 +103,16 @@ impl Markdown {
                             unreachable!()
                         }
                     }
-                    TypeDefKind::List(Type::Char) => self.src.push_str("`string`"),
                     TypeDefKind::List(t) => {
                         self.src.push_str("list<");
                         self.print_ty(iface, t, false);
                         self.src.push_str(">");
                     }
+                    TypeDefKind::Array(t, len) => {
+                        self.src.push_str("array<");
+                        self.print_ty(iface, t, false);
+                        self.src.push_str(&format!(", {}>", len));
+                    }
                     TypeDefKind::PushBuffer(t) => {
                         self.src.push_str("push-buffer<");
                         self.print_ty(iface, t, false);
@@ -134,13 +139,23 @@ impl Markdown {
     }
 
     fn docs(&mut self, docs: &Docs) {
-        let docs = match &docs.contents {
-            Some(docs) => docs,
-            None => return,
-        };
-        for line in docs.lines() {
-            self.src.push_str(line.trim());
-            self.src.push_str("\n");
+        if let Some(reason) = &docs.deprecated {
+            self.src.push_str("**Deprecated**");
+            if !reason.is_empty() {
+                self.src.push_str(": ");
+                self.src.push_str(reason);
+            }
+            self.src.push_str("\n\n");
+        }
+        if let Some(description) = &docs.description {
+            for line in description.lines() {
+                self.src.push_str(line.trim());
+                self.src.push_str("\n");
+            }
+        }
+        if let Some(since) = &docs.since {
+            self.src
+                .push_str(&format!("\n*Available since {}.*\n", since));
         }
     }
 
@@ -185,7 +200,7 @@ impl Generator for Markdown {
         self.src.push_str("record\n\n");
         self.print_type_info(id, docs);
         self.src.push_str("\n### Record Fields\n\n");
-        for (i, field) in record.fields.iter().enumerate() {
+        for field in record.fields.iter() {
             self.src.push_str(&format!(
                 "- <a href=\"{r}.{f}\" name=\"{r}.{f}\"></a> [`{name}`](#{r}.{f}): ",
                 r = name.to_snake_case(),
@@ -202,7 +217,7 @@ impl Generator for Markdown {
             self.docs(&field.docs);
             self.src.deindent(1);
             if record.is_flags() {
-                self.src.push_str(&format!("Bit: {}\n", i));
+                self.src.push_str(&format!("Bit: {}\n", field.bit));
             }
             self.src.push_str("\n");
         }
@@ -239,6 +254,10 @@ impl Generator for Markdown {
             self.src.push_str("\n\n");
             self.docs(&case.docs);
             self.src.deindent(1);
+            if !variant.has_default_discriminants() {
+                self.src
+                    .push_str(&format!("Discriminant: {}\n", case.discriminant));
+            }
             self.src.push_str("\n");
         }
     }
@@ -259,6 +278,18 @@ impl Generator for Markdown {
         self.type_alias(iface, id, name, &Type::Id(id), docs);
     }
 
+    fn type_array(
+        &mut self,
+        iface: &Interface,
+        id: TypeId,
+        name: &str,
+        _ty: &Type,
+        _len: u32,
+        docs: &Docs,
+    ) {
+        self.type_alias(iface, id, name, &Type::Id(id), docs);
+    }
+
     fn type_pointer(
         &mut self,
         iface: &Interface,
@@ -297,6 +328,41 @@ impl Generator for Markdown {
         self.type_alias(iface, id, name, &Type::Id(id), docs);
     }
 
+    fn const_(
+        &mut self,
+        iface: &Interface,
+        name: &str,
+        ty: &Type,
+        value: &ConstValue,
+        docs: &Docs,
+    ) {
+        if self.consts == 0 {
+            self.src.push_str("# Constants\n\n");
+        }
+        self.consts += 1;
+        self.src.push_str(&format!(
+            "## <a href=\"#{}\" name=\"{0}\"></a> `{}`: ",
+            name.to_snake_case(),
+            name,
+        ));
+        self.hrefs
+            .insert(name.to_string(), format!("#{}", name.to_snake_case()));
+        self.print_ty(iface, ty, false);
+        self.src.push_str("\n\n");
+        self.docs(docs);
+        let value = match value {
+            ConstValue::Bool(b) => b.to_string(),
+            ConstValue::Unsigned(n) => n.to_string(),
+            ConstValue::Signed(n) => n.to_string(),
+            ConstValue::Float(n) => format!("{:?}", n),
+            ConstValue::Char(c) => format!("{:?}", c),
+            ConstValue::String(s) => format!("{:?}", s),
+            ConstValue::Enum { case, .. } => case.clone(),
+            ConstValue::Flags { flags, .. } => format!("{{{}}}", flags.join(", ")),
+        };
+        self.src.push_str(&format!("\nValue: `{}`\n\n", value));
+    }
+
     fn import(&mut self, iface: &Interface, func: &Function) {
         if self.funcs == 0 {
             self.src.push_str("# Functions\n\n");
@@ -325,6 +391,10 @@ impl Generator for Markdown {
                     p = name.to_snake_case(),
                 ));
                 self.print_ty(iface, ty, false);
+                if let Some(docs) = func.docs.param(name) {
+                    self.src.push_str(" - ");
+                    self.src.push_str(docs);
+                }
                 self.src.push_str("\n");
             }
         }
@@ -341,6 +411,20 @@ impl Generator for Markdown {
                 self.src.push_str("\n");
             }
         }
+        if let Some(returns) = &func.docs.returns {
+            if func.results.is_empty() {
+                self.src.push_str("##### Results\n\n");
+            } else {
+                self.src.push_str("\n");
+            }
+            self.src.push_str(returns);
+            self.src.push_str("\n");
+        }
+        if let Some(errors) = &func.docs.errors {
+            self.src.push_str("\n##### Errors\n\n");
+            self.src.push_str(errors);
+            self.src.push_str("\n");
+        }
 
         self.src.push_str("\n");
     }
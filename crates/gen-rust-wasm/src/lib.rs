@@ -4,9 +4,10 @@ use std::io::{Read, Write};
 use std::mem;
 use std::process::{Command, Stdio};
 use witx_bindgen_gen_core::witx2::abi::{
-    Bindgen, Direction, Instruction, LiftLower, WasmType, WitxInstruction,
+    Bindgen, Direction, Instruction, LiftLower, MultiValue, StringEncoding, WasmType,
+    WitxInstruction,
 };
-use witx_bindgen_gen_core::{witx2::*, Files, Generator, Source, TypeInfo, Types};
+use witx_bindgen_gen_core::{witx2::*, Files, Generator, Origin, Source, TypeInfo, Types};
 use witx_bindgen_gen_rust::{
     int_repr, wasm_type, FnSig, RustFunctionGenerator, RustGenerator, TypeMode,
 };
@@ -40,6 +41,25 @@ pub struct Opts {
     #[cfg_attr(feature = "structopt", structopt(long))]
     pub unchecked: bool,
 
+    /// The encoding of strings in linear memory: `utf8`, `utf16` or
+    /// `latin1+utf16`. This must match the encoding used by the other side.
+    #[cfg_attr(
+        feature = "structopt",
+        structopt(long = "string-encoding", default_value = "utf8")
+    )]
+    pub string_encoding: StringEncoding,
+
+    /// The maximum number of results returned natively with wasm multi-value,
+    /// or `disabled` to always use a return pointer for more than one result.
+    /// This must match the setting used by the other side. Functions with
+    /// more than one result use the unstable `extern "wasm"` ABI, which
+    /// requires `#![feature(wasm_abi)]` and `-C target-feature=+multivalue`.
+    #[cfg_attr(
+        feature = "structopt",
+        structopt(long = "multi-value", default_value = "disabled")
+    )]
+    pub multi_value: MultiValue,
+
     /// A prefix to prepend to all exported symbols. Note that this is only
     /// intended for testing because it breaks the general form of the ABI.
     #[cfg_attr(feature = "structopt", structopt(skip))]
@@ -121,7 +141,7 @@ impl RustGenerator for RustWasm {
         }
         let manually_drop = match ty {
             Type::Id(id) => match &iface.types[*id].kind {
-                TypeDefKind::Record(_) => true,
+                TypeDefKind::Record(_) | TypeDefKind::Array(..) => true,
                 TypeDefKind::List(_)
                 | TypeDefKind::Variant(_)
                 | TypeDefKind::PushBuffer(_)
@@ -141,6 +161,25 @@ impl RustGenerator for RustWasm {
         }
     }
 
+    fn print_const_flags(&mut self, iface: &Interface, ty: TypeId, flags: &[String]) {
+        if flags.is_empty() {
+            self.push_str("0");
+            return;
+        }
+        let name = iface.types[ty].name.as_ref().unwrap();
+        let flags = flags
+            .iter()
+            .map(|f| {
+                format!(
+                    "{}_{}",
+                    name.to_shouty_snake_case(),
+                    f.to_shouty_snake_case()
+                )
+            })
+            .collect::<Vec<_>>();
+        self.push_str(&flags.join(" | "));
+    }
+
     fn print_borrowed_slice(
         &mut self,
         iface: &Interface,
@@ -200,15 +239,27 @@ impl RustGenerator for RustWasm {
 }
 
 impl Generator for RustWasm {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            anonymous_records: false,
+            anonymous_variants: false,
+            ..Capabilities::default()
+        }
+    }
+
     fn preprocess_one(&mut self, iface: &Interface, dir: Direction) {
         self.in_import = dir == Direction::Import;
         self.types.analyze(iface);
         self.trait_name = iface.name.to_camel_case();
+        // Items documented as `@deprecated` are still used by the generated
+        // glue code.
+        self.src.push_str("#[allow(deprecated)]\n");
         self.src
             .push_str(&format!("mod {} {{\n", iface.name.to_snake_case()));
 
-        for func in iface.functions.iter() {
-            let sig = iface.wasm_signature(dir, func);
+        let getters = iface.globals.iter().map(|g| g.getter()).collect::<Vec<_>>();
+        for func in iface.functions.iter().chain(&getters) {
+            let sig = iface.wasm_signature(dir, self.opts.multi_value, func);
             if let Some(results) = sig.retptr {
                 self.i64_return_pointer_area_size =
                     self.i64_return_pointer_area_size.max(results.len());
@@ -217,6 +268,10 @@ impl Generator for RustWasm {
         self.sizes.fill(dir, iface);
     }
 
+    fn origin(&mut self, origin: Option<Origin>) {
+        self.src.set_origin(origin);
+    }
+
     fn type_record(
         &mut self,
         iface: &Interface,
@@ -234,14 +289,14 @@ impl Generator for RustWasm {
                 .expect("unsupported number of flags");
             self.src.push_str(int_repr(repr));
             self.src.push_str(";\n");
-            for (i, field) in record.fields.iter().enumerate() {
+            for field in record.fields.iter() {
                 self.rustdoc(&field.docs);
                 self.src.push_str(&format!(
                     "pub const {}_{}: {} = 1 << {};\n",
                     name.to_shouty_snake_case(),
                     field.name.to_shouty_snake_case(),
                     name.to_camel_case(),
-                    i,
+                    field.bit,
                 ));
             }
             return;
@@ -431,6 +486,18 @@ impl Generator for RustWasm {
         self.print_type_list(iface, id, ty, docs);
     }
 
+    fn type_array(
+        &mut self,
+        iface: &Interface,
+        id: TypeId,
+        _name: &str,
+        ty: &Type,
+        len: u32,
+        docs: &Docs,
+    ) {
+        self.print_type_array(iface, id, ty, len, docs);
+    }
+
     fn type_pointer(
         &mut self,
         iface: &Interface,
@@ -479,16 +546,16 @@ impl Generator for RustWasm {
         self.print_typedef_buffer(iface, id, false, ty, docs);
     }
 
-    // fn const_(&mut self, name: &Id, ty: &Id, val: u64, docs: &str) {
-    //     self.rustdoc(docs);
-    //     self.src.push_str(&format!(
-    //         "pub const {}_{}: {} = {};\n",
-    //         ty.to_shouty_snake_case(),
-    //         name.to_shouty_snake_case(),
-    //         ty.to_camel_case(),
-    //         val
-    //     ));
-    // }
+    fn const_(
+        &mut self,
+        iface: &Interface,
+        name: &str,
+        ty: &Type,
+        value: &ConstValue,
+        docs: &Docs,
+    ) {
+        self.print_const(iface, name, ty, value, docs);
+    }
 
     fn import(&mut self, iface: &Interface, func: &Function) {
         let is_dtor = self.types.is_preview1_dtor_func(func);
@@ -524,6 +591,8 @@ impl Generator for RustWasm {
         iface.call(
             Direction::Import,
             LiftLower::LowerArgsLiftResults,
+            f.gen.opts.string_encoding,
+            f.gen.opts.multi_value,
             func,
             &mut f,
         );
@@ -559,10 +628,12 @@ impl Generator for RustWasm {
         self.src.push_str(&self.opts.symbol_namespace);
         self.src.push_str(&func.name);
         self.src.push_str("\"]\n");
-        self.src.push_str("unsafe extern \"C\" fn __witx_bindgen_");
+        let sig = iface.wasm_signature(Direction::Export, self.opts.multi_value, func);
+        self.src.push_str("unsafe extern \"");
+        self.src.push_str(wasm_abi(&sig.results));
+        self.src.push_str("\" fn __witx_bindgen_");
         self.src.push_str(&rust_name);
         self.src.push_str("(");
-        let sig = iface.wasm_signature(Direction::Export, func);
         let mut params = Vec::new();
         for (i, param) in sig.params.iter().enumerate() {
             let name = format!("arg{}", i);
@@ -580,7 +651,14 @@ impl Generator for RustWasm {
                 self.src.push_str(" -> ");
                 self.wasm_type(sig.results[0]);
             }
-            _ => unimplemented!(),
+            _ => {
+                self.src.push_str(" -> (");
+                for result in sig.results.iter() {
+                    self.wasm_type(*result);
+                    self.src.push_str(", ");
+                }
+                self.src.push_str(")");
+            }
         }
         self.src.push_str("{\n");
 
@@ -592,6 +670,8 @@ impl Generator for RustWasm {
         iface.call(
             Direction::Export,
             LiftLower::LiftArgsLowerResults,
+            f.gen.opts.string_encoding,
+            f.gen.opts.multi_value,
             func,
             &mut f,
         );
@@ -704,9 +784,12 @@ impl Generator for RustWasm {
                 .unwrap();
             let status = child.wait().unwrap();
             assert!(status.success());
+            // Lines have moved around, so the origins recorded for them no
+            // longer apply.
+            files.push("bindings.rs", src.as_bytes());
+        } else {
+            files.push_source("bindings.rs", &src);
         }
-
-        files.push("bindings.rs", src.as_bytes());
     }
 }
 
@@ -738,13 +821,20 @@ impl FunctionBindgen<'_> {
     }
 
     fn emit_cleanup(&mut self) {
+        // Zero-sized allocations are dangling pointers which must not be
+        // deallocated.
         for (ptr, layout) in mem::take(&mut self.cleanup) {
-            self.push_str(&format!("std::alloc::dealloc({}, {});\n", ptr, layout));
+            self.push_str(&format!(
+                "if {1}.size() != 0 {{ std::alloc::dealloc({}, {1}); }}\n",
+                ptr, layout
+            ));
         }
         if self.needs_cleanup_list {
             self.push_str(
                 "for (ptr, layout) in cleanup_list {
-                    std::alloc::dealloc(ptr, layout);
+                    if layout.size() != 0 {
+                        std::alloc::dealloc(ptr, layout);
+                    }
                 }\n",
             );
         }
@@ -761,7 +851,9 @@ impl FunctionBindgen<'_> {
         self.push_str("#[link(wasm_import_module = \"");
         self.push_str(module);
         self.push_str("\")]\n");
-        self.push_str("extern \"C\" {\n");
+        self.push_str("extern \"");
+        self.push_str(wasm_abi(results));
+        self.push_str("\" {\n");
         self.push_str("#[cfg_attr(target_arch = \"wasm32\", link_name = \"");
         self.push_str(name);
         self.push_str("\")]\n");
@@ -777,10 +869,20 @@ impl FunctionBindgen<'_> {
             self.push_str(", ");
         }
         self.push_str(")");
-        assert!(results.len() < 2);
-        for result in results.iter() {
-            self.push_str(" -> ");
-            self.push_str(wasm_type(*result));
+        match results.len() {
+            0 => {}
+            1 => {
+                self.push_str(" -> ");
+                self.push_str(wasm_type(results[0]));
+            }
+            _ => {
+                self.push_str(" -> (");
+                for result in results.iter() {
+                    self.push_str(wasm_type(*result));
+                    self.push_str(", ");
+                }
+                self.push_str(")");
+            }
         }
         self.push_str(";\n}\n");
         "witx_import".to_string()
@@ -959,6 +1061,20 @@ impl Bindgen for FunctionBindgen<'_> {
                 }
             }
 
+            Instruction::I32FromBool => {
+                results.push(format!("{} as i32", operands[0]));
+            }
+            Instruction::BoolFromI32 => {
+                if unchecked {
+                    results.push(format!("{} != 0", operands[0]));
+                } else {
+                    results.push(format!(
+                        "match {} {{ 0 => false, 1 => true, _ => panic!(\"invalid bool discriminant\") }}",
+                        operands[0]
+                    ));
+                }
+            }
+
             Instruction::Bitcasts { casts } => {
                 witx_bindgen_gen_rust::bitcast(casts, operands, results)
             }
@@ -1014,12 +1130,108 @@ impl Bindgen for FunctionBindgen<'_> {
                 results.push(result);
             }
 
+            Instruction::ArrayLower { len, .. } => {
+                self.array_lower(*len, &operands[0], results);
+            }
+            Instruction::ArrayLift { .. } => {
+                self.array_lift(operands, results);
+            }
+            Instruction::ArrayLowerMemory {
+                element,
+                len,
+                realloc,
+                ..
+            } => {
+                let body = self.blocks.pop().unwrap();
+                let tmp = self.tmp();
+                let result = format!("result{}", tmp);
+                let layout = format!("layout{}", tmp);
+                let size = self.gen.sizes.size(element);
+                let align = self.gen.sizes.align(element);
+                self.push_str(&format!(
+                    "let {} = core::alloc::Layout::from_size_align_unchecked({}, {});\n",
+                    layout,
+                    size * *len as usize,
+                    align,
+                ));
+                self.push_str(&format!(
+                    "let {} = if {1}.size() != 0 {{
+                        let ptr = std::alloc::alloc({1});
+                        if ptr.is_null() {{ std::alloc::handle_alloc_error({1}); }}
+                        ptr
+                    }} else {{
+                        {1}.align() as *mut u8
+                    }};\n",
+                    result, layout,
+                ));
+                // Arrays are iterated by value, even in the 2018 edition.
+                self.push_str(&format!(
+                    "for (i, e) in IntoIterator::into_iter({}).enumerate() {{\n",
+                    operands[0]
+                ));
+                self.push_str(&format!(
+                    "let base = {} as i32 + (i as i32) * {};\n",
+                    result, size,
+                ));
+                self.push_str(&body);
+                self.push_str("}\n");
+                results.push(format!("{} as i32", result));
+
+                if realloc.is_none() {
+                    self.cleanup.push((result, layout));
+                }
+            }
+            Instruction::ArrayLiftMemory {
+                element, len, free, ..
+            } => {
+                // As with lists this only happens when we're receiving an
+                // array from the outside world.
+                assert!(free.is_some());
+                let body = self.blocks.pop().unwrap();
+                let tmp = self.tmp();
+                let size = self.gen.sizes.size(element);
+                let align = self.gen.sizes.align(element);
+                let base = format!("base{}", tmp);
+                let result = format!("result{}", tmp);
+                self.push_str(&format!("let {} = {};\n", base, operands[0]));
+                self.push_str(&format!(
+                    "let mut {} = Vec::with_capacity({});\n",
+                    result, len,
+                ));
+                self.push_str(&format!(
+                    "for i in 0..{} {{
+                        let base = {} + i * {};
+                        {}.push({});
+                    }}\n",
+                    len, base, size, result, body,
+                ));
+                self.push_str(&format!(
+                    "std::alloc::dealloc(
+                        {} as *mut _,
+                        std::alloc::Layout::from_size_align_unchecked({}, {}),
+                    );\n",
+                    base,
+                    size * *len as usize,
+                    align
+                ));
+                results.push(format!(
+                    "match core::convert::TryInto::<[_; {}]>::try_into({}) {{
+                        Ok(a) => a,
+                        Err(_) => unreachable!(),
+                    }}",
+                    len, result
+                ));
+            }
+
             Instruction::RecordLower { ty, record, .. } => {
                 self.record_lower(iface, *ty, record, &operands[0], results);
             }
             Instruction::RecordLift { ty, record, .. } => {
                 self.record_lift(iface, *ty, record, operands, results);
             }
+            Instruction::RecordCanonLower { .. } | Instruction::RecordCanonLift { .. } => {
+                unimplemented!()
+            }
 
             Instruction::VariantPayloadName => results.push("e".to_string()),
             Instruction::BufferPayloadName => results.push("e".to_string()),
@@ -1075,7 +1287,7 @@ impl Bindgen for FunctionBindgen<'_> {
                     if i == variant.cases.len() - 1 && unchecked {
                         result.push_str("_");
                     } else {
-                        result.push_str(&i.to_string());
+                        result.push_str(&case.discriminant.to_string());
                     }
                     result.push_str(" => ");
                     self.variant_lift_case(iface, *ty, variant, case, &block, &mut result);
@@ -1088,7 +1300,89 @@ impl Bindgen for FunctionBindgen<'_> {
                 results.push(result);
             }
 
-            Instruction::ListCanonLower { element, realloc } => {
+            Instruction::StringLower {
+                realloc,
+                encoding: StringEncoding::Utf8,
+            } => {
+                let tmp = self.tmp();
+                let val = format!("vec{}", tmp);
+                let ptr = format!("ptr{}", tmp);
+                let len = format!("len{}", tmp);
+                if realloc.is_none() {
+                    self.push_str(&format!("let {} = {};\n", val, operands[0]));
+                } else {
+                    self.push_str(&format!(
+                        "let {} = ({}.into_bytes()).into_boxed_slice();\n",
+                        val, operands[0]
+                    ));
+                }
+                self.push_str(&format!("let {} = {}.as_ptr() as i32;\n", ptr, val));
+                self.push_str(&format!("let {} = {}.len() as i32;\n", len, val));
+                if realloc.is_some() {
+                    self.push_str(&format!("core::mem::forget({});\n", val));
+                }
+                results.push(ptr);
+                results.push(len);
+            }
+
+            Instruction::StringLift {
+                free,
+                encoding: StringEncoding::Utf8,
+            } => {
+                // This only happens when we're receiving a string from the
+                // outside world, so `free` should always be `Some`.
+                assert!(free.is_some());
+                let tmp = self.tmp();
+                let len = format!("len{}", tmp);
+                self.push_str(&format!("let {} = {} as usize;\n", len, operands[1]));
+                let result = format!(
+                    "Vec::from_raw_parts({} as *mut _, {1}, {1})",
+                    operands[0], len
+                );
+                if unchecked {
+                    results.push(format!("String::from_utf8_unchecked({})", result));
+                } else {
+                    results.push(format!("String::from_utf8({}).unwrap()", result));
+                }
+            }
+
+            // Other encodings are transcoded into a separate allocation, which
+            // is cleaned up after the call if ownership isn't transferred.
+            Instruction::StringLower { realloc, encoding } => {
+                let tmp = self.tmp();
+                let ptr = format!("ptr{}", tmp);
+                let len = format!("len{}", tmp);
+                let layout = format!("layout{}", tmp);
+                let encode = match encoding {
+                    StringEncoding::Utf8 => unreachable!(),
+                    StringEncoding::Utf16 => "utf16_encode",
+                    StringEncoding::CompactUtf16 => "compact_utf16_encode",
+                };
+                self.push_str(&format!(
+                    "let ({}, {}, {}) = witx_bindgen_rust::rt::{}(&{});\n",
+                    ptr, len, layout, encode, operands[0],
+                ));
+                results.push(format!("{} as i32", ptr));
+                results.push(len);
+                if realloc.is_none() {
+                    self.cleanup.push((ptr, layout));
+                }
+            }
+
+            Instruction::StringLift { free, encoding } => {
+                assert!(free.is_some());
+                let decode = match encoding {
+                    StringEncoding::Utf8 => unreachable!(),
+                    StringEncoding::Utf16 => "utf16_decode",
+                    StringEncoding::CompactUtf16 => "compact_utf16_decode",
+                };
+                results.push(format!(
+                    "witx_bindgen_rust::rt::{}({}, {})",
+                    decode, operands[0], operands[1]
+                ));
+            }
+
+            Instruction::ListCanonLower { realloc, .. } => {
                 let tmp = self.tmp();
                 let val = format!("vec{}", tmp);
                 let ptr = format!("ptr{}", tmp);
@@ -1096,12 +1390,7 @@ impl Bindgen for FunctionBindgen<'_> {
                 if realloc.is_none() {
                     self.push_str(&format!("let {} = {};\n", val, operands[0]));
                 } else {
-                    let op0 = match element {
-                        Type::Char => {
-                            format!("{}.into_bytes()", operands[0])
-                        }
-                        _ => operands.pop().unwrap(),
-                    };
+                    let op0 = operands.pop().unwrap();
                     self.push_str(&format!("let {} = ({}).into_boxed_slice();\n", val, op0));
                 }
                 self.push_str(&format!("let {} = {}.as_ptr() as i32;\n", ptr, val));
@@ -1113,7 +1402,7 @@ impl Bindgen for FunctionBindgen<'_> {
                 results.push(len);
             }
 
-            Instruction::ListCanonLift { element, free, .. } => {
+            Instruction::ListCanonLift { free, .. } => {
                 // This only happens when we're receiving a list from the
                 // outside world, so `free` should always be `Some`.
                 assert!(free.is_some());
@@ -1124,16 +1413,7 @@ impl Bindgen for FunctionBindgen<'_> {
                     "Vec::from_raw_parts({} as *mut _, {1}, {1})",
                     operands[0], len
                 );
-                match element {
-                    Type::Char => {
-                        if unchecked {
-                            results.push(format!("String::from_utf8_unchecked({})", result));
-                        } else {
-                            results.push(format!("String::from_utf8({}).unwrap()", result));
-                        }
-                    }
-                    _ => results.push(result),
-                }
+                results.push(result);
             }
 
             Instruction::ListLower { element, realloc } => {
@@ -1152,11 +1432,13 @@ impl Bindgen for FunctionBindgen<'_> {
                     layout, vec, size, align,
                 ));
                 self.push_str(&format!(
-                    "let {} = std::alloc::alloc({});\n",
-                    result, layout,
-                ));
-                self.push_str(&format!(
-                    "if {}.is_null() {{ std::alloc::handle_alloc_error({}); }}\n",
+                    "let {} = if {1}.size() != 0 {{
+                        let ptr = std::alloc::alloc({1});
+                        if ptr.is_null() {{ std::alloc::handle_alloc_error({1}); }}
+                        ptr
+                    }} else {{
+                        {1}.align() as *mut u8
+                    }};\n",
                     result, layout,
                 ));
                 self.push_str(&format!(
@@ -1312,9 +1594,16 @@ impl Bindgen for FunctionBindgen<'_> {
                 let func = self.declare_import(module, name, &sig.params, &sig.results);
 
                 // ... then call the function with all our operands
-                if sig.results.len() > 0 {
-                    self.push_str("let ret = ");
-                    results.push("ret".to_string());
+                match sig.results.len() {
+                    0 => {}
+                    1 => {
+                        self.push_str("let ret = ");
+                        results.push("ret".to_string());
+                    }
+                    n => {
+                        self.push_str("let ret = ");
+                        results.extend((0..n).map(|i| format!("ret.{}", i)));
+                    }
                 }
                 self.push_str(&func);
                 self.push_str("(");
@@ -1538,3 +1827,15 @@ impl Bindgen for FunctionBindgen<'_> {
         }
     }
 }
+
+/// Returns the ABI of the raw wasm function with `results`.
+///
+/// The `"C"` ABI returns aggregates through memory, so functions with more
+/// than one result use the `"wasm"` ABI to return them with multi-value.
+fn wasm_abi(results: &[WasmType]) -> &'static str {
+    if results.len() > 1 {
+        "wasm"
+    } else {
+        "C"
+    }
+}
@@ -40,51 +40,49 @@ pub trait RustGenerator {
     }
 
     fn rustdoc(&mut self, docs: &Docs) {
-        let docs = match &docs.contents {
-            Some(docs) => docs,
-            None => return,
-        };
-        for line in docs.trim().lines() {
-            self.push_str("/// ");
-            self.push_str(line);
+        let mut lines = Vec::new();
+        if let Some(description) = &docs.description {
+            lines.extend(description.lines().map(|l| l.to_string()));
+        }
+        if let Some(since) = &docs.since {
+            lines.push(String::new());
+            lines.push(format!("Available since {}.", since));
+        }
+        if !docs.params.is_empty() {
+            lines.extend(vec![
+                String::new(),
+                "# Parameters".to_string(),
+                String::new(),
+            ]);
+            for (name, text) in docs.params.iter() {
+                let name = to_rust_ident(&name.to_snake_case());
+                let mut text = text.lines();
+                lines.push(format!("* `{}` - {}", name, text.next().unwrap_or("")));
+                lines.extend(text.map(|l| format!("  {}", l)));
+            }
+        }
+        for (header, text) in [("Returns", &docs.returns), ("Errors", &docs.errors)].iter() {
+            if let Some(text) = text {
+                lines.extend(vec![String::new(), format!("# {}", header), String::new()]);
+                lines.extend(text.lines().map(|l| l.to_string()));
+            }
+        }
+        while lines.first().map(|l| l.is_empty()) == Some(true) {
+            lines.remove(0);
+        }
+        for line in lines {
+            self.push_str("///");
+            if !line.is_empty() {
+                self.push_str(" ");
+                self.push_str(&line);
+            }
             self.push_str("\n");
         }
-    }
-
-    fn rustdoc_params(&mut self, docs: &[(String, Type)], header: &str) {
-        drop((docs, header));
-        // let docs = docs
-        //     .iter()
-        //     .filter(|param| param.docs.trim().len() > 0)
-        //     .collect::<Vec<_>>();
-        // if docs.len() == 0 {
-        //     return;
-        // }
-
-        // self.push_str("///\n");
-        // self.push_str("/// ## ");
-        // self.push_str(header);
-        // self.push_str("\n");
-        // self.push_str("///\n");
-
-        // for param in docs {
-        //     for (i, line) in param.docs.lines().enumerate() {
-        //         self.push_str("/// ");
-        //         // Currently wasi only has at most one return value, so there's no
-        //         // need to indent it or name it.
-        //         if header != "Return" {
-        //             if i == 0 {
-        //                 self.push_str("* `");
-        //                 self.push_str(to_rust_ident(param.name.as_str()));
-        //                 self.push_str("` - ");
-        //             } else {
-        //                 self.push_str("  ");
-        //             }
-        //         }
-        //         self.push_str(line);
-        //         self.push_str("\n");
-        //     }
-        // }
+        match &docs.deprecated {
+            Some(note) if note.is_empty() => self.push_str("#[deprecated]\n"),
+            Some(note) => self.push_str(&format!("#[deprecated(note = {:?})]\n", note)),
+            None => {}
+        }
     }
 
     fn print_signature(
@@ -110,8 +108,6 @@ pub trait RustGenerator {
         sig: &FnSig,
     ) -> Vec<String> {
         self.rustdoc(&func.docs);
-        self.rustdoc_params(&func.params, "Parameters");
-        self.rustdoc_params(&func.results, "Return");
 
         if !sig.private {
             self.push_str("pub ");
@@ -220,6 +216,13 @@ pub trait RustGenerator {
             Type::F32 => self.push_str("f32"),
             Type::F64 => self.push_str("f64"),
             Type::Char => self.push_str("char"),
+            Type::Bool => self.push_str("bool"),
+            Type::String => match mode {
+                TypeMode::AllBorrowed(lt) | TypeMode::LeafBorrowed(lt) => {
+                    self.print_borrowed_str(lt)
+                }
+                TypeMode::Owned | TypeMode::HandlesBorrowed(_) => self.push_str("String"),
+            },
         }
     }
 
@@ -251,8 +254,13 @@ pub trait RustGenerator {
                     | TypeDefKind::List(_)
                     | TypeDefKind::PushBuffer(_)
                     | TypeDefKind::PullBuffer(_) => true,
-                    TypeDefKind::Type(Type::Id(t)) => needs_generics(iface, &iface.types[*t].kind),
-                    TypeDefKind::Type(Type::Handle(_)) => true,
+                    TypeDefKind::Type(Type::Id(t)) | TypeDefKind::Array(Type::Id(t), _) => {
+                        needs_generics(iface, &iface.types[*t].kind)
+                    }
+                    TypeDefKind::Type(Type::Handle(_))
+                    | TypeDefKind::Type(Type::String)
+                    | TypeDefKind::Array(Type::Handle(_), _)
+                    | TypeDefKind::Array(Type::String, _) => true,
                     _ => false,
                 }
             }
@@ -260,13 +268,17 @@ pub trait RustGenerator {
 
         match &ty.kind {
             TypeDefKind::List(t) => self.print_list(iface, t, mode),
+            TypeDefKind::Array(t, len) => {
+                self.push_str("[");
+                self.print_ty(iface, t, mode);
+                self.push_str(&format!("; {}]", len));
+            }
 
             TypeDefKind::Pointer(t) => self.print_pointer(iface, false, t),
             TypeDefKind::ConstPointer(t) => self.print_pointer(iface, true, t),
 
-            // Variants can be printed natively if they're `Option`,
-            // `Result` , or `bool`, otherwise they must be named for now.
-            TypeDefKind::Variant(v) if v.is_bool() => self.push_str("bool"),
+            // Variants can be printed natively if they're `Option` or
+            // `Result`, otherwise they must be named for now.
             TypeDefKind::Variant(v) => match v.as_expected() {
                 Some((ok, err)) => {
                     self.push_str("Result<");
@@ -314,34 +326,26 @@ pub trait RustGenerator {
     }
 
     fn print_list(&mut self, iface: &Interface, ty: &Type, mode: TypeMode) {
-        match ty {
-            Type::Char => match mode {
-                TypeMode::AllBorrowed(lt) | TypeMode::LeafBorrowed(lt) => {
-                    self.print_borrowed_str(lt)
-                }
-                TypeMode::Owned | TypeMode::HandlesBorrowed(_) => self.push_str("String"),
-            },
-            t => match mode {
-                TypeMode::AllBorrowed(lt) => {
+        match mode {
+            TypeMode::AllBorrowed(lt) => {
+                let mutbl = self.needs_mutable_slice(iface, ty);
+                self.print_borrowed_slice(iface, mutbl, ty, lt);
+            }
+            TypeMode::LeafBorrowed(lt) => {
+                if iface.all_bits_valid(ty) {
                     let mutbl = self.needs_mutable_slice(iface, ty);
                     self.print_borrowed_slice(iface, mutbl, ty, lt);
-                }
-                TypeMode::LeafBorrowed(lt) => {
-                    if iface.all_bits_valid(t) {
-                        let mutbl = self.needs_mutable_slice(iface, ty);
-                        self.print_borrowed_slice(iface, mutbl, ty, lt);
-                    } else {
-                        self.push_str("Vec<");
-                        self.print_ty(iface, ty, mode);
-                        self.push_str(">");
-                    }
-                }
-                TypeMode::HandlesBorrowed(_) | TypeMode::Owned => {
+                } else {
                     self.push_str("Vec<");
                     self.print_ty(iface, ty, mode);
                     self.push_str(">");
                 }
-            },
+            }
+            TypeMode::HandlesBorrowed(_) | TypeMode::Owned => {
+                self.push_str("Vec<");
+                self.print_ty(iface, ty, mode);
+                self.push_str(">");
+            }
         }
     }
 
@@ -444,14 +448,21 @@ pub trait RustGenerator {
                 }
                 self.push_str(");\n");
             } else {
-                if info.has_pull_buffer || info.has_push_buffer {
+                let derived: &[&str] = if info.has_pull_buffer || info.has_push_buffer {
                     // skip copy/clone ...
+                    &[]
                 } else if !info.owns_data() {
                     self.push_str("#[repr(C)]\n");
                     self.push_str("#[derive(Copy, Clone)]\n");
+                    &["Copy", "Clone"]
                 } else if !info.has_handle {
                     self.push_str("#[derive(Clone)]\n");
-                }
+                    &["Clone"]
+                } else {
+                    &[]
+                };
+                // `Debug` is implemented by hand below.
+                self.print_extra_derives(iface, id, &[derived, &["Debug"]].concat());
                 self.push_str(&format!("pub struct {}", name));
                 self.print_generics(&info, lt, true);
                 self.push_str(" {\n");
@@ -489,6 +500,23 @@ pub trait RustGenerator {
         }
     }
 
+    /// Prints any additional derives requested with `@rust(derive = "...")`,
+    /// skipping those in `provided` which are already derived or implemented
+    /// for the type.
+    fn print_extra_derives(&mut self, iface: &Interface, id: TypeId, provided: &[&str]) {
+        let derives = iface.types[id]
+            .attrs
+            .named("rust", "derive")
+            .flat_map(|d| d.split(','))
+            .map(|d| d.trim())
+            .filter(|d| !d.is_empty())
+            .filter(|d| !provided.contains(&d.rsplit("::").next().unwrap().trim()))
+            .collect::<Vec<_>>();
+        if !derives.is_empty() {
+            self.push_str(&format!("#[derive({})]\n", derives.join(", ")));
+        }
+    }
+
     fn print_typedef_variant(
         &mut self,
         iface: &Interface,
@@ -497,17 +525,16 @@ pub trait RustGenerator {
         variant: &Variant,
         docs: &Docs,
     ) {
-        // TODO: should this perhaps be an attribute in the witx file?
-        let is_error = name.contains("errno") && variant.is_enum();
+        // Enums can be marked as errors with `@error`, but for compatibility
+        // anything that looks like an `errno` is treated as one too.
+        let is_error = variant.is_enum()
+            && (iface.types[id].attrs.contains("error") || name.contains("errno"));
         let info = self.info(id);
 
         for (name, mode) in self.modes_of(iface, id) {
             self.rustdoc(docs);
             let lt = self.lifetime_for(&info, mode);
-            if variant.is_bool() {
-                self.push_str(&format!("pub type {} = bool;\n", name));
-                continue;
-            } else if let Some(ty) = variant.as_option() {
+            if let Some(ty) = variant.as_option() {
                 self.push_str(&format!("pub type {}", name));
                 self.print_generics(&info, lt, true);
                 self.push_str("= Option<");
@@ -530,15 +557,22 @@ pub trait RustGenerator {
                 self.push_str(">;\n");
                 continue;
             }
-            if variant.is_enum() {
+            let derived: &[&str] = if variant.is_enum() {
                 self.push_str("#[repr(");
                 self.int_repr(variant.tag);
                 self.push_str(")]\n#[derive(Clone, Copy, PartialEq, Eq)]\n");
+                &["Clone", "Copy", "PartialEq", "Eq"]
             } else if info.has_pull_buffer || info.has_push_buffer {
                 // skip copy/clone
+                &[]
             } else if !info.owns_data() {
                 self.push_str("#[derive(Clone, Copy)]\n");
-            }
+                &["Clone", "Copy"]
+            } else {
+                &[]
+            };
+            // `Debug` is implemented by hand below.
+            self.print_extra_derives(iface, id, &[derived, &["Debug"]].concat());
             self.push_str(&format!("pub enum {}", name.to_camel_case()));
             self.print_generics(&info, lt, true);
             self.push_str("{\n");
@@ -549,6 +583,8 @@ pub trait RustGenerator {
                     self.push_str("(");
                     self.print_ty(iface, ty, mode);
                     self.push_str(")")
+                } else if variant.is_enum() && !variant.has_default_discriminants() {
+                    self.push_str(&format!(" = {}", case.discriminant));
                 }
                 self.push_str(",\n");
             }
@@ -581,8 +617,8 @@ pub trait RustGenerator {
                     self.push_str("::");
                     self.push_str(&case_name(&case.name));
                     self.push_str(" => \"");
-                    if let Some(contents) = &case.docs.contents {
-                        self.push_str(contents.trim());
+                    if let Some(description) = &case.docs.description {
+                        self.push_str(description);
                     }
                     self.push_str("\",\n");
                 }
@@ -681,6 +717,26 @@ pub trait RustGenerator {
         }
     }
 
+    fn print_type_array(
+        &mut self,
+        iface: &Interface,
+        id: TypeId,
+        ty: &Type,
+        len: u32,
+        docs: &Docs,
+    ) {
+        let info = self.info(id);
+        for (name, mode) in self.modes_of(iface, id) {
+            let lt = self.lifetime_for(&info, mode);
+            self.rustdoc(docs);
+            self.push_str(&format!("pub type {}", name));
+            self.print_generics(&info, lt, true);
+            self.push_str(" = [");
+            self.print_ty(iface, ty, mode);
+            self.push_str(&format!("; {}];\n", len));
+        }
+    }
+
     fn print_typedef_buffer(
         &mut self,
         iface: &Interface,
@@ -701,6 +757,44 @@ pub trait RustGenerator {
         }
     }
 
+    fn print_const(
+        &mut self,
+        iface: &Interface,
+        name: &str,
+        ty: &Type,
+        value: &ConstValue,
+        docs: &Docs,
+    ) {
+        self.rustdoc(docs);
+        self.push_str(&format!("pub const {}: ", name.to_shouty_snake_case()));
+        match value {
+            // Owned strings can't be constructed in constants.
+            ConstValue::String(_) => self.push_str("&str"),
+            _ => self.print_ty(iface, ty, TypeMode::Owned),
+        }
+        self.push_str(" = ");
+        match value {
+            ConstValue::Bool(b) => self.push_str(&b.to_string()),
+            ConstValue::Unsigned(n) => self.push_str(&n.to_string()),
+            ConstValue::Signed(n) => self.push_str(&n.to_string()),
+            // `Debug` formatting always produces a float literal, and for
+            // chars and strings it produces valid escapes as well.
+            ConstValue::Float(n) => self.push_str(&format!("{:?}", n)),
+            ConstValue::Char(c) => self.push_str(&format!("{:?}", c)),
+            ConstValue::String(s) => self.push_str(&format!("{:?}", s)),
+            ConstValue::Enum { ty, case } => {
+                let name = iface.types[*ty].name.as_ref().unwrap();
+                self.push_str(&format!("{}::{}", name.to_camel_case(), case_name(case)));
+            }
+            ConstValue::Flags { ty, flags } => self.print_const_flags(iface, *ty, flags),
+        }
+        self.push_str(";\n");
+    }
+
+    /// Prints a constant expression for the set of `flags` of the flags type
+    /// `ty`.
+    fn print_const_flags(&mut self, iface: &Interface, ty: TypeId, flags: &[String]);
+
     fn param_name(&self, iface: &Interface, ty: TypeId) -> String {
         let info = self.info(ty);
         let name = iface.types[ty].name.as_ref().unwrap().to_camel_case();
@@ -771,7 +865,8 @@ pub trait RustGenerator {
             | TypeDefKind::Pointer(t)
             | TypeDefKind::ConstPointer(t)
             | TypeDefKind::PushBuffer(t)
-            | TypeDefKind::List(t) => self.has_pull_buffer_invalid_bits(iface, t),
+            | TypeDefKind::List(t)
+            | TypeDefKind::Array(t, _) => self.has_pull_buffer_invalid_bits(iface, t),
             TypeDefKind::Record(r) => r
                 .fields
                 .iter()
@@ -898,6 +993,24 @@ pub trait RustFunctionGenerator {
         }
     }
 
+    fn array_lower(&mut self, len: u32, operand: &str, results: &mut Vec<String>) {
+        let tmp = self.tmp();
+        self.push_str("let [");
+        for i in 0..len {
+            let arg = format!("t{}_{}", tmp, i);
+            self.push_str(&arg);
+            self.push_str(", ");
+            results.push(arg);
+        }
+        self.push_str("] = ");
+        self.push_str(operand);
+        self.push_str(";\n");
+    }
+
+    fn array_lift(&mut self, operands: &[String], results: &mut Vec<String>) {
+        results.push(format!("[{}]", operands.join(", ")));
+    }
+
     fn typename_lower(&self, iface: &Interface, id: TypeId) -> String {
         match self.lift_lower() {
             LiftLower::LowerArgsLiftResults => self.rust_gen().param_name(iface, id),
@@ -939,9 +1052,7 @@ pub trait RustFunctionGenerator {
         self.push_str(operand);
         self.push_str("{\n");
         for (case, block) in ty.cases.iter().zip(blocks) {
-            if ty.is_bool() {
-                self.push_str(case.name.as_str());
-            } else if ty.as_expected().is_some() {
+            if ty.as_expected().is_some() {
                 self.push_str(&case.name.to_camel_case());
                 self.push_str("(");
                 self.push_str(if case.ty.is_some() { "e" } else { "()" });
@@ -978,9 +1089,7 @@ pub trait RustFunctionGenerator {
         block: &str,
         result: &mut String,
     ) {
-        if ty.is_bool() {
-            result.push_str(case.name.as_str());
-        } else if ty.as_expected().is_some() {
+        if ty.as_expected().is_some() {
             result.push_str(&case.name.to_camel_case());
             result.push_str("(");
             result.push_str(block);
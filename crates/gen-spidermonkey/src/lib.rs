@@ -20,7 +20,7 @@ use wasm_encoder::Instruction;
 use witx_bindgen_gen_core::{
     witx2::{
         self,
-        abi::{WasmSignature, WasmType},
+        abi::{MultiValue, WasmSignature, WasmType},
     },
     Files, Generator,
 };
@@ -856,6 +856,17 @@ impl SpiderMonkeyWasm<'_> {
 }
 
 impl Generator for SpiderMonkeyWasm<'_> {
+    fn capabilities(&self) -> witx2::Capabilities {
+        witx2::Capabilities {
+            preview1: false,
+            async_functions: false,
+            buffers: false,
+            // TODO: resources aren't supported yet, see `TODO.md`.
+            resources: false,
+            ..witx2::Capabilities::default()
+        }
+    }
+
     fn preprocess_all(&mut self, imports: &[witx2::Interface], exports: &[witx2::Interface]) {
         assert!(
             self.num_import_functions.is_none() && self.num_export_functions.is_none(),
@@ -865,10 +876,17 @@ impl Generator for SpiderMonkeyWasm<'_> {
             exports.len() <= 1,
             "only one exported interface is currently supported"
         );
-        self.num_import_functions =
-            Some(u32::try_from(imports.iter().map(|i| i.functions.len()).sum::<usize>()).unwrap());
-        self.num_export_functions =
-            Some(u32::try_from(exports.iter().map(|i| i.functions.len()).sum::<usize>()).unwrap());
+        // Globals are imported and exported as getter functions, so they
+        // need function indices too.
+        let num_functions = |ifaces: &[witx2::Interface]| {
+            let n = ifaces
+                .iter()
+                .map(|i| i.functions.len() + i.globals.len())
+                .sum::<usize>();
+            Some(u32::try_from(n).unwrap())
+        };
+        self.num_import_functions = num_functions(imports);
+        self.num_export_functions = num_functions(exports);
 
         // Figure out what the maximum return pointer area we will need is.
         for (iface, dir) in imports
@@ -880,8 +898,9 @@ impl Generator for SpiderMonkeyWasm<'_> {
                     .zip(std::iter::repeat(witx2::abi::Direction::Export)),
             )
         {
-            for func in iface.functions.iter() {
-                let sig = iface.wasm_signature(dir, func);
+            let getters = iface.globals.iter().map(|g| g.getter()).collect::<Vec<_>>();
+            for func in iface.functions.iter().chain(getters.iter()) {
+                let sig = iface.wasm_signature(dir, MultiValue::Disabled, func);
                 if let Some(results) = sig.retptr {
                     self.i64_return_pointer_area_size =
                         self.i64_return_pointer_area_size.max(results.len());
@@ -947,6 +966,19 @@ impl Generator for SpiderMonkeyWasm<'_> {
         todo!()
     }
 
+    fn type_array(
+        &mut self,
+        iface: &witx2::Interface,
+        id: witx2::TypeId,
+        name: &str,
+        ty: &witx2::Type,
+        len: u32,
+        docs: &witx2::Docs,
+    ) {
+        let _ = (iface, id, name, ty, len, docs);
+        todo!()
+    }
+
     fn type_pointer(
         &mut self,
         iface: &witx2::Interface,
@@ -996,6 +1028,18 @@ impl Generator for SpiderMonkeyWasm<'_> {
         todo!()
     }
 
+    fn const_(
+        &mut self,
+        iface: &witx2::Interface,
+        name: &str,
+        ty: &witx2::Type,
+        value: &witx2::ConstValue,
+        docs: &witx2::Docs,
+    ) {
+        // Constants don't need any glue, the JS source can define its own.
+        let _ = (iface, name, ty, value, docs);
+    }
+
     fn import(&mut self, iface: &witx2::Interface, func: &witx2::Function) {
         assert!(!func.is_async, "async not supported yet");
         assert!(
@@ -1004,7 +1048,8 @@ impl Generator for SpiderMonkeyWasm<'_> {
         );
 
         // Add the raw Wasm import.
-        let wasm_sig = iface.wasm_signature(witx2::abi::Direction::Import, func);
+        let wasm_sig =
+            iface.wasm_signature(witx2::abi::Direction::Import, MultiValue::Disabled, func);
         let type_index = self.intern_type(wasm_sig.clone());
         let import_fn_index = self.witx_import(self.imports.len());
         self.imports.import(
@@ -1032,6 +1077,8 @@ impl Generator for SpiderMonkeyWasm<'_> {
         iface.call(
             witx2::abi::Direction::Import,
             witx2::abi::LiftLower::LowerArgsLiftResults,
+            witx2::abi::StringEncoding::Utf8,
+            MultiValue::Disabled,
             func,
             &mut bindgen,
         );
@@ -1046,7 +1093,8 @@ impl Generator for SpiderMonkeyWasm<'_> {
             "We only support the canonical ABI right now"
         );
 
-        let wasm_sig = iface.wasm_signature(witx2::abi::Direction::Export, func);
+        let wasm_sig =
+            iface.wasm_signature(witx2::abi::Direction::Export, MultiValue::Disabled, func);
         let type_index = self.intern_type(wasm_sig.clone());
         let export_fn_index = self.witx_export(self.exports.len());
         self.exports
@@ -1061,6 +1109,8 @@ impl Generator for SpiderMonkeyWasm<'_> {
         iface.call(
             witx2::abi::Direction::Export,
             witx2::abi::LiftLower::LiftArgsLowerResults,
+            witx2::abi::StringEncoding::Utf8,
+            MultiValue::Disabled,
             func,
             &mut bindgen,
         );
@@ -1465,6 +1515,7 @@ impl witx2::abi::Bindgen for Bindgen<'_, '_> {
             witx2::abi::Instruction::F32Store { offset: _ } => todo!(),
             witx2::abi::Instruction::F64Store { offset: _ } => todo!(),
             witx2::abi::Instruction::I32FromChar => todo!(),
+            witx2::abi::Instruction::I32FromBool => todo!(),
             witx2::abi::Instruction::I64FromU64 => todo!(),
             witx2::abi::Instruction::I64FromS64 => todo!(),
             witx2::abi::Instruction::I32FromU32 => {
@@ -1516,6 +1567,7 @@ impl witx2::abi::Bindgen for Bindgen<'_, '_> {
             witx2::abi::Instruction::S64FromI64 => todo!(),
             witx2::abi::Instruction::U64FromI64 => todo!(),
             witx2::abi::Instruction::CharFromI32 => todo!(),
+            witx2::abi::Instruction::BoolFromI32 => todo!(),
             witx2::abi::Instruction::If32FromF32 => todo!(),
             witx2::abi::Instruction::If64FromF64 => todo!(),
             witx2::abi::Instruction::Char8FromI32 => todo!(),
@@ -1524,7 +1576,7 @@ impl witx2::abi::Bindgen for Bindgen<'_, '_> {
             witx2::abi::Instruction::I32FromOwnedHandle { ty: _ } => todo!(),
             witx2::abi::Instruction::HandleOwnedFromI32 { ty: _ } => todo!(),
             witx2::abi::Instruction::HandleBorrowedFromI32 { ty: _ } => todo!(),
-            witx2::abi::Instruction::ListCanonLower { element, realloc } => {
+            witx2::abi::Instruction::StringLower { realloc, .. } => {
                 let js = pop_js(operands);
                 let ptr = self.new_local(wasm_encoder::ValType::I32);
                 let len = self.new_local(wasm_encoder::ValType::I32);
@@ -1568,16 +1620,15 @@ impl witx2::abi::Bindgen for Bindgen<'_, '_> {
                 // If `realloc` is `None`, then we are responsible for freeing
                 // this pointer after the call.
                 if realloc.is_none() {
-                    self.to_free.push((
-                        ptr,
-                        len,
-                        u32::try_from(self.gen.sizes.align(element)).unwrap(),
-                    ));
+                    self.to_free.push((ptr, len, 1));
                 }
 
                 results.push(Operand::Wasm(ptr));
                 results.push(Operand::Wasm(len));
             }
+            // `is_list_canonical` always returns `false`, so these never happen.
+            witx2::abi::Instruction::ListCanonLower { .. } => unreachable!(),
+            witx2::abi::Instruction::ListCanonLift { .. } => unreachable!(),
             witx2::abi::Instruction::ListLower { element, realloc } => {
                 let iterable = pop_js(operands);
                 let (block, block_results) = self.pop_block();
@@ -1699,13 +1750,7 @@ impl witx2::abi::Bindgen for Bindgen<'_, '_> {
                 results.push(Operand::Wasm(ptr));
                 results.push(Operand::Wasm(length));
             }
-            witx2::abi::Instruction::ListCanonLift {
-                element,
-                free,
-                ty: _,
-            } => {
-                assert_eq!(**element, witx2::Type::Char);
-
+            witx2::abi::Instruction::StringLift { free, .. } => {
                 let len = pop_wasm(operands);
                 let ptr = pop_wasm(operands);
                 let result = self.next_js();
@@ -1728,7 +1773,7 @@ impl witx2::abi::Bindgen for Bindgen<'_, '_> {
                     // [i32]
                     self.inst(Instruction::LocalGet(len));
                     // [i32 i32]
-                    self.inst(Instruction::I32Const(self.gen.sizes.align(element) as _));
+                    self.inst(Instruction::I32Const(1));
                     // [i32 i32 i32]
                     self.inst(Instruction::Call(self.gen.spidermonkey_import(free)));
                     // []
@@ -1873,6 +1918,28 @@ impl witx2::abi::Bindgen for Bindgen<'_, '_> {
             witx2::abi::Instruction::BufferLowerHandle { push: _, ty: _ } => todo!(),
             witx2::abi::Instruction::BufferLiftPtrLen { push: _, ty: _ } => todo!(),
             witx2::abi::Instruction::BufferLiftHandle { push: _, ty: _ } => todo!(),
+            witx2::abi::Instruction::ArrayLower {
+                element: _,
+                len: _,
+                ty: _,
+            } => todo!(),
+            witx2::abi::Instruction::ArrayLift {
+                element: _,
+                len: _,
+                ty: _,
+            } => todo!(),
+            witx2::abi::Instruction::ArrayLowerMemory {
+                element: _,
+                len: _,
+                ty: _,
+                realloc: _,
+            } => todo!(),
+            witx2::abi::Instruction::ArrayLiftMemory {
+                element: _,
+                len: _,
+                ty: _,
+                free: _,
+            } => todo!(),
             witx2::abi::Instruction::RecordLower {
                 record: _,
                 name: _,
@@ -1883,6 +1950,18 @@ impl witx2::abi::Bindgen for Bindgen<'_, '_> {
                 name: _,
                 ty: _,
             } => todo!(),
+            witx2::abi::Instruction::RecordCanonLower {
+                record: _,
+                name: _,
+                ty: _,
+                offset: _,
+            } => todo!(),
+            witx2::abi::Instruction::RecordCanonLift {
+                record: _,
+                name: _,
+                ty: _,
+                offset: _,
+            } => todo!(),
             witx2::abi::Instruction::FlagsLower {
                 record: _,
                 name: _,
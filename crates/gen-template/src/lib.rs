@@ -0,0 +1,392 @@
+//! A generator which renders user-supplied [Handlebars] templates, for
+//! producing simple bindings in languages which don't have a generator of
+//! their own.
+//!
+//! Each template is rendered once per interface with the interface's
+//! representation from [`witx2::json`] as its data, along with a `direction`
+//! of either `"import"` or `"export"`. The name of the file that's produced
+//! is the template's own name without its `.hbs` extension, which is also
+//! rendered as a template, so `{{snake name}}.go.hbs` produces `foo_bar.go`
+//! for the interface `foo-bar`.
+//!
+//! On top of the helpers built into Handlebars, templates can use:
+//!
+//! * `snake`, `shouty`, `camel`, `mixed` and `kebab` to convert a name to
+//!   `snake_case`, `SHOUTY_SNAKE_CASE`, `CamelCase`, `mixedCase` and
+//!   `kebab-case` respectively.
+//! * `unique` to return its argument, with a number appended if it was
+//!   already returned for the file being rendered, for example to name
+//!   temporaries.
+//! * `comment` to prefix every line of its first argument, such as `docs`,
+//!   with its second, for example `{{comment docs "// "}}`. Nothing is
+//!   rendered if the first argument is missing.
+//! * `ty` to spell a type as it's written in `*.witx` files, for example
+//!   `list<u8>` or the name of a named type.
+//!
+//! [Handlebars]: https://handlebarsjs.com
+
+use anyhow::{anyhow, Context as _, Result};
+use handlebars::{Context, Handlebars, Helper, HelperResult, Output, RenderContext, RenderError};
+use heck::*;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use witx2::abi::Direction;
+use witx2::*;
+use witx_bindgen_gen_core::{Files, Generator, Ns};
+
+pub struct Template {
+    registry: Handlebars<'static>,
+    /// The names of the templates to render, in the order they were added.
+    templates: Vec<String>,
+    /// The names returned by the `unique` helper in the current file.
+    names: Arc<Mutex<Ns>>,
+    /// The direction of the interface being generated.
+    dir: Direction,
+}
+
+#[derive(Default, Debug, Clone)]
+#[cfg_attr(feature = "structopt", derive(structopt::StructOpt))]
+pub struct Opts {
+    /// A Handlebars template to render for each interface. Can be specified
+    /// multiple times.
+    #[cfg_attr(feature = "structopt", structopt(long = "template"))]
+    pub templates: Vec<PathBuf>,
+}
+
+impl Opts {
+    /// Creates a generator which renders the templates in `self.templates`.
+    ///
+    /// Returns an error if any of the templates can't be read or parsed.
+    pub fn build(&self) -> Result<Template> {
+        let mut r = Template::new();
+        for path in self.templates.iter() {
+            let source = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            let name = path
+                .file_name()
+                .and_then(|s| s.to_str())
+                .ok_or_else(|| anyhow!("invalid template name {}", path.display()))?;
+            r.add_template(name, &source)
+                .with_context(|| format!("failed to parse {}", path.display()))?;
+        }
+        Ok(r)
+    }
+}
+
+impl Template {
+    pub fn new() -> Template {
+        let mut registry = Handlebars::new();
+        registry.register_escape_fn(handlebars::no_escape);
+        registry.register_helper("snake", case(|s| s.to_snake_case()));
+        registry.register_helper("shouty", case(|s| s.to_shouty_snake_case()));
+        registry.register_helper("camel", case(|s| s.to_camel_case()));
+        registry.register_helper("mixed", case(|s| s.to_mixed_case()));
+        registry.register_helper("kebab", case(|s| s.to_kebab_case()));
+        registry.register_helper("comment", Box::new(comment));
+        registry.register_helper("ty", Box::new(ty));
+        let names = Arc::new(Mutex::new(Ns::default()));
+        let ns = names.clone();
+        registry.register_helper(
+            "unique",
+            Box::new(
+                move |h: &Helper,
+                      _: &Handlebars,
+                      _: &Context,
+                      _: &mut RenderContext,
+                      out: &mut dyn Output|
+                      -> HelperResult {
+                    let name = str_param(h, 0)?;
+                    out.write(&ns.lock().unwrap().tmp(name))?;
+                    Ok(())
+                },
+            ),
+        );
+        Template {
+            registry,
+            templates: Vec::new(),
+            names,
+            dir: Direction::Import,
+        }
+    }
+
+    /// Adds a template to render for each interface, producing a file named
+    /// `name`, without any `.hbs` extension, after rendering it as a template
+    /// itself.
+    pub fn add_template(&mut self, name: &str, source: &str) -> Result<()> {
+        self.registry
+            .register_template_string(name, source)
+            .map_err(|e| anyhow!("{}", e))?;
+        self.templates.push(name.to_string());
+        Ok(())
+    }
+}
+
+impl Default for Template {
+    fn default() -> Template {
+        Template::new()
+    }
+}
+
+/// Returns a helper which converts the case of its argument with `convert`.
+fn case(convert: fn(&str) -> String) -> Box<dyn handlebars::HelperDef + Send + Sync> {
+    Box::new(
+        move |h: &Helper,
+              _: &Handlebars,
+              _: &Context,
+              _: &mut RenderContext,
+              out: &mut dyn Output|
+              -> HelperResult {
+            out.write(&convert(str_param(h, 0)?))?;
+            Ok(())
+        },
+    )
+}
+
+fn comment(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let text = match h.param(0).and_then(|p| p.value().as_str()) {
+        Some(text) => text,
+        None => return Ok(()),
+    };
+    let prefix = str_param(h, 1)?;
+    for line in text.lines() {
+        out.write(prefix.trim_end())?;
+        if !line.is_empty() {
+            out.write(&prefix[prefix.trim_end().len()..])?;
+            out.write(line)?;
+        }
+        out.write("\n")?;
+    }
+    Ok(())
+}
+
+fn ty(
+    h: &Helper,
+    _: &Handlebars,
+    ctx: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let ty = h
+        .param(0)
+        .ok_or_else(|| RenderError::new("`ty` requires a type"))?;
+    out.write(&type_name(ctx.data(), ty.value())?)?;
+    Ok(())
+}
+
+/// Spells the type `ty`, from the [`json::Interface`] in `root`, as it's
+/// written in `*.witx` files.
+fn type_name(root: &Value, ty: &Value) -> Result<String, RenderError> {
+    let invalid = || RenderError::new(format!("`{}` is not a type", ty));
+    if let Some(name) = ty.as_str() {
+        return Ok(name.to_string());
+    }
+    if let Some(index) = ty.get("handle").and_then(|i| i.as_u64()) {
+        let name = &root["resources"][index as usize]["name"];
+        return Ok(format!("handle {}", name.as_str().ok_or_else(invalid)?));
+    }
+    let def = &root["types"][ty.get("id").and_then(|i| i.as_u64()).ok_or_else(invalid)? as usize];
+    if let Some(name) = def["name"].as_str() {
+        return Ok(name.to_string());
+    }
+    let join = |name: &str, tys: Vec<&Value>| -> Result<String, RenderError> {
+        let tys = tys
+            .into_iter()
+            .map(|t| match t {
+                Value::Null => Ok("_".to_string()),
+                t => type_name(root, t),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(format!("{}<{}>", name, tys.join(", ")))
+    };
+    let kind = &def["kind"];
+    if let Some(t) = kind.get("list") {
+        return join("list", vec![t]);
+    }
+    if let Some(array) = kind.get("array") {
+        let element = type_name(root, &array["element"])?;
+        return Ok(format!("array<{}, {}>", element, array["len"]));
+    }
+    if let Some(t) = kind.get("push-buffer") {
+        return join("push-buffer", vec![t]);
+    }
+    if let Some(t) = kind.get("pull-buffer") {
+        return join("pull-buffer", vec![t]);
+    }
+    if let Some(t) = kind.get("pointer") {
+        return Ok(format!("{}*", type_name(root, t)?));
+    }
+    if let Some(t) = kind.get("const-pointer") {
+        return Ok(format!("const {}*", type_name(root, t)?));
+    }
+    if let Some(t) = kind.get("type") {
+        return type_name(root, t);
+    }
+    if let Some(record) = kind.get("record") {
+        let fields = record["fields"].as_array().ok_or_else(invalid)?;
+        if record["kind"] != "tuple" && !fields.is_empty() {
+            return Err(invalid());
+        }
+        return join("tuple", fields.iter().map(|f| &f["ty"]).collect());
+    }
+    if let Some(variant) = kind.get("variant") {
+        let cases = variant["cases"].as_array().ok_or_else(invalid)?;
+        let tys = cases.iter().map(|c| &c["ty"]).collect::<Vec<_>>();
+        return match cases.iter().map(|c| c["name"].as_str()).collect::<Vec<_>>()[..] {
+            [Some("none"), Some("some")] => join("option", vec![tys[1]]),
+            [Some("ok"), Some("err")] => join("expected", tys),
+            _ => Err(invalid()),
+        };
+    }
+    Err(invalid())
+}
+
+fn str_param<'a>(h: &'a Helper, idx: usize) -> Result<&'a str, RenderError> {
+    h.param(idx)
+        .and_then(|p| p.value().as_str())
+        .ok_or_else(|| RenderError::new(format!("`{}` requires a string", h.name())))
+}
+
+// Templates are rendered over the whole interface at once in `finish_one`,
+// so none of the per-item callbacks need to do anything.
+impl Generator for Template {
+    fn preprocess_one(&mut self, _iface: &Interface, dir: Direction) {
+        self.dir = dir;
+    }
+
+    fn type_record(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _record: &Record,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_variant(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _variant: &Variant,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_resource(&mut self, _iface: &Interface, _ty: ResourceId) {}
+
+    fn type_alias(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_list(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_array(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _len: u32,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_pointer(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _const_: bool,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_builtin(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_push_buffer(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn type_pull_buffer(
+        &mut self,
+        _iface: &Interface,
+        _id: TypeId,
+        _name: &str,
+        _ty: &Type,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn const_(
+        &mut self,
+        _iface: &Interface,
+        _name: &str,
+        _ty: &Type,
+        _value: &ConstValue,
+        _docs: &Docs,
+    ) {
+    }
+
+    fn import(&mut self, _iface: &Interface, _func: &Function) {}
+
+    fn export(&mut self, _iface: &Interface, _func: &Function) {}
+
+    fn finish_one(&mut self, iface: &Interface, files: &mut Files) {
+        let mut data = serde_json::to_value(json::Interface::new(iface)).unwrap();
+        data["direction"] = match self.dir {
+            Direction::Import => "import".into(),
+            Direction::Export => "export".into(),
+        };
+
+        for template in self.templates.iter() {
+            let render = |name: &str, source: Option<&str>| {
+                *self.names.lock().unwrap() = Ns::default();
+                let result = match source {
+                    Some(source) => self.registry.render_template(source, &data),
+                    None => self.registry.render(name, &data),
+                };
+                result.unwrap_or_else(|e| panic!("failed to render template `{}`: {}", name, e))
+            };
+            let contents = render(template, None);
+            let file = render(template, Some(template.trim_end_matches(".hbs")));
+            files.push(&file, contents.as_bytes());
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::path::Path;
+use witx2::abi::Direction;
+use witx_bindgen_gen_core::{Files, Generator};
+use witx_bindgen_gen_template::Opts;
+
+fn iface() -> witx2::Interface {
+    witx2::Interface::parse(
+        "geo-shapes",
+        "
+            /// A point
+            record point { x: u32, y: u64 }
+            /// Moves a point
+            move-point: function(p: point, by-x: u32) -> point
+            names: function(points: list<point>) -> expected<list<string>, _>
+        ",
+    )
+    .unwrap()
+}
+
+fn render(gen: &mut dyn Generator, dir: Direction) -> Vec<(String, String)> {
+    let mut files = Files::default();
+    gen.generate_one(&iface(), dir, &mut files);
+    files
+        .iter()
+        .map(|(name, contents)| {
+            (
+                name.to_string(),
+                String::from_utf8(contents.to_vec()).unwrap(),
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn go() {
+    let template = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/go/{{snake name}}.go.hbs");
+    let mut gen = Opts {
+        templates: vec![template],
+    }
+    .build()
+    .unwrap();
+    let files = render(&mut gen, Direction::Export);
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].0, "geo_shapes.go");
+    assert_eq!(
+        files[0].1,
+        "\
+// Code generated by witx-bindgen from the exports of `geo-shapes`.
+
+package geo_shapes
+
+// A point
+type Point struct {
+	X uint32
+	Y uint64
+}
+
+// Moves a point
+// The witx signature is `func(point, u32) -> point`.
+func MovePoint(p Point, byX uint32) Point {
+	panic(\"unimplemented\")
+}
+
+// The witx signature is `func(list<point>) -> expected<list<string>, _>`.
+func Names(points interface{}) interface{} {
+	panic(\"unimplemented\")
+}
+"
+    );
+}
+
+#[test]
+fn helpers() {
+    let mut gen = witx_bindgen_gen_template::Template::new();
+    gen.add_template(
+        "{{shouty name}}.txt.hbs",
+        "{{kebab (camel name)}} {{unique \"a\"}} {{unique \"a\"}} {{unique \"a0\"}} {{direction}}",
+    )
+    .unwrap();
+    let files = render(&mut gen, Direction::Import);
+    assert_eq!(
+        files,
+        [(
+            "GEO_SHAPES.txt".to_string(),
+            "geo-shapes a a0 a01 import".to_string()
+        )]
+    );
+}
+
+#[test]
+fn errors() {
+    let mut gen = witx_bindgen_gen_template::Template::new();
+    assert!(gen.add_template("a", "{{#if}}").is_err());
+    let missing = Opts {
+        templates: vec!["missing.hbs".into()],
+    };
+    assert!(missing.build().is_err());
+}
@@ -2,7 +2,8 @@ use heck::*;
 use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::mem;
 use witx_bindgen_gen_core::witx2::abi::{
-    Bindgen, Bitcast, Direction, Instruction, LiftLower, WasmType, WitxInstruction,
+    Bindgen, Bitcast, Direction, Instruction, LiftLower, MultiValue, StringEncoding, WasmType,
+    WitxInstruction,
 };
 use witx_bindgen_gen_core::{witx2::*, Files, Generator, Ns};
 
@@ -44,6 +45,7 @@ struct Import {
     src: Source,
     wasm_ty: String,
     pysig: String,
+    docs: Docs,
 }
 
 #[derive(Default)]
@@ -423,7 +425,8 @@ impl WasmtimePy {
             | Type::Usize
             | Type::CChar => self.src.push_str("int"),
             Type::F32 | Type::F64 => self.src.push_str("float"),
-            Type::Char => self.src.push_str("str"),
+            Type::Char | Type::String => self.src.push_str("str"),
+            Type::Bool => self.src.push_str("bool"),
             Type::Handle(id) => {
                 // In general we want to use quotes around this to support
                 // forward-references (such as a method on a resource returning
@@ -456,9 +459,7 @@ impl WasmtimePy {
                     }
                     TypeDefKind::Record(_) => unreachable!(),
                     TypeDefKind::Variant(v) => {
-                        if v.is_bool() {
-                            self.src.push_str("bool");
-                        } else if let Some(t) = v.as_option() {
+                        if let Some(t) = v.as_option() {
                             self.pyimport("typing", "Optional");
                             self.src.push_str("Optional[");
                             self.print_ty(iface, t);
@@ -480,7 +481,7 @@ impl WasmtimePy {
                             unreachable!()
                         }
                     }
-                    TypeDefKind::List(t) => self.print_list(iface, t),
+                    TypeDefKind::List(t) | TypeDefKind::Array(t, _) => self.print_list(iface, t),
                     TypeDefKind::Pointer(_) | TypeDefKind::ConstPointer(_) => {
                         self.src.push_str("int")
                     }
@@ -522,7 +523,6 @@ impl WasmtimePy {
 
     fn print_list(&mut self, iface: &Interface, element: &Type) {
         match element {
-            Type::Char => self.src.push_str("str"),
             Type::U8 => self.src.push_str("bytes"),
             t => {
                 self.pyimport("typing", "List");
@@ -563,7 +563,7 @@ impl WasmtimePy {
             Type::S64 => Some("c_int64"),
             Type::F32 => Some("c_float"),
             Type::F64 => Some("c_double"),
-            Type::Char => None,
+            Type::Char | Type::Bool | Type::String => None,
             Type::Handle(_) => None,
             Type::Id(id) => match &iface.types[*id].kind {
                 TypeDefKind::Type(t) => self.array_ty(iface, t),
@@ -573,11 +573,17 @@ impl WasmtimePy {
     }
 
     fn docs(&mut self, docs: &Docs) {
-        let docs = match &docs.contents {
-            Some(docs) => docs,
-            None => return,
-        };
-        for line in docs.lines() {
+        let mut lines = Vec::new();
+        if let Some(description) = &docs.description {
+            lines.extend(description.lines().map(|l| l.to_string()));
+        }
+        if let Some(since) = &docs.since {
+            lines.push(format!("Available since {}.", since));
+        }
+        if let Some(reason) = &docs.deprecated {
+            lines.push(format!("Deprecated: {}", reason).trim_end().to_string());
+        }
+        for line in lines {
             self.src.push_str(&format!("# {}\n", line));
         }
     }
@@ -630,6 +636,16 @@ impl WasmtimePy {
 }
 
 impl Generator for WasmtimePy {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            preview1: false,
+            async_functions: false,
+            anonymous_records: false,
+            anonymous_variants: false,
+            ..Capabilities::default()
+        }
+    }
+
     fn preprocess_one(&mut self, iface: &Interface, dir: Direction) {
         self.sizes.fill(dir, iface);
         self.in_import = dir == Direction::Import;
@@ -648,15 +664,23 @@ impl Generator for WasmtimePy {
             self.src.push_str(&format!("{} = ", name.to_camel_case()));
             self.print_tuple(iface, record.fields.iter().map(|f| &f.ty));
         } else if record.is_flags() {
+            let default_bits = record.has_default_bits();
             self.pyimport("enum", "Flag");
-            self.pyimport("enum", "auto");
+            if default_bits {
+                self.pyimport("enum", "auto");
+            }
             self.src
                 .push_str(&format!("class {}(Flag):\n", name.to_camel_case()));
             self.indent();
             for field in record.fields.iter() {
                 self.docs(&field.docs);
-                self.src
-                    .push_str(&format!("{} = auto()\n", field.name.to_shouty_snake_case()));
+                let name = field.name.to_shouty_snake_case();
+                if default_bits {
+                    self.src.push_str(&format!("{} = auto()\n", name));
+                } else {
+                    self.src
+                        .push_str(&format!("{} = {}\n", name, 1u64 << field.bit));
+                }
             }
             if record.fields.is_empty() {
                 self.src.push_str("pass\n");
@@ -691,15 +715,12 @@ impl Generator for WasmtimePy {
         docs: &Docs,
     ) {
         self.docs(docs);
-        if variant.is_bool() {
-            self.src
-                .push_str(&format!("{} = bool\n", name.to_camel_case()));
-        } else if variant.is_enum() {
+        if variant.is_enum() {
             self.pyimport("enum", "Enum");
             self.src
                 .push_str(&format!("class {}(Enum):\n", name.to_camel_case()));
             self.indent();
-            for (i, case) in variant.cases.iter().enumerate() {
+            for case in variant.cases.iter() {
                 self.docs(&case.docs);
 
                 // TODO this handling of digits should be more general and
@@ -711,7 +732,8 @@ impl Generator for WasmtimePy {
                 if name.chars().next().unwrap().is_digit(10) {
                     name = format!("_{}", name);
                 }
-                self.src.push_str(&format!("{} = {}\n", name, i));
+                self.src
+                    .push_str(&format!("{} = {}\n", name, case.discriminant));
             }
             self.deindent();
         } else if let Some(t) = variant.as_option() {
@@ -786,6 +808,18 @@ impl Generator for WasmtimePy {
         self.src.push_str("\n");
     }
 
+    fn type_array(
+        &mut self,
+        iface: &Interface,
+        id: TypeId,
+        name: &str,
+        ty: &Type,
+        _len: u32,
+        docs: &Docs,
+    ) {
+        self.type_list(iface, id, name, ty, docs);
+    }
+
     fn type_pointer(
         &mut self,
         _iface: &Interface,
@@ -830,6 +864,53 @@ impl Generator for WasmtimePy {
         self.src.push_str("\n");
     }
 
+    fn const_(
+        &mut self,
+        iface: &Interface,
+        name: &str,
+        ty: &Type,
+        value: &ConstValue,
+        docs: &Docs,
+    ) {
+        self.docs(docs);
+        self.src
+            .push_str(&format!("{}: ", name.to_shouty_snake_case()));
+        self.print_ty(iface, ty);
+        self.src.push_str(" = ");
+        match value {
+            ConstValue::Bool(true) => self.src.push_str("True"),
+            ConstValue::Bool(false) => self.src.push_str("False"),
+            ConstValue::Unsigned(n) => self.src.push_str(&n.to_string()),
+            ConstValue::Signed(n) => self.src.push_str(&n.to_string()),
+            ConstValue::Float(n) => self.src.push_str(&format!("{:?}", n)),
+            ConstValue::Char(c) => self.src.push_str(&py_string(&c.to_string())),
+            ConstValue::String(s) => self.src.push_str(&py_string(s)),
+            ConstValue::Enum { ty, case } => {
+                let name = iface.types[*ty].name.as_ref().unwrap();
+                let mut case = case.to_shouty_snake_case();
+                if case.chars().next().unwrap().is_digit(10) {
+                    case = format!("_{}", case);
+                }
+                self.src
+                    .push_str(&format!("{}.{}", name.to_camel_case(), case));
+            }
+            ConstValue::Flags { ty, flags } => {
+                let name = iface.types[*ty].name.as_ref().unwrap().to_camel_case();
+                if flags.is_empty() {
+                    self.src.push_str(&format!("{}(0)", name));
+                }
+                for (i, flag) in flags.iter().enumerate() {
+                    if i > 0 {
+                        self.src.push_str(" | ");
+                    }
+                    self.src
+                        .push_str(&format!("{}.{}", name, flag.to_shouty_snake_case()));
+                }
+            }
+        }
+        self.src.push_str("\n");
+    }
+
     fn import(&mut self, iface: &Interface, func: &Function) {
         assert!(!func.is_async, "async not supported yet");
         let prev = mem::take(&mut self.src);
@@ -837,7 +918,7 @@ impl Generator for WasmtimePy {
         self.print_sig(iface, func);
         let pysig = mem::take(&mut self.src).into();
 
-        let sig = iface.wasm_signature(Direction::Import, func);
+        let sig = iface.wasm_signature(Direction::Import, MultiValue::Disabled, func);
         self.src.push_str(&format!(
             "def {}(caller: wasmtime.Caller",
             func.name.to_snake_case(),
@@ -864,6 +945,8 @@ impl Generator for WasmtimePy {
         iface.call(
             Direction::Import,
             LiftLower::LiftArgsLowerResults,
+            StringEncoding::Utf8,
+            MultiValue::Disabled,
             func,
             &mut f,
         );
@@ -927,6 +1010,7 @@ impl Generator for WasmtimePy {
             src,
             wasm_ty,
             pysig,
+            docs: func.docs.clone(),
         };
         let imports = self
             .imports
@@ -951,6 +1035,7 @@ impl Generator for WasmtimePy {
         let params = self.print_sig(iface, func);
         self.src.push_str(":\n");
         self.indent();
+        docstring(&mut self.src, &func.docs);
 
         let src_object = match &func.kind {
             FunctionKind::Freestanding => "self".to_string(),
@@ -962,6 +1047,8 @@ impl Generator for WasmtimePy {
         iface.call(
             Direction::Export,
             LiftLower::LowerArgsLiftResults,
+            StringEncoding::Utf8,
+            MultiValue::Disabled,
             func,
             &mut f,
         );
@@ -1081,6 +1168,7 @@ impl Generator for WasmtimePy {
                             self.src.push_str(&func.pysig);
                             self.src.push_str(":\n");
                             self.src.indent(2);
+                            docstring(&mut self.src, &func.docs);
                             self.src.push_str("raise NotImplementedError\n");
                             self.src.deindent(2);
                         }
@@ -1146,6 +1234,7 @@ impl Generator for WasmtimePy {
                 self.src.push_str(&func.pysig);
                 self.src.push_str(":\n");
                 self.indent();
+                docstring(&mut self.src, &func.docs);
                 self.src.push_str("raise NotImplementedError\n");
                 self.deindent();
             }
@@ -1490,6 +1579,21 @@ impl Bindgen for FunctionBindgen<'_> {
                 results.push(format!("ord({})", operands[0]));
             }
 
+            Instruction::BoolFromI32 => {
+                let b = self.locals.tmp("boolean");
+                self.src.push_str(&format!("{} = {}\n", b, operands[0]));
+                self.src.push_str(&format!("if {} not in (0, 1):\n", b));
+                self.src.indent(2);
+                self.src
+                    .push_str("raise TypeError(\"invalid variant discriminant for bool\")\n");
+                self.src.deindent(2);
+                results.push(format!("bool({})", b));
+            }
+
+            Instruction::I32FromBool => {
+                results.push(format!("int({})", operands[0]));
+            }
+
             Instruction::Bitcasts { casts } => {
                 for (cast, op) in casts.iter().zip(operands) {
                     match cast {
@@ -1557,6 +1661,113 @@ impl Bindgen for FunctionBindgen<'_> {
                     operands[0],
                 ));
             }
+            // Unpacking checks the length of the array, raising an error if
+            // it's not the expected one.
+            Instruction::ArrayLower { len, .. } => {
+                self.src.push_str("(");
+                for _ in 0..*len {
+                    let name = self.locals.tmp("element");
+                    self.src.push_str(&name);
+                    self.src.push_str(",");
+                    results.push(name);
+                }
+                self.src.push_str(") = ");
+                self.src.push_str(&operands[0]);
+                self.src.push_str("\n");
+            }
+            Instruction::ArrayLift { element, .. } => match element {
+                Type::U8 => results.push(format!("bytes([{}])", operands.join(", "))),
+                _ => results.push(format!("[{}]", operands.join(", "))),
+            },
+            Instruction::ArrayLowerMemory {
+                element,
+                len,
+                realloc,
+                ..
+            } => {
+                let base = self.payloads.pop().unwrap();
+                let e = self.payloads.pop().unwrap();
+                let realloc = realloc.unwrap();
+                let (body, body_results) = self.blocks.pop().unwrap();
+                assert!(body_results.is_empty());
+                let array = self.locals.tmp("array");
+                let result = self.locals.tmp("result");
+                self.needs_realloc = Some(realloc.to_string());
+                let size = self.gen.sizes.size(element);
+                let align = self.gen.sizes.align(element);
+
+                // Raise the same error as unpacking does in `ArrayLower`, and
+                // then write the elements to memory as with lists.
+                self.src.push_str(&format!("{} = {}\n", array, operands[0]));
+                self.src
+                    .push_str(&format!("if len({}) != {}:\n", array, len));
+                self.src.indent(2);
+                self.src.push_str(&format!(
+                    "raise ValueError('expected an array of {} elements')\n",
+                    len
+                ));
+                self.src.deindent(2);
+                self.src.push_str(&format!(
+                    "{} = realloc(caller, 0, 0, {}, {})\n",
+                    result,
+                    align,
+                    size * *len as usize,
+                ));
+                self.src
+                    .push_str(&format!("assert(isinstance({}, int))\n", result));
+                let i = self.locals.tmp("i");
+                self.src
+                    .push_str(&format!("for {} in range(0, {}):\n", i, len));
+                self.src.indent(2);
+                self.src.push_str(&format!("{} = {}[{}]\n", e, array, i));
+                self.src
+                    .push_str(&format!("{} = {} + {} * {}\n", base, result, i, size));
+                self.src.push_str(&body);
+                self.src.deindent(2);
+
+                results.push(result);
+            }
+            Instruction::ArrayLiftMemory {
+                element, len, free, ..
+            } => {
+                let (body, body_results) = self.blocks.pop().unwrap();
+                let base = self.payloads.pop().unwrap();
+                let size = self.gen.sizes.size(element);
+                let align = self.gen.sizes.align(element);
+                let ptr = self.locals.tmp("ptr");
+                self.src.push_str(&format!("{} = {}\n", ptr, operands[0]));
+                let result = self.locals.tmp("result");
+                let ty = self.gen.type_string(iface, element);
+                self.src
+                    .push_str(&format!("{}: List[{}] = []\n", result, ty));
+
+                let i = self.locals.tmp("i");
+                self.src
+                    .push_str(&format!("for {} in range(0, {}):\n", i, len));
+                self.src.indent(2);
+                self.src
+                    .push_str(&format!("{} = {} + {} * {}\n", base, ptr, i, size));
+                self.src.push_str(&body);
+                assert_eq!(body_results.len(), 1);
+                self.src
+                    .push_str(&format!("{}.append({})\n", result, body_results[0]));
+                self.src.deindent(2);
+
+                if let Some(free) = free {
+                    self.needs_free = Some(free.to_string());
+                    self.src.push_str(&format!(
+                        "free(caller, {}, {}, {})\n",
+                        ptr,
+                        size * *len as usize,
+                        align,
+                    ));
+                }
+                match element {
+                    Type::U8 => results.push(format!("bytes({})", result)),
+                    _ => results.push(result),
+                }
+            }
+
             Instruction::RecordLower { record, .. } => {
                 if record.fields.is_empty() {
                     return;
@@ -1628,13 +1839,8 @@ impl Bindgen for FunctionBindgen<'_> {
                     .drain(self.payloads.len() - variant.cases.len()..)
                     .collect::<Vec<_>>();
 
-                if result_types.len() == 1 && variant.is_enum() {
-                    if variant.is_bool() {
-                        return results.push(format!("int({})", operands[0]));
-                    }
-                    if name.is_some() {
-                        return results.push(format!("({}).value", operands[0]));
-                    }
+                if result_types.len() == 1 && variant.is_enum() && name.is_some() {
+                    return results.push(format!("({}).value", operands[0]));
                 }
 
                 for _ in 0..result_types.len() {
@@ -1654,16 +1860,15 @@ impl Bindgen for FunctionBindgen<'_> {
                         self.src.push_str("elif ");
                     }
 
-                    if variant.is_bool() {
-                        self.src
-                            .push_str(&format!("{} == bool({}):\n", operands[0], i));
-                    } else if variant.as_option().is_some() {
+                    if variant.as_option().is_some() {
                         if i == 0 {
                             self.src.push_str(&format!("{} is None:\n", operands[0]));
                         }
                     } else if variant.is_enum() && variant.as_expected().is_none() {
-                        self.src
-                            .push_str(&format!("{}.value == {}:\n", operands[0], i));
+                        self.src.push_str(&format!(
+                            "{}.value == {}:\n",
+                            operands[0], case.discriminant
+                        ));
                     } else {
                         self.src.push_str(&format!(
                             "isinstance({}, {}{}):\n",
@@ -1698,9 +1903,7 @@ impl Bindgen for FunctionBindgen<'_> {
                 if needs_else {
                     let variant_name = name.map(|s| s.to_camel_case());
                     let variant_name = variant_name.as_deref().unwrap_or_else(|| {
-                        if variant.is_bool() {
-                            "bool"
-                        } else if variant.as_expected().is_some() {
+                        if variant.as_expected().is_some() {
                             "expected"
                         } else if variant.as_option().is_some() {
                             "option"
@@ -1747,15 +1950,12 @@ impl Bindgen for FunctionBindgen<'_> {
                     } else {
                         self.src.push_str("elif ");
                     }
-                    self.src.push_str(&format!("{} == {}:\n", operands[0], i));
+                    self.src
+                        .push_str(&format!("{} == {}:\n", operands[0], case.discriminant));
                     self.src.indent(2);
                     self.src.push_str(&block);
 
-                    if variant.is_bool() {
-                        assert!(block_results.is_empty());
-                        self.src
-                            .push_str(&format!("{} = {}\n", result, case.name.to_camel_case(),));
-                    } else if variant.as_option().is_some() {
+                    if variant.as_option().is_some() {
                         if case.ty.is_none() {
                             assert!(block_results.is_empty());
                             self.src.push_str(&format!("{} = None\n", result));
@@ -1792,9 +1992,7 @@ impl Bindgen for FunctionBindgen<'_> {
                 self.src.indent(2);
                 let variant_name = name.map(|s| s.to_camel_case());
                 let variant_name = variant_name.as_deref().unwrap_or_else(|| {
-                    if variant.is_bool() {
-                        "bool"
-                    } else if variant.as_expected().is_some() {
+                    if variant.as_expected().is_some() {
                         "expected"
                     } else if variant.as_option().is_some() {
                         "option"
@@ -1810,6 +2008,44 @@ impl Bindgen for FunctionBindgen<'_> {
                 results.push(result);
             }
 
+            Instruction::StringLower { realloc, .. } => {
+                // Like lists, strings are only lowered when they're passed
+                // into wasm, so `realloc` should always be `Some`.
+                let realloc = realloc.unwrap();
+                self.needs_memory = true;
+                self.needs_realloc = Some(realloc.to_string());
+
+                let ptr = self.locals.tmp("ptr");
+                let len = self.locals.tmp("len");
+                self.gen.needs_encode_utf8 = true;
+                self.src.push_str(&format!(
+                    "{}, {} = _encode_utf8({}, realloc, memory, caller)\n",
+                    ptr, len, operands[0],
+                ));
+                results.push(ptr);
+                results.push(len);
+            }
+            Instruction::StringLift { free, .. } => {
+                self.needs_memory = true;
+                let ptr = self.locals.tmp("ptr");
+                let len = self.locals.tmp("len");
+                self.src.push_str(&format!("{} = {}\n", ptr, operands[0]));
+                self.src.push_str(&format!("{} = {}\n", len, operands[1]));
+                self.gen.needs_decode_utf8 = true;
+                let result = format!("_decode_utf8(memory, caller, {}, {})", ptr, len);
+                match free {
+                    Some(free) => {
+                        self.needs_free = Some(free.to_string());
+                        let list = self.locals.tmp("list");
+                        self.src.push_str(&format!("{} = {}\n", list, result));
+                        self.src
+                            .push_str(&format!("free(caller, {}, {}, 1)\n", ptr, len));
+                        results.push(list);
+                    }
+                    None => results.push(result),
+                }
+            }
+
             Instruction::ListCanonLower { element, realloc } => {
                 // Lowering only happens when we're passing lists into wasm,
                 // which forces us to always allocate, so this should always be
@@ -1820,25 +2056,14 @@ impl Bindgen for FunctionBindgen<'_> {
 
                 let ptr = self.locals.tmp("ptr");
                 let len = self.locals.tmp("len");
-                match element {
-                    Type::Char => {
-                        self.gen.needs_encode_utf8 = true;
-                        self.src.push_str(&format!(
-                            "{}, {} = _encode_utf8({}, realloc, memory, caller)\n",
-                            ptr, len, operands[0],
-                        ));
-                    }
-                    _ => {
-                        let array_ty = self.gen.array_ty(iface, element).unwrap();
-                        self.gen.needs_list_canon_lower = true;
-                        let size = self.gen.sizes.size(element);
-                        let align = self.gen.sizes.align(element);
-                        self.src.push_str(&format!(
-                            "{}, {} = _list_canon_lower({}, ctypes.{}, {}, {}, realloc, memory, caller)\n",
-                            ptr, len, operands[0], array_ty, size, align,
-                        ));
-                    }
-                };
+                let array_ty = self.gen.array_ty(iface, element).unwrap();
+                self.gen.needs_list_canon_lower = true;
+                let size = self.gen.sizes.size(element);
+                let align = self.gen.sizes.align(element);
+                self.src.push_str(&format!(
+                    "{}, {} = _list_canon_lower({}, ctypes.{}, {}, {}, realloc, memory, caller)\n",
+                    ptr, len, operands[0], array_ty, size, align,
+                ));
                 results.push(ptr);
                 results.push(len);
             }
@@ -1848,35 +2073,25 @@ impl Bindgen for FunctionBindgen<'_> {
                 let len = self.locals.tmp("len");
                 self.src.push_str(&format!("{} = {}\n", ptr, operands[0]));
                 self.src.push_str(&format!("{} = {}\n", len, operands[1]));
-                let (result, align) = match element {
-                    Type::Char => {
-                        self.gen.needs_decode_utf8 = true;
-                        (format!("_decode_utf8(memory, caller, {}, {})", ptr, len), 1)
-                    }
+                let array_ty = self.gen.array_ty(iface, element).unwrap();
+                self.gen.needs_list_canon_lift = true;
+                let lift = format!(
+                    "_list_canon_lift({}, {}, {}, ctypes.{}, memory, caller)",
+                    ptr,
+                    len,
+                    self.gen.sizes.size(element),
+                    array_ty,
+                );
+                let pyty = match element {
+                    Type::U8 => "bytes".to_string(),
                     _ => {
-                        let array_ty = self.gen.array_ty(iface, element).unwrap();
-                        self.gen.needs_list_canon_lift = true;
-                        let lift = format!(
-                            "_list_canon_lift({}, {}, {}, ctypes.{}, memory, caller)",
-                            ptr,
-                            len,
-                            self.gen.sizes.size(element),
-                            array_ty,
-                        );
-                        let pyty = match element {
-                            Type::U8 => "bytes".to_string(),
-                            _ => {
-                                self.gen.pyimport("typing", "List");
-                                format!("List[{}]", self.gen.type_string(iface, element))
-                            }
-                        };
-                        self.gen.pyimport("typing", "cast");
-                        (
-                            format!("cast({}, {})", pyty, lift),
-                            self.gen.sizes.align(element),
-                        )
+                        self.gen.pyimport("typing", "List");
+                        format!("List[{}]", self.gen.type_string(iface, element))
                     }
                 };
+                self.gen.pyimport("typing", "cast");
+                let result = format!("cast({}, {})", pyty, lift);
+                let align = self.gen.sizes.align(element);
                 match free {
                     Some(free) => {
                         self.needs_free = Some(free.to_string());
@@ -2154,6 +2369,61 @@ impl Bindgen for FunctionBindgen<'_> {
     }
 }
 
+/// Prints the docs of a function as a docstring in Google style, which
+/// must be the first statement of the function's body.
+fn docstring(src: &mut Source, docs: &Docs) {
+    let mut lines = Vec::new();
+    if let Some(description) = &docs.description {
+        lines.extend(description.lines().map(|l| l.to_string()));
+    }
+    let mut section = |header: &str, items: Vec<String>| {
+        if items.is_empty() {
+            return;
+        }
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        lines.push(format!("{}:", header));
+        lines.extend(items.iter().map(|l| format!("    {}", l)));
+    };
+    let text = |text: &Option<String>| match text {
+        Some(text) => text.lines().map(|l| l.to_string()).collect(),
+        None => Vec::new(),
+    };
+    let mut args = Vec::new();
+    for (name, text) in docs.params.iter() {
+        let mut text = text.lines();
+        let name = name.to_snake_case();
+        args.push(format!("{}: {}", name, text.next().unwrap_or("")));
+        args.extend(text.map(|l| format!("    {}", l)));
+    }
+    section("Args", args);
+    section("Returns", text(&docs.returns));
+    section("Errors", text(&docs.errors));
+    section("Deprecated", text(&docs.deprecated));
+    if let Some(since) = &docs.since {
+        if !lines.is_empty() {
+            lines.push(String::new());
+        }
+        lines.push(format!("Available since {}.", since));
+    }
+    if lines.is_empty() {
+        return;
+    }
+    src.push_str("\"\"\"");
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            src.push_str("\n");
+        }
+        let line = line.replace('\\', "\\\\").replace("\"\"\"", "\\\"\"\"");
+        src.push_str(line.trim_end());
+    }
+    if lines.len() > 1 {
+        src.push_str("\n");
+    }
+    src.push_str("\"\"\"\n");
+}
+
 #[derive(Default)]
 pub struct Source {
     s: String,
@@ -2267,3 +2537,18 @@ mod tests {
         assert_eq!(s.s, "def foo():\n  return 1\n");
     }
 }
+
+/// Renders `s` as a Python string literal.
+fn py_string(s: &str) -> String {
+    let mut ret = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            ' '..='~' => ret.push(c),
+            _ => ret.push_str(&format!("\\U{:08x}", c as u32)),
+        }
+    }
+    ret.push('"');
+    ret
+}
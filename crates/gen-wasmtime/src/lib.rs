@@ -5,7 +5,8 @@ use std::mem;
 use std::process::{Command, Stdio};
 use std::str::FromStr;
 use witx_bindgen_gen_core::witx2::abi::{
-    Abi, Bindgen, Direction, Instruction, LiftLower, WasmType, WitxInstruction,
+    Abi, Bindgen, Direction, Instruction, LiftLower, MultiValue, StringEncoding, WasmType,
+    WitxInstruction,
 };
 use witx_bindgen_gen_core::{witx2::*, Files, Generator, Source, TypeInfo, Types};
 use witx_bindgen_gen_rust::{
@@ -82,6 +83,23 @@ pub struct Opts {
     /// custom trait-defined error. Applicable for import bindings.
     #[cfg_attr(feature = "structopt", structopt(long))]
     pub custom_error: bool,
+
+    /// The encoding of strings in linear memory: `utf8`, `utf16` or
+    /// `latin1+utf16`. This must match the encoding used by the other side.
+    #[cfg_attr(
+        feature = "structopt",
+        structopt(long = "string-encoding", default_value = "utf8")
+    )]
+    pub string_encoding: StringEncoding,
+
+    /// The maximum number of results returned natively with wasm multi-value,
+    /// or `disabled` to always use a return pointer for more than one result.
+    /// This must match the setting used by the other side.
+    #[cfg_attr(
+        feature = "structopt",
+        structopt(long = "multi-value", default_value = "disabled")
+    )]
+    pub multi_value: MultiValue,
 }
 
 #[derive(Debug, Clone)]
@@ -260,6 +278,22 @@ impl RustGenerator for Wasmtime {
         self.push_str("u32");
     }
 
+    fn print_const_flags(&mut self, iface: &Interface, ty: TypeId, flags: &[String]) {
+        // The `|` operator isn't usable in constants, so the bits are
+        // combined by hand instead.
+        let name = iface.types[ty].name.as_ref().unwrap().to_camel_case();
+        let bits = flags
+            .iter()
+            .map(|f| format!("{}::{}.bits", name, f.to_shouty_snake_case()))
+            .collect::<Vec<_>>();
+        let bits = if bits.is_empty() {
+            "0".to_string()
+        } else {
+            bits.join(" | ")
+        };
+        self.push_str(&format!("{} {{ bits: {} }}", name, bits));
+    }
+
     fn print_borrowed_slice(
         &mut self,
         iface: &Interface,
@@ -358,10 +392,22 @@ impl RustGenerator for Wasmtime {
 }
 
 impl Generator for Wasmtime {
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            async_functions: false,
+            anonymous_records: false,
+            anonymous_variants: false,
+            ..Capabilities::default()
+        }
+    }
+
     fn preprocess_one(&mut self, iface: &Interface, dir: Direction) {
         self.types.analyze(iface);
         self.in_import = dir == Direction::Import;
         self.trait_name = iface.name.to_camel_case();
+        // Items documented as `@deprecated` are still used by the generated
+        // glue code.
+        self.src.push_str("#[allow(deprecated)]\n");
         self.src
             .push_str(&format!("pub mod {} {{\n", iface.name.to_snake_case()));
         self.src
@@ -388,12 +434,12 @@ impl Generator for Wasmtime {
                 .expect("unsupported number of flags");
             self.int_repr(repr);
             self.src.push_str(" {\n");
-            for (i, field) in record.fields.iter().enumerate() {
+            for field in record.fields.iter() {
                 self.rustdoc(&field.docs);
                 self.src.push_str(&format!(
                     "const {} = 1 << {};\n",
                     field.name.to_shouty_snake_case(),
-                    i,
+                    field.bit,
                 ));
             }
             self.src.push_str("}\n");
@@ -509,6 +555,18 @@ impl Generator for Wasmtime {
         self.print_type_list(iface, id, ty, docs);
     }
 
+    fn type_array(
+        &mut self,
+        iface: &Interface,
+        id: TypeId,
+        _name: &str,
+        ty: &Type,
+        len: u32,
+        docs: &Docs,
+    ) {
+        self.print_type_array(iface, id, ty, len, docs);
+    }
+
     fn type_pointer(
         &mut self,
         iface: &Interface,
@@ -557,16 +615,16 @@ impl Generator for Wasmtime {
         self.print_typedef_buffer(iface, id, false, ty, docs);
     }
 
-    // fn const_(&mut self, name: &Id, ty: &Id, val: u64, docs: &str) {
-    //     self.rustdoc(docs);
-    //     self.src.push_str(&format!(
-    //         "pub const {}_{}: {} = {};\n",
-    //         ty.to_shouty_snake_case(),
-    //         name.to_shouty_snake_case(),
-    //         ty.to_camel_case(),
-    //         val
-    //     ));
-    // }
+    fn const_(
+        &mut self,
+        iface: &Interface,
+        name: &str,
+        ty: &Type,
+        value: &ConstValue,
+        docs: &Docs,
+    ) {
+        self.print_const(iface, name, ty, value, docs);
+    }
 
     fn import(&mut self, iface: &Interface, func: &Function) {
         assert!(!func.is_async, "async not supported yet");
@@ -577,7 +635,7 @@ impl Generator for Wasmtime {
 
         // Generate the closure that's passed to a `Linker`, the final piece of
         // codegen here.
-        let sig = iface.wasm_signature(Direction::Import, func);
+        let sig = iface.wasm_signature(Direction::Import, self.opts.multi_value, func);
         let params = (0..sig.params.len())
             .map(|i| format!("arg{}", i))
             .collect::<Vec<_>>();
@@ -590,6 +648,8 @@ impl Generator for Wasmtime {
         iface.call(
             Direction::Import,
             LiftLower::LiftArgsLowerResults,
+            f.gen.opts.string_encoding,
+            f.gen.opts.multi_value,
             func,
             &mut f,
         );
@@ -597,6 +657,7 @@ impl Generator for Wasmtime {
             src,
             cleanup,
             needs_borrow_checker,
+            needs_string_arena,
             needs_memory,
             needs_buffer_transaction,
             needs_functions,
@@ -725,6 +786,10 @@ impl Generator for Wasmtime {
         } else {
             self.src.push_str("let host = get(caller.data_mut());\n");
         }
+        if needs_string_arena {
+            self.src
+                .push_str("let _strings = witx_bindgen_wasmtime::rt::StringArena::default();\n");
+        }
 
         if self.all_needed_handles.len() > 0 {
             self.src.push_str("let (host, _tables) = host;\n");
@@ -779,6 +844,8 @@ impl Generator for Wasmtime {
         iface.call(
             Direction::Export,
             LiftLower::LowerArgsLiftResults,
+            f.gen.opts.string_encoding,
+            f.gen.opts.multi_value,
             func,
             &mut f,
         );
@@ -843,7 +910,7 @@ impl Generator for Wasmtime {
         // Create the code snippet which will define the type of this field in
         // the struct that we're exporting and additionally extracts the
         // function from an instantiated instance.
-        let sig = iface.wasm_signature(Direction::Export, func);
+        let sig = iface.wasm_signature(Direction::Export, self.opts.multi_value, func);
         let mut cvt = "(".to_string();
         for param in sig.params.iter() {
             cvt.push_str(wasm_type(*param));
@@ -1404,6 +1471,7 @@ struct FunctionBindgen<'a> {
     // satisfied in the function header if any are set.
     needs_buffer_transaction: bool,
     needs_borrow_checker: bool,
+    needs_string_arena: bool,
     needs_memory: bool,
     needs_functions: HashMap<String, NeededFunction>,
 }
@@ -1424,6 +1492,7 @@ impl FunctionBindgen<'_> {
             closures: Source::default(),
             needs_buffer_transaction: false,
             needs_borrow_checker: false,
+            needs_string_arena: false,
             needs_memory: false,
             needs_functions: HashMap::new(),
             is_dtor,
@@ -1650,6 +1719,21 @@ impl Bindgen for FunctionBindgen<'_> {
                 results.push(format!("char_from_i32({})?", operands[0]));
             }
 
+            Instruction::I32FromBool => {
+                results.push(format!("{} as i32", operands[0]));
+            }
+            Instruction::BoolFromI32 => {
+                results.push(format!(
+                    "match {} {{
+                        0 => false,
+                        1 => true,
+                        _ => return Err(invalid_variant(\"bool\")),
+                    }}",
+                    operands[0]
+                ));
+                self.gen.needs_invalid_variant = true;
+            }
+
             Instruction::Bitcasts { casts } => {
                 witx_bindgen_gen_rust::bitcast(casts, operands, results)
             }
@@ -1710,12 +1794,92 @@ impl Bindgen for FunctionBindgen<'_> {
                 results.push(format!("{}(handle{})", name, tmp));
             }
 
+            Instruction::ArrayLower { len, .. } => {
+                self.array_lower(*len, &operands[0], results);
+            }
+            Instruction::ArrayLift { .. } => {
+                self.array_lift(operands, results);
+            }
+            Instruction::ArrayLowerMemory {
+                element,
+                len,
+                realloc,
+                ..
+            } => {
+                let realloc = realloc.unwrap();
+                let body = self.blocks.pop().unwrap();
+                let tmp = self.tmp();
+                let result = format!("result{}", tmp);
+                self.needs_functions
+                    .insert(realloc.to_string(), NeededFunction::Realloc);
+                let size = self.gen.sizes.size(element);
+                let align = self.gen.sizes.align(element);
+
+                // Allocate space for the elements in the guest module and then
+                // use the block to lower each element, as with lists.
+                self.push_str(&format!("let {} = ", result));
+                self.call_intrinsic(
+                    realloc,
+                    format!("(0, 0, {}, {})", align, size * *len as usize),
+                );
+                self.push_str(&format!(
+                    "for (i, e) in IntoIterator::into_iter({}).enumerate() {{\n",
+                    operands[0]
+                ));
+                self.push_str(&format!("let base = {} + (i as i32) * {};\n", result, size));
+                self.push_str(&body);
+                self.push_str("}");
+
+                results.push(result);
+            }
+            Instruction::ArrayLiftMemory {
+                element, len, free, ..
+            } => {
+                let body = self.blocks.pop().unwrap();
+                let tmp = self.tmp();
+                let size = self.gen.sizes.size(element);
+                let align = self.gen.sizes.align(element);
+                let base = format!("base{}", tmp);
+                let result = format!("result{}", tmp);
+                self.push_str(&format!("let {} = {};\n", base, operands[0]));
+                self.push_str(&format!(
+                    "let mut {} = Vec::with_capacity({});\n",
+                    result, len,
+                ));
+                self.push_str(&format!(
+                    "for i in 0..{} {{
+                        let base = {} + i * {};
+                        {}.push({});
+                    }}\n",
+                    len, base, size, result, body,
+                ));
+
+                if let Some(free) = free {
+                    self.call_intrinsic(
+                        free,
+                        format!("({}, {}, {})", base, size * *len as usize, align),
+                    );
+                    self.needs_functions
+                        .insert(free.to_string(), NeededFunction::Free);
+                }
+                results.push(format!(
+                    "match std::convert::TryInto::<[_; {}]>::try_into({}) {{
+                        Ok(a) => a,
+                        Err(_) => unreachable!(),
+                    }}",
+                    len, result
+                ));
+            }
+
             Instruction::RecordLower { ty, record, .. } => {
                 self.record_lower(iface, *ty, record, &operands[0], results);
             }
             Instruction::RecordLift { ty, record, .. } => {
                 self.record_lift(iface, *ty, record, operands, results);
             }
+            Instruction::RecordCanonLower { .. } | Instruction::RecordCanonLift { .. } => {
+                unimplemented!()
+            }
 
             Instruction::FlagsLower { record, .. } => {
                 let tmp = self.tmp();
@@ -1782,17 +1946,15 @@ impl Bindgen for FunctionBindgen<'_> {
                 let mut result = format!("match ");
                 result.push_str(&operands[0]);
                 result.push_str(" {\n");
-                for (i, (case, block)) in variant.cases.iter().zip(blocks).enumerate() {
-                    result.push_str(&i.to_string());
+                for (case, block) in variant.cases.iter().zip(blocks) {
+                    result.push_str(&case.discriminant.to_string());
                     result.push_str(" => ");
                     self.variant_lift_case(iface, *ty, variant, case, &block, &mut result);
                     result.push_str(",\n");
                 }
                 let variant_name = name.map(|s| s.to_camel_case());
                 let variant_name = variant_name.as_deref().unwrap_or_else(|| {
-                    if variant.is_bool() {
-                        "bool"
-                    } else if variant.as_expected().is_some() {
+                    if variant.as_expected().is_some() {
                         "Result"
                     } else if variant.as_option().is_some() {
                         "Option"
@@ -1808,21 +1970,131 @@ impl Bindgen for FunctionBindgen<'_> {
                 self.gen.needs_invalid_variant = true;
             }
 
+            Instruction::StringLower { realloc, encoding } => {
+                // Lowering only happens when we're passing strings into wasm,
+                // which forces us to always allocate, so this should always be
+                // `Some`.
+                let realloc = realloc.unwrap();
+                self.needs_functions
+                    .insert(realloc.to_string(), NeededFunction::Realloc);
+
+                let tmp = self.tmp();
+                let val = format!("vec{}", tmp);
+                self.push_str(&format!("let {} = {};\n", val, operands[0]));
+
+                let ptr = format!("ptr{}", tmp);
+                // The bytes to store, the size of their allocation, and the
+                // length and alignment of the string in its encoding.
+                let (bytes, size, len, align) = match encoding {
+                    StringEncoding::Utf8 => (
+                        format!("{}.as_bytes()", val),
+                        format!("{}.len() as i32", val),
+                        format!("{}.len() as i32", val),
+                        "1".to_string(),
+                    ),
+                    StringEncoding::Utf16 | StringEncoding::CompactUtf16 => {
+                        let encode = match encoding {
+                            StringEncoding::Utf16 => "utf16_encode",
+                            _ => "compact_utf16_encode",
+                        };
+                        self.push_str(&format!(
+                            "let (bytes{0}, len{0}, align{0}) = witx_bindgen_wasmtime::rt::{1}(&{2});\n",
+                            tmp, encode, val,
+                        ));
+                        (
+                            format!("&bytes{}", tmp),
+                            format!("bytes{}.len() as i32", tmp),
+                            format!("len{}", tmp),
+                            format!("align{}", tmp),
+                        )
+                    }
+                };
+                self.push_str(&format!("let {} = ", ptr));
+                self.call_intrinsic(realloc, format!("(0, 0, {}, {})", align, size));
+
+                let mem = self.memory_src();
+                self.push_str(&format!("{}.store_many({}, {})?;\n", mem, ptr, bytes));
+                self.gen.needs_raw_mem = true;
+                self.needs_memory = true;
+                results.push(ptr);
+                results.push(len);
+            }
+
+            Instruction::StringLift { free, encoding } => match free {
+                Some(free) => {
+                    self.needs_memory = true;
+                    self.needs_functions
+                        .insert(free.to_string(), NeededFunction::Free);
+                    let tmp = self.tmp();
+                    self.push_str(&format!("let ptr{} = {};\n", tmp, operands[0]));
+                    self.push_str(&format!("let len{} = {};\n", tmp, operands[1]));
+                    match encoding {
+                        StringEncoding::Utf8 => {
+                            self.gen.needs_copy_slice = true;
+                            results.push(format!(
+                                "
+                                    String::from_utf8(copy_slice(
+                                        &mut caller,
+                                        memory,
+                                        func_{},
+                                        ptr{tmp}, len{tmp}, 1
+                                    )?)
+                                    .map_err(|_| wasmtime::Trap::new(\"invalid utf-8\"))?
+                                ",
+                                free,
+                                tmp = tmp
+                            ));
+                        }
+                        StringEncoding::Utf16 | StringEncoding::CompactUtf16 => {
+                            let copy = match encoding {
+                                StringEncoding::Utf16 => "copy_utf16_string",
+                                _ => "copy_compact_utf16_string",
+                            };
+                            results.push(format!(
+                                "witx_bindgen_wasmtime::rt::{}(&mut caller, memory, func_{}, ptr{tmp}, len{tmp})?",
+                                copy,
+                                free,
+                                tmp = tmp
+                            ));
+                        }
+                    }
+                }
+                None => {
+                    self.needs_borrow_checker = true;
+                    let tmp = self.tmp();
+                    self.push_str(&format!("let ptr{} = {};\n", tmp, operands[0]));
+                    self.push_str(&format!("let len{} = {};\n", tmp, operands[1]));
+                    match encoding {
+                        StringEncoding::Utf8 => {
+                            results.push(format!("_bc.slice_str(ptr{0}, len{0})?", tmp));
+                        }
+                        // Transcoded strings are owned by an arena which
+                        // lives until the host function returns, so they
+                        // can be lent to it like UTF-8 strings are.
+                        StringEncoding::Utf16 | StringEncoding::CompactUtf16 => {
+                            self.needs_string_arena = true;
+                            let decode = match encoding {
+                                StringEncoding::Utf16 => "utf16_string",
+                                _ => "compact_utf16_string",
+                            };
+                            results.push(format!(
+                                "_strings.alloc(_bc.{}(ptr{1}, len{1})?)",
+                                decode, tmp
+                            ));
+                        }
+                    }
+                }
+            },
+
             Instruction::ListCanonLower { element, realloc } => {
                 // Lowering only happens when we're passing lists into wasm,
                 // which forces us to always allocate, so this should always be
                 // `Some`.
-                //
-                // Note that the size of a list of `char` is 1 because it's
-                // encoded as utf-8, otherwise it's just normal contiguous array
-                // elements.
                 let realloc = realloc.unwrap();
                 self.needs_functions
                     .insert(realloc.to_string(), NeededFunction::Realloc);
-                let (size, align) = match element {
-                    Type::Char => (1, 1),
-                    _ => (self.gen.sizes.size(element), self.gen.sizes.align(element)),
-                };
+                let size = self.gen.sizes.size(element);
+                let align = self.gen.sizes.align(element);
 
                 // Store the operand into a temporary...
                 let tmp = self.tmp();
@@ -1855,10 +2127,7 @@ impl Bindgen for FunctionBindgen<'_> {
                     self.gen.needs_copy_slice = true;
                     self.needs_functions
                         .insert(free.to_string(), NeededFunction::Free);
-                    let (stringify, align) = match element {
-                        Type::Char => (true, 1),
-                        _ => (false, self.gen.sizes.align(element)),
-                    };
+                    let align = self.gen.sizes.align(element);
                     let tmp = self.tmp();
                     self.push_str(&format!("let ptr{} = {};\n", tmp, operands[0]));
                     self.push_str(&format!("let len{} = {};\n", tmp, operands[1]));
@@ -1875,27 +2144,14 @@ impl Bindgen for FunctionBindgen<'_> {
                         align,
                         tmp = tmp
                     );
-                    if stringify {
-                        results.push(format!(
-                            "String::from_utf8({})
-                                    .map_err(|_| wasmtime::Trap::new(\"invalid utf-8\"))?",
-                            result
-                        ));
-                    } else {
-                        results.push(result);
-                    }
+                    results.push(result);
                 }
                 None => {
                     self.needs_borrow_checker = true;
-                    let method = match element {
-                        Type::Char => "slice_str",
-                        _ => "slice",
-                    };
                     let tmp = self.tmp();
                     self.push_str(&format!("let ptr{} = {};\n", tmp, operands[0]));
                     self.push_str(&format!("let len{} = {};\n", tmp, operands[1]));
-                    let slice = format!("_bc.{}(ptr{1}, len{1})?", method, tmp);
-                    results.push(slice);
+                    results.push(format!("_bc.slice(ptr{0}, len{0})?", tmp));
                 }
             },
 
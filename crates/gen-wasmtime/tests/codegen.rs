@@ -106,3 +106,36 @@ mod custom_errors {
         custom_error: true,
     });
 }
+
+mod multi_value {
+    mod imports {
+        witx_bindgen_wasmtime::import!({
+            src["x"]: "
+                record point {
+                    x: f32,
+                    y: f64,
+                }
+                pair: function() -> tuple<u32, u64>
+                point: function() -> point
+                name: function() -> string
+                spilled: function() -> tuple<u8, u16, u32>
+            ",
+            multi_value: 2,
+        });
+    }
+    mod exports {
+        witx_bindgen_wasmtime::export!({
+            src["x"]: "
+                record point {
+                    x: f32,
+                    y: f64,
+                }
+                pair: function() -> tuple<u32, u64>
+                point: function() -> point
+                name: function() -> string
+                spilled: function() -> tuple<u8, u16, u32>
+            ",
+            multi_value: 2,
+        });
+    }
+}
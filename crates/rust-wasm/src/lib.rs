@@ -159,6 +159,69 @@ pub mod rt {
         alloc::dealloc(ptr, layout);
     }
 
+    /// Set in the length of a UTF-16 string in the `latin1+utf16` encoding.
+    const UTF16_TAG: i32 = i32::MIN;
+
+    unsafe fn string_alloc(size: usize, align: usize) -> (*mut u8, Layout) {
+        let layout = Layout::from_size_align_unchecked(size, align);
+        (
+            canonical_abi_realloc(std::ptr::null_mut(), 0, align, size),
+            layout,
+        )
+    }
+
+    /// Encodes `s` as UTF-16 into a new allocation, returning its pointer,
+    /// its length in code units and its layout.
+    ///
+    /// The allocation is made with `canonical_abi_realloc`, so it can be
+    /// handed over to the other side. Zero-sized allocations are dangling.
+    pub fn utf16_encode(s: &str) -> (*mut u8, i32, Layout) {
+        let len = s.encode_utf16().count();
+        unsafe {
+            let (ptr, layout) = string_alloc(len * 2, 2);
+            for (i, unit) in s.encode_utf16().enumerate() {
+                (ptr as *mut u16).add(i).write(unit.to_le());
+            }
+            (ptr, len as i32, layout)
+        }
+    }
+
+    /// Same as `utf16_encode` but for the `latin1+utf16` encoding.
+    pub fn compact_utf16_encode(s: &str) -> (*mut u8, i32, Layout) {
+        if s.chars().any(|c| u32::from(c) > 0xff) {
+            let (ptr, len, layout) = utf16_encode(s);
+            return (ptr, len | UTF16_TAG, layout);
+        }
+        let len = s.chars().count();
+        unsafe {
+            let (ptr, layout) = string_alloc(len, 1);
+            for (i, c) in s.chars().enumerate() {
+                *ptr.add(i) = c as u8;
+            }
+            (ptr, len as i32, layout)
+        }
+    }
+
+    /// Decodes a UTF-16 string of `len` code units at `ptr`, taking ownership
+    /// of its allocation.
+    pub unsafe fn utf16_decode(ptr: i32, len: i32) -> String {
+        let units = std::slice::from_raw_parts(ptr as *const u16, len as usize);
+        let ret = String::from_utf16(units).unwrap();
+        canonical_abi_free(ptr as *mut u8, len as usize * 2, 2);
+        ret
+    }
+
+    /// Same as `utf16_decode` but for the `latin1+utf16` encoding.
+    pub unsafe fn compact_utf16_decode(ptr: i32, len: i32) -> String {
+        if len & UTF16_TAG != 0 {
+            return utf16_decode(ptr, len & !UTF16_TAG);
+        }
+        let bytes = std::slice::from_raw_parts(ptr as *const u8, len as usize);
+        let ret = bytes.iter().map(|b| char::from(*b)).collect();
+        canonical_abi_free(ptr as *mut u8, len as usize, 1);
+        ret
+    }
+
     macro_rules! as_traits {
         ($(($trait_:ident $func:ident $ty:ident <=> $($tys:ident)*))*) => ($(
             pub fn $func<T: $trait_>(t: T) -> $ty {
@@ -2,8 +2,13 @@ use std::env;
 use std::fs;
 use std::path::PathBuf;
 use std::process::Command;
+use witx_bindgen_gen_core::witx2::abi::MultiValue;
 use witx_bindgen_gen_core::{witx2, Generator};
 
+/// The multi-value setting that `$test-multi-value` wasms are compiled with,
+/// which hosts need to use as well.
+const MULTI_VALUE: MultiValue = MultiValue::Enabled { max_results: 16 };
+
 fn main() {
     let out_dir = PathBuf::from(std::env::var_os("OUT_DIR").unwrap());
 
@@ -46,80 +51,114 @@ fn main() {
         println!("cargo:rerun-if-changed=../test-rust-wasm/Cargo.toml");
     }
 
+    // Rust's `extern "wasm"` ABI, which multi-value results are returned
+    // through, is unstable, so these guests are built separately with a
+    // nightly compiler.
+    if cfg!(feature = "wasm-rust-multi-value") {
+        let target_dir = out_dir.join("multi-value");
+        let mut cmd = Command::new("cargo");
+        cmd.arg("build")
+            .current_dir("../test-rust-wasm")
+            .arg("--target=wasm32-wasi")
+            .arg("--features=multi-value")
+            .arg("--bin=lists-multi-value")
+            .env("CARGO_TARGET_DIR", &target_dir)
+            .env("CARGO_PROFILE_DEV_DEBUG", "1")
+            .env("RUSTUP_TOOLCHAIN", "nightly")
+            .env(
+                "RUSTFLAGS",
+                "-Clink-args=--export-table -Ctarget-feature=+multivalue",
+            )
+            .env_remove("RUSTC")
+            .env_remove("CARGO_ENCODED_RUSTFLAGS");
+        let status = cmd.status().unwrap();
+        assert!(status.success());
+        let file = target_dir.join("wasm32-wasi/debug/lists-multi-value.wasm");
+        wasms.push((
+            "rust",
+            "lists-multi-value".to_string(),
+            file.to_str().unwrap().to_string(),
+        ));
+        println!("cargo:rerun-if-changed=../../tests/runtime/lists");
+    }
+
     if cfg!(feature = "wasm-c") {
         for test_dir in fs::read_dir("../../tests/runtime").unwrap() {
             let test_dir = test_dir.unwrap().path();
-            let c_impl = test_dir.join("wasm.c");
-            if !c_impl.exists() {
-                continue;
-            }
-            let imports = test_dir.join("imports.witx");
-            let exports = test_dir.join("exports.witx");
-            println!("cargo:rerun-if-changed={}", imports.display());
-            println!("cargo:rerun-if-changed={}", exports.display());
-            println!("cargo:rerun-if-changed={}", c_impl.display());
+            // Variants of a test compiled with other ABI options are in
+            // `wasm-$variant.c`, and their wasm is named `$test-$variant`.
+            let variants = [("", MultiValue::Disabled), ("-multi-value", MULTI_VALUE)];
+            for (variant, multi_value) in variants.iter() {
+                let c_impl = test_dir.join(format!("wasm{}.c", variant));
+                if !c_impl.exists() {
+                    continue;
+                }
+                let test = test_dir.file_name().unwrap().to_str().unwrap();
+                let name = format!("{}{}", test, variant);
+                let imports = test_dir.join("imports.witx");
+                let exports = test_dir.join("exports.witx");
+                println!("cargo:rerun-if-changed={}", imports.display());
+                println!("cargo:rerun-if-changed={}", exports.display());
+                println!("cargo:rerun-if-changed={}", c_impl.display());
 
-            let import = witx2::Interface::parse_file(&test_dir.join("imports.witx")).unwrap();
-            let export = witx2::Interface::parse_file(&test_dir.join("exports.witx")).unwrap();
-            let mut files = Default::default();
-            // TODO: should combine this into one
-            witx_bindgen_gen_c::Opts::default()
-                .build()
-                .generate_all(&[import], &[], &mut files);
-            witx_bindgen_gen_c::Opts::default()
-                .build()
-                .generate_all(&[], &[export], &mut files);
+                let import = witx2::Interface::parse_file(&imports).unwrap();
+                let export = witx2::Interface::parse_file(&exports).unwrap();
+                let mut files = Default::default();
+                let opts = witx_bindgen_gen_c::Opts {
+                    multi_value: *multi_value,
+                };
+                // TODO: should combine this into one
+                opts.build().generate_all(&[import], &[], &mut files);
+                opts.build().generate_all(&[], &[export], &mut files);
 
-            let out_dir = out_dir.join(format!(
-                "c-{}",
-                test_dir.file_name().unwrap().to_str().unwrap()
-            ));
-            drop(fs::remove_dir_all(&out_dir));
-            fs::create_dir(&out_dir).unwrap();
-            for (file, contents) in files.iter() {
-                let dst = out_dir.join(file);
-                fs::write(dst, contents).unwrap();
-            }
+                let out_dir = out_dir.join(format!("c-{}", name));
+                drop(fs::remove_dir_all(&out_dir));
+                fs::create_dir(&out_dir).unwrap();
+                for (file, contents) in files.iter() {
+                    let dst = out_dir.join(file);
+                    fs::write(dst, contents).unwrap();
+                }
 
-            let path = PathBuf::from(env::var_os("WASI_SDK_PATH").expect(
-                "point the `WASI_SDK_PATH` environment variable to the path of your wasi-sdk",
-            ));
-            let mut cmd = Command::new(path.join("bin/clang"));
-            let out_wasm = out_dir.join("c.wasm");
-            cmd.arg("--sysroot").arg(path.join("share/wasi-sysroot"));
-            cmd.arg(c_impl)
-                .arg(out_dir.join("imports.c"))
-                .arg(out_dir.join("exports.c"))
-                .arg("-I")
-                .arg(&out_dir)
-                .arg("-Wall")
-                .arg("-Wextra")
-                .arg("-Werror")
-                .arg("-Wno-unused-parameter")
-                .arg("-mexec-model=reactor")
-                .arg("-g")
-                .arg("-o")
-                .arg(&out_wasm);
-            println!("{:?}", cmd);
-            let output = match cmd.output() {
-                Ok(output) => output,
-                Err(e) => panic!("failed to spawn compiler: {}", e),
-            };
-
-            if !output.status.success() {
-                println!("status: {}", output.status);
-                println!("stdout: ------------------------------------------");
-                println!("{}", String::from_utf8_lossy(&output.stdout));
-                println!("stderr: ------------------------------------------");
-                println!("{}", String::from_utf8_lossy(&output.stderr));
-                panic!("failed to compile");
-            }
+                let path = PathBuf::from(env::var_os("WASI_SDK_PATH").expect(
+                    "point the `WASI_SDK_PATH` environment variable to the path of your wasi-sdk",
+                ));
+                let mut cmd = Command::new(path.join("bin/clang"));
+                let out_wasm = out_dir.join("c.wasm");
+                cmd.arg("--sysroot").arg(path.join("share/wasi-sysroot"));
+                cmd.arg(c_impl)
+                    .arg(out_dir.join("imports.c"))
+                    .arg(out_dir.join("exports.c"))
+                    .arg("-I")
+                    .arg(&out_dir)
+                    .arg("-Wall")
+                    .arg("-Wextra")
+                    .arg("-Werror")
+                    .arg("-Wno-unused-parameter")
+                    .arg("-mexec-model=reactor")
+                    .arg("-g")
+                    .arg("-o")
+                    .arg(&out_wasm);
+                if *multi_value != MultiValue::Disabled {
+                    cmd.args(["-mmultivalue", "-Xclang", "-target-abi", "-Xclang"])
+                        .arg("experimental-mv");
+                }
+                println!("{:?}", cmd);
+                let output = match cmd.output() {
+                    Ok(output) => output,
+                    Err(e) => panic!("failed to spawn compiler: {}", e),
+                };
 
-            wasms.push((
-                "c",
-                test_dir.file_stem().unwrap().to_str().unwrap().to_string(),
-                out_wasm.to_str().unwrap().to_string(),
-            ));
+                if !output.status.success() {
+                    println!("status: {}", output.status);
+                    println!("stdout: ------------------------------------------");
+                    println!("{}", String::from_utf8_lossy(&output.stdout));
+                    println!("stderr: ------------------------------------------");
+                    println!("{}", String::from_utf8_lossy(&output.stderr));
+                    panic!("failed to compile");
+                }
+
+                wasms.push(("c", name, out_wasm.to_str().unwrap().to_string()));
+            }
         }
     }
 
@@ -163,6 +202,10 @@ fn main() {
         }
     }
 
-    let src = format!("const WASMS: &[(&str, &str, &str)] = &{:?};", wasms);
+    let mut src = format!("const WASMS: &[(&str, &str, &str)] = &{:?};\n", wasms);
+    src.push_str(&format!(
+        "const MULTI_VALUE_MAX_RESULTS: usize = {};\n",
+        MULTI_VALUE.max_results()
+    ));
     std::fs::write(out_dir.join("wasms.rs"), src).unwrap();
 }
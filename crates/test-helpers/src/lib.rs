@@ -63,7 +63,7 @@ pub fn codegen_rust_wasm_export(input: TokenStream) -> TokenStream {
 
     fn gen_extra(iface: &witx2::Interface) -> proc_macro2::TokenStream {
         let mut ret = quote::quote!();
-        if iface.resources.len() == 0 && iface.functions.len() == 0 {
+        if iface.resources.len() == 0 && iface.functions.len() == 0 && iface.globals.len() == 0 {
             return ret;
         }
 
@@ -79,7 +79,8 @@ pub fn codegen_rust_wasm_export(input: TokenStream) -> TokenStream {
         let mut resources = BTreeMap::new();
 
         let mut async_trait = quote::quote!();
-        for f in iface.functions.iter() {
+        let getters = iface.globals.iter().map(|g| g.getter()).collect::<Vec<_>>();
+        for f in iface.functions.iter().chain(&getters) {
             let name = quote::format_ident!("{}", f.item_name().to_snake_case());
             let mut params = f
                 .params
@@ -158,6 +159,8 @@ pub fn codegen_rust_wasm_export(input: TokenStream) -> TokenStream {
             Type::F32 => quote::quote! { f32 },
             Type::F64 => quote::quote! { f64 },
             Type::Char => quote::quote! { char },
+            Type::Bool => quote::quote! { bool },
+            Type::String => quote::quote! { String },
             Type::Handle(resource) => {
                 let name =
                     quote::format_ident!("{}", iface.resources[resource].name.to_camel_case());
@@ -189,12 +192,13 @@ pub fn codegen_rust_wasm_export(input: TokenStream) -> TokenStream {
                 quote::quote! { *const #t }
             }
             TypeDefKind::List(t) => {
-                if *t == Type::Char {
-                    quote::quote! { String }
-                } else {
-                    let t = quote_ty(param, iface, t);
-                    quote::quote! { Vec<#t> }
-                }
+                let t = quote_ty(param, iface, t);
+                quote::quote! { Vec<#t> }
+            }
+            TypeDefKind::Array(t, len) => {
+                let t = quote_ty(param, iface, t);
+                let len = *len as usize;
+                quote::quote! { [#t; #len] }
             }
             TypeDefKind::PushBuffer(_) => panic!("unimplemented push-buffer"),
             TypeDefKind::PullBuffer(_) => panic!("unimplemented pull-buffer"),
@@ -203,9 +207,7 @@ pub fn codegen_rust_wasm_export(input: TokenStream) -> TokenStream {
                 quote::quote! { (#(#fields,)*) }
             }
             TypeDefKind::Variant(v) => {
-                if v.is_bool() {
-                    quote::quote! { bool }
-                } else if let Some(ty) = v.as_option() {
+                if let Some(ty) = v.as_option() {
                     let ty = quote_ty(param, iface, ty);
                     quote::quote! { Option<#ty> }
                 } else if let Some((ok, err)) = v.as_expected() {
@@ -551,6 +553,104 @@ pub fn runtime_tests(input: TokenStream) -> TokenStream {
     (quote::quote!(#(#tests)*)).into()
 }
 
+/// The suffix of wasms compiled with multi-value returns of up to
+/// `MULTI_VALUE_MAX_RESULTS` results.
+const MULTI_VALUE: &str = "multi-value";
+
+/// Returns the tests of `wasm` compiled with non-default ABI options, which
+/// are named `$test-$variant`. The variant is either [`MULTI_VALUE`] or a
+/// string encoding with `+` replaced by `-`.
+///
+/// Each item is the name of the test directory, the wasm's language, the
+/// variant's suffix in the wasm's name, and the path to the wasm.
+fn variant_wasms() -> Vec<(&'static str, &'static str, &'static str, &'static str)> {
+    WASMS
+        .iter()
+        .filter_map(|(lang, name, wasm)| {
+            let (test, suffix) = name.split_once('-')?;
+            Some((test, *lang, suffix, *wasm))
+        })
+        .collect()
+}
+
+/// Invoked as `runtime_tests_string_encodings!("js")` to run a top-level
+/// `execute_string_encoding` function with all host tests that use the "js"
+/// extension and have wasm compiled with a non-default string encoding.
+#[proc_macro]
+pub fn runtime_tests_string_encodings(input: TokenStream) -> TokenStream {
+    let host_extension = input.to_string();
+    let host_extension = host_extension.trim_matches('"');
+    let host_file = format!("host.{}", host_extension);
+    let mut tests = Vec::new();
+    let cwd = std::env::current_dir().unwrap();
+    for (test, lang, suffix, wasm) in variant_wasms() {
+        let entry = cwd.join("tests/runtime").join(test);
+        if suffix == MULTI_VALUE || !entry.join(&host_file).exists() {
+            continue;
+        }
+        let name_str = format!("{}_{}_{}", test, lang, suffix.replace('-', "_"));
+        let name = quote::format_ident!("{}", name_str);
+        let encoding = suffix.replace('-', "+");
+        let host_file = entry.join(&host_file).to_str().unwrap().to_string();
+        let import_witx = entry.join("imports.witx").to_str().unwrap().to_string();
+        let export_witx = entry.join("exports.witx").to_str().unwrap().to_string();
+        tests.push(quote::quote! {
+            #[test]
+            fn #name() {
+                crate::execute_string_encoding(
+                    #name_str,
+                    #wasm.as_ref(),
+                    #host_file.as_ref(),
+                    #import_witx.as_ref(),
+                    #export_witx.as_ref(),
+                    #encoding.parse().unwrap(),
+                )
+            }
+        });
+    }
+
+    (quote::quote!(#(#tests)*)).into()
+}
+
+/// Invoked as `runtime_tests_multi_value!("js")` to run a top-level
+/// `execute_multi_value` function with all host tests that use the "js"
+/// extension and have wasm compiled with multi-value returns.
+#[proc_macro]
+pub fn runtime_tests_multi_value(input: TokenStream) -> TokenStream {
+    let host_extension = input.to_string();
+    let host_extension = host_extension.trim_matches('"');
+    let host_file = format!("host.{}", host_extension);
+    let mut tests = Vec::new();
+    let cwd = std::env::current_dir().unwrap();
+    for (test, lang, suffix, wasm) in variant_wasms() {
+        let entry = cwd.join("tests/runtime").join(test);
+        if suffix != MULTI_VALUE || !entry.join(&host_file).exists() {
+            continue;
+        }
+        let name_str = format!("{}_{}_multi_value", test, lang);
+        let name = quote::format_ident!("{}", name_str);
+        let max_results = MULTI_VALUE_MAX_RESULTS.to_string();
+        let host_file = entry.join(&host_file).to_str().unwrap().to_string();
+        let import_witx = entry.join("imports.witx").to_str().unwrap().to_string();
+        let export_witx = entry.join("exports.witx").to_str().unwrap().to_string();
+        tests.push(quote::quote! {
+            #[test]
+            fn #name() {
+                crate::execute_multi_value(
+                    #name_str,
+                    #wasm.as_ref(),
+                    #host_file.as_ref(),
+                    #import_witx.as_ref(),
+                    #export_witx.as_ref(),
+                    #max_results.parse().unwrap(),
+                )
+            }
+        });
+    }
+
+    (quote::quote!(#(#tests)*)).into()
+}
+
 #[proc_macro]
 #[cfg(feature = "witx-bindgen-gen-wasmtime")]
 pub fn runtime_tests_wasmtime(_input: TokenStream) -> TokenStream {
@@ -581,5 +681,29 @@ pub fn runtime_tests_wasmtime(_input: TokenStream) -> TokenStream {
         }
     }
 
+    // Hosts for wasm compiled with other ABI options are in
+    // `host-$variant.rs` since their bindings must use the same options.
+    for (test, lang, suffix, wasm) in variant_wasms() {
+        let host_file = cwd
+            .join("tests/runtime")
+            .join(test)
+            .join(format!("host-{}.rs", suffix));
+        if !host_file.exists() {
+            continue;
+        }
+        let name = quote::format_ident!("{}_{}_{}", test, lang, suffix.replace('-', "_"));
+        let host_file = host_file.to_str().unwrap().to_string();
+        tests.push(quote::quote! {
+            mod #name {
+                include!(#host_file);
+
+                #[test]
+                fn test() -> anyhow::Result<()> {
+                    run(#wasm)
+                }
+            }
+        });
+    }
+
     (quote::quote!(#(#tests)*)).into()
 }
@@ -0,0 +1,3 @@
+include!("../../../../tests/runtime/arrays/wasm.rs");
+
+fn main() {}
@@ -0,0 +1,3 @@
+include!("../../../../tests/runtime/globals/wasm.rs");
+
+fn main() {}
@@ -0,0 +1,3 @@
+include!("../../../../tests/runtime/lists/wasm-latin1-utf16.rs");
+
+fn main() {}
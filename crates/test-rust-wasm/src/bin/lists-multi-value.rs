@@ -0,0 +1,6 @@
+// Multi-value results are returned through the unstable `extern "wasm"` ABI.
+#![feature(wasm_abi)]
+
+include!("../../../../tests/runtime/lists/wasm-multi-value.rs");
+
+fn main() {}
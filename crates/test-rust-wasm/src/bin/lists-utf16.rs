@@ -0,0 +1,3 @@
+include!("../../../../tests/runtime/lists/wasm-utf16.rs");
+
+fn main() {}
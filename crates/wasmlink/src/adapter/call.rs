@@ -95,12 +95,14 @@ enum ValueRef {
     Return(u32),
     /// A reference to a 32-bit value via an index into the retptr array (multi-value return).
     RetPtr(u32),
+    /// A constant 32-bit value, such as the length of an array.
+    Const(u32),
 }
 
 impl ValueRef {
     fn offset(&self) -> Option<u32> {
         match self {
-            ValueRef::Local(_) | ValueRef::Return(_) => None,
+            ValueRef::Local(_) | ValueRef::Return(_) | ValueRef::Const(_) => None,
             ValueRef::ElementOffset(o) => Some(*o),
             ValueRef::RetPtr(i) => Some(*i * 8),
         }
@@ -117,6 +119,10 @@ impl ValueRef {
                 function.instruction(Instruction::LocalGet(*i));
                 return;
             }
+            ValueRef::Const(val) => {
+                function.instruction(Instruction::I32Const(*val as i32));
+                return;
+            }
             ValueRef::RetPtr(_) | ValueRef::ElementOffset(_) => {}
         }
 
@@ -180,17 +186,6 @@ impl Operand<'_> {
     }
 }
 
-fn is_char(interface: &witx2::Interface, ty: &Type) -> bool {
-    match ty {
-        Type::Char => true,
-        Type::Id(id) => match &interface.types[*id].kind {
-            TypeDefKind::Type(t) => is_char(interface, t),
-            _ => false,
-        },
-        _ => false,
-    }
-}
-
 #[derive(Debug, Clone, Copy)]
 enum PushMode {
     Params,
@@ -449,7 +444,7 @@ impl<'a> CallAdapter<'a> {
                     let len = params.next().unwrap();
 
                     let mut element_operands = Vec::new();
-                    if !interface.all_bits_valid(element) && !is_char(interface, element) {
+                    if !interface.all_bits_valid(element) {
                         Self::push_element_operands(
                             interface,
                             sizes,
@@ -473,10 +468,8 @@ impl<'a> CallAdapter<'a> {
                         *locals_count += 1;
                     }
 
-                    let (element_size, element_alignment) = match element {
-                        Type::Char => (1, 1), // UTF-8
-                        _ => (sizes.size(element) as u32, sizes.align(element) as u32),
-                    };
+                    let element_size = sizes.size(element) as u32;
+                    let element_alignment = sizes.align(element) as u32;
 
                     operands.push(Operand::List {
                         addr: mode.create_value_ref(addr),
@@ -511,14 +504,71 @@ impl<'a> CallAdapter<'a> {
                         }
                     }
                 },
-                TypeDefKind::Variant(v) if v.is_bool() || v.is_enum() => {
+                // Arrays passed through memory are copied like lists of a
+                // constant length.
+                TypeDefKind::Array(element, len)
+                    if interface.array_in_memory(
+                        witx2::abi::Abi::Canonical,
+                        witx2::abi::Direction::Import,
+                        element,
+                        *len,
+                    ) =>
+                {
+                    let addr = params.next().unwrap();
+
+                    let mut element_operands = Vec::new();
+                    if !interface.all_bits_valid(element) {
+                        Self::push_element_operands(
+                            interface,
+                            sizes,
+                            element,
+                            0,
+                            mode,
+                            locals_count,
+                            &mut element_operands,
+                        );
+                    }
+
+                    // Unlike lists these are a single value, so they can also
+                    // be returned directly.
+                    *locals_count += match mode {
+                        PushMode::Params | PushMode::Return => 1,
+                        PushMode::RetPtr => 2,
+                    };
+
+                    if !element_operands.is_empty() {
+                        *locals_count += 1;
+                    }
+
+                    operands.push(Operand::List {
+                        addr: mode.create_value_ref(addr),
+                        len: ValueRef::Const(*len),
+                        element_size: sizes.size(element) as u32,
+                        element_alignment: sizes.align(element) as u32,
+                        operands: element_operands,
+                    });
+                }
+                TypeDefKind::Array(element, len) => {
+                    for _ in 0..*len {
+                        Self::push_operands(
+                            interface,
+                            sizes,
+                            element,
+                            params,
+                            mode,
+                            locals_count,
+                            operands,
+                        );
+                    }
+                }
+                TypeDefKind::Variant(v) if v.is_enum() => {
                     params.next().unwrap();
                 }
                 TypeDefKind::Variant(v) => {
                     let discriminant = params.next().unwrap();
                     let mut count = 0;
                     let mut cases = Vec::new();
-                    for (i, c) in v.cases.iter().enumerate() {
+                    for c in v.cases.iter() {
                         if let Some(ty) = &c.ty {
                             let mut iter = params.clone();
                             let mut operands = Vec::new();
@@ -534,7 +584,7 @@ impl<'a> CallAdapter<'a> {
                             );
 
                             if !operands.is_empty() {
-                                cases.push((i as u32, operands));
+                                cases.push((c.discriminant, operands));
                             }
 
                             count = std::cmp::max(count, params.len() - iter.len());
@@ -557,6 +607,25 @@ impl<'a> CallAdapter<'a> {
                 }
                 TypeDefKind::PushBuffer(_) | TypeDefKind::PullBuffer(_) => todo!(),
             },
+            Type::String => {
+                let addr = params.next().unwrap();
+                let len = params.next().unwrap();
+
+                // Strings are copied like lists of bytes
+                *locals_count += match mode {
+                    PushMode::Params => 1,
+                    PushMode::RetPtr => 2,
+                    PushMode::Return => unreachable!(),
+                };
+
+                operands.push(Operand::List {
+                    addr: mode.create_value_ref(addr),
+                    len: mode.create_value_ref(len),
+                    element_size: 1,
+                    element_alignment: 1,
+                    operands: Vec::new(),
+                });
+            }
             Type::Handle(id) => {
                 let addr = params.next().unwrap();
 
@@ -599,7 +668,7 @@ impl<'a> CallAdapter<'a> {
                 ),
                 TypeDefKind::List(element) => {
                     let mut element_operands = Vec::new();
-                    if !interface.all_bits_valid(element) && !is_char(interface, element) {
+                    if !interface.all_bits_valid(element) {
                         Self::push_element_operands(
                             interface,
                             sizes,
@@ -619,10 +688,8 @@ impl<'a> CallAdapter<'a> {
                         *locals_count += 1;
                     }
 
-                    let (element_size, element_alignment) = match element {
-                        Type::Char => (1, 1), // UTF-8
-                        _ => (sizes.size(element) as u32, sizes.align(element) as u32),
-                    };
+                    let element_size = sizes.size(element) as u32;
+                    let element_alignment = sizes.align(element) as u32;
 
                     operands.push(Operand::List {
                         addr: ValueRef::ElementOffset(offset),
@@ -650,12 +717,27 @@ impl<'a> CallAdapter<'a> {
                         }
                     }
                 },
-                TypeDefKind::Variant(v) if v.is_bool() || v.is_enum() => {}
+                TypeDefKind::Array(element, len) => {
+                    let element_size = sizes.size(element) as u32;
+
+                    for i in 0..*len {
+                        Self::push_element_operands(
+                            interface,
+                            sizes,
+                            element,
+                            offset + i * element_size,
+                            mode,
+                            locals_count,
+                            operands,
+                        );
+                    }
+                }
+                TypeDefKind::Variant(v) if v.is_enum() => {}
                 TypeDefKind::Variant(v) => {
                     let payload_offset = sizes.payload_offset(v) as u32;
 
                     let mut cases = Vec::new();
-                    for (i, c) in v.cases.iter().enumerate() {
+                    for c in v.cases.iter() {
                         if let Some(ty) = &c.ty {
                             let mut operands = Vec::new();
                             Self::push_element_operands(
@@ -668,7 +750,7 @@ impl<'a> CallAdapter<'a> {
                                 &mut operands,
                             );
                             if !operands.is_empty() {
-                                cases.push((i as u32, operands));
+                                cases.push((c.discriminant, operands));
                             }
                         }
                     }
@@ -683,6 +765,18 @@ impl<'a> CallAdapter<'a> {
                 TypeDefKind::Pointer(_) | TypeDefKind::ConstPointer(_) => {}
                 TypeDefKind::PushBuffer(_) | TypeDefKind::PullBuffer(_) => todo!(),
             },
+            Type::String => {
+                // Every string copied needs a source and destination local
+                *locals_count += 2;
+
+                operands.push(Operand::List {
+                    addr: ValueRef::ElementOffset(offset),
+                    len: ValueRef::ElementOffset(offset + 4),
+                    element_size: 1,
+                    element_alignment: 1,
+                    operands: Vec::new(),
+                });
+            }
             Type::Handle(id) => {
                 // Params need to be cloned, so add a local
                 *locals_count += match mode {
@@ -832,6 +926,7 @@ impl<'a> CallAdapter<'a> {
                         let dst = match addr {
                             ValueRef::Local(i) | ValueRef::Return(i) => locals.map(*i),
                             ValueRef::ElementOffset(_) | ValueRef::RetPtr(_) => locals.allocate(),
+                            ValueRef::Const(_) => unreachable!(),
                         };
 
                         function.instruction(Instruction::LocalSet(dst));
@@ -950,14 +1045,14 @@ impl<'a> CallAdapter<'a> {
         };
 
         let (src, dst) = match addr {
-            ValueRef::Local(i) => (i, locals.map(i)),
+            ValueRef::Local(i) | ValueRef::Return(i) => (i, locals.map(i)),
             ValueRef::ElementOffset(_) | ValueRef::RetPtr(_) => {
                 let src = locals.allocate();
                 addr.emit_load(function, src_base, LoadType::I32);
                 function.instruction(Instruction::LocalSet(src));
                 (src, locals.allocate())
             }
-            ValueRef::Return(_) => unreachable!(),
+            ValueRef::Const(_) => unreachable!(),
         };
 
         function.instruction(Instruction::Block(BlockType::Empty));
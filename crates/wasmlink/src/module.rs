@@ -9,7 +9,7 @@ use wasmparser::{
     SectionReader, Type, TypeDef, Validator,
 };
 use witx2::{
-    abi::{Direction, WasmSignature, WasmType},
+    abi::{Direction, MultiValue, WasmSignature, WasmType},
     Function, SizeAlign,
 };
 
@@ -46,7 +46,7 @@ fn has_list(interface: &witx2::Interface, ty: &witx2::Type) -> bool {
     match ty {
         Type::Id(id) => match &interface.types[*id].kind {
             TypeDefKind::List(_) => true,
-            TypeDefKind::Type(t) => has_list(interface, t),
+            TypeDefKind::Type(t) | TypeDefKind::Array(t, _) => has_list(interface, t),
             TypeDefKind::Record(r) => r.fields.iter().any(|f| has_list(interface, &f.ty)),
             TypeDefKind::Variant(v) => v.cases.iter().any(|c| {
                 c.ty.as_ref()
@@ -55,6 +55,7 @@ fn has_list(interface: &witx2::Interface, ty: &witx2::Type) -> bool {
             }),
             _ => false,
         },
+        Type::String => true,
         _ => false,
     }
 }
@@ -86,8 +87,10 @@ impl Interface {
             .functions
             .iter()
             .map(|f| {
-                let import_signature = inner.wasm_signature(Direction::Import, f);
-                let export_signature = inner.wasm_signature(Direction::Export, f);
+                let import_signature =
+                    inner.wasm_signature(Direction::Import, MultiValue::Disabled, f);
+                let export_signature =
+                    inner.wasm_signature(Direction::Export, MultiValue::Disabled, f);
                 let import_type = Self::sig_to_type(&import_signature);
                 let export_type = Self::sig_to_type(&export_signature);
 
@@ -1,4 +1,8 @@
 use proc_macro::TokenStream;
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
 use syn::parse::{Error, Parse, ParseStream, Result};
 use syn::punctuated::Punctuated;
 use syn::{token, Token};
@@ -6,6 +10,12 @@ use witx2::abi::Direction;
 use witx_bindgen_gen_core::{witx2, Files, Generator};
 use witx_bindgen_gen_wasmtime::Async;
 
+thread_local! {
+    /// Interfaces parsed by earlier invocations of these macros, which rustc
+    /// runs in the same process for every invocation in a crate.
+    static CACHE: RefCell<witx2::ParseCache> = RefCell::new(witx2::ParseCache::new());
+}
+
 #[proc_macro]
 pub fn import(input: TokenStream) -> TokenStream {
     run(input, Direction::Import)
@@ -18,18 +28,11 @@ pub fn export(input: TokenStream) -> TokenStream {
 
 fn run(input: TokenStream, dir: Direction) -> TokenStream {
     let input = syn::parse_macro_input!(input as Opts);
-    let mut gen = input.opts.build();
-    let mut files = Files::default();
-    let (imports, exports) = match dir {
-        Direction::Import => (input.interfaces, vec![]),
-        Direction::Export => (vec![], input.interfaces),
+    let contents = CACHE.with(|cache| input.generate(&mut cache.borrow_mut(), dir));
+    let mut contents = match contents {
+        Ok(contents) => contents.parse::<TokenStream>().unwrap(),
+        Err(e) => return e.to_compile_error().into(),
     };
-    gen.generate_all(&imports, &exports, &mut files);
-
-    let (_, contents) = files.iter().next().unwrap();
-
-    let contents = std::str::from_utf8(contents).unwrap();
-    let mut contents = contents.parse::<TokenStream>().unwrap();
 
     // Include a dummy `include_str!` for any files we read so rustc knows that
     // we depend on the contents of those files.
@@ -50,14 +53,78 @@ fn run(input: TokenStream, dir: Direction) -> TokenStream {
 
 struct Opts {
     opts: witx_bindgen_gen_wasmtime::Opts,
-    interfaces: Vec<witx2::Interface>,
+    inputs: Vec<Input>,
+    witx_path: Vec<PathBuf>,
     files: Vec<String>,
 }
 
+impl Opts {
+    /// Generates the bindings for this macro invocation.
+    ///
+    /// If the `WITX_BINDGEN_CACHE` environment variable is set then the
+    /// bindings are also cached in `OUT_DIR`, keyed by the contents of every
+    /// `*.witx` file used and the options given, so they're only generated
+    /// again when one of those changes.
+    fn generate(&self, cache: &mut witx2::ParseCache, dir: Direction) -> Result<String> {
+        let cached = match disk_cache_dir() {
+            Some(cache_dir) => {
+                let mut hasher = DefaultHasher::new();
+                env!("CARGO_PKG_VERSION").hash(&mut hasher);
+                (dir == Direction::Import).hash(&mut hasher);
+                format!("{:?}", self.opts).hash(&mut hasher);
+                for input in self.inputs.iter() {
+                    input.key(&self.witx_path, cache)?.hash(&mut hasher);
+                }
+                Some(cache_dir.join(format!("{:016x}.rs", hasher.finish())))
+            }
+            None => None,
+        };
+        if let Some(contents) = cached
+            .as_ref()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+        {
+            return Ok(contents);
+        }
+
+        let mut interfaces = Vec::new();
+        for input in self.inputs.iter() {
+            interfaces.push(input.parse(&self.witx_path, cache)?);
+        }
+        let mut gen = self.opts.clone().build();
+        let mut files = Files::default();
+        let (imports, exports) = match dir {
+            Direction::Import => (interfaces, vec![]),
+            Direction::Export => (vec![], interfaces),
+        };
+        gen.generate_all(&imports, &exports, &mut files);
+        let (_, contents) = files.iter().next().unwrap();
+        let contents = std::str::from_utf8(contents).unwrap().to_string();
+
+        // Failing to write to the cache only means the bindings are generated
+        // again next time.
+        if let Some(path) = cached {
+            let _ = std::fs::create_dir_all(path.parent().unwrap());
+            let _ = std::fs::write(&path, &contents);
+        }
+        Ok(contents)
+    }
+}
+
+/// Returns the directory generated bindings are cached in, if caching them
+/// is enabled.
+fn disk_cache_dir() -> Option<PathBuf> {
+    std::env::var_os("WITX_BINDGEN_CACHE")?;
+    let out_dir = std::env::var_os("OUT_DIR")?;
+    Some(PathBuf::from(out_dir).join("witx-bindgen-cache"))
+}
+
 mod kw {
     syn::custom_keyword!(src);
     syn::custom_keyword!(paths);
+    syn::custom_keyword!(witx_path);
     syn::custom_keyword!(custom_error);
+    syn::custom_keyword!(string_encoding);
+    syn::custom_keyword!(multi_value);
 }
 
 impl Parse for Opts {
@@ -67,50 +134,54 @@ impl Parse for Opts {
         let mut files = Vec::new();
         opts.tracing = cfg!(feature = "tracing");
 
-        let interfaces = if input.peek(token::Brace) {
+        let mut inputs = Vec::new();
+        let mut witx_path = Vec::new();
+        if input.peek(token::Brace) {
             let content;
             syn::braced!(content in input);
-            let mut interfaces = Vec::new();
             let fields = Punctuated::<ConfigField, Token![,]>::parse_terminated(&content)?;
             for field in fields.into_pairs() {
                 match field.into_value() {
-                    ConfigField::Interfaces(v) => interfaces = v,
+                    ConfigField::Interfaces(v) => inputs = v,
+                    ConfigField::WitxPath(v) => witx_path = v,
                     ConfigField::Async(v) => opts.async_ = v,
                     ConfigField::CustomError(v) => opts.custom_error = v,
+                    ConfigField::StringEncoding(v) => opts.string_encoding = v,
+                    ConfigField::MultiValue(v) => opts.multi_value = v,
                 }
             }
-            if interfaces.is_empty() {
+            if inputs.is_empty() {
                 return Err(Error::new(
                     call_site,
                     "must either specify `src` or `paths` keys",
                 ));
             }
-            interfaces
         } else {
             while !input.is_empty() {
                 let s = input.parse::<syn::LitStr>()?;
                 files.push(s.value());
             }
-            let mut interfaces = Vec::new();
-            for path in files.iter() {
-                let iface =
-                    witx2::Interface::parse_file(path).map_err(|e| Error::new(call_site, e))?;
-                interfaces.push(iface);
-            }
-            interfaces
-        };
+            inputs.extend(files.iter().map(|path| Input::Path {
+                path: path.into(),
+                span: call_site,
+            }));
+        }
         Ok(Opts {
             opts,
-            interfaces,
+            inputs,
+            witx_path,
             files,
         })
     }
 }
 
 enum ConfigField {
-    Interfaces(Vec<witx2::Interface>),
+    Interfaces(Vec<Input>),
+    WitxPath(Vec<PathBuf>),
     Async(witx_bindgen_gen_wasmtime::Async),
     CustomError(bool),
+    StringEncoding(witx2::abi::StringEncoding),
+    MultiValue(witx2::abi::MultiValue),
 }
 
 impl Parse for ConfigField {
@@ -123,23 +194,33 @@ impl Parse for ConfigField {
             let name = name.parse::<syn::LitStr>()?;
             input.parse::<Token![:]>()?;
             let s = input.parse::<syn::LitStr>()?;
-            let interface = witx2::Interface::parse(&name.value(), &s.value())
-                .map_err(|e| Error::new(s.span(), e))?;
-            Ok(ConfigField::Interfaces(vec![interface]))
+            Ok(ConfigField::Interfaces(vec![Input::Src {
+                name: name.value(),
+                src: s,
+            }]))
         } else if l.peek(kw::paths) {
             input.parse::<kw::paths>()?;
             input.parse::<Token![:]>()?;
             let paths;
             let bracket = syn::bracketed!(paths in input);
             let paths = Punctuated::<syn::LitStr, Token![,]>::parse_terminated(&paths)?;
-            let values = paths.iter().map(|s| s.value()).collect::<Vec<_>>();
-            let mut interfaces = Vec::new();
-            for value in &values {
-                let interface =
-                    witx2::Interface::parse_file(value).map_err(|e| Error::new(bracket.span, e))?;
-                interfaces.push(interface);
-            }
-            Ok(ConfigField::Interfaces(interfaces))
+            let inputs = paths
+                .iter()
+                .map(|s| Input::Path {
+                    path: s.value().into(),
+                    span: bracket.span,
+                })
+                .collect();
+            Ok(ConfigField::Interfaces(inputs))
+        } else if l.peek(kw::witx_path) {
+            input.parse::<kw::witx_path>()?;
+            input.parse::<Token![:]>()?;
+            let paths;
+            syn::bracketed!(paths in input);
+            let paths = Punctuated::<syn::LitStr, Token![,]>::parse_terminated(&paths)?;
+            Ok(ConfigField::WitxPath(
+                paths.iter().map(|s| s.value().into()).collect(),
+            ))
         } else if l.peek(token::Async) {
             if !cfg!(feature = "async") {
                 return Err(
@@ -164,8 +245,76 @@ impl Parse for ConfigField {
             Ok(ConfigField::CustomError(
                 input.parse::<syn::LitBool>()?.value,
             ))
+        } else if l.peek(kw::string_encoding) {
+            input.parse::<kw::string_encoding>()?;
+            input.parse::<Token![:]>()?;
+            let s = input.parse::<syn::LitStr>()?;
+            let encoding = s.value().parse().map_err(|e| Error::new(s.span(), e))?;
+            Ok(ConfigField::StringEncoding(encoding))
+        } else if l.peek(kw::multi_value) {
+            input.parse::<kw::multi_value>()?;
+            input.parse::<Token![:]>()?;
+            let max_results = input.parse::<syn::LitInt>()?.base10_parse()?;
+            Ok(ConfigField::MultiValue(witx2::abi::MultiValue::Enabled {
+                max_results,
+            }))
         } else {
             Err(l.error())
         }
     }
 }
+
+/// An interface named in the macro's configuration, which is parsed once the
+/// `witx_path` to resolve its modules with is known.
+enum Input {
+    Src {
+        name: String,
+        src: syn::LitStr,
+    },
+    Path {
+        path: PathBuf,
+        span: proc_macro2::Span,
+    },
+}
+
+impl Input {
+    /// Returns the file name, contents and resolver to parse this input with.
+    fn load(
+        &self,
+        witx_path: &[PathBuf],
+    ) -> Result<(PathBuf, String, witx2::FsResolver, proc_macro2::Span)> {
+        match self {
+            Input::Src { name, src } => {
+                let resolver = witx2::FsResolver::new(witx_path.to_vec());
+                Ok((name.into(), src.value(), resolver, src.span()))
+            }
+            Input::Path { path, span } => {
+                let mut search_path = vec![path.parent().unwrap().to_path_buf()];
+                search_path.extend(witx_path.iter().cloned());
+                let resolver = witx2::FsResolver::new(search_path);
+                let contents = std::fs::read_to_string(path).map_err(|e| {
+                    Error::new(*span, format!("failed to read: {}: {}", path.display(), e))
+                })?;
+                Ok((path.clone(), contents, resolver, *span))
+            }
+        }
+    }
+
+    fn key(&self, witx_path: &[PathBuf], cache: &mut witx2::ParseCache) -> Result<u64> {
+        let (filename, contents, mut resolver, span) = self.load(witx_path)?;
+        cache
+            .key(&filename, &contents, &mut resolver)
+            .map_err(|e| Error::new(span, format!("{:#}", e)))
+    }
+
+    fn parse(
+        &self,
+        witx_path: &[PathBuf],
+        cache: &mut witx2::ParseCache,
+    ) -> Result<witx2::Interface> {
+        let (filename, contents, mut resolver, span) = self.load(witx_path)?;
+        cache
+            .parse_with_resolver(&filename, &contents, &mut resolver)
+            .map_err(|e| Error::new(span, format!("{:#}", e)))
+    }
+}
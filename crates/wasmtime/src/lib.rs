@@ -155,6 +155,77 @@ pub mod rt {
         Ok(result)
     }
 
+    /// Set in the length of a UTF-16 string in the `latin1+utf16` encoding.
+    const UTF16_TAG: i32 = i32::MIN;
+
+    /// Encodes `s` as little-endian UTF-16, returning its bytes, its length in
+    /// code units and the alignment of its allocation.
+    pub fn utf16_encode(s: &str) -> (Vec<u8>, i32, i32) {
+        let bytes = s
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect::<Vec<u8>>();
+        let len = (bytes.len() / 2) as i32;
+        (bytes, len, 2)
+    }
+
+    /// Same as `utf16_encode` but for the `latin1+utf16` encoding.
+    pub fn compact_utf16_encode(s: &str) -> (Vec<u8>, i32, i32) {
+        if s.chars().any(|c| u32::from(c) > 0xff) {
+            let (bytes, len, align) = utf16_encode(s);
+            return (bytes, len | UTF16_TAG, align);
+        }
+        let bytes = s.chars().map(|c| c as u8).collect::<Vec<u8>>();
+        let len = bytes.len() as i32;
+        (bytes, len, 1)
+    }
+
+    /// Copies a UTF-16 string out of linear memory, freeing it afterwards.
+    pub fn copy_utf16_string(
+        store: impl AsContextMut,
+        memory: &Memory,
+        free: &TypedFunc<(i32, i32, i32), ()>,
+        base: i32,
+        len: i32,
+    ) -> Result<String, Trap> {
+        let units = copy_slice::<u16>(store, memory, free, base, len, 2)?;
+        String::from_utf16(&units).map_err(|_| Trap::new("invalid utf-16"))
+    }
+
+    /// Same as `copy_utf16_string` but for the `latin1+utf16` encoding.
+    pub fn copy_compact_utf16_string(
+        store: impl AsContextMut,
+        memory: &Memory,
+        free: &TypedFunc<(i32, i32, i32), ()>,
+        base: i32,
+        len: i32,
+    ) -> Result<String, Trap> {
+        if len & UTF16_TAG != 0 {
+            return copy_utf16_string(store, memory, free, base, len & !UTF16_TAG);
+        }
+        let bytes = copy_slice::<u8>(store, memory, free, base, len, 1)?;
+        Ok(bytes.into_iter().map(char::from).collect())
+    }
+
+    /// Owns strings which have been transcoded out of linear memory so they
+    /// can be lent to the host for the duration of a call.
+    #[derive(Default)]
+    pub struct StringArena {
+        strings: std::cell::RefCell<Vec<Box<str>>>,
+    }
+
+    impl StringArena {
+        pub fn alloc(&self, s: String) -> &str {
+            let s = s.into_boxed_str();
+            let ret = &*s as *const str;
+            self.strings.borrow_mut().push(s);
+            // SAFETY: strings are never removed from the arena, and moving
+            // the `Box` doesn't move its contents, so this lives as long as
+            // `self`.
+            unsafe { &*ret }
+        }
+    }
+
     macro_rules! as_traits {
         ($(($name:ident $tr:ident $ty:ident ($($tys:ident)*)))*) => ($(
             pub fn $name<T: $tr>(t: T) -> $ty {
@@ -128,6 +128,24 @@ impl<'a> BorrowChecker<'a> {
         std::str::from_utf8(bytes).map_err(to_trap)
     }
 
+    /// Decodes the UTF-16 string of `len` code units at `ptr`.
+    pub fn utf16_string(&mut self, ptr: i32, len: i32) -> Result<String, Trap> {
+        let units = self.slice::<Le<u16>>(ptr, len)?;
+        char::decode_utf16(units.iter().map(|unit| unit.get()))
+            .collect::<Result<String, _>>()
+            .map_err(to_trap)
+    }
+
+    /// Decodes the string at `ptr` in the `latin1+utf16` encoding, where the
+    /// high bit of `len` is set for UTF-16 strings.
+    pub fn compact_utf16_string(&mut self, ptr: i32, len: i32) -> Result<String, Trap> {
+        if len < 0 {
+            return self.utf16_string(ptr, len & i32::MAX);
+        }
+        let bytes = self.slice::<u8>(ptr, len)?;
+        Ok(bytes.iter().map(|b| char::from(*b)).collect())
+    }
+
     fn validate_contains(&self, region: &Region) -> Result<(), Trap> {
         let end = region
             .start
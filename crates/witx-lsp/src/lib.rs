@@ -0,0 +1,641 @@
+//! A language server for `*.witx` files.
+//!
+//! The server is built on `witx2`'s recovering parser and resolver and
+//! communicates with an editor over stdin/stdout. Documents are always synced
+//! in full and are re-analyzed from scratch on every change, which is plenty
+//! fast for interfaces of the size people write by hand.
+
+use anyhow::Result;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{self, Notification as _};
+use lsp_types::request::{self, Request as _};
+use lsp_types::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use witx2::abi::Direction;
+use witx2::ast::{self, Ast, Item};
+use witx2::printer::print_typedef;
+use witx2::{FsResolver, Interface, Resolver, SizeAlign, Span, Type, TypeDefKind};
+
+/// Runs the language server over `connection` until the client shuts it
+/// down.
+pub fn run(connection: &Connection) -> Result<()> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions::default()),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    let mut server = Server {
+        connection,
+        documents: HashMap::new(),
+    };
+    for msg in &connection.receiver {
+        match msg {
+            Message::Request(req) => {
+                if connection.handle_shutdown(&req)? {
+                    break;
+                }
+                server.request(req)?;
+            }
+            Message::Notification(not) => server.notification(not)?,
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+struct Server<'a> {
+    connection: &'a Connection,
+    /// Contents of all documents currently open in the editor, which take
+    /// precedence over what's on disk.
+    documents: HashMap<Url, String>,
+}
+
+/// What an identifier in a document refers to.
+enum Target<'a> {
+    /// A type or resource name, looked up in the document's own scope.
+    Name(&'a str),
+    /// The name `name` defined in `module`, known locally as `local`.
+    Import {
+        module: &'a str,
+        name: &'a str,
+        local: &'a str,
+    },
+    /// The module `module` itself, as named in a `use`.
+    Module(&'a str),
+}
+
+impl Server<'_> {
+    fn request(&self, req: Request) -> Result<()> {
+        match req.method.as_str() {
+            request::HoverRequest::METHOD => {
+                self.respond::<request::HoverRequest>(req, Server::hover)
+            }
+            request::GotoDefinition::METHOD => {
+                self.respond::<request::GotoDefinition>(req, Server::definition)
+            }
+            request::Completion::METHOD => {
+                self.respond::<request::Completion>(req, Server::completion)
+            }
+            request::DocumentSymbolRequest::METHOD => {
+                self.respond::<request::DocumentSymbolRequest>(req, Server::document_symbols)
+            }
+            _ => {
+                let response = Response::new_err(
+                    req.id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("unsupported request `{}`", req.method),
+                );
+                self.connection.sender.send(Message::Response(response))?;
+                Ok(())
+            }
+        }
+    }
+
+    fn respond<R: request::Request>(
+        &self,
+        req: Request,
+        handler: fn(&Self, R::Params) -> R::Result,
+    ) -> Result<()> {
+        // A bad request is the client's problem, so it's reported back to it
+        // rather than shutting down the server.
+        let response = match serde_json::from_value(req.params) {
+            Ok(params) => Response::new_ok(req.id, handler(self, params)),
+            Err(e) => Response::new_err(
+                req.id,
+                ErrorCode::InvalidParams as i32,
+                format!("invalid parameters for `{}`: {}", req.method, e),
+            ),
+        };
+        self.connection.sender.send(Message::Response(response))?;
+        Ok(())
+    }
+
+    fn notification(&mut self, not: Notification) -> Result<()> {
+        match not.method.as_str() {
+            notification::DidOpenTextDocument::METHOD => {
+                let params = match self.params::<notification::DidOpenTextDocument>(not.params)? {
+                    Some(params) => params,
+                    None => return Ok(()),
+                };
+                let doc = params.text_document;
+                self.documents.insert(doc.uri, doc.text);
+            }
+            notification::DidChangeTextDocument::METHOD => {
+                let params = match self.params::<notification::DidChangeTextDocument>(not.params)? {
+                    Some(params) => params,
+                    None => return Ok(()),
+                };
+                // With full document sync the last change is the entire
+                // document.
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    self.documents.insert(params.text_document.uri, change.text);
+                }
+            }
+            notification::DidCloseTextDocument::METHOD => {
+                let params = match self.params::<notification::DidCloseTextDocument>(not.params)? {
+                    Some(params) => params,
+                    None => return Ok(()),
+                };
+                let uri = params.text_document.uri;
+                self.documents.remove(&uri);
+                self.notify::<notification::PublishDiagnostics>(PublishDiagnosticsParams {
+                    uri,
+                    diagnostics: Vec::new(),
+                    version: None,
+                })?;
+            }
+            _ => return Ok(()),
+        }
+
+        // Documents can import each other so any change may affect the
+        // diagnostics of every open document.
+        let uris = self.documents.keys().cloned().collect::<Vec<_>>();
+        for uri in uris {
+            self.publish_diagnostics(&uri)?;
+        }
+        Ok(())
+    }
+
+    /// Parses the parameters of a notification, logging and ignoring it if
+    /// they're malformed since notifications can't be responded to.
+    fn params<N: notification::Notification>(
+        &self,
+        params: serde_json::Value,
+    ) -> Result<Option<N::Params>> {
+        match serde_json::from_value(params) {
+            Ok(params) => Ok(Some(params)),
+            Err(e) => {
+                self.notify::<notification::LogMessage>(LogMessageParams {
+                    typ: MessageType::WARNING,
+                    message: format!("invalid parameters for `{}`: {}", N::METHOD, e),
+                })?;
+                Ok(None)
+            }
+        }
+    }
+
+    fn notify<N: notification::Notification>(&self, params: N::Params) -> Result<()> {
+        let not = Notification::new(N::METHOD.to_string(), params);
+        self.connection.sender.send(Message::Notification(not))?;
+        Ok(())
+    }
+
+    fn publish_diagnostics(&self, uri: &Url) -> Result<()> {
+        let text = &self.documents[uri];
+        let path = file_path(uri);
+        let (_, diagnostics) = self.analyze(uri, text);
+        let diagnostics = diagnostics
+            .iter()
+            // Problems in other files are reported when those files are
+            // opened themselves.
+            .filter(|d| d.file == path)
+            .map(|d| {
+                let mut message = d.message.clone();
+                for note in d.notes.iter() {
+                    message.push('\n');
+                    message.push_str(note);
+                }
+                Diagnostic {
+                    range: range(text, d.span),
+                    severity: Some(match d.severity {
+                        witx2::Severity::Error => DiagnosticSeverity::ERROR,
+                        witx2::Severity::Warning => DiagnosticSeverity::WARNING,
+                    }),
+                    source: Some("witx".to_string()),
+                    message,
+                    ..Diagnostic::default()
+                }
+            })
+            .collect();
+        self.notify::<notification::PublishDiagnostics>(PublishDiagnosticsParams {
+            uri: uri.clone(),
+            diagnostics,
+            version: None,
+        })
+    }
+
+    fn analyze(&self, uri: &Url, text: &str) -> (Interface, Vec<witx2::Diagnostic>) {
+        let path = file_path(uri);
+        Interface::parse_with_diagnostics(&path, text, |name| self.load(&path, name))
+    }
+
+    /// Loads the module `name` as referred to by a `use` in the file `from`,
+    /// preferring the editor's contents if the module is open.
+    fn load(&self, from: &Path, name: &str) -> Result<(PathBuf, String)> {
+        let dir = from.parent().unwrap_or(Path::new("")).to_path_buf();
+        let mut resolver = FsResolver::new(vec![dir]);
+        // Prefer the contents of open documents over what's on disk, since
+        // they may have unsaved changes.
+        for path in resolver.candidates(name) {
+            let open = Url::from_file_path(&path)
+                .ok()
+                .and_then(|uri| self.documents.get(&uri));
+            if let Some(text) = open {
+                return Ok((path, text.clone()));
+            }
+        }
+        resolver.load(name)
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let params = params.text_document_position_params;
+        let uri = &params.text_document.uri;
+        let text = self.documents.get(uri)?;
+        let (ast, _) = Ast::parse_recover(text);
+        let (span, target) = target_at(&ast, offset(text, params.position))?;
+        let name = match target {
+            Target::Name(name) => name,
+            Target::Import { local, .. } => local,
+            Target::Module(_) => return None,
+        };
+
+        let (iface, _) = self.analyze(uri, text);
+        let id = *iface.type_lookup.get(name)?;
+        let mut sizes = SizeAlign::default();
+        sizes.fill(Direction::Export, &iface);
+        let ty = Type::Id(id);
+
+        let mut value = format!("```witx\n{}\n```\n", print_typedef(&iface, id));
+        if let Some(docs) = &iface.types[id].docs.contents {
+            value.push('\n');
+            value.push_str(docs);
+        }
+        value.push_str(&format!(
+            "\nsize: {}, align: {}",
+            sizes.size(&ty),
+            sizes.align(&ty)
+        ));
+        Some(Hover {
+            contents: HoverContents::Markup(MarkupContent {
+                kind: MarkupKind::Markdown,
+                value,
+            }),
+            range: Some(range(text, span)),
+        })
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let params = params.text_document_position_params;
+        let uri = &params.text_document.uri;
+        let text = self.documents.get(uri)?;
+        let (ast, _) = Ast::parse_recover(text);
+        let (_, target) = target_at(&ast, offset(text, params.position))?;
+        let location = self.locate(uri, text, &ast, target, 0)?;
+        Some(GotoDefinitionResponse::Scalar(location))
+    }
+
+    /// Finds where `target`, referenced from the document `uri`, is defined.
+    ///
+    /// Names brought in with `use` are followed into the module that defines
+    /// them, up to a fixed depth to guard against import cycles.
+    fn locate(
+        &self,
+        uri: &Url,
+        text: &str,
+        ast: &Ast<'_>,
+        target: Target<'_>,
+        depth: usize,
+    ) -> Option<Location> {
+        if depth > 32 {
+            return None;
+        }
+        let name = match target {
+            Target::Name(name) => name,
+            Target::Import { module, name, .. } => {
+                return self.locate_in_module(uri, module, name, depth)
+            }
+            Target::Module(module) => {
+                let (path, _) = self.load(&file_path(uri), module).ok()?;
+                return Some(Location {
+                    uri: Url::from_file_path(path).ok()?,
+                    range: Range::default(),
+                });
+            }
+        };
+
+        for item in ast.items.iter() {
+            let id = match item {
+                Item::TypeDef(t) => &t.name,
+                Item::Resource(r) => &r.name,
+                _ => continue,
+            };
+            if id.name == name {
+                return Some(Location {
+                    uri: uri.clone(),
+                    range: range(text, id.span),
+                });
+            }
+        }
+
+        // Not defined in this file, so see if it was imported.
+        for item in ast.items.iter() {
+            let u = match item {
+                Item::Use(u) => u,
+                _ => continue,
+            };
+            let module = &u.from[0].name;
+            match &u.names {
+                Some(names) => {
+                    for n in names {
+                        if n.as_.as_ref().unwrap_or(&n.name).name == name {
+                            return self.locate_in_module(uri, module, &n.name.name, depth);
+                        }
+                    }
+                }
+                None => {
+                    if let Some(location) = self.locate_in_module(uri, module, name, depth) {
+                        return Some(location);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn locate_in_module(
+        &self,
+        from: &Url,
+        module: &str,
+        name: &str,
+        depth: usize,
+    ) -> Option<Location> {
+        let (path, text) = self.load(&file_path(from), module).ok()?;
+        let uri = Url::from_file_path(&path).ok()?;
+        let (ast, _) = Ast::parse_recover(&text);
+        self.locate(&uri, &text, &ast, Target::Name(name), depth + 1)
+    }
+
+    fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let uri = &params.text_document_position.text_document.uri;
+        let text = self.documents.get(uri)?;
+        let (iface, _) = self.analyze(uri, text);
+
+        let mut names = iface.type_lookup.iter().collect::<Vec<_>>();
+        names.sort();
+        let mut items = names
+            .into_iter()
+            .map(|(name, id)| CompletionItem {
+                label: name.clone(),
+                kind: Some(match &iface.types[*id].kind {
+                    TypeDefKind::Type(Type::Handle(_)) => CompletionItemKind::CLASS,
+                    TypeDefKind::Variant(_) => CompletionItemKind::ENUM,
+                    _ => CompletionItemKind::STRUCT,
+                }),
+                detail: Some(print_typedef(&iface, *id)),
+                ..CompletionItem::default()
+            })
+            .collect::<Vec<_>>();
+        items.extend(BUILTIN_TYPES.iter().map(|name| CompletionItem {
+            label: name.to_string(),
+            kind: Some(CompletionItemKind::KEYWORD),
+            ..CompletionItem::default()
+        }));
+        Some(CompletionResponse::Array(items))
+    }
+
+    fn document_symbols(&self, params: DocumentSymbolParams) -> Option<DocumentSymbolResponse> {
+        let text = self.documents.get(&params.text_document.uri)?;
+        let (ast, _) = Ast::parse_recover(text);
+        let mut symbols = Vec::new();
+        for item in ast.items.iter() {
+            match item {
+                Item::Resource(r) => {
+                    let children = r
+                        .values
+                        .iter()
+                        .filter(|(_, v)| is_function(v))
+                        .map(|(statik, v)| {
+                            let kind = if *statik {
+                                SymbolKind::FUNCTION
+                            } else {
+                                SymbolKind::METHOD
+                            };
+                            symbol(text, &v.name, kind, None)
+                        })
+                        .collect();
+                    symbols.push(symbol(text, &r.name, SymbolKind::CLASS, Some(children)));
+                }
+                Item::Value(v) if is_function(v) => {
+                    symbols.push(symbol(text, &v.name, SymbolKind::FUNCTION, None));
+                }
+                _ => {}
+            }
+        }
+        Some(DocumentSymbolResponse::Nested(symbols))
+    }
+}
+
+const BUILTIN_TYPES: &[&str] = &[
+    "u8",
+    "u16",
+    "u32",
+    "u64",
+    "s8",
+    "s16",
+    "s32",
+    "s64",
+    "f32",
+    "f64",
+    "char",
+    "bool",
+    "string",
+    "handle",
+    "list",
+    "array",
+    "option",
+    "expected",
+    "tuple",
+    "push-buffer",
+    "pull-buffer",
+];
+
+fn is_function(value: &ast::Value<'_>) -> bool {
+    match value.kind {
+        ast::ValueKind::Function { .. } => true,
+        ast::ValueKind::Global(_) => false,
+    }
+}
+
+#[allow(deprecated)] // `DocumentSymbol::deprecated` has to be specified
+fn symbol(
+    text: &str,
+    name: &ast::Id<'_>,
+    kind: SymbolKind,
+    children: Option<Vec<DocumentSymbol>>,
+) -> DocumentSymbol {
+    DocumentSymbol {
+        name: name.name.to_string(),
+        detail: None,
+        kind,
+        tags: None,
+        deprecated: None,
+        range: range(text, name.span),
+        selection_range: range(text, name.span),
+        children,
+    }
+}
+
+/// Finds the identifier at `offset` in `ast`, if any, and what it refers to.
+fn target_at<'a>(ast: &'a Ast<'a>, offset: usize) -> Option<(Span, Target<'a>)> {
+    let mut finder = Finder {
+        offset,
+        found: None,
+    };
+    finder.items(&ast.items);
+    return finder.found;
+
+    struct Finder<'a> {
+        offset: usize,
+        found: Option<(Span, Target<'a>)>,
+    }
+
+    impl<'a> Finder<'a> {
+        fn id(&mut self, id: &ast::Id<'_>, target: Target<'a>) {
+            if id.span.start as usize <= self.offset && self.offset <= id.span.end as usize {
+                self.found = Some((id.span, target));
+            }
+        }
+
+        fn items(&mut self, items: &'a [Item<'a>]) {
+            for item in items {
+                match item {
+                    Item::Use(u) => {
+                        let module = &u.from[0].name;
+                        self.id(&u.from[0], Target::Module(module));
+                        for n in u.names.iter().flatten() {
+                            let local = n.as_.as_ref().unwrap_or(&n.name);
+                            let target = || Target::Import {
+                                module,
+                                name: &n.name.name,
+                                local: &local.name,
+                            };
+                            self.id(&n.name, target());
+                            if let Some(as_) = &n.as_ {
+                                self.id(as_, target());
+                            }
+                        }
+                    }
+                    Item::Resource(r) => {
+                        self.id(&r.name, Target::Name(&r.name.name));
+                        for (_, value) in r.values.iter() {
+                            self.value(value);
+                        }
+                    }
+                    Item::TypeDef(t) => {
+                        self.id(&t.name, Target::Name(&t.name.name));
+                        self.ty(&t.ty);
+                    }
+                    Item::Value(v) => self.value(v),
+                    Item::Const(c) => self.ty(&c.ty),
+                    Item::Interface(i) => self.items(&i.items),
+                }
+            }
+        }
+
+        fn value(&mut self, value: &'a ast::Value<'a>) {
+            match &value.kind {
+                ast::ValueKind::Function {
+                    params, results, ..
+                } => {
+                    for (_, ty) in params.iter().chain(results) {
+                        self.ty(ty);
+                    }
+                }
+                ast::ValueKind::Global(ty) => self.ty(ty),
+            }
+        }
+
+        fn ty(&mut self, ty: &'a ast::Type<'a>) {
+            match ty {
+                ast::Type::Name(id) | ast::Type::Handle(id) => self.id(id, Target::Name(&id.name)),
+                ast::Type::Generic(id, args) => {
+                    self.id(id, Target::Name(&id.name));
+                    for arg in args {
+                        self.ty(arg);
+                    }
+                }
+                ast::Type::List(ty)
+                | ast::Type::Array(ty, _)
+                | ast::Type::PushBuffer(ty)
+                | ast::Type::PullBuffer(ty)
+                | ast::Type::Pointer(ty)
+                | ast::Type::ConstPointer(ty) => self.ty(ty),
+                ast::Type::Record(r) => {
+                    for field in r.fields.iter() {
+                        self.ty(&field.ty);
+                    }
+                }
+                ast::Type::Variant(v) => {
+                    for case in v.cases.iter() {
+                        if let Some(ty) = &case.ty {
+                            self.ty(ty);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn file_path(uri: &Url) -> PathBuf {
+    let path = uri
+        .to_file_path()
+        .unwrap_or_else(|()| PathBuf::from(uri.path()));
+    // The parser names interfaces after the file stem, so make sure there is
+    // one even for unsaved documents.
+    if path.file_stem().is_none() {
+        return PathBuf::from("untitled.witx");
+    }
+    path
+}
+
+/// Converts a byte range in `text` to an LSP range.
+///
+/// Empty spans, such as those of lexer errors, are widened to cover the
+/// character they point at so editors have something to underline.
+fn range(text: &str, span: Span) -> Range {
+    let start = span.start as usize;
+    let mut end = span.end as usize;
+    if start == end {
+        if let Some(c) = text.get(start..).and_then(|s| s.chars().next()) {
+            end += c.len_utf8();
+        }
+    }
+    Range::new(position(text, start), position(text, end))
+}
+
+/// Converts a byte offset in `text` to an LSP position, which counts columns
+/// in UTF-16 code units.
+fn position(text: &str, offset: usize) -> Position {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count();
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let character = before[line_start..].encode_utf16().count();
+    Position::new(line as u32, character as u32)
+}
+
+/// The inverse of `position`.
+fn offset(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return text.len(),
+        }
+    }
+    let mut character = 0;
+    for (i, c) in text[line_start..].char_indices() {
+        if character >= position.character as usize || c == '\n' {
+            return line_start + i;
+        }
+        character += c.len_utf16();
+    }
+    text.len()
+}
@@ -0,0 +1,299 @@
+//! Drives the `witx-lsp` binary over stdio with files from the `witx2` test
+//! corpus.
+
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+struct Client {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+}
+
+impl Client {
+    fn start() -> Client {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_witx-lsp"))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut client = Client {
+            stdin: child.stdin.take().unwrap(),
+            stdout: BufReader::new(child.stdout.take().unwrap()),
+            child,
+            next_id: 0,
+        };
+        client.request("initialize", json!({ "capabilities": {} }));
+        client.notify("initialized", json!({}));
+        client
+    }
+
+    fn send(&mut self, msg: Value) {
+        let msg = msg.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{}", msg.len(), msg).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    fn recv(&mut self) -> Value {
+        let mut len = None;
+        loop {
+            let mut line = String::new();
+            self.stdout.read_line(&mut line).unwrap();
+            let line = line.trim();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(n) = line.strip_prefix("Content-Length: ") {
+                len = Some(n.parse().unwrap());
+            }
+        }
+        let mut buf = vec![0; len.expect("missing Content-Length header")];
+        self.stdout.read_exact(&mut buf).unwrap();
+        serde_json::from_slice(&buf).unwrap()
+    }
+
+    /// Sends a request and returns the whole response to it.
+    fn call(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+        loop {
+            let msg = self.recv();
+            if msg["id"] == id {
+                return msg;
+            }
+        }
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        let msg = self.call(method, params);
+        assert!(msg.get("error").is_none(), "request failed: {}", msg);
+        msg["result"].clone()
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+    }
+
+    /// Opens `path` and returns the diagnostics published for it.
+    fn open(&mut self, path: &Path) -> Vec<Value> {
+        let uri = uri(path);
+        self.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": {
+                    "uri": uri,
+                    "languageId": "witx",
+                    "version": 0,
+                    "text": fs::read_to_string(path).unwrap(),
+                }
+            }),
+        );
+        loop {
+            let msg = self.recv();
+            if msg["method"] == "textDocument/publishDiagnostics" && msg["params"]["uri"] == uri {
+                return msg["params"]["diagnostics"].as_array().unwrap().clone();
+            }
+        }
+    }
+
+    fn position_request(&mut self, method: &str, path: &Path, line: u32, col: u32) -> Value {
+        self.request(
+            method,
+            json!({
+                "textDocument": { "uri": uri(path) },
+                "position": { "line": line, "character": col },
+            }),
+        )
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.request("shutdown", Value::Null);
+        self.notify("exit", Value::Null);
+        self.child.wait().unwrap();
+    }
+}
+
+fn ui(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("../witx2/tests/ui")
+        .join(name)
+        .canonicalize()
+        .unwrap()
+}
+
+fn uri(path: &Path) -> String {
+    format!("file://{}", path.display())
+}
+
+#[test]
+fn diagnostics_for_corpus() {
+    let mut client = Client::start();
+    let mut files = Vec::new();
+    for dir in ["", "parse-fail", "diagnostics"].iter() {
+        for entry in fs::read_dir(ui(dir)).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|s| s.to_str()) == Some("witx") {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    for path in files {
+        let contents = fs::read_to_string(&path).unwrap();
+        let should_fail = contents.contains("// parse-fail") || contents.contains("// diagnostics");
+        let diagnostics = client.open(&path);
+        assert_eq!(
+            diagnostics.is_empty(),
+            !should_fail,
+            "unexpected diagnostics for {:?}: {:#?}",
+            path,
+            diagnostics
+        );
+    }
+}
+
+#[test]
+fn diagnostics_have_ranges() {
+    let mut client = Client::start();
+    let diagnostics = client.open(&ui("diagnostics/many-errors.witx"));
+    assert_eq!(diagnostics.len(), 7);
+    assert_eq!(diagnostics[0]["message"], "expected a type, found ','");
+    assert_eq!(
+        diagnostics[0]["range"],
+        json!({
+            "start": { "line": 5, "character": 5 },
+            "end": { "line": 5, "character": 6 },
+        })
+    );
+}
+
+#[test]
+fn hover_shows_size_and_alignment() {
+    let mut client = Client::start();
+    let path = ui("import-me.witx");
+    client.open(&path);
+
+    // `type some_record = tuple<u32, u64, f32>`
+    let hover = client.position_request("textDocument/hover", &path, 6, 8);
+    let value = hover["contents"]["value"].as_str().unwrap();
+    assert!(
+        value.contains("type some_record = tuple<u32, u64, f32>"),
+        "{}",
+        value
+    );
+    assert!(value.contains("size: 24, align: 8"), "{}", value);
+
+    // `resource x`
+    let hover = client.position_request("textDocument/hover", &path, 2, 9);
+    let value = hover["contents"]["value"].as_str().unwrap();
+    assert!(value.contains("resource x"), "{}", value);
+    assert!(value.contains("size: 4, align: 4"), "{}", value);
+}
+
+#[test]
+fn definition_follows_imports() {
+    let mut client = Client::start();
+    let path = ui("imports.witx");
+    client.open(&path);
+
+    // `bar` in `type y = bar` is `foo` renamed from `import-me`
+    let def = client.position_request("textDocument/definition", &path, 5, 9);
+    assert_eq!(def["uri"], uri(&ui("import-me.witx")));
+    assert_eq!(def["range"]["start"], json!({ "line": 0, "character": 5 }));
+
+    // `xyz` in `type my_handle = handle xyz` is defined locally
+    let def = client.position_request("textDocument/definition", &path, 11, 25);
+    assert_eq!(def["uri"], uri(&path));
+    assert_eq!(def["range"]["start"], json!({ "line": 10, "character": 9 }));
+}
+
+#[test]
+fn completion_lists_type_names() {
+    let mut client = Client::start();
+    let path = ui("imports.witx");
+    client.open(&path);
+    let items = client.position_request("textDocument/completion", &path, 0, 0);
+    let labels = items
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| i["label"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    for name in [
+        "foo",
+        "bar",
+        "xyz",
+        "my_handle",
+        "some_record",
+        "u32",
+        "string",
+    ]
+    .iter()
+    {
+        assert!(labels.contains(name), "missing {} in {:?}", name, labels);
+    }
+}
+
+#[test]
+fn document_symbols() {
+    let mut client = Client::start();
+    let path = ui("resource.witx");
+    client.open(&path);
+    let symbols = client.request(
+        "textDocument/documentSymbol",
+        json!({ "textDocument": { "uri": uri(&path) } }),
+    );
+    let symbols = symbols.as_array().unwrap();
+    let names = symbols
+        .iter()
+        .map(|s| s["name"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["a", "b", "c", "d", "e", "f"]);
+    let children = symbols[5]["children"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["name"].as_str().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(children, ["x", "y"]);
+}
+
+#[test]
+fn invalid_params_are_reported() {
+    let mut client = Client::start();
+    let msg = client.call("textDocument/hover", json!({ "position": "nowhere" }));
+    assert_eq!(msg["error"]["code"], -32602, "{}", msg);
+
+    // The server keeps serving requests afterwards.
+    let path = ui("import-me.witx");
+    client.open(&path);
+    let hover = client.position_request("textDocument/hover", &path, 2, 9);
+    assert!(hover["contents"]["value"].is_string(), "{}", hover);
+}
+
+#[test]
+fn invalid_notification_params_are_logged() {
+    let mut client = Client::start();
+    client.notify("textDocument/didOpen", json!({ "textDocument": 1 }));
+    loop {
+        let msg = client.recv();
+        if msg["method"] == "window/logMessage" {
+            let message = msg["params"]["message"].as_str().unwrap();
+            assert!(message.contains("textDocument/didOpen"), "{}", msg);
+            break;
+        }
+    }
+
+    // The server keeps serving requests afterwards.
+    let path = ui("import-me.witx");
+    client.open(&path);
+    let hover = client.position_request("textDocument/hover", &path, 2, 9);
+    assert!(hover["contents"]["value"].is_string(), "{}", hover);
+}
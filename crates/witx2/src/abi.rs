@@ -3,6 +3,9 @@ use crate::{
 };
 use std::mem;
 
+pub mod interp;
+pub mod program;
+
 /// A raw WebAssembly signature with params and results.
 #[derive(Clone, Debug, Hash, Eq, PartialEq, PartialOrd, Ord)]
 pub struct WasmSignature {
@@ -66,6 +69,115 @@ pub enum Abi {
     Canonical,
 }
 
+/// The encoding that strings use in linear memory.
+///
+/// Both sides of a call must agree on the encoding used, so this is chosen by
+/// the module and configured identically in the glue code of its host and of
+/// the module itself. Strings are always passed as a pointer and a length, so
+/// the encoding has no effect on the wasm signature of a function.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum StringEncoding {
+    /// Strings are UTF-8 and the length is a number of bytes.
+    #[default]
+    Utf8,
+
+    /// Strings are little-endian UTF-16, aligned to 2 bytes, and the length is
+    /// a number of 16-bit code units.
+    Utf16,
+
+    /// Strings are either Latin-1 or UTF-16.
+    ///
+    /// If the high bit of the length is set (see `UTF16_TAG`) then the string
+    /// is UTF-16, and the remaining bits of the length are its number of code
+    /// units, as with `Utf16`. Otherwise the string is Latin-1 and the length
+    /// is its number of bytes.
+    CompactUtf16,
+}
+
+impl StringEncoding {
+    /// The bit of the length which is set for UTF-16 strings in the
+    /// `CompactUtf16` encoding.
+    pub const UTF16_TAG: u32 = 1 << 31;
+
+    /// Returns the alignment of the allocation holding a string in this
+    /// encoding.
+    ///
+    /// For `CompactUtf16` this is the alignment of UTF-16 strings, Latin-1
+    /// strings only require an alignment of 1.
+    pub fn align(&self) -> usize {
+        match self {
+            StringEncoding::Utf8 => 1,
+            StringEncoding::Utf16 | StringEncoding::CompactUtf16 => 2,
+        }
+    }
+}
+
+impl std::str::FromStr for StringEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<StringEncoding, String> {
+        match s {
+            "utf8" => Ok(StringEncoding::Utf8),
+            "utf16" => Ok(StringEncoding::Utf16),
+            "latin1+utf16" => Ok(StringEncoding::CompactUtf16),
+            _ => Err(format!(
+                "unknown string encoding `{}`, expected one of `utf8`, `utf16` or `latin1+utf16`",
+                s
+            )),
+        }
+    }
+}
+
+/// The most core wasm values that an array is flattened into before it's
+/// passed through memory instead, see `Interface::array_in_memory`.
+pub const MAX_FLAT_ARRAY: usize = 16;
+
+/// How functions whose results flatten to more than one wasm value return
+/// them.
+///
+/// By default such results are written to memory and a return pointer is
+/// passed around instead. Enabling multi-value returns up to `max_results`
+/// values natively from the wasm function, and only results which flatten to
+/// more values than that still go through a return pointer. As with
+/// `StringEncoding` both sides of a call must be configured identically.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum MultiValue {
+    /// At most one value is returned natively.
+    #[default]
+    Disabled,
+
+    /// Up to `max_results` values are returned natively.
+    Enabled { max_results: usize },
+}
+
+impl MultiValue {
+    /// Returns the maximum number of wasm values that a function returns
+    /// without a return pointer.
+    pub fn max_results(&self) -> usize {
+        match self {
+            MultiValue::Disabled => 1,
+            MultiValue::Enabled { max_results } => (*max_results).max(1),
+        }
+    }
+}
+
+impl std::str::FromStr for MultiValue {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<MultiValue, String> {
+        if s == "disabled" {
+            return Ok(MultiValue::Disabled);
+        }
+        match s.parse() {
+            Ok(max_results) => Ok(MultiValue::Enabled { max_results }),
+            Err(_) => Err(format!(
+                "invalid multi-value setting `{}`, expected `disabled` or a maximum number of results",
+                s
+            )),
+        }
+    }
+}
+
 // Helper macro for defining instructions without having to have tons of
 // exhaustive `match` statements to update
 macro_rules! def_instruction {
@@ -225,6 +337,9 @@ def_instruction! {
         I32FromUsize : [1] => [1],
         /// Converts a language-specific C `char` value to a wasm `i32`.
         I32FromChar8 : [1] => [1],
+        /// Converts an interface type `bool` value to a wasm `i32`, which is
+        /// `1` for `true` and `0` for `false`.
+        I32FromBool : [1] => [1],
         /// Conversion an interface type `f32` value to a wasm `f32`.
         ///
         /// This may be a noop for some implementations, but it's here in case the
@@ -276,6 +391,10 @@ def_instruction! {
         Char8FromI32 : [1] => [1],
         /// Converts a native wasm `i32` to a language-specific `usize`.
         UsizeFromI32 : [1] => [1],
+        /// Converts a native wasm `i32` to an interface type `bool`.
+        ///
+        /// Values other than `0` and `1` are invalid.
+        BoolFromI32 : [1] => [1],
 
         // Handles
 
@@ -384,6 +503,28 @@ def_instruction! {
         ///   parameter, that is defined by the host itself.
         HandleBorrowedFromI32 { ty: ResourceId } : [1] => [1],
 
+        // strings
+
+        /// Lowers a string into a pointer and a length of its encoding in
+        /// `encoding`.
+        ///
+        /// The `realloc` field is set in the same situations as it is for
+        /// `ListCanonLower`, and has the same meaning.
+        StringLower {
+            realloc: Option<&'a str>,
+            encoding: StringEncoding,
+        } : [1] => [2],
+
+        /// Lifts a pointer and a length of a string encoded in `encoding` into
+        /// a string.
+        ///
+        /// The `free` field is set in the same situations as it is for
+        /// `ListCanonLift`, and has the same meaning.
+        StringLift {
+            free: Option<&'a str>,
+            encoding: StringEncoding,
+        } : [2] => [1],
+
         // lists
 
         /// Lowers a list where the element's layout in the native language is
@@ -500,6 +641,53 @@ def_instruction! {
         /// Pops an i32, pushes a buffer wrapping that i32 handle.
         BufferLiftHandle { push: bool, ty: &'a Type } : [1] => [1],
 
+        // arrays
+
+        /// Pops an array value off the stack, decomposes it into its `len`
+        /// elements, and then pushes the elements onto the stack with the
+        /// first element pushed first.
+        ArrayLower {
+            element: &'a Type,
+            len: u32,
+            ty: TypeId,
+        } : [1] => [*len as usize],
+
+        /// Pops the `len` elements of an array off the stack and then composes
+        /// them into an array.
+        ArrayLift {
+            element: &'a Type,
+            len: u32,
+            ty: TypeId,
+        } : [*len as usize] => [1],
+
+        /// Lowers an array which is too large to flatten, as determined by
+        /// `Interface::array_in_memory`, into a pointer to its elements in
+        /// memory.
+        ///
+        /// Pops an array value from the stack and pushes the pointer onto the
+        /// stack. This also pops a block from the block stack which is used as
+        /// the iteration body of writing each element, as with `ListLower`,
+        /// and `realloc` has the same meaning as it does there.
+        ArrayLowerMemory {
+            element: &'a Type,
+            len: u32,
+            ty: TypeId,
+            realloc: Option<&'a str>,
+        } : [1] => [1],
+
+        /// Lifts a pointer to the `len` elements of an array in memory into an
+        /// array, the reverse of `ArrayLowerMemory`.
+        ///
+        /// This also pops a block from the block stack which is used to read
+        /// each element, as with `ListLift`, and `free` has the same meaning
+        /// as it does there.
+        ArrayLiftMemory {
+            element: &'a Type,
+            len: u32,
+            ty: TypeId,
+            free: Option<&'a str>,
+        } : [1] => [1],
+
         // records
 
         /// Pops a record value off the stack, decomposes the record to all of
@@ -518,6 +706,33 @@ def_instruction! {
             ty: TypeId,
         } : [record.fields.len()] => [1],
 
+        /// Pops a base pointer off the stack and pushes the record stored
+        /// `offset` bytes past it.
+        ///
+        /// This is only emitted by an optimized [`program::Program`] for records
+        /// where [`Bindgen::is_record_canonical`] returns `true`, and replaces
+        /// the individual loads and lifts of each field. The record's native
+        /// representation is expected to match the canonical ABI so it can be
+        /// copied out of memory as-is.
+        RecordCanonLift {
+            record: &'a Record,
+            name: Option<&'a str>,
+            ty: TypeId,
+            offset: i32,
+        } : [1] => [1],
+
+        /// Pops a base pointer and then a record off the stack, and stores the
+        /// record `offset` bytes past the base pointer.
+        ///
+        /// This is the dual of `RecordCanonLift`, replacing the lowering and
+        /// store of each field of the record.
+        RecordCanonLower {
+            record: &'a Record,
+            name: Option<&'a str>,
+            ty: TypeId,
+            offset: i32,
+        } : [2] => [0],
+
         /// Converts a language-specific record-of-bools to a list of `i32`.
         FlagsLower {
             record: &'a Record,
@@ -568,6 +783,9 @@ def_instruction! {
         /// Pops an `i32` off the stack as well as `ty.cases.len()` blocks
         /// from the code generator. Uses each of those blocks and the value
         /// from the stack to produce a final variant.
+        ///
+        /// The `i32` is the discriminant of a case, which is the index of the
+        /// case's block only if discriminants weren't given explicitly.
         VariantLift {
             variant: &'a Variant,
             name: Option<&'a str>,
@@ -644,6 +862,10 @@ def_instruction! {
         /// Returns `amt` values on the stack. This is always the last
         /// instruction.
         ///
+        /// When results are lowered `amt` is the number of results of the
+        /// wasm signature, which is more than one if multi-value returns are
+        /// enabled and used for this function.
+        ///
         /// Note that this instruction is used for asynchronous functions where
         /// the results are *lifted*, not when they're *lowered*, though. For
         /// those modes the `ReturnAsyncExport` and `ReturnAsyncImport`
@@ -687,7 +909,7 @@ def_instruction! {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Bitcast {
     // Upcasts
     F32ToF64,
@@ -842,6 +1064,19 @@ pub trait Bindgen {
     /// "canonical" form for lists. This dictates whether the `ListCanonLower`
     /// and `ListCanonLift` instructions are used or not.
     fn is_list_canonical(&self, iface: &Interface, element: &Type) -> bool;
+
+    /// Returns whether or not the specified record is represented in a
+    /// "canonical" form, meaning that it can be copied to and from linear
+    /// memory as-is. This dictates whether the `RecordCanonLower` and
+    /// `RecordCanonLift` instructions are used by an optimized
+    /// [`program::Program`].
+    ///
+    /// This is only consulted for records whose fields are all scalars where
+    /// all bit patterns are valid, and defaults to `false`.
+    fn is_record_canonical(&self, iface: &Interface, ty: TypeId) -> bool {
+        let _ = (iface, ty);
+        false
+    }
 }
 
 impl Interface {
@@ -873,6 +1108,7 @@ impl Interface {
     fn validate_preview1_return(&self, ty: &Type) -> Result<(), String> {
         let id = match ty {
             Type::Id(id) => *id,
+            Type::String => return Err("invalid return type".to_string()),
             _ => return Ok(()),
         };
         match &self.types[id].kind {
@@ -913,6 +1149,7 @@ impl Interface {
             TypeDefKind::Record(r) if r.is_flags() => Ok(()),
             TypeDefKind::Record(_)
             | TypeDefKind::List(_)
+            | TypeDefKind::Array(..)
             | TypeDefKind::PushBuffer(_)
             | TypeDefKind::PullBuffer(_) => Err("invalid return type".to_string()),
             TypeDefKind::Pointer(_) | TypeDefKind::ConstPointer(_) => Ok(()),
@@ -965,6 +1202,12 @@ impl Interface {
                 Ok(())
             }
             TypeDefKind::List(t) => self.validate_abi_ty(abi, t, param),
+            TypeDefKind::Array(t, _) => {
+                if let Abi::Preview1 = abi {
+                    return Err("cannot use arrays in this ABI".to_string());
+                }
+                self.validate_abi_ty(abi, t, param)
+            }
             TypeDefKind::Pointer(t) => {
                 if let Abi::Canonical = abi {
                     return Err("cannot use `(@witx pointer)` in this ABI".to_string());
@@ -997,7 +1240,17 @@ impl Interface {
     ///
     /// The first entry returned is the list of parameters and the second entry
     /// is the list of results for the wasm function signature.
-    pub fn wasm_signature(&self, dir: Direction, func: &Function) -> WasmSignature {
+    ///
+    /// This is the same for all `StringEncoding`s since strings are always
+    /// passed as a pointer and a length. The `multi_value` setting decides
+    /// how many results are returned natively before they're spilled to a
+    /// return pointer, see `MultiValue`.
+    pub fn wasm_signature(
+        &self,
+        dir: Direction,
+        multi_value: MultiValue,
+        func: &Function,
+    ) -> WasmSignature {
         let mut params = Vec::new();
         let mut results = Vec::new();
         for (_, param) in func.params.iter() {
@@ -1062,11 +1315,16 @@ impl Interface {
                 }
             }
         } else {
-            // Rust/C don't support multi-value well right now, so if a function
-            // would have multiple results then instead truncate it. Imports take a
-            // return pointer to write into and exports return a pointer they wrote
-            // into.
-            if results.len() > 1 {
+            // Rust/C don't support multi-value well right now, so unless it's
+            // been opted into, if a function would have multiple results then
+            // instead truncate it. Imports take a return pointer to write into
+            // and exports return a pointer they wrote into. The Preview1 ABI
+            // never uses multi-value.
+            let max_results = match func.abi {
+                Abi::Preview1 => 1,
+                Abi::Canonical => multi_value.max_results(),
+            };
+            if results.len() > max_results {
                 retptr = Some(mem::take(&mut results));
                 match dir {
                     Direction::Import => {
@@ -1105,10 +1363,16 @@ impl Interface {
             | Type::S32
             | Type::U32
             | Type::Char
+            | Type::Bool
             | Type::Handle(_)
             | Type::CChar
             | Type::Usize => result.push(WasmType::I32),
 
+            Type::String => {
+                result.push(WasmType::I32);
+                result.push(WasmType::I32);
+            }
+
             Type::U64 | Type::S64 => result.push(WasmType::I64),
             Type::F32 => result.push(WasmType::F32),
             Type::F64 => result.push(WasmType::F64),
@@ -1136,6 +1400,16 @@ impl Interface {
                     result.push(WasmType::I32);
                 }
 
+                TypeDefKind::Array(element, len) => {
+                    if self.array_in_memory(abi, dir, element, *len) {
+                        result.push(WasmType::I32);
+                    } else {
+                        for _ in 0..*len {
+                            self.push_wasm(abi, dir, element, result);
+                        }
+                    }
+                }
+
                 TypeDefKind::Pointer(_) | TypeDefKind::ConstPointer(_) => {
                     result.push(WasmType::I32);
                 }
@@ -1178,13 +1452,25 @@ impl Interface {
         }
     }
 
+    /// Returns whether an array of `len` `element`s is passed as a pointer to
+    /// its elements in memory, rather than flattened into the core wasm
+    /// values of each element.
+    ///
+    /// Arrays are only flattened while they take at most
+    /// `MAX_FLAT_ARRAY` core wasm values.
+    pub fn array_in_memory(&self, abi: Abi, dir: Direction, element: &Type, len: u32) -> bool {
+        let mut temp = Vec::new();
+        self.push_wasm(abi, dir, element, &mut temp);
+        temp.len().saturating_mul(len as usize) > MAX_FLAT_ARRAY
+    }
+
     pub fn flags_repr(&self, record: &Record) -> Option<Int> {
         match record.kind {
             RecordKind::Flags(Some(hint)) => Some(hint),
-            RecordKind::Flags(None) if record.fields.len() <= 8 => Some(Int::U8),
-            RecordKind::Flags(None) if record.fields.len() <= 16 => Some(Int::U16),
-            RecordKind::Flags(None) if record.fields.len() <= 32 => Some(Int::U32),
-            RecordKind::Flags(None) if record.fields.len() <= 64 => Some(Int::U64),
+            RecordKind::Flags(None) if record.num_bits() <= 8 => Some(Int::U8),
+            RecordKind::Flags(None) if record.num_bits() <= 16 => Some(Int::U16),
+            RecordKind::Flags(None) if record.num_bits() <= 32 => Some(Int::U32),
+            RecordKind::Flags(None) if record.num_bits() <= 64 => Some(Int::U64),
             RecordKind::Flags(None) => None,
             _ => panic!("not a flags record"),
         }
@@ -1205,21 +1491,39 @@ impl Interface {
     /// language-specific values into the wasm types to call a WASI function,
     /// and it will also automatically convert the results of the WASI function
     /// back to a language-specific value.
+    ///
+    /// Strings are lifted and lowered with `string_encoding`, except for
+    /// functions using the Preview1 ABI which always use UTF-8. Results are
+    /// returned according to `multi_value`, which must match the setting
+    /// used with `wasm_signature`.
     pub fn call(
         &self,
         dir: Direction,
         lift_lower: LiftLower,
+        string_encoding: StringEncoding,
+        multi_value: MultiValue,
         func: &Function,
         bindgen: &mut impl Bindgen,
     ) {
+        let mut string_encoding = string_encoding;
         if Abi::Preview1 == func.abi {
             // The Preview1 ABI only works with WASI which is only intended
             // for use with these modes.
             if dir == Direction::Export {
                 panic!("the preview1 ABI only supports import modes");
             }
+            string_encoding = StringEncoding::Utf8;
         }
-        Generator::new(self, func.abi, dir, lift_lower, bindgen).call(func);
+        Generator::new(
+            self,
+            func.abi,
+            dir,
+            lift_lower,
+            string_encoding,
+            multi_value,
+            bindgen,
+        )
+        .call(func);
     }
 }
 
@@ -1227,6 +1531,8 @@ struct Generator<'a, B: Bindgen> {
     abi: Abi,
     dir: Direction,
     lift_lower: LiftLower,
+    string_encoding: StringEncoding,
+    multi_value: MultiValue,
     bindgen: &'a mut B,
     iface: &'a Interface,
     operands: Vec<B::Operand>,
@@ -1241,6 +1547,8 @@ impl<'a, B: Bindgen> Generator<'a, B> {
         abi: Abi,
         dir: Direction,
         lift_lower: LiftLower,
+        string_encoding: StringEncoding,
+        multi_value: MultiValue,
         bindgen: &'a mut B,
     ) -> Generator<'a, B> {
         Generator {
@@ -1248,6 +1556,8 @@ impl<'a, B: Bindgen> Generator<'a, B> {
             abi,
             dir,
             lift_lower,
+            string_encoding,
+            multi_value,
             bindgen,
             operands: Vec::new(),
             results: Vec::new(),
@@ -1257,7 +1567,7 @@ impl<'a, B: Bindgen> Generator<'a, B> {
     }
 
     fn call(&mut self, func: &Function) {
-        let sig = self.iface.wasm_signature(self.dir, func);
+        let sig = self.iface.wasm_signature(self.dir, self.multi_value, func);
 
         match self.lift_lower {
             LiftLower::LowerArgsLiftResults => {
@@ -1617,6 +1927,26 @@ impl<'a, B: Bindgen> Generator<'a, B> {
             Type::Char => self.emit(&I32FromChar),
             Type::F32 => self.emit(&F32FromIf32),
             Type::F64 => self.emit(&F64FromIf64),
+            Type::Bool => self.emit(&I32FromBool),
+            Type::String => match self.abi {
+                Abi::Preview1 => self.emit(&StringLower {
+                    realloc: None,
+                    encoding: self.string_encoding,
+                }),
+                Abi::Canonical => {
+                    // Like lists, ownership is passed in all cases except
+                    // for lowering the parameters of a call to a wasm
+                    // import.
+                    let realloc = match (self.dir, self.lift_lower) {
+                        (Direction::Import, LiftLower::LowerArgsLiftResults) => None,
+                        _ => Some("canonical_abi_realloc"),
+                    };
+                    self.emit(&StringLower {
+                        realloc,
+                        encoding: self.string_encoding,
+                    });
+                }
+            },
             Type::Handle(ty) => {
                 let borrowed = match self.lift_lower {
                     // This means that a return value is being lowered, which is
@@ -1668,9 +1998,7 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                             (Direction::Import, LiftLower::LowerArgsLiftResults) => None,
                             _ => Some("canonical_abi_realloc"),
                         };
-                        if self.is_char(element)
-                            || self.bindgen.is_list_canonical(self.iface, element)
-                        {
+                        if self.bindgen.is_list_canonical(self.iface, element) {
                             self.emit(&ListCanonLower { element, realloc });
                         } else {
                             self.push_block();
@@ -1683,6 +2011,45 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                         }
                     }
                 },
+                TypeDefKind::Array(element, len)
+                    if self
+                        .iface
+                        .array_in_memory(self.abi, self.dir, element, *len) =>
+                {
+                    // Ownership is passed in the same situations as it is for
+                    // lists.
+                    let realloc = match (self.dir, self.lift_lower) {
+                        (Direction::Import, LiftLower::LowerArgsLiftResults) => None,
+                        _ => Some("canonical_abi_realloc"),
+                    };
+                    self.push_block();
+                    self.emit(&IterElem { element });
+                    self.emit(&IterBasePointer);
+                    let addr = self.stack.pop().unwrap();
+                    self.write_to_memory(element, addr, 0);
+                    self.finish_block(0);
+                    self.emit(&ArrayLowerMemory {
+                        element,
+                        len: *len,
+                        ty: id,
+                        realloc,
+                    });
+                }
+                TypeDefKind::Array(element, len) => {
+                    self.emit(&ArrayLower {
+                        element,
+                        len: *len,
+                        ty: id,
+                    });
+                    let values = self
+                        .stack
+                        .drain(self.stack.len() - *len as usize..)
+                        .collect::<Vec<_>>();
+                    for value in values {
+                        self.stack.push(value);
+                        self.lower(element, None);
+                    }
+                }
                 TypeDefKind::PushBuffer(ty) | TypeDefKind::PullBuffer(ty) => {
                     let push = match &self.iface.types[id].kind {
                         TypeDefKind::PushBuffer(_) => true,
@@ -1820,11 +2187,13 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                     let mut temp = Vec::new();
                     let mut casts = Vec::new();
                     self.iface.push_wasm(self.abi, self.dir, ty, &mut results);
-                    for (i, case) in v.cases.iter().enumerate() {
+                    for case in v.cases.iter() {
                         self.push_block();
                         self.emit(&VariantPayloadName);
                         let payload_name = self.stack.pop().unwrap();
-                        self.emit(&I32Const { val: i as i32 });
+                        self.emit(&I32Const {
+                            val: case.discriminant as i32,
+                        });
                         let mut pushed = 1;
                         if let Some(ty) = &case.ty {
                             // Using the payload of this block we lower the type to
@@ -1948,6 +2317,23 @@ impl<'a, B: Bindgen> Generator<'a, B> {
             Type::Char => self.emit(&CharFromI32),
             Type::F32 => self.emit(&If32FromF32),
             Type::F64 => self.emit(&If64FromF64),
+            Type::Bool => self.emit(&BoolFromI32),
+            Type::String => match self.abi {
+                Abi::Preview1 => self.emit(&StringLift {
+                    free: None,
+                    encoding: self.string_encoding,
+                }),
+                Abi::Canonical => {
+                    let free = match (self.dir, self.lift_lower) {
+                        (Direction::Import, LiftLower::LiftArgsLowerResults) => None,
+                        _ => Some("canonical_abi_free"),
+                    };
+                    self.emit(&StringLift {
+                        free,
+                        encoding: self.string_encoding,
+                    });
+                }
+            },
             Type::Handle(ty) => {
                 // For more information on these values see the comments in
                 // `lower` above.
@@ -1979,9 +2365,7 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                             (Direction::Import, LiftLower::LiftArgsLowerResults) => None,
                             _ => Some("canonical_abi_free"),
                         };
-                        if self.is_char(element)
-                            || self.bindgen.is_list_canonical(self.iface, element)
-                        {
+                        if self.bindgen.is_list_canonical(self.iface, element) {
                             self.emit(&ListCanonLift {
                                 element,
                                 free,
@@ -2001,6 +2385,44 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                         }
                     }
                 },
+                TypeDefKind::Array(element, len)
+                    if self
+                        .iface
+                        .array_in_memory(self.abi, self.dir, element, *len) =>
+                {
+                    let free = match (self.dir, self.lift_lower) {
+                        (Direction::Import, LiftLower::LiftArgsLowerResults) => None,
+                        _ => Some("canonical_abi_free"),
+                    };
+                    self.push_block();
+                    self.emit(&IterBasePointer);
+                    let addr = self.stack.pop().unwrap();
+                    self.read_from_memory(element, addr, 0);
+                    self.finish_block(1);
+                    self.emit(&ArrayLiftMemory {
+                        element,
+                        len: *len,
+                        ty: id,
+                        free,
+                    });
+                }
+                TypeDefKind::Array(element, len) => {
+                    let mut temp = Vec::new();
+                    self.iface.push_wasm(self.abi, self.dir, element, &mut temp);
+                    let mut args = self
+                        .stack
+                        .drain(self.stack.len() - temp.len() * *len as usize..)
+                        .collect::<Vec<_>>();
+                    for _ in 0..*len {
+                        self.stack.extend(args.drain(..temp.len()));
+                        self.lift(element);
+                    }
+                    self.emit(&ArrayLift {
+                        element,
+                        len: *len,
+                        ty: id,
+                    });
+                }
                 TypeDefKind::PushBuffer(ty) | TypeDefKind::PullBuffer(ty) => {
                     let push = match &self.iface.types[id].kind {
                         TypeDefKind::PushBuffer(_) => true,
@@ -2179,7 +2601,7 @@ impl<'a, B: Bindgen> Generator<'a, B> {
         match *ty {
             // Builtin types need different flavors of storage instructions
             // depending on the size of the value written.
-            Type::U8 | Type::S8 | Type::CChar => {
+            Type::U8 | Type::S8 | Type::CChar | Type::Bool => {
                 self.lower_and_emit(ty, addr, &I32Store8 { offset })
             }
             Type::U16 | Type::S16 => self.lower_and_emit(ty, addr, &I32Store16 { offset }),
@@ -2190,6 +2612,15 @@ impl<'a, B: Bindgen> Generator<'a, B> {
             Type::F32 => self.lower_and_emit(ty, addr, &F32Store { offset }),
             Type::F64 => self.lower_and_emit(ty, addr, &F64Store { offset }),
 
+            // Strings are stored like lists, as a pointer and then a length.
+            Type::String => {
+                self.lower(ty, None);
+                self.stack.push(addr.clone());
+                self.emit(&I32Store { offset: offset + 4 });
+                self.stack.push(addr);
+                self.emit(&I32Store { offset });
+            }
+
             Type::Id(id) => match &self.iface.types[id].kind {
                 TypeDefKind::Type(t) => self.write_to_memory(t, addr, offset),
                 TypeDefKind::Pointer(_) | TypeDefKind::ConstPointer(_) => {
@@ -2207,6 +2638,25 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                     self.emit(&I32Store { offset });
                 }
 
+                // Decompose the array into its elements and then write each
+                // element into memory one after another.
+                TypeDefKind::Array(element, len) => {
+                    self.emit(&ArrayLower {
+                        element,
+                        len: *len,
+                        ty: id,
+                    });
+                    let size = self.bindgen.sizes().size(element) as i32;
+                    let values = self
+                        .stack
+                        .drain(self.stack.len() - *len as usize..)
+                        .collect::<Vec<_>>();
+                    for (i, value) in values.into_iter().enumerate() {
+                        self.stack.push(value);
+                        self.write_to_memory(element, addr.clone(), offset + (i as i32) * size);
+                    }
+                }
+
                 // Lower the buffer to its raw values, and then write the values
                 // into memory, which may be more than one value depending on
                 // our import/export direction.
@@ -2275,11 +2725,13 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                 // to the type's alignment.
                 TypeDefKind::Variant(v) => {
                     let payload_offset = offset + (self.bindgen.sizes().payload_offset(v) as i32);
-                    for (i, case) in v.cases.iter().enumerate() {
+                    for case in v.cases.iter() {
                         self.push_block();
                         self.emit(&VariantPayloadName);
                         let payload_name = self.stack.pop().unwrap();
-                        self.emit(&I32Const { val: i as i32 });
+                        self.emit(&I32Const {
+                            val: case.discriminant as i32,
+                        });
                         self.stack.push(addr.clone());
                         self.store_intrepr(offset, v.tag);
                         if let Some(ty) = &case.ty {
@@ -2309,7 +2761,9 @@ impl<'a, B: Bindgen> Generator<'a, B> {
         use Instruction::*;
 
         match *ty {
-            Type::U8 | Type::CChar => self.emit_and_lift(ty, addr, &I32Load8U { offset }),
+            Type::U8 | Type::CChar | Type::Bool => {
+                self.emit_and_lift(ty, addr, &I32Load8U { offset })
+            }
             Type::S8 => self.emit_and_lift(ty, addr, &I32Load8S { offset }),
             Type::U16 => self.emit_and_lift(ty, addr, &I32Load16U { offset }),
             Type::S16 => self.emit_and_lift(ty, addr, &I32Load16S { offset }),
@@ -2320,6 +2774,14 @@ impl<'a, B: Bindgen> Generator<'a, B> {
             Type::F32 => self.emit_and_lift(ty, addr, &F32Load { offset }),
             Type::F64 => self.emit_and_lift(ty, addr, &F64Load { offset }),
 
+            Type::String => {
+                self.stack.push(addr.clone());
+                self.emit(&I32Load { offset });
+                self.stack.push(addr);
+                self.emit(&I32Load { offset: offset + 4 });
+                self.lift(ty);
+            }
+
             Type::Id(id) => match &self.iface.types[id].kind {
                 TypeDefKind::Type(t) => self.read_from_memory(t, addr, offset),
                 TypeDefKind::Pointer(_) | TypeDefKind::ConstPointer(_) => {
@@ -2336,6 +2798,20 @@ impl<'a, B: Bindgen> Generator<'a, B> {
                     self.lift(ty);
                 }
 
+                // Read and lift each element individually, and then aggregate
+                // all the elements into the array.
+                TypeDefKind::Array(element, len) => {
+                    let size = self.bindgen.sizes().size(element) as i32;
+                    for i in 0..*len as i32 {
+                        self.read_from_memory(element, addr.clone(), offset + i * size);
+                    }
+                    self.emit(&ArrayLift {
+                        element,
+                        len: *len,
+                        ty: id,
+                    });
+                }
+
                 // Read the requisite number of values from memory and then lift as
                 // appropriate.
                 TypeDefKind::PushBuffer(_) | TypeDefKind::PullBuffer(_) => {
@@ -2470,17 +2946,6 @@ impl<'a, B: Bindgen> Generator<'a, B> {
             self.finish_block(1);
         }
     }
-
-    fn is_char(&self, ty: &Type) -> bool {
-        match ty {
-            Type::Char => true,
-            Type::Id(id) => match &self.iface.types[*id].kind {
-                TypeDefKind::Type(t) => self.is_char(t),
-                _ => false,
-            },
-            _ => false,
-        }
-    }
 }
 
 fn cast(from: WasmType, to: WasmType) -> Bitcast {
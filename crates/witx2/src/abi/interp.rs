@@ -0,0 +1,858 @@
+//! A reference interpreter for the canonical ABI.
+//!
+//! The [`Interpreter`] executes a [`Program`] against a byte buffer which
+//! stands in for linear memory. This allows testing the instructions generated
+//! for a function, and optimizations of them, without compiling any glue code
+//! to wasm. Both interface values and wasm values are represented with
+//! [`Val`].
+//!
+//! Lists are always lifted and lowered element-by-element here, and the
+//! bounds of a list, or of an array passed through memory, are checked once
+//! before any element is read.
+//! Only the canonical ABI is supported, so programs for `Abi::Preview1`
+//! functions, buffers and async functions fail to execute.
+
+use super::program::{Block, Node, Op, Program};
+use super::{Bitcast, Instruction, StringEncoding, WasmType};
+use crate::{Interface, SizeAlign, Type, TypeDefKind};
+use anyhow::{anyhow, bail, Result};
+use std::collections::BTreeMap;
+use std::convert::{TryFrom, TryInto};
+
+/// A value operated on by the interpreter.
+///
+/// The `I32`, `I64`, `F32` and `F64` variants are used for wasm values, and
+/// `F32` and `F64` are also used for interface floats.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Val {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    Bool(bool),
+    U8(u8),
+    S8(i8),
+    U16(u16),
+    S16(i16),
+    U32(u32),
+    S32(i32),
+    U64(u64),
+    S64(i64),
+    Usize(u32),
+    Char8(u8),
+    Char(char),
+    String(String),
+    List(Vec<Val>),
+    /// Fixed-size arrays, with a value for each element.
+    Array(Vec<Val>),
+    /// Records and tuples, with a value for each field.
+    Record(Vec<Val>),
+    /// Flags, with whether each flag is set.
+    Flags(Vec<bool>),
+    /// The index of a variant's case and its payload, if any.
+    Variant(usize, Option<Box<Val>>),
+    Handle(i32),
+}
+
+/// The function called by `CallWasm` and `CallInterface` instructions.
+///
+/// It's given the interpreter, to access memory or run another program, and
+/// the arguments of the call.
+pub type Callee<'c, 'a> = dyn FnMut(&mut Interpreter<'a>, Vec<Val>) -> Result<Vec<Val>> + 'c;
+
+/// Executes programs against a byte buffer of memory.
+pub struct Interpreter<'a> {
+    iface: &'a Interface,
+    sizes: &'a SizeAlign,
+    /// The contents of linear memory, which grows as memory is allocated.
+    pub memory: Vec<u8>,
+    allocations: BTreeMap<u32, (u32, u32)>,
+    ret_area: (u32, u32),
+}
+
+struct Frame<'c, 'd, 'a> {
+    args: &'c [Val],
+    values: Vec<Option<Val>>,
+    results: Option<Vec<Val>>,
+    temporaries: Vec<(u32, u32, u32)>,
+    call: &'c mut Callee<'d, 'a>,
+}
+
+/// The values of instructions which depend on the block being executed.
+#[derive(Clone, Default)]
+struct Context {
+    elem: Option<Val>,
+    base: Option<i32>,
+    payload: Option<Val>,
+}
+
+impl<'a> Interpreter<'a> {
+    /// Creates an interpreter for programs of `iface`, laid out according to
+    /// `sizes`.
+    pub fn new(iface: &'a Interface, sizes: &'a SizeAlign) -> Interpreter<'a> {
+        Interpreter {
+            iface,
+            sizes,
+            // Leave some room at the start so that no allocation is at 0.
+            memory: vec![0; 8],
+            allocations: BTreeMap::new(),
+            ret_area: (0, 0),
+        }
+    }
+
+    /// Executes `program` with `args`, returning the values it returns.
+    ///
+    /// The wasm or interface function called by the program is implemented by
+    /// `call`.
+    pub fn run(
+        &mut self,
+        program: &Program,
+        args: &[Val],
+        call: &mut Callee<'_, 'a>,
+    ) -> Result<Vec<Val>> {
+        let mut frame = Frame {
+            args,
+            values: vec![None; program.values],
+            results: None,
+            temporaries: Vec::new(),
+            call,
+        };
+        let result = self.block(&program.body, &mut frame, &Context::default());
+        for (ptr, len, align) in frame.temporaries {
+            self.free(ptr, len, align)?;
+        }
+        result?;
+        frame
+            .results
+            .ok_or_else(|| anyhow!("program finished without returning"))
+    }
+
+    /// Allocates `new_len` bytes aligned to `align`, copying and freeing the
+    /// previous allocation of `old_len` bytes at `old_ptr`, if any.
+    ///
+    /// This has the same semantics as `canonical_abi_realloc`.
+    pub fn realloc(&mut self, old_ptr: u32, old_len: u32, align: u32, new_len: u32) -> Result<u32> {
+        if old_len != 0 {
+            self.free(old_ptr, old_len, align)?;
+        }
+        if new_len == 0 {
+            return Ok(align);
+        }
+        let ptr = self.grow(align, new_len);
+        if old_len != 0 {
+            let len = old_len.min(new_len) as usize;
+            self.memory
+                .copy_within(old_ptr as usize..old_ptr as usize + len, ptr as usize);
+        }
+        self.allocations.insert(ptr, (new_len, align));
+        Ok(ptr)
+    }
+
+    /// Frees `len` bytes aligned to `align` at `ptr`, which must have been
+    /// allocated with `realloc`.
+    ///
+    /// This has the same semantics as `canonical_abi_free`.
+    pub fn free(&mut self, ptr: u32, len: u32, align: u32) -> Result<()> {
+        if len == 0 {
+            return Ok(());
+        }
+        match self.allocations.remove(&ptr) {
+            Some(allocation) if allocation == (len, align) => Ok(()),
+            _ => bail!(
+                "invalid free of {} bytes aligned to {} at {:#x}",
+                len,
+                align,
+                ptr
+            ),
+        }
+    }
+
+    /// Returns the number of allocations which haven't been freed yet.
+    pub fn allocations(&self) -> usize {
+        self.allocations.len()
+    }
+
+    fn grow(&mut self, align: u32, len: u32) -> u32 {
+        let align = align.max(1) as usize;
+        let ptr = (self.memory.len() + align - 1) & !(align - 1);
+        self.memory.resize(ptr + len as usize, 0);
+        ptr as u32
+    }
+
+    fn bytes(&self, addr: u32, len: u32) -> Result<&[u8]> {
+        match addr.checked_add(len) {
+            Some(end) if end as usize <= self.memory.len() => {
+                Ok(&self.memory[addr as usize..end as usize])
+            }
+            _ => bail!("out of bounds access of {} bytes at {:#x}", len, addr),
+        }
+    }
+
+    fn bytes_mut(&mut self, addr: u32, len: u32) -> Result<&mut [u8]> {
+        self.bytes(addr, len)?;
+        Ok(&mut self.memory[addr as usize..(addr + len) as usize])
+    }
+
+    fn block(
+        &mut self,
+        block: &Block,
+        frame: &mut Frame<'_, '_, 'a>,
+        cx: &Context,
+    ) -> Result<Vec<Val>> {
+        for node in block.nodes.iter() {
+            let operands = node
+                .operands
+                .iter()
+                .map(|v| {
+                    frame.values[*v]
+                        .clone()
+                        .expect("value used before definition")
+                })
+                .collect();
+            let results = self.node(node, operands, frame, cx)?;
+            assert_eq!(results.len(), node.results.len());
+            for (id, result) in node.results.iter().zip(results) {
+                frame.values[*id] = Some(result);
+            }
+        }
+        Ok(block
+            .results
+            .iter()
+            .map(|v| {
+                frame.values[*v]
+                    .clone()
+                    .expect("value used before definition")
+            })
+            .collect())
+    }
+
+    fn node(
+        &mut self,
+        node: &Node,
+        mut operands: Vec<Val>,
+        frame: &mut Frame<'_, '_, 'a>,
+        cx: &Context,
+    ) -> Result<Vec<Val>> {
+        let iface = self.iface;
+        let sizes = self.sizes;
+        Ok(match &node.op {
+            Op::Plain(inst) => self.plain(inst, operands, frame, cx)?,
+            Op::Bitcasts(casts) => casts
+                .iter()
+                .zip(operands)
+                .map(|(cast, val)| bitcast(*cast, val))
+                .collect::<Result<_>>()?,
+            Op::ConstZero(tys) => tys
+                .iter()
+                .map(|ty| match ty {
+                    WasmType::I32 => Val::I32(0),
+                    WasmType::I64 => Val::I64(0),
+                    WasmType::F32 => Val::F32(0.0),
+                    WasmType::F64 => Val::F64(0.0),
+                })
+                .collect(),
+
+            Op::StringLower { realloc, encoding } => {
+                let s = match operands.pop().unwrap() {
+                    Val::String(s) => s,
+                    val => bail!("expected a string, found {:?}", val),
+                };
+                let (bytes, len, align) = encode(&s, *encoding);
+                let ptr = self.allocate(frame, realloc.is_some(), align, bytes.len() as u32)?;
+                self.bytes_mut(ptr, bytes.len() as u32)?
+                    .copy_from_slice(&bytes);
+                vec![Val::I32(ptr as i32), Val::I32(len as i32)]
+            }
+            Op::StringLift { free, encoding } => {
+                let (ptr, len) = (u32_of(&operands[0])?, u32_of(&operands[1])?);
+                let (s, size, align) = self.decode(ptr, len, *encoding)?;
+                if free.is_some() {
+                    self.free(ptr, size, align)?;
+                }
+                vec![Val::String(s)]
+            }
+
+            Op::ListCanonLower { element, realloc } | Op::ListLower { element, realloc } => {
+                let list = match operands.pop().unwrap() {
+                    Val::List(list) => list,
+                    val => bail!("expected a list, found {:?}", val),
+                };
+                let size = sizes.size(element) as u32;
+                let align = sizes.align(element) as u32;
+                let len = list.len() as u32;
+                let ptr = self.allocate(frame, realloc.is_some(), align, len * size)?;
+                for (i, elem) in list.into_iter().enumerate() {
+                    let base = ptr + (i as u32) * size;
+                    if node.blocks.is_empty() {
+                        self.store_canon(element, base, &elem)?;
+                    } else {
+                        let cx = Context {
+                            elem: Some(elem),
+                            base: Some(base as i32),
+                            ..cx.clone()
+                        };
+                        self.block(&node.blocks[0], frame, &cx)?;
+                    }
+                }
+                vec![Val::I32(ptr as i32), Val::I32(len as i32)]
+            }
+            Op::ListCanonLift { element, free, .. } | Op::ListLift { element, free, .. } => {
+                let (ptr, len) = (u32_of(&operands[0])?, u32_of(&operands[1])?);
+                let size = sizes.size(element) as u32;
+                let align = sizes.align(element) as u32;
+                let total = len
+                    .checked_mul(size)
+                    .ok_or_else(|| anyhow!("list of {} elements is too large", len))?;
+                self.bytes(ptr, total)?;
+                let mut list = Vec::new();
+                for i in 0..len {
+                    let base = ptr + i * size;
+                    if node.blocks.is_empty() {
+                        list.push(self.load_canon(element, base)?);
+                    } else {
+                        let cx = Context {
+                            base: Some(base as i32),
+                            ..cx.clone()
+                        };
+                        list.extend(self.block(&node.blocks[0], frame, &cx)?);
+                    }
+                }
+                if free.is_some() {
+                    self.free(ptr, total, align)?;
+                }
+                vec![Val::List(list)]
+            }
+            Op::IterElem(_) => vec![cx
+                .elem
+                .clone()
+                .ok_or_else(|| anyhow!("no list element outside of a list"))?],
+
+            Op::ArrayLower { len, .. } => match operands.pop().unwrap() {
+                Val::Array(vals) if vals.len() == *len as usize => vals,
+                val => bail!("expected an array of {} elements, found {:?}", len, val),
+            },
+            Op::ArrayLift { .. } => vec![Val::Array(operands)],
+            Op::ArrayLowerMemory {
+                element,
+                len,
+                realloc,
+                ..
+            } => {
+                let vals = match operands.pop().unwrap() {
+                    Val::Array(vals) if vals.len() == *len as usize => vals,
+                    val => bail!("expected an array of {} elements, found {:?}", len, val),
+                };
+                let size = sizes.size(element) as u32;
+                let align = sizes.align(element) as u32;
+                let ptr = self.allocate(frame, realloc.is_some(), align, len * size)?;
+                for (i, elem) in vals.into_iter().enumerate() {
+                    let cx = Context {
+                        elem: Some(elem),
+                        base: Some((ptr + (i as u32) * size) as i32),
+                        ..cx.clone()
+                    };
+                    self.block(&node.blocks[0], frame, &cx)?;
+                }
+                vec![Val::I32(ptr as i32)]
+            }
+            Op::ArrayLiftMemory {
+                element, len, free, ..
+            } => {
+                let ptr = u32_of(&operands[0])?;
+                let size = sizes.size(element) as u32;
+                let align = sizes.align(element) as u32;
+                self.bytes(ptr, len * size)?;
+                let mut vals = Vec::new();
+                for i in 0..*len {
+                    let cx = Context {
+                        base: Some((ptr + i * size) as i32),
+                        ..cx.clone()
+                    };
+                    vals.extend(self.block(&node.blocks[0], frame, &cx)?);
+                }
+                if free.is_some() {
+                    self.free(ptr, len * size, align)?;
+                }
+                vec![Val::Array(vals)]
+            }
+            Op::RecordLower { record, .. } => {
+                let fields = match &iface.types[*record].kind {
+                    TypeDefKind::Record(r) => r.fields.len(),
+                    _ => unreachable!(),
+                };
+                match operands.pop().unwrap() {
+                    Val::Record(vals) if vals.len() == fields => vals,
+                    val => bail!("expected a record of {} fields, found {:?}", fields, val),
+                }
+            }
+            Op::RecordLift { .. } => vec![Val::Record(operands)],
+            Op::RecordCanonLower { ty, offset } => {
+                let base = address(&operands[1], *offset)?;
+                self.store_canon(&Type::Id(*ty), base, &operands[0])?;
+                Vec::new()
+            }
+            Op::RecordCanonLift { ty, offset } => {
+                let base = address(&operands[0], *offset)?;
+                vec![self.load_canon(&Type::Id(*ty), base)?]
+            }
+
+            Op::FlagsLower(ty) | Op::FlagsLower64(ty) => {
+                let flags = match &iface.types[*ty].kind {
+                    TypeDefKind::Record(r) => r,
+                    _ => unreachable!(),
+                };
+                let bits = match operands.pop().unwrap() {
+                    Val::Flags(bits) if bits.len() == flags.fields.len() => bits,
+                    val => bail!("expected {} flags, found {:?}", flags.fields.len(), val),
+                };
+                let mut words = vec![0u32; flags.num_i32s().max(2)];
+                for (field, set) in flags.fields.iter().zip(&bits) {
+                    if *set {
+                        let bit = field.bit as usize;
+                        words[bit / 32] |= 1 << (bit % 32);
+                    }
+                }
+                match &node.op {
+                    Op::FlagsLower64(_) => vec![Val::I64(
+                        (u64::from(words[1]) << 32 | u64::from(words[0])) as i64,
+                    )],
+                    _ => words
+                        .into_iter()
+                        .take(flags.num_i32s())
+                        .map(|w| Val::I32(w as i32))
+                        .collect(),
+                }
+            }
+            Op::FlagsLift(ty) | Op::FlagsLift64(ty) => {
+                let flags = match &iface.types[*ty].kind {
+                    TypeDefKind::Record(r) => r,
+                    _ => unreachable!(),
+                };
+                let words = match &node.op {
+                    Op::FlagsLift64(_) => {
+                        let bits = i64_of(&operands[0])? as u64;
+                        vec![bits as u32, (bits >> 32) as u32]
+                    }
+                    _ => operands.iter().map(u32_of).collect::<Result<_>>()?,
+                };
+                let is_set =
+                    |bit: usize| words.get(bit / 32).copied().unwrap_or(0) & (1 << (bit % 32)) != 0;
+                for bit in 0..words.len() * 32 {
+                    if is_set(bit) && !flags.fields.iter().any(|f| f.bit as usize == bit) {
+                        bail!("bit {} is set but isn't the bit of any flag", bit);
+                    }
+                }
+                let bits = flags
+                    .fields
+                    .iter()
+                    .map(|f| is_set(f.bit as usize))
+                    .collect();
+                vec![Val::Flags(bits)]
+            }
+
+            Op::VariantLower { .. } => {
+                let (case, payload) = match operands.pop().unwrap() {
+                    Val::Variant(case, payload) if case < node.blocks.len() => (case, payload),
+                    val => bail!("expected a variant, found {:?}", val),
+                };
+                let cx = Context {
+                    payload: Some(payload.map(|p| *p).unwrap_or(Val::Record(Vec::new()))),
+                    ..cx.clone()
+                };
+                self.block(&node.blocks[case], frame, &cx)?
+            }
+            Op::VariantLift(ty) => {
+                let variant = match &iface.types[*ty].kind {
+                    TypeDefKind::Variant(v) => v,
+                    _ => unreachable!(),
+                };
+                let discriminant = match &operands[0] {
+                    Val::I32(tag) => *tag as u32 as u64,
+                    Val::I64(tag) => *tag as u64,
+                    val => bail!("expected a variant discriminant, found {:?}", val),
+                };
+                let case = u32::try_from(discriminant)
+                    .ok()
+                    .and_then(|d| variant.case_index(d))
+                    .ok_or_else(|| anyhow!("invalid variant discriminant {}", discriminant))?;
+                let block = &node.blocks[case];
+                let payload = self.block(block, frame, cx)?.pop().map(Box::new);
+                vec![Val::Variant(case, payload)]
+            }
+
+            Op::CallWasm(_) | Op::CallInterface => {
+                let results = (frame.call)(self, operands)?;
+                if results.len() != node.results.len() {
+                    bail!(
+                        "expected {} results from the callee, found {}",
+                        node.results.len(),
+                        results.len()
+                    );
+                }
+                results
+            }
+            Op::Return(_) => {
+                frame.results = Some(operands);
+                Vec::new()
+            }
+            Op::ReturnPointerArea(amt) => {
+                let size = (*amt as u32) * 8;
+                if self.ret_area.1 < size {
+                    self.ret_area = (self.grow(8, size), size);
+                }
+                vec![Val::I32(self.ret_area.0 as i32)]
+            }
+
+            Op::BufferLowerPtrLen { .. }
+            | Op::BufferLowerHandle { .. }
+            | Op::BufferLiftPtrLen { .. }
+            | Op::BufferLiftHandle { .. } => bail!("buffers aren't supported"),
+            Op::CallWasmAsyncImport { .. }
+            | Op::CallWasmAsyncExport { .. }
+            | Op::ReturnAsyncExport
+            | Op::ReturnAsyncImport(_) => bail!("async functions aren't supported"),
+            Op::Witx(_) | Op::AllocateTypedSpace(_) => bail!("the preview1 ABI isn't supported"),
+        })
+    }
+
+    fn plain(
+        &mut self,
+        inst: &Instruction<'_>,
+        mut operands: Vec<Val>,
+        frame: &mut Frame<'_, '_, 'a>,
+        cx: &Context,
+    ) -> Result<Vec<Val>> {
+        use Instruction::*;
+
+        let result = match inst {
+            GetArg { nth } => frame
+                .args
+                .get(*nth)
+                .cloned()
+                .ok_or_else(|| anyhow!("missing argument {}", nth))?,
+            I32Const { val } => Val::I32(*val),
+            IterBasePointer => Val::I32(
+                cx.base
+                    .ok_or_else(|| anyhow!("no base pointer outside of a list"))?,
+            ),
+            VariantPayloadName => cx
+                .payload
+                .clone()
+                .ok_or_else(|| anyhow!("no payload outside of a variant"))?,
+            BufferPayloadName => bail!("buffers aren't supported"),
+
+            I32Load { offset } => Val::I32(i32::from_le_bytes(self.load(&operands, *offset)?)),
+            I32Load8U { offset } => {
+                Val::I32(i32::from(self.load::<[u8; 1]>(&operands, *offset)?[0]))
+            }
+            I32Load8S { offset } => Val::I32(i32::from(
+                self.load::<[u8; 1]>(&operands, *offset)?[0] as i8,
+            )),
+            I32Load16U { offset } => Val::I32(i32::from(u16::from_le_bytes(
+                self.load(&operands, *offset)?,
+            ))),
+            I32Load16S { offset } => Val::I32(i32::from(i16::from_le_bytes(
+                self.load(&operands, *offset)?,
+            ))),
+            I64Load { offset } => Val::I64(i64::from_le_bytes(self.load(&operands, *offset)?)),
+            F32Load { offset } => Val::F32(f32::from_le_bytes(self.load(&operands, *offset)?)),
+            F64Load { offset } => Val::F64(f64::from_le_bytes(self.load(&operands, *offset)?)),
+
+            I32Store { offset } => {
+                let val = i32_of(&operands[0])?;
+                return self.store(&operands, *offset, &val.to_le_bytes());
+            }
+            I32Store8 { offset } => {
+                let val = i32_of(&operands[0])?;
+                return self.store(&operands, *offset, &[val as u8]);
+            }
+            I32Store16 { offset } => {
+                let val = i32_of(&operands[0])?;
+                return self.store(&operands, *offset, &(val as u16).to_le_bytes());
+            }
+            I64Store { offset } => {
+                let val = i64_of(&operands[0])?;
+                return self.store(&operands, *offset, &val.to_le_bytes());
+            }
+            F32Store { offset } => match operands[0] {
+                Val::F32(val) => return self.store(&operands, *offset, &val.to_le_bytes()),
+                ref val => bail!("expected an f32, found {:?}", val),
+            },
+            F64Store { offset } => match operands[0] {
+                Val::F64(val) => return self.store(&operands, *offset, &val.to_le_bytes()),
+                ref val => bail!("expected an f64, found {:?}", val),
+            },
+
+            inst => convert(inst, operands.pop().unwrap())?,
+        };
+        Ok(vec![result])
+    }
+
+    fn load<T>(&self, operands: &[Val], offset: i32) -> Result<T>
+    where
+        for<'b> &'b [u8]: TryInto<T>,
+    {
+        let addr = address(&operands[0], offset)?;
+        let len = std::mem::size_of::<T>() as u32;
+        Ok(self.bytes(addr, len)?.try_into().ok().unwrap())
+    }
+
+    fn store(&mut self, operands: &[Val], offset: i32, bytes: &[u8]) -> Result<Vec<Val>> {
+        let addr = address(&operands[1], offset)?;
+        self.bytes_mut(addr, bytes.len() as u32)?
+            .copy_from_slice(bytes);
+        Ok(Vec::new())
+    }
+
+    /// Allocates memory for a string or list. Unless the memory is `owned` by
+    /// the callee it's freed when the current program finishes.
+    fn allocate(
+        &mut self,
+        frame: &mut Frame<'_, '_, 'a>,
+        owned: bool,
+        align: u32,
+        len: u32,
+    ) -> Result<u32> {
+        let ptr = self.realloc(0, 0, align, len)?;
+        if !owned {
+            frame.temporaries.push((ptr, len, align));
+        }
+        Ok(ptr)
+    }
+
+    /// Decodes a string, returning it along with the size and alignment of
+    /// its allocation.
+    fn decode(&self, ptr: u32, len: u32, encoding: StringEncoding) -> Result<(String, u32, u32)> {
+        let utf16 = |len: u32| -> Result<(String, u32, u32)> {
+            let size = len
+                .checked_mul(2)
+                .ok_or_else(|| anyhow!("string of {} code units is too large", len))?;
+            let units = self
+                .bytes(ptr, size)?
+                .chunks(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect::<Vec<_>>();
+            Ok((String::from_utf16(&units)?, size, 2))
+        };
+        match encoding {
+            StringEncoding::Utf8 => {
+                Ok((String::from_utf8(self.bytes(ptr, len)?.to_vec())?, len, 1))
+            }
+            StringEncoding::Utf16 => utf16(len),
+            StringEncoding::CompactUtf16 => {
+                if len & StringEncoding::UTF16_TAG != 0 {
+                    return utf16(len & !StringEncoding::UTF16_TAG);
+                }
+                let s = self
+                    .bytes(ptr, len)?
+                    .iter()
+                    .map(|b| char::from(*b))
+                    .collect();
+                Ok((s, len, 1))
+            }
+        }
+    }
+
+    /// Reads a value of `ty` at `addr`, for types which have all bit patterns
+    /// valid.
+    fn load_canon(&self, ty: &Type, addr: u32) -> Result<Val> {
+        let bytes = |len: u32| self.bytes(addr, len);
+        Ok(match ty {
+            Type::U8 => Val::U8(bytes(1)?[0]),
+            Type::S8 => Val::S8(bytes(1)?[0] as i8),
+            Type::CChar => Val::Char8(bytes(1)?[0]),
+            Type::U16 => Val::U16(u16::from_le_bytes(bytes(2)?.try_into()?)),
+            Type::S16 => Val::S16(i16::from_le_bytes(bytes(2)?.try_into()?)),
+            Type::U32 => Val::U32(u32::from_le_bytes(bytes(4)?.try_into()?)),
+            Type::S32 => Val::S32(i32::from_le_bytes(bytes(4)?.try_into()?)),
+            Type::Usize => Val::Usize(u32::from_le_bytes(bytes(4)?.try_into()?)),
+            Type::U64 => Val::U64(u64::from_le_bytes(bytes(8)?.try_into()?)),
+            Type::S64 => Val::S64(i64::from_le_bytes(bytes(8)?.try_into()?)),
+            Type::F32 => Val::F32(f32::from_le_bytes(bytes(4)?.try_into()?)),
+            Type::F64 => Val::F64(f64::from_le_bytes(bytes(8)?.try_into()?)),
+            Type::Id(id) => match &self.iface.types[*id].kind {
+                TypeDefKind::Type(t) => return self.load_canon(t, addr),
+                TypeDefKind::Record(r) if !r.is_flags() => {
+                    let offsets = self.sizes.field_offsets(r);
+                    let fields = r
+                        .fields
+                        .iter()
+                        .zip(offsets)
+                        .map(|(f, offset)| self.load_canon(&f.ty, addr + offset as u32))
+                        .collect::<Result<_>>()?;
+                    Val::Record(fields)
+                }
+                TypeDefKind::Array(t, len) => {
+                    let size = self.sizes.size(t) as u32;
+                    let elems = (0..*len)
+                        .map(|i| self.load_canon(t, addr + i * size))
+                        .collect::<Result<_>>()?;
+                    Val::Array(elems)
+                }
+                _ => bail!("cannot copy {:?} out of memory", ty),
+            },
+            _ => bail!("cannot copy {:?} out of memory", ty),
+        })
+    }
+
+    /// Writes `val` of `ty` at `addr`, for types which have all bit patterns
+    /// valid.
+    fn store_canon(&mut self, ty: &Type, addr: u32, val: &Val) -> Result<()> {
+        let bytes = match (ty, val) {
+            (Type::U8, Val::U8(v)) | (Type::CChar, Val::Char8(v)) => vec![*v],
+            (Type::S8, Val::S8(v)) => vec![*v as u8],
+            (Type::U16, Val::U16(v)) => v.to_le_bytes().to_vec(),
+            (Type::S16, Val::S16(v)) => v.to_le_bytes().to_vec(),
+            (Type::U32, Val::U32(v)) | (Type::Usize, Val::Usize(v)) => v.to_le_bytes().to_vec(),
+            (Type::S32, Val::S32(v)) => v.to_le_bytes().to_vec(),
+            (Type::U64, Val::U64(v)) => v.to_le_bytes().to_vec(),
+            (Type::S64, Val::S64(v)) => v.to_le_bytes().to_vec(),
+            (Type::F32, Val::F32(v)) => v.to_le_bytes().to_vec(),
+            (Type::F64, Val::F64(v)) => v.to_le_bytes().to_vec(),
+            (Type::Id(id), _) => match (&self.iface.types[*id].kind, val) {
+                (TypeDefKind::Type(t), _) => return self.store_canon(t, addr, val),
+                (TypeDefKind::Record(r), Val::Record(vals))
+                    if !r.is_flags() && vals.len() == r.fields.len() =>
+                {
+                    let offsets = self.sizes.field_offsets(r);
+                    for ((f, offset), val) in r.fields.iter().zip(offsets).zip(vals) {
+                        self.store_canon(&f.ty, addr + offset as u32, val)?;
+                    }
+                    return Ok(());
+                }
+                (TypeDefKind::Array(t, len), Val::Array(vals)) if vals.len() == *len as usize => {
+                    let size = self.sizes.size(t) as u32;
+                    for (i, val) in vals.iter().enumerate() {
+                        self.store_canon(t, addr + i as u32 * size, val)?;
+                    }
+                    return Ok(());
+                }
+                _ => bail!("cannot copy {:?} of type {:?} into memory", val, ty),
+            },
+            _ => bail!("cannot copy {:?} of type {:?} into memory", val, ty),
+        };
+        self.bytes_mut(addr, bytes.len() as u32)?
+            .copy_from_slice(&bytes);
+        Ok(())
+    }
+}
+
+/// Encodes `s`, returning its bytes, its length and the alignment of its
+/// allocation.
+fn encode(s: &str, encoding: StringEncoding) -> (Vec<u8>, u32, u32) {
+    let utf16 = || {
+        let units = s.encode_utf16().collect::<Vec<_>>();
+        let bytes = units
+            .iter()
+            .flat_map(|u| u.to_le_bytes().to_vec())
+            .collect();
+        (bytes, units.len() as u32, 2)
+    };
+    match encoding {
+        StringEncoding::Utf8 => (s.as_bytes().to_vec(), s.len() as u32, 1),
+        StringEncoding::Utf16 => utf16(),
+        StringEncoding::CompactUtf16 => {
+            if s.chars().any(|c| u32::from(c) > 0xff) {
+                let (bytes, len, align) = utf16();
+                return (bytes, len | StringEncoding::UTF16_TAG, align);
+            }
+            let bytes = s.chars().map(|c| c as u8).collect::<Vec<_>>();
+            let len = bytes.len() as u32;
+            (bytes, len, 1)
+        }
+    }
+}
+
+fn address(base: &Val, offset: i32) -> Result<u32> {
+    u32_of(base)?
+        .checked_add(offset as u32)
+        .ok_or_else(|| anyhow!("address overflow"))
+}
+
+fn i32_of(val: &Val) -> Result<i32> {
+    match val {
+        Val::I32(v) => Ok(*v),
+        val => bail!("expected an i32, found {:?}", val),
+    }
+}
+
+fn u32_of(val: &Val) -> Result<u32> {
+    Ok(i32_of(val)? as u32)
+}
+
+fn i64_of(val: &Val) -> Result<i64> {
+    match val {
+        Val::I64(v) => Ok(*v),
+        val => bail!("expected an i64, found {:?}", val),
+    }
+}
+
+fn bitcast(cast: Bitcast, val: Val) -> Result<Val> {
+    Ok(match (cast, val) {
+        (Bitcast::None, val) => val,
+        (Bitcast::F32ToF64, Val::F32(v)) => Val::F64(f64::from(v)),
+        (Bitcast::F32ToI32, Val::F32(v)) => Val::I32(v.to_bits() as i32),
+        (Bitcast::F64ToI64, Val::F64(v)) => Val::I64(v.to_bits() as i64),
+        (Bitcast::I32ToI64, Val::I32(v)) => Val::I64(i64::from(v)),
+        (Bitcast::F32ToI64, Val::F32(v)) => Val::I64(i64::from(v.to_bits())),
+        (Bitcast::F64ToF32, Val::F64(v)) => Val::F32(v as f32),
+        (Bitcast::I32ToF32, Val::I32(v)) => Val::F32(f32::from_bits(v as u32)),
+        (Bitcast::I64ToF64, Val::I64(v)) => Val::F64(f64::from_bits(v as u64)),
+        (Bitcast::I64ToI32, Val::I64(v)) => Val::I32(v as i32),
+        (Bitcast::I64ToF32, Val::I64(v)) => Val::F32(f32::from_bits(v as u32)),
+        (cast, val) => bail!("cannot apply {:?} to {:?}", cast, val),
+    })
+}
+
+/// Executes the instructions converting between scalars and handles and
+/// their wasm representations.
+fn convert(inst: &Instruction<'_>, val: Val) -> Result<Val> {
+    use Instruction::*;
+
+    macro_rules! narrow {
+        ($v:ident as $ty:ident) => {
+            $ty::try_from($v)
+                .map_err(|_| anyhow!("{} is out of range for {}", $v, stringify!($ty)))?
+        };
+    }
+
+    Ok(match (inst, val) {
+        (I32FromU8, Val::U8(v)) | (I32FromChar8, Val::Char8(v)) => Val::I32(i32::from(v)),
+        (I32FromS8, Val::S8(v)) => Val::I32(i32::from(v)),
+        (I32FromU16, Val::U16(v)) => Val::I32(i32::from(v)),
+        (I32FromS16, Val::S16(v)) => Val::I32(i32::from(v)),
+        (I32FromU32, Val::U32(v)) | (I32FromUsize, Val::Usize(v)) => Val::I32(v as i32),
+        (I32FromS32, Val::S32(v)) => Val::I32(v),
+        (I64FromU64, Val::U64(v)) => Val::I64(v as i64),
+        (I64FromS64, Val::S64(v)) => Val::I64(v),
+        (I32FromChar, Val::Char(c)) => Val::I32(c as i32),
+        (I32FromBool, Val::Bool(b)) => Val::I32(b as i32),
+        (F32FromIf32, Val::F32(v)) => Val::F32(v),
+        (F64FromIf64, Val::F64(v)) => Val::F64(v),
+        (I32FromBorrowedHandle { .. }, Val::Handle(h))
+        | (I32FromOwnedHandle { .. }, Val::Handle(h)) => Val::I32(h),
+
+        (U8FromI32, Val::I32(v)) => Val::U8(narrow!(v as u8)),
+        (Char8FromI32, Val::I32(v)) => Val::Char8(narrow!(v as u8)),
+        (S8FromI32, Val::I32(v)) => Val::S8(narrow!(v as i8)),
+        (U16FromI32, Val::I32(v)) => Val::U16(narrow!(v as u16)),
+        (S16FromI32, Val::I32(v)) => Val::S16(narrow!(v as i16)),
+        (U32FromI32, Val::I32(v)) => Val::U32(v as u32),
+        (UsizeFromI32, Val::I32(v)) => Val::Usize(v as u32),
+        (S32FromI32, Val::I32(v)) => Val::S32(v),
+        (U64FromI64, Val::I64(v)) => Val::U64(v as u64),
+        (S64FromI64, Val::I64(v)) => Val::S64(v),
+        (CharFromI32, Val::I32(v)) => Val::Char(
+            std::char::from_u32(v as u32).ok_or_else(|| anyhow!("{:#x} is not a valid char", v))?,
+        ),
+        (BoolFromI32, Val::I32(0)) => Val::Bool(false),
+        (BoolFromI32, Val::I32(1)) => Val::Bool(true),
+        (If32FromF32, Val::F32(v)) => Val::F32(v),
+        (If64FromF64, Val::F64(v)) => Val::F64(v),
+        (HandleOwnedFromI32 { .. }, Val::I32(h)) | (HandleBorrowedFromI32 { .. }, Val::I32(h)) => {
+            Val::Handle(h)
+        }
+
+        (inst, val) => bail!("invalid operand {:?} for {:?}", val, inst),
+    })
+}
@@ -0,0 +1,1325 @@
+//! An owned and replayable form of the instructions generated for a function.
+//!
+//! [`Interface::call`] hands instructions to a [`Bindgen`] one at a time, so a
+//! backend never gets to see more than one instruction at once. A [`Program`]
+//! instead records the entire stream, identifying each value flowing between
+//! instructions by a number. This means that the stream can be optimized once
+//! for all backends with [`Program::optimize`] before being handed to a
+//! backend with [`Program::emit`], and it can also be executed directly by the
+//! [`interp`](super::interp) module.
+
+use super::{
+    Bindgen, Bitcast, Direction, Instruction, LiftLower, MultiValue, StringEncoding, WasmSignature,
+    WasmType, WitxInstruction,
+};
+use crate::{Function, Interface, Record, SizeAlign, Type, TypeDefKind, TypeId};
+use std::collections::{HashMap, HashSet};
+use std::mem;
+
+/// The recorded instructions of the glue code for one function.
+pub struct Program {
+    pub(super) body: Block,
+    pub(super) values: usize,
+}
+
+/// A sequence of instructions and the values that it results in.
+///
+/// Blocks other than the body of a program are owned by the instruction that
+/// uses them, which may execute them any number of times.
+pub(super) struct Block {
+    pub(super) nodes: Vec<Node>,
+    pub(super) results: Vec<usize>,
+}
+
+/// One instruction of a program, along with the values it consumes and
+/// produces and the blocks it owns.
+pub(super) struct Node {
+    pub(super) op: Op,
+    pub(super) operands: Vec<usize>,
+    pub(super) results: Vec<usize>,
+    pub(super) blocks: Vec<Block>,
+}
+
+/// An owned version of an `Instruction`.
+///
+/// Borrowed data is either copied, or it's looked up again in the interface
+/// when the instruction is emitted.
+pub(super) enum Op {
+    /// An instruction which doesn't borrow anything.
+    Plain(Instruction<'static>),
+    Bitcasts(Vec<Bitcast>),
+    ConstZero(Vec<WasmType>),
+    StringLower {
+        realloc: Option<String>,
+        encoding: StringEncoding,
+    },
+    StringLift {
+        free: Option<String>,
+        encoding: StringEncoding,
+    },
+    ListCanonLower {
+        element: Type,
+        realloc: Option<String>,
+    },
+    ListLower {
+        element: Type,
+        realloc: Option<String>,
+    },
+    ListCanonLift {
+        element: Type,
+        free: Option<String>,
+        ty: TypeId,
+    },
+    ListLift {
+        element: Type,
+        free: Option<String>,
+        ty: TypeId,
+    },
+    IterElem(Type),
+    BufferLowerPtrLen {
+        push: bool,
+        ty: Type,
+    },
+    BufferLowerHandle {
+        push: bool,
+        ty: Type,
+    },
+    BufferLiftPtrLen {
+        push: bool,
+        ty: Type,
+    },
+    BufferLiftHandle {
+        push: bool,
+        ty: Type,
+    },
+    ArrayLower {
+        element: Type,
+        len: u32,
+        ty: TypeId,
+    },
+    ArrayLift {
+        element: Type,
+        len: u32,
+        ty: TypeId,
+    },
+    ArrayLowerMemory {
+        element: Type,
+        len: u32,
+        ty: TypeId,
+        realloc: Option<String>,
+    },
+    ArrayLiftMemory {
+        element: Type,
+        len: u32,
+        ty: TypeId,
+        free: Option<String>,
+    },
+    /// `record` is the type that defines the record, which is the same as
+    /// `ty` except for tuples returned from `Abi::Preview1` functions.
+    RecordLower {
+        ty: TypeId,
+        record: TypeId,
+    },
+    RecordLift {
+        ty: TypeId,
+        record: TypeId,
+    },
+    /// Only created by `Program::optimize`, the single block of these nodes
+    /// holds the instructions they replaced.
+    RecordCanonLower {
+        ty: TypeId,
+        offset: i32,
+    },
+    RecordCanonLift {
+        ty: TypeId,
+        offset: i32,
+    },
+    FlagsLower(TypeId),
+    FlagsLower64(TypeId),
+    FlagsLift(TypeId),
+    FlagsLift64(TypeId),
+    VariantLower {
+        ty: TypeId,
+        results: Vec<WasmType>,
+    },
+    VariantLift(TypeId),
+    CallWasm(WasmSignature),
+    CallWasmAsyncImport {
+        params: Vec<WasmType>,
+        results: Vec<WasmType>,
+    },
+    CallWasmAsyncExport {
+        params: Vec<WasmType>,
+        results: Vec<WasmType>,
+    },
+    CallInterface,
+    Return(usize),
+    ReturnAsyncExport,
+    ReturnAsyncImport(usize),
+    Witx(Witx),
+
+    /// A call to `Bindgen::allocate_typed_space`.
+    AllocateTypedSpace(TypeId),
+    /// A call to `Bindgen::i64_return_pointer_area`.
+    ReturnPointerArea(usize),
+}
+
+/// An owned version of a `WitxInstruction`.
+pub(super) enum Witx {
+    AddrOf,
+    I32FromPointer,
+    I32FromConstPointer,
+    PointerFromI32(Type),
+    ConstPointerFromI32(Type),
+    ReuseReturn,
+}
+
+impl Program {
+    /// Records the instructions that [`Interface::call`] generates for `func`.
+    ///
+    /// Lists are always recorded with a block to lift or lower each element,
+    /// and whether they're canonical is only decided when the program is
+    /// emitted.
+    pub fn new(
+        iface: &Interface,
+        dir: Direction,
+        lift_lower: LiftLower,
+        string_encoding: StringEncoding,
+        multi_value: MultiValue,
+        func: &Function,
+        sizes: &SizeAlign,
+    ) -> Program {
+        let mut recorder = Recorder {
+            sizes,
+            blocks: vec![Vec::new()],
+            finished: Vec::new(),
+            values: 0,
+        };
+        iface.call(
+            dir,
+            lift_lower,
+            string_encoding,
+            multi_value,
+            func,
+            &mut recorder,
+        );
+        assert!(recorder.finished.is_empty());
+        assert_eq!(recorder.blocks.len(), 1);
+        Program {
+            body: Block {
+                nodes: recorder.blocks.pop().unwrap(),
+                results: Vec::new(),
+            },
+            values: recorder.values,
+        }
+    }
+
+    /// Optimizes this program in-place.
+    ///
+    /// The optimizations performed are:
+    ///
+    /// * Bitcasts which don't change their operand, or which undo a previous
+    ///   lossless bitcast, are removed.
+    /// * Loads or stores of each field of a record whose fields all have only
+    ///   valid bit patterns are fused into a single `RecordCanonLift` or
+    ///   `RecordCanonLower` instruction. When emitted to a backend where
+    ///   [`Bindgen::is_record_canonical`] is `false` the original instructions
+    ///   are emitted instead.
+    /// * Instructions in the block for each element of a list which don't
+    ///   depend on the element, like constants, are hoisted out of the block.
+    pub fn optimize(&mut self, iface: &Interface, sizes: &SizeAlign) {
+        remove_bitcasts(&mut self.body, &mut HashMap::new(), &mut HashMap::new());
+        let mut uses = HashMap::new();
+        count_uses(&self.body, &mut uses);
+        remove_dead_bitcasts(&mut self.body, &uses);
+        fuse_records(iface, sizes, &mut self.body);
+        hoist(iface, &mut self.body);
+    }
+
+    /// Feeds the instructions of this program to `bindgen`.
+    ///
+    /// The `func` must be the function this program was created for. Unless
+    /// the program was optimized this is equivalent to calling
+    /// [`Interface::call`] with `bindgen`.
+    pub fn emit<B: Bindgen>(&self, iface: &Interface, func: &Function, bindgen: &mut B) {
+        Replay {
+            iface,
+            func,
+            bindgen,
+            values: vec![None; self.values],
+        }
+        .nodes(&self.body.nodes);
+    }
+}
+
+struct Recorder<'a> {
+    sizes: &'a SizeAlign,
+    blocks: Vec<Vec<Node>>,
+    finished: Vec<Block>,
+    values: usize,
+}
+
+impl Recorder<'_> {
+    fn push(
+        &mut self,
+        op: Op,
+        operands: Vec<usize>,
+        results: usize,
+        blocks: Vec<Block>,
+    ) -> &[usize] {
+        let ids = (self.values..self.values + results).collect();
+        self.values += results;
+        let nodes = self.blocks.last_mut().unwrap();
+        nodes.push(Node {
+            op,
+            operands,
+            results: ids,
+            blocks,
+        });
+        &nodes.last().unwrap().results
+    }
+}
+
+impl Bindgen for Recorder<'_> {
+    type Operand = usize;
+
+    fn emit(
+        &mut self,
+        iface: &Interface,
+        inst: &Instruction<'_>,
+        operands: &mut Vec<usize>,
+        results: &mut Vec<usize>,
+    ) {
+        let blocks = match inst {
+            Instruction::VariantLower { variant, .. }
+            | Instruction::VariantLift { variant, .. } => variant.cases.len(),
+            Instruction::ListLower { .. }
+            | Instruction::ListLift { .. }
+            | Instruction::ArrayLowerMemory { .. }
+            | Instruction::ArrayLiftMemory { .. }
+            | Instruction::BufferLowerPtrLen { .. }
+            | Instruction::BufferLowerHandle { .. }
+            | Instruction::BufferLiftPtrLen { .. }
+            | Instruction::BufferLiftHandle { .. } => 1,
+            _ => 0,
+        };
+        let blocks = self.finished.split_off(self.finished.len() - blocks);
+        let op = Op::new(iface, inst);
+        let ids = self.push(op, mem::take(operands), inst.results_len(), blocks);
+        results.extend_from_slice(ids);
+    }
+
+    fn allocate_typed_space(&mut self, _iface: &Interface, ty: TypeId) -> usize {
+        self.push(Op::AllocateTypedSpace(ty), Vec::new(), 1, Vec::new())[0]
+    }
+
+    fn i64_return_pointer_area(&mut self, amt: usize) -> usize {
+        self.push(Op::ReturnPointerArea(amt), Vec::new(), 1, Vec::new())[0]
+    }
+
+    fn push_block(&mut self) {
+        self.blocks.push(Vec::new());
+    }
+
+    fn finish_block(&mut self, operands: &mut Vec<usize>) {
+        let nodes = self.blocks.pop().unwrap();
+        self.finished.push(Block {
+            nodes,
+            results: mem::take(operands),
+        });
+    }
+
+    fn sizes(&self) -> &SizeAlign {
+        self.sizes
+    }
+
+    fn is_list_canonical(&self, _iface: &Interface, _element: &Type) -> bool {
+        false
+    }
+}
+
+struct Replay<'a, B: Bindgen> {
+    iface: &'a Interface,
+    func: &'a Function,
+    bindgen: &'a mut B,
+    values: Vec<Option<B::Operand>>,
+}
+
+impl<B: Bindgen> Replay<'_, B> {
+    fn nodes(&mut self, nodes: &[Node]) {
+        let iface = self.iface;
+        for node in nodes {
+            let canonical = match &node.op {
+                Op::AllocateTypedSpace(ty) => {
+                    let ptr = self.bindgen.allocate_typed_space(iface, *ty);
+                    self.values[node.results[0]] = Some(ptr);
+                    continue;
+                }
+                Op::ReturnPointerArea(amt) => {
+                    let ptr = self.bindgen.i64_return_pointer_area(*amt);
+                    self.values[node.results[0]] = Some(ptr);
+                    continue;
+                }
+                Op::ListLower { element, .. } | Op::ListLift { element, .. } => {
+                    self.bindgen.is_list_canonical(iface, element)
+                }
+                Op::RecordCanonLower { ty, .. } | Op::RecordCanonLift { ty, .. } => {
+                    if !self.bindgen.is_record_canonical(iface, *ty) {
+                        let original = &node.blocks[0];
+                        self.nodes(&original.nodes);
+                        for (result, value) in node.results.iter().zip(&original.results) {
+                            self.values[*result] = self.values[*value].clone();
+                        }
+                        continue;
+                    }
+                    true
+                }
+                _ => false,
+            };
+            if !canonical {
+                for block in node.blocks.iter() {
+                    self.bindgen.push_block();
+                    self.nodes(&block.nodes);
+                    let mut operands = self.operands(&block.results);
+                    self.bindgen.finish_block(&mut operands);
+                }
+            }
+
+            let mut operands = self.operands(&node.operands);
+            let mut results = Vec::new();
+            let bindgen = &mut *self.bindgen;
+            node.op
+                .with_instruction(iface, self.func, canonical, |inst| {
+                    bindgen.emit(iface, inst, &mut operands, &mut results)
+                });
+            assert_eq!(results.len(), node.results.len());
+            for (id, result) in node.results.iter().zip(results) {
+                self.values[*id] = Some(result);
+            }
+        }
+    }
+
+    fn operands(&self, values: &[usize]) -> Vec<B::Operand> {
+        values
+            .iter()
+            .map(|v| {
+                self.values[*v]
+                    .clone()
+                    .expect("value used before definition")
+            })
+            .collect()
+    }
+}
+
+// Helper macro to copy instructions which don't borrow anything, listing the
+// fields of each one.
+macro_rules! plain {
+    ($($variant:ident $({ $($field:ident),* })?,)*) => {
+        fn plain(inst: &Instruction<'_>) -> Option<Instruction<'static>> {
+            Some(match inst {
+                $(
+                    Instruction::$variant $({ $($field),* })? => {
+                        Instruction::$variant $({ $($field: *$field),* })?
+                    }
+                )*
+                _ => return None,
+            })
+        }
+    };
+}
+
+plain! {
+    GetArg { nth },
+    I32Const { val },
+    I32Load { offset },
+    I32Load8U { offset },
+    I32Load8S { offset },
+    I32Load16U { offset },
+    I32Load16S { offset },
+    I64Load { offset },
+    F32Load { offset },
+    F64Load { offset },
+    I32Store { offset },
+    I32Store8 { offset },
+    I32Store16 { offset },
+    I64Store { offset },
+    F32Store { offset },
+    F64Store { offset },
+    I32FromChar,
+    I64FromU64,
+    I64FromS64,
+    I32FromU32,
+    I32FromS32,
+    I32FromU16,
+    I32FromS16,
+    I32FromU8,
+    I32FromS8,
+    I32FromUsize,
+    I32FromChar8,
+    I32FromBool,
+    F32FromIf32,
+    F64FromIf64,
+    S8FromI32,
+    U8FromI32,
+    S16FromI32,
+    U16FromI32,
+    S32FromI32,
+    U32FromI32,
+    S64FromI64,
+    U64FromI64,
+    CharFromI32,
+    If32FromF32,
+    If64FromF64,
+    Char8FromI32,
+    UsizeFromI32,
+    BoolFromI32,
+    I32FromBorrowedHandle { ty },
+    I32FromOwnedHandle { ty },
+    HandleOwnedFromI32 { ty },
+    HandleBorrowedFromI32 { ty },
+    IterBasePointer,
+    VariantPayloadName,
+    BufferPayloadName,
+}
+
+impl Op {
+    fn new(iface: &Interface, inst: &Instruction<'_>) -> Op {
+        use Instruction::*;
+
+        if let Some(inst) = plain(inst) {
+            return Op::Plain(inst);
+        }
+        let owned = |s: &Option<&str>| s.map(|s| s.to_string());
+        match inst {
+            Bitcasts { casts } => Op::Bitcasts(casts.to_vec()),
+            ConstZero { tys } => Op::ConstZero(tys.to_vec()),
+            StringLower { realloc, encoding } => Op::StringLower {
+                realloc: owned(realloc),
+                encoding: *encoding,
+            },
+            StringLift { free, encoding } => Op::StringLift {
+                free: owned(free),
+                encoding: *encoding,
+            },
+            ListCanonLower { element, realloc } => Op::ListCanonLower {
+                element: **element,
+                realloc: owned(realloc),
+            },
+            ListLower { element, realloc } => Op::ListLower {
+                element: **element,
+                realloc: owned(realloc),
+            },
+            ListCanonLift { element, free, ty } => Op::ListCanonLift {
+                element: **element,
+                free: owned(free),
+                ty: *ty,
+            },
+            ListLift { element, free, ty } => Op::ListLift {
+                element: **element,
+                free: owned(free),
+                ty: *ty,
+            },
+            IterElem { element } => Op::IterElem(**element),
+            BufferLowerPtrLen { push, ty } => Op::BufferLowerPtrLen {
+                push: *push,
+                ty: **ty,
+            },
+            BufferLowerHandle { push, ty } => Op::BufferLowerHandle {
+                push: *push,
+                ty: **ty,
+            },
+            BufferLiftPtrLen { push, ty } => Op::BufferLiftPtrLen {
+                push: *push,
+                ty: **ty,
+            },
+            BufferLiftHandle { push, ty } => Op::BufferLiftHandle {
+                push: *push,
+                ty: **ty,
+            },
+            ArrayLower { element, len, ty } => Op::ArrayLower {
+                element: **element,
+                len: *len,
+                ty: *ty,
+            },
+            ArrayLift { element, len, ty } => Op::ArrayLift {
+                element: **element,
+                len: *len,
+                ty: *ty,
+            },
+            ArrayLowerMemory {
+                element,
+                len,
+                ty,
+                realloc,
+            } => Op::ArrayLowerMemory {
+                element: **element,
+                len: *len,
+                ty: *ty,
+                realloc: owned(realloc),
+            },
+            ArrayLiftMemory {
+                element,
+                len,
+                ty,
+                free,
+            } => Op::ArrayLiftMemory {
+                element: **element,
+                len: *len,
+                ty: *ty,
+                free: owned(free),
+            },
+            RecordLower { record, ty, .. } => Op::RecordLower {
+                ty: *ty,
+                record: record_id(iface, *ty, record),
+            },
+            RecordLift { record, ty, .. } => Op::RecordLift {
+                ty: *ty,
+                record: record_id(iface, *ty, record),
+            },
+            RecordCanonLower { ty, offset, .. } => Op::RecordCanonLower {
+                ty: *ty,
+                offset: *offset,
+            },
+            RecordCanonLift { ty, offset, .. } => Op::RecordCanonLift {
+                ty: *ty,
+                offset: *offset,
+            },
+            FlagsLower { ty, .. } => Op::FlagsLower(*ty),
+            FlagsLower64 { ty, .. } => Op::FlagsLower64(*ty),
+            FlagsLift { ty, .. } => Op::FlagsLift(*ty),
+            FlagsLift64 { ty, .. } => Op::FlagsLift64(*ty),
+            VariantLower { ty, results, .. } => Op::VariantLower {
+                ty: *ty,
+                results: results.to_vec(),
+            },
+            VariantLift { ty, .. } => Op::VariantLift(*ty),
+            CallWasm { sig, .. } => Op::CallWasm((*sig).clone()),
+            CallWasmAsyncImport {
+                params, results, ..
+            } => Op::CallWasmAsyncImport {
+                params: params.to_vec(),
+                results: results.to_vec(),
+            },
+            CallWasmAsyncExport {
+                params, results, ..
+            } => Op::CallWasmAsyncExport {
+                params: params.to_vec(),
+                results: results.to_vec(),
+            },
+            CallInterface { .. } => Op::CallInterface,
+            Return { amt, .. } => Op::Return(*amt),
+            ReturnAsyncExport { .. } => Op::ReturnAsyncExport,
+            ReturnAsyncImport { params, .. } => Op::ReturnAsyncImport(*params),
+            Witx { instr } => Op::Witx(match instr {
+                WitxInstruction::AddrOf => self::Witx::AddrOf,
+                WitxInstruction::I32FromPointer => self::Witx::I32FromPointer,
+                WitxInstruction::I32FromConstPointer => self::Witx::I32FromConstPointer,
+                WitxInstruction::PointerFromI32 { ty } => self::Witx::PointerFromI32(**ty),
+                WitxInstruction::ConstPointerFromI32 { ty } => {
+                    self::Witx::ConstPointerFromI32(**ty)
+                }
+                WitxInstruction::ReuseReturn => self::Witx::ReuseReturn,
+            }),
+            _ => unreachable!("{:?} should be a plain instruction", inst),
+        }
+    }
+
+    /// Calls `f` with the instruction that this was created from.
+    ///
+    /// If `canonical` is set then lists are emitted with the instructions for
+    /// canonical lists, without their block.
+    fn with_instruction<R>(
+        &self,
+        iface: &Interface,
+        func: &Function,
+        canonical: bool,
+        f: impl FnOnce(&Instruction<'_>) -> R,
+    ) -> R {
+        let record = |id: TypeId| match &iface.types[id].kind {
+            TypeDefKind::Record(r) => r,
+            _ => unreachable!(),
+        };
+        let variant = |id: TypeId| match &iface.types[id].kind {
+            TypeDefKind::Variant(v) => v,
+            _ => unreachable!(),
+        };
+        let name = |id: TypeId| iface.types[id].name.as_deref();
+        let flags_name = |id: TypeId| name(id).unwrap();
+        let module = &iface.name[..];
+        let name_ = &func.name[..];
+
+        let witx;
+        let inst = match self {
+            Op::Plain(inst) => return f(inst),
+            Op::Bitcasts(casts) => Instruction::Bitcasts { casts },
+            Op::ConstZero(tys) => Instruction::ConstZero { tys },
+            Op::StringLower { realloc, encoding } => Instruction::StringLower {
+                realloc: realloc.as_deref(),
+                encoding: *encoding,
+            },
+            Op::StringLift { free, encoding } => Instruction::StringLift {
+                free: free.as_deref(),
+                encoding: *encoding,
+            },
+            Op::ListCanonLower { element, realloc } => Instruction::ListCanonLower {
+                element,
+                realloc: realloc.as_deref(),
+            },
+            Op::ListLower { element, realloc } if canonical => Instruction::ListCanonLower {
+                element,
+                realloc: realloc.as_deref(),
+            },
+            Op::ListLower { element, realloc } => Instruction::ListLower {
+                element,
+                realloc: realloc.as_deref(),
+            },
+            Op::ListCanonLift { element, free, ty } => Instruction::ListCanonLift {
+                element,
+                free: free.as_deref(),
+                ty: *ty,
+            },
+            Op::ListLift { element, free, ty } if canonical => Instruction::ListCanonLift {
+                element,
+                free: free.as_deref(),
+                ty: *ty,
+            },
+            Op::ListLift { element, free, ty } => Instruction::ListLift {
+                element,
+                free: free.as_deref(),
+                ty: *ty,
+            },
+            Op::IterElem(element) => Instruction::IterElem { element },
+            Op::BufferLowerPtrLen { push, ty } => {
+                Instruction::BufferLowerPtrLen { push: *push, ty }
+            }
+            Op::BufferLowerHandle { push, ty } => {
+                Instruction::BufferLowerHandle { push: *push, ty }
+            }
+            Op::BufferLiftPtrLen { push, ty } => Instruction::BufferLiftPtrLen { push: *push, ty },
+            Op::BufferLiftHandle { push, ty } => Instruction::BufferLiftHandle { push: *push, ty },
+            Op::ArrayLower { element, len, ty } => Instruction::ArrayLower {
+                element,
+                len: *len,
+                ty: *ty,
+            },
+            Op::ArrayLift { element, len, ty } => Instruction::ArrayLift {
+                element,
+                len: *len,
+                ty: *ty,
+            },
+            Op::ArrayLowerMemory {
+                element,
+                len,
+                ty,
+                realloc,
+            } => Instruction::ArrayLowerMemory {
+                element,
+                len: *len,
+                ty: *ty,
+                realloc: realloc.as_deref(),
+            },
+            Op::ArrayLiftMemory {
+                element,
+                len,
+                ty,
+                free,
+            } => Instruction::ArrayLiftMemory {
+                element,
+                len: *len,
+                ty: *ty,
+                free: free.as_deref(),
+            },
+            Op::RecordLower { ty, record: id } => Instruction::RecordLower {
+                record: record(*id),
+                name: name(*id),
+                ty: *ty,
+            },
+            Op::RecordLift { ty, record: id } => Instruction::RecordLift {
+                record: record(*id),
+                name: name(*id),
+                ty: *ty,
+            },
+            Op::RecordCanonLower { ty, offset } => Instruction::RecordCanonLower {
+                record: record(*ty),
+                name: name(*ty),
+                ty: *ty,
+                offset: *offset,
+            },
+            Op::RecordCanonLift { ty, offset } => Instruction::RecordCanonLift {
+                record: record(*ty),
+                name: name(*ty),
+                ty: *ty,
+                offset: *offset,
+            },
+            Op::FlagsLower(ty) => Instruction::FlagsLower {
+                record: record(*ty),
+                name: flags_name(*ty),
+                ty: *ty,
+            },
+            Op::FlagsLower64(ty) => Instruction::FlagsLower64 {
+                record: record(*ty),
+                name: flags_name(*ty),
+                ty: *ty,
+            },
+            Op::FlagsLift(ty) => Instruction::FlagsLift {
+                record: record(*ty),
+                name: flags_name(*ty),
+                ty: *ty,
+            },
+            Op::FlagsLift64(ty) => Instruction::FlagsLift64 {
+                record: record(*ty),
+                name: flags_name(*ty),
+                ty: *ty,
+            },
+            Op::VariantLower { ty, results } => Instruction::VariantLower {
+                variant: variant(*ty),
+                name: name(*ty),
+                ty: *ty,
+                results,
+            },
+            Op::VariantLift(ty) => Instruction::VariantLift {
+                variant: variant(*ty),
+                name: name(*ty),
+                ty: *ty,
+            },
+            Op::CallWasm(sig) => Instruction::CallWasm {
+                module,
+                name: name_,
+                sig,
+            },
+            Op::CallWasmAsyncImport { params, results } => Instruction::CallWasmAsyncImport {
+                module,
+                name: name_,
+                params,
+                results,
+            },
+            Op::CallWasmAsyncExport { params, results } => Instruction::CallWasmAsyncExport {
+                module,
+                name: name_,
+                params,
+                results,
+            },
+            Op::CallInterface => Instruction::CallInterface { module, func },
+            Op::Return(amt) => Instruction::Return { amt: *amt, func },
+            Op::ReturnAsyncExport => Instruction::ReturnAsyncExport { func },
+            Op::ReturnAsyncImport(params) => Instruction::ReturnAsyncImport {
+                func,
+                params: *params,
+            },
+            Op::Witx(instr) => {
+                witx = match instr {
+                    Witx::AddrOf => WitxInstruction::AddrOf,
+                    Witx::I32FromPointer => WitxInstruction::I32FromPointer,
+                    Witx::I32FromConstPointer => WitxInstruction::I32FromConstPointer,
+                    Witx::PointerFromI32(ty) => WitxInstruction::PointerFromI32 { ty },
+                    Witx::ConstPointerFromI32(ty) => WitxInstruction::ConstPointerFromI32 { ty },
+                    Witx::ReuseReturn => WitxInstruction::ReuseReturn,
+                };
+                Instruction::Witx { instr: &witx }
+            }
+            Op::AllocateTypedSpace(_) | Op::ReturnPointerArea(_) => unreachable!(),
+        };
+        f(&inst)
+    }
+}
+
+/// Returns the type which defines `record`, which is `ty` in all cases except
+/// for tuples returned from `Abi::Preview1` functions.
+fn record_id(iface: &Interface, ty: TypeId, record: &Record) -> TypeId {
+    let defines = |id: TypeId| match &iface.types[id].kind {
+        TypeDefKind::Record(r) => std::ptr::eq(r, record),
+        _ => false,
+    };
+    if defines(ty) {
+        return ty;
+    }
+    iface
+        .types
+        .iter()
+        .map(|(id, _)| id)
+        .find(|id| defines(*id))
+        .expect("record isn't defined in the interface")
+}
+
+/// Removes bitcasts which are no-ops, rewriting uses of their results to use
+/// the original value instead.
+///
+/// `casts` maps the result of each remaining bitcast to its cast and operand,
+/// and `renamed` maps the results of removed bitcasts to their replacement.
+fn remove_bitcasts(
+    block: &mut Block,
+    casts: &mut HashMap<usize, (Bitcast, usize)>,
+    renamed: &mut HashMap<usize, usize>,
+) {
+    for node in block.nodes.iter_mut() {
+        for operand in node.operands.iter_mut() {
+            if let Some(value) = renamed.get(operand) {
+                *operand = *value;
+            }
+        }
+        for block in node.blocks.iter_mut() {
+            remove_bitcasts(block, casts, renamed);
+        }
+        if let Op::Bitcasts(_) = node.op {
+            retain_lanes(node, |cast, operand, result| {
+                let replacement = match cast {
+                    Bitcast::None => Some(operand),
+                    _ => match casts.get(&operand) {
+                        Some((prev, value)) if undo(*prev) == Some(cast) => Some(*value),
+                        _ => None,
+                    },
+                };
+                match replacement {
+                    Some(value) => {
+                        renamed.insert(result, value);
+                        false
+                    }
+                    None => {
+                        casts.insert(result, (cast, operand));
+                        true
+                    }
+                }
+            });
+        }
+    }
+    for result in block.results.iter_mut() {
+        if let Some(value) = renamed.get(result) {
+            *result = *value;
+        }
+    }
+    block.nodes.retain(|node| match &node.op {
+        Op::Bitcasts(casts) => !casts.is_empty(),
+        _ => true,
+    });
+}
+
+/// Keeps only the casts of a `Bitcasts` node for which `keep` returns `true`,
+/// along with their operands and results.
+fn retain_lanes(node: &mut Node, mut keep: impl FnMut(Bitcast, usize, usize) -> bool) {
+    let casts = match &mut node.op {
+        Op::Bitcasts(casts) => casts,
+        _ => unreachable!(),
+    };
+    let mut kept = (Vec::new(), Vec::new(), Vec::new());
+    for ((cast, operand), result) in casts.iter().zip(&node.operands).zip(&node.results) {
+        if keep(*cast, *operand, *result) {
+            kept.0.push(*cast);
+            kept.1.push(*operand);
+            kept.2.push(*result);
+        }
+    }
+    *casts = kept.0;
+    node.operands = kept.1;
+    node.results = kept.2;
+}
+
+/// Returns the bitcast which undoes `cast`, if `cast` doesn't lose any
+/// information.
+fn undo(cast: Bitcast) -> Option<Bitcast> {
+    match cast {
+        Bitcast::F32ToF64 => Some(Bitcast::F64ToF32),
+        Bitcast::F32ToI32 => Some(Bitcast::I32ToF32),
+        Bitcast::F64ToI64 => Some(Bitcast::I64ToF64),
+        Bitcast::I32ToI64 => Some(Bitcast::I64ToI32),
+        Bitcast::F32ToI64 => Some(Bitcast::I64ToF32),
+        _ => None,
+    }
+}
+
+fn count_uses(block: &Block, uses: &mut HashMap<usize, usize>) {
+    for node in block.nodes.iter() {
+        for operand in node.operands.iter() {
+            *uses.entry(*operand).or_insert(0) += 1;
+        }
+        for block in node.blocks.iter() {
+            count_uses(block, uses);
+        }
+    }
+    for result in block.results.iter() {
+        *uses.entry(*result).or_insert(0) += 1;
+    }
+}
+
+/// Removes the bitcasts whose results are no longer used after
+/// `remove_bitcasts` rewrote their uses.
+fn remove_dead_bitcasts(block: &mut Block, uses: &HashMap<usize, usize>) {
+    for node in block.nodes.iter_mut() {
+        for block in node.blocks.iter_mut() {
+            remove_dead_bitcasts(block, uses);
+        }
+        if let Op::Bitcasts(_) = node.op {
+            retain_lanes(node, |_, _, result| uses.contains_key(&result));
+        }
+    }
+    block.nodes.retain(|node| match &node.op {
+        Op::Bitcasts(casts) => !casts.is_empty(),
+        _ => true,
+    });
+}
+
+/// The instructions used to move a scalar whose bit patterns are all valid
+/// in and out of memory, with an offset of 0.
+struct Scalar {
+    load: Instruction<'static>,
+    lift: Instruction<'static>,
+    lower: Instruction<'static>,
+    store: Instruction<'static>,
+}
+
+fn scalar(iface: &Interface, ty: &Type) -> Option<Scalar> {
+    use Instruction::*;
+
+    let offset = 0;
+    let (load, lift, lower, store) = match ty {
+        Type::U8 => (
+            I32Load8U { offset },
+            U8FromI32,
+            I32FromU8,
+            I32Store8 { offset },
+        ),
+        Type::CChar => (
+            I32Load8U { offset },
+            Char8FromI32,
+            I32FromChar8,
+            I32Store8 { offset },
+        ),
+        Type::S8 => (
+            I32Load8S { offset },
+            S8FromI32,
+            I32FromS8,
+            I32Store8 { offset },
+        ),
+        Type::U16 => (
+            I32Load16U { offset },
+            U16FromI32,
+            I32FromU16,
+            I32Store16 { offset },
+        ),
+        Type::S16 => (
+            I32Load16S { offset },
+            S16FromI32,
+            I32FromS16,
+            I32Store16 { offset },
+        ),
+        Type::U32 => (
+            I32Load { offset },
+            U32FromI32,
+            I32FromU32,
+            I32Store { offset },
+        ),
+        Type::S32 => (
+            I32Load { offset },
+            S32FromI32,
+            I32FromS32,
+            I32Store { offset },
+        ),
+        Type::Usize => (
+            I32Load { offset },
+            UsizeFromI32,
+            I32FromUsize,
+            I32Store { offset },
+        ),
+        Type::U64 => (
+            I64Load { offset },
+            U64FromI64,
+            I64FromU64,
+            I64Store { offset },
+        ),
+        Type::S64 => (
+            I64Load { offset },
+            S64FromI64,
+            I64FromS64,
+            I64Store { offset },
+        ),
+        Type::F32 => (
+            F32Load { offset },
+            If32FromF32,
+            F32FromIf32,
+            F32Store { offset },
+        ),
+        Type::F64 => (
+            F64Load { offset },
+            If64FromF64,
+            F64FromIf64,
+            F64Store { offset },
+        ),
+        Type::Id(id) => match &iface.types[*id].kind {
+            TypeDefKind::Type(t) => return scalar(iface, t),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(Scalar {
+        load,
+        lift,
+        lower,
+        store,
+    })
+}
+
+/// Returns the record type that `ty` refers to, if any.
+fn record_type(iface: &Interface, ty: &Type) -> Option<TypeId> {
+    match ty {
+        Type::Id(id) => match &iface.types[*id].kind {
+            TypeDefKind::Type(t) => record_type(iface, t),
+            TypeDefKind::Record(r) if !r.is_flags() => Some(*id),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Returns the offset of a load or store instruction of `node` if it's the
+/// same kind of instruction as `expected`.
+fn memory_offset(node: &Node, expected: &Instruction<'_>) -> Option<i32> {
+    use Instruction::*;
+
+    let inst = match &node.op {
+        Op::Plain(inst) if mem::discriminant(inst) == mem::discriminant(expected) => inst,
+        _ => return None,
+    };
+    match inst {
+        I32Load { offset }
+        | I32Load8U { offset }
+        | I32Load8S { offset }
+        | I32Load16U { offset }
+        | I32Load16S { offset }
+        | I64Load { offset }
+        | F32Load { offset }
+        | F64Load { offset }
+        | I32Store { offset }
+        | I32Store8 { offset }
+        | I32Store16 { offset }
+        | I64Store { offset }
+        | F32Store { offset }
+        | F64Store { offset } => Some(*offset),
+        _ => None,
+    }
+}
+
+fn is(node: &Node, expected: &Instruction<'_>) -> bool {
+    match &node.op {
+        Op::Plain(inst) => mem::discriminant(inst) == mem::discriminant(expected),
+        _ => false,
+    }
+}
+
+/// Fuses the loads or stores of every field of a record into one instruction
+/// where possible.
+fn fuse_records(iface: &Interface, sizes: &SizeAlign, block: &mut Block) {
+    for node in block.nodes.iter_mut() {
+        for block in node.blocks.iter_mut() {
+            fuse_records(iface, sizes, block);
+        }
+    }
+
+    // Records are lifted after their fields, so nested records are fused
+    // first by going forwards...
+    let mut i = 0;
+    while i < block.nodes.len() {
+        if let Some((start, ty, offset)) = fusable_lift(iface, sizes, &block.nodes, i) {
+            let nodes = block.nodes.drain(start..=i).collect::<Vec<_>>();
+            let last = nodes.last().unwrap();
+            let node = Node {
+                op: Op::RecordCanonLift { ty, offset },
+                operands: vec![nodes[0].operands[0]],
+                results: last.results.clone(),
+                blocks: vec![Block {
+                    results: last.results.clone(),
+                    nodes,
+                }],
+            };
+            block.nodes.insert(start, node);
+            i = start;
+        }
+        i += 1;
+    }
+
+    // ... but they're lowered before their fields, so go backwards to fuse
+    // nested records first.
+    for i in (0..block.nodes.len()).rev() {
+        if let Some((end, ty, offset)) = fusable_lower(iface, sizes, &block.nodes, i) {
+            let nodes = block.nodes.drain(i..end).collect::<Vec<_>>();
+            let base = match nodes.last().unwrap().operands.as_slice() {
+                [_, base] => *base,
+                _ => unreachable!(),
+            };
+            let node = Node {
+                op: Op::RecordCanonLower { ty, offset },
+                operands: vec![nodes[0].operands[0], base],
+                results: Vec::new(),
+                blocks: vec![Block {
+                    nodes,
+                    results: Vec::new(),
+                }],
+            };
+            block.nodes.insert(i, node);
+        }
+    }
+}
+
+/// If `nodes[end]` lifts a record whose fields are all loaded from the same
+/// base pointer by the nodes before it, returns the index of the first of
+/// those nodes, the record's type and its offset from the base pointer.
+fn fusable_lift(
+    iface: &Interface,
+    sizes: &SizeAlign,
+    nodes: &[Node],
+    end: usize,
+) -> Option<(usize, TypeId, i32)> {
+    let node = &nodes[end];
+    let (ty, record) = match &node.op {
+        Op::RecordLift { ty, record } if ty == record => match &iface.types[*ty].kind {
+            TypeDefKind::Record(r) if !r.fields.is_empty() => (*ty, r),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let offsets = sizes.field_offsets(record);
+    let mut start = end;
+    let mut location = None;
+    for ((field, field_offset), value) in
+        record.fields.iter().zip(offsets).zip(&node.operands).rev()
+    {
+        let (base, offset) = if let Some(scalar) = scalar(iface, &field.ty) {
+            if start < 2 {
+                return None;
+            }
+            let (load, lift) = (&nodes[start - 2], &nodes[start - 1]);
+            if !is(lift, &scalar.lift) || lift.results != [*value] || lift.operands != load.results
+            {
+                return None;
+            }
+            let offset = memory_offset(load, &scalar.load)?;
+            start -= 2;
+            (load.operands[0], offset)
+        } else {
+            let nested = record_type(iface, &field.ty)?;
+            let lift = &nodes[start.checked_sub(1)?];
+            start -= 1;
+            match lift.op {
+                Op::RecordCanonLift { ty, offset } if ty == nested && lift.results == [*value] => {
+                    (lift.operands[0], offset)
+                }
+                _ => return None,
+            }
+        };
+        let here = (base, offset - field_offset as i32);
+        if *location.get_or_insert(here) != here {
+            return None;
+        }
+    }
+    let (_, offset) = location.unwrap();
+    Some((start, ty, offset))
+}
+
+/// If `nodes[start]` lowers a record whose fields are then all stored to the
+/// same base pointer, returns the index after the last of those stores, the
+/// record's type and its offset from the base pointer.
+fn fusable_lower(
+    iface: &Interface,
+    sizes: &SizeAlign,
+    nodes: &[Node],
+    start: usize,
+) -> Option<(usize, TypeId, i32)> {
+    let node = &nodes[start];
+    let (ty, record) = match &node.op {
+        Op::RecordLower { ty, record } if ty == record => match &iface.types[*ty].kind {
+            TypeDefKind::Record(r) if !r.fields.is_empty() => (*ty, r),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let offsets = sizes.field_offsets(record);
+    let mut end = start + 1;
+    let mut location = None;
+    for ((field, field_offset), value) in record.fields.iter().zip(offsets).zip(&node.results) {
+        let (base, offset) = if let Some(scalar) = scalar(iface, &field.ty) {
+            let (lower, store) = (nodes.get(end)?, nodes.get(end + 1)?);
+            if !is(lower, &scalar.lower)
+                || lower.operands != [*value]
+                || store.operands.first() != lower.results.first()
+            {
+                return None;
+            }
+            let offset = memory_offset(store, &scalar.store)?;
+            end += 2;
+            (store.operands[1], offset)
+        } else {
+            let nested = record_type(iface, &field.ty)?;
+            let store = nodes.get(end)?;
+            end += 1;
+            match store.op {
+                Op::RecordCanonLower { ty, offset }
+                    if ty == nested && store.operands[0] == *value =>
+                {
+                    (store.operands[1], offset)
+                }
+                _ => return None,
+            }
+        };
+        let here = (base, offset - field_offset as i32);
+        if *location.get_or_insert(here) != here {
+            return None;
+        }
+    }
+    let (_, offset) = location.unwrap();
+    Some((end, ty, offset))
+}
+
+/// Hoists instructions which don't depend on the element out of the block
+/// of each list and array in memory.
+fn hoist(iface: &Interface, block: &mut Block) {
+    let mut i = 0;
+    while i < block.nodes.len() {
+        let node = &mut block.nodes[i];
+        for block in node.blocks.iter_mut() {
+            hoist(iface, block);
+        }
+        let hoisted = match &node.op {
+            // Lists of elements with only valid bit patterns may be emitted
+            // without their block, so leave those alone.
+            Op::ListLower { element, .. } | Op::ListLift { element, .. }
+                if !iface.all_bits_valid(element) =>
+            {
+                invariants(&mut node.blocks[0])
+            }
+            Op::ArrayLowerMemory { .. } | Op::ArrayLiftMemory { .. } => {
+                invariants(&mut node.blocks[0])
+            }
+            _ => Vec::new(),
+        };
+        let n = hoisted.len();
+        block.nodes.splice(i..i, hoisted);
+        i += n + 1;
+    }
+}
+
+/// Removes and returns the nodes of `block` which don't depend on anything
+/// defined within the block and are free of side effects.
+fn invariants(block: &mut Block) -> Vec<Node> {
+    let mut defined = HashSet::new();
+    let mut hoisted = Vec::new();
+    for node in mem::take(&mut block.nodes) {
+        let pure = matches!(
+            &node.op,
+            Op::Plain(Instruction::I32Const { .. }) | Op::ConstZero(_) | Op::Bitcasts(_)
+        );
+        if pure && node.operands.iter().all(|v| !defined.contains(v)) {
+            hoisted.push(node);
+        } else {
+            define(&node, &mut defined);
+            block.nodes.push(node);
+        }
+    }
+    hoisted
+}
+
+fn define(node: &Node, defined: &mut HashSet<usize>) {
+    defined.extend(node.results.iter().cloned());
+    for block in node.blocks.iter() {
+        for node in block.nodes.iter() {
+            define(node, defined);
+        }
+    }
+}
@@ -1,14 +1,16 @@
 use crate::abi::Abi;
 use anyhow::Result;
-use lex::{Span, Token, Tokenizer};
+use lex::{Token, Tokenizer};
 use std::borrow::Cow;
 use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::fmt;
 
-mod lex;
+pub(crate) mod lex;
 mod resolve;
 
+pub use lex::Span;
+
 pub struct Ast<'a> {
     pub items: Vec<Item<'a>>,
 }
@@ -18,6 +20,7 @@ pub enum Item<'a> {
     Resource(Resource<'a>),
     TypeDef(TypeDef<'a>),
     Value(Value<'a>),
+    Const(Const<'a>),
     Interface(Interface<'a>),
 }
 
@@ -45,33 +48,50 @@ impl<'a> From<String> for Id<'a> {
 }
 
 pub struct Use<'a> {
+    pub docs: Docs<'a>,
     pub from: Vec<Id<'a>>,
-    names: Option<Vec<UseName<'a>>>,
+    pub names: Option<Vec<UseName<'a>>>,
 }
 
-struct UseName<'a> {
-    name: Id<'a>,
-    as_: Option<Id<'a>>,
+pub struct UseName<'a> {
+    pub name: Id<'a>,
+    pub as_: Option<Id<'a>>,
 }
 
 pub struct Resource<'a> {
-    docs: Docs<'a>,
-    name: Id<'a>,
-    values: Vec<(bool, Value<'a>)>,
+    pub docs: Docs<'a>,
+    pub attrs: Vec<Attribute<'a>>,
+    pub name: Id<'a>,
+    pub values: Vec<(bool, Value<'a>)>,
 }
 
 #[derive(Default)]
-struct Docs<'a> {
-    docs: Vec<Cow<'a, str>>,
+pub struct Docs<'a> {
+    pub docs: Vec<Cow<'a, str>>,
+}
+
+/// An `@name` or `@name(arg, key = arg, ...)` annotation on an item.
+pub struct Attribute<'a> {
+    pub name: Id<'a>,
+    pub args: Vec<AttributeArg<'a>>,
+}
+
+pub struct AttributeArg<'a> {
+    pub name: Option<Id<'a>>,
+    pub value: Id<'a>,
 }
 
 pub struct TypeDef<'a> {
-    docs: Docs<'a>,
-    name: Id<'a>,
-    ty: Type<'a>,
+    pub docs: Docs<'a>,
+    pub attrs: Vec<Attribute<'a>>,
+    pub name: Id<'a>,
+    /// The type parameters of a generic type such as `page<T>`, empty for
+    /// ordinary types.
+    pub params: Vec<Id<'a>>,
+    pub ty: Type<'a>,
 }
 
-enum Type<'a> {
+pub enum Type<'a> {
     U8,
     U16,
     U32,
@@ -87,9 +107,15 @@ enum Type<'a> {
     Usize,
     #[allow(dead_code)]
     CChar,
+    Bool,
+    String,
     Handle(Id<'a>),
     Name(Id<'a>),
+    /// An instantiation of a generic type, such as `page<user>`.
+    Generic(Id<'a>, Vec<Type<'a>>),
     List(Box<Type<'a>>),
+    /// A fixed-size array, such as `array<u8, 32>`.
+    Array(Box<Type<'a>>, u32),
     Record(Record<'a>),
     Variant(Variant<'a>),
     PushBuffer(Box<Type<'a>>),
@@ -100,37 +126,44 @@ enum Type<'a> {
     ConstPointer(Box<Type<'a>>),
 }
 
-struct Record<'a> {
-    tuple_hint: bool,
-    flags_repr: Option<Box<Type<'a>>>,
-    fields: Vec<Field<'a>>,
+pub struct Record<'a> {
+    pub tuple_hint: bool,
+    pub flags_repr: Option<Box<Type<'a>>>,
+    pub fields: Vec<Field<'a>>,
 }
 
-struct Field<'a> {
-    docs: Docs<'a>,
-    name: Id<'a>,
-    ty: Type<'a>,
+pub struct Field<'a> {
+    pub docs: Docs<'a>,
+    pub attrs: Vec<Attribute<'a>>,
+    pub name: Id<'a>,
+    pub ty: Type<'a>,
+    /// The explicit bit position of a flag, such as `2` for `read = 0x4`.
+    pub bit: Option<(Span, u32)>,
 }
 
-struct Variant<'a> {
-    tag: Option<Box<Type<'a>>>,
-    span: Span,
-    cases: Vec<Case<'a>>,
+pub struct Variant<'a> {
+    pub tag: Option<Box<Type<'a>>>,
+    pub span: Span,
+    pub cases: Vec<Case<'a>>,
 }
 
-struct Case<'a> {
-    docs: Docs<'a>,
-    name: Id<'a>,
-    ty: Option<Type<'a>>,
+pub struct Case<'a> {
+    pub docs: Docs<'a>,
+    pub attrs: Vec<Attribute<'a>>,
+    pub name: Id<'a>,
+    pub ty: Option<Type<'a>>,
+    /// The explicit discriminant of a case, such as `6` for `again = 6`.
+    pub discriminant: Option<(Span, u32)>,
 }
 
 pub struct Value<'a> {
-    docs: Docs<'a>,
-    name: Id<'a>,
-    kind: ValueKind<'a>,
+    pub docs: Docs<'a>,
+    pub attrs: Vec<Attribute<'a>>,
+    pub name: Id<'a>,
+    pub kind: ValueKind<'a>,
 }
 
-enum ValueKind<'a> {
+pub enum ValueKind<'a> {
     Function {
         is_async: bool,
         abi: crate::abi::Abi,
@@ -140,11 +173,28 @@ enum ValueKind<'a> {
     Global(Type<'a>),
 }
 
+pub struct Const<'a> {
+    pub docs: Docs<'a>,
+    pub attrs: Vec<Attribute<'a>>,
+    pub name: Id<'a>,
+    pub ty: Type<'a>,
+    pub value: ConstValue<'a>,
+}
+
+pub enum ConstValue<'a> {
+    /// A bare value such as `1`, `-2.5`, `true` or the name of an enum case.
+    Bare(Id<'a>),
+    /// A `"string"`.
+    String(Id<'a>),
+    /// A set of flags such as `{ read, write }`.
+    Flags(Vec<Id<'a>>),
+}
+
 #[allow(dead_code)] // TODO
 pub struct Interface<'a> {
-    docs: Docs<'a>,
-    name: Id<'a>,
-    items: Vec<Item<'a>>,
+    pub docs: Docs<'a>,
+    pub name: Id<'a>,
+    pub items: Vec<Item<'a>>,
 }
 
 impl<'a> Ast<'a> {
@@ -155,21 +205,81 @@ impl<'a> Ast<'a> {
             return Ast::parse_old_witx(input);
         }
         let mut items = Vec::new();
-        while lexer.clone().next()?.is_some() {
-            let docs = parse_docs(&mut lexer)?;
-            items.push(Item::parse(&mut lexer, docs)?);
+        while let Some(item) = Ast::parse_item(&mut lexer)? {
+            items.push(item);
         }
         Ok(Ast { items })
     }
 
+    /// Same as [`Ast::parse`], except that parsing doesn't stop at the first
+    /// error.
+    ///
+    /// When an item fails to parse the error is recorded and tokens are
+    /// skipped until something that looks like the start of the next
+    /// top-level item. All items which parsed successfully are returned
+    /// alongside every error that was encountered.
+    pub fn parse_recover(input: &'a str) -> (Ast<'a>, Vec<anyhow::Error>) {
+        let mut lexer = Tokenizer::new(input);
+        #[cfg(feature = "old-witx-compat")]
+        if lexer.eat(Token::Semicolon).unwrap_or(false)
+            || lexer.eat(Token::LeftParen).unwrap_or(false)
+        {
+            return match Ast::parse_old_witx(input) {
+                Ok(ast) => (ast, Vec::new()),
+                Err(e) => (Ast { items: Vec::new() }, vec![e]),
+            };
+        }
+        let mut items = Vec::new();
+        let mut errors = Vec::new();
+        loop {
+            let start = lexer.clone();
+            match Ast::parse_item(&mut lexer) {
+                Ok(Some(item)) => items.push(item),
+                Ok(None) => break,
+                Err(e) => {
+                    errors.push(e);
+                    lexer = start;
+                    skip_to_next_item(&mut lexer);
+                }
+            }
+        }
+        (Ast { items }, errors)
+    }
+
+    fn parse_item(lexer: &mut Tokenizer<'a>) -> Result<Option<Item<'a>>> {
+        if lexer.clone().next()?.is_none() {
+            return Ok(None);
+        }
+        let docs = parse_docs(lexer)?;
+        Ok(Some(Item::parse(lexer, docs)?))
+    }
+
     pub fn resolve(
         &self,
         name: &str,
         map: &HashMap<String, crate::Interface>,
     ) -> Result<crate::Interface> {
+        let (instance, errors) = self.resolve_recover(name, map);
+        match errors.into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(instance),
+        }
+    }
+
+    /// Same as [`Ast::resolve`], except that resolution continues past
+    /// errors.
+    ///
+    /// The returned interface contains everything that could be resolved.
+    /// Types whose definition failed to resolve are still present but have a
+    /// placeholder definition, and functions which failed to resolve are
+    /// omitted entirely.
+    pub fn resolve_recover(
+        &self,
+        name: &str,
+        map: &HashMap<String, crate::Interface>,
+    ) -> (crate::Interface, Vec<anyhow::Error>) {
         let mut resolver = resolve::Resolver::default();
-        let instance = resolver.resolve(name, &self.items, map)?;
-        Ok(instance)
+        resolver.resolve(name, &self.items, map)
     }
 
     #[cfg(feature = "old-witx-compat")]
@@ -181,6 +291,7 @@ impl<'a> Ast<'a> {
         for d in doc.decls {
             let item = match d.item {
                 old::TopLevelSyntax::Use(u) => Item::Use(Use {
+                    docs: Docs::default(),
                     from: vec![id(&u.from)],
                     names: match u.names {
                         old::UsedNames::All(_) => None,
@@ -198,11 +309,14 @@ impl<'a> Ast<'a> {
                 old::TopLevelSyntax::Decl(u) => match u {
                     old::DeclSyntax::Typename(t) => Item::TypeDef(TypeDef {
                         docs: docs(&d.comments),
+                        attrs: Vec::new(),
                         name: id(&t.ident),
+                        params: Vec::new(),
                         ty: ty(&t.def),
                     }),
                     old::DeclSyntax::Resource(r) => Item::Resource(Resource {
                         docs: docs(&d.comments),
+                        attrs: Vec::new(),
                         name: id(&r.ident),
                         values: Vec::new(),
                     }),
@@ -215,6 +329,7 @@ impl<'a> Ast<'a> {
         for f in doc.functions {
             let item = Item::Value(Value {
                 docs: docs(&f.comments),
+                attrs: Vec::new(),
                 name: Id {
                     name: f.item.export.to_string().into(),
                     span: span(f.item.export_loc),
@@ -254,8 +369,10 @@ impl<'a> Ast<'a> {
                         .iter()
                         .map(|f| Field {
                             docs: docs(&f.comments),
+                            attrs: Vec::new(),
                             name: id(&f.item.name),
                             ty: ty(&f.item.type_),
+                            bit: None,
                         })
                         .collect(),
                 }),
@@ -267,8 +384,10 @@ impl<'a> Ast<'a> {
                         .iter()
                         .map(|f| Field {
                             docs: docs(&f.comments),
+                            attrs: Vec::new(),
                             name: id(&f.item),
-                            ty: Type::bool(),
+                            ty: Type::Bool,
+                            bit: None,
                         })
                         .collect(),
                 }),
@@ -281,8 +400,10 @@ impl<'a> Ast<'a> {
                         .enumerate()
                         .map(|(i, t)| Field {
                             docs: Docs::default(),
+                            attrs: Vec::new(),
                             name: Id::from(i.to_string()),
                             ty: ty(t),
+                            bit: None,
                         })
                         .collect(),
                 }),
@@ -295,8 +416,10 @@ impl<'a> Ast<'a> {
                         .iter()
                         .map(|c| Case {
                             docs: docs(&c.comments),
+                            attrs: Vec::new(),
                             name: id(&c.item.name),
                             ty: c.item.ty.as_ref().map(ty),
+                            discriminant: None,
                         })
                         .collect(),
                 }),
@@ -308,8 +431,10 @@ impl<'a> Ast<'a> {
                         .iter()
                         .map(|c| Case {
                             docs: docs(&c.comments),
+                            attrs: Vec::new(),
                             name: id(&c.item),
                             ty: None,
+                            discriminant: None,
                         })
                         .collect(),
                 }),
@@ -319,13 +444,17 @@ impl<'a> Ast<'a> {
                     cases: vec![
                         Case {
                             docs: Docs::default(),
+                            attrs: Vec::new(),
                             name: "ok".into(),
                             ty: e.ok.as_ref().map(|t| ty(t)),
+                            discriminant: None,
                         },
                         Case {
                             docs: Docs::default(),
+                            attrs: Vec::new(),
                             name: "err".into(),
                             ty: e.err.as_ref().map(|t| ty(t)),
+                            discriminant: None,
                         },
                     ],
                 }),
@@ -335,13 +464,17 @@ impl<'a> Ast<'a> {
                     cases: vec![
                         Case {
                             docs: Docs::default(),
+                            attrs: Vec::new(),
                             name: "none".into(),
                             ty: None,
+                            discriminant: None,
                         },
                         Case {
                             docs: Docs::default(),
+                            attrs: Vec::new(),
                             name: "some".into(),
                             ty: Some(ty(&e.ty)),
+                            discriminant: None,
                         },
                     ],
                 }),
@@ -354,8 +487,10 @@ impl<'a> Ast<'a> {
                         .enumerate()
                         .map(|(i, c)| Case {
                             docs: docs(&c.comments),
+                            attrs: Vec::new(),
                             name: i.to_string().into(),
                             ty: Some(ty(&c.item)),
+                            discriminant: None,
                         })
                         .collect(),
                 }),
@@ -373,8 +508,8 @@ impl<'a> Ast<'a> {
                 }
                 old::TypedefSyntax::Builtin(e) => builtin(e),
                 old::TypedefSyntax::Ident(e) => Type::Name(id(e)),
-                old::TypedefSyntax::String => Type::List(Box::new(Type::Char)),
-                old::TypedefSyntax::Bool => Type::bool(),
+                old::TypedefSyntax::String => Type::String,
+                old::TypedefSyntax::Bool => Type::Bool,
             }
         }
 
@@ -445,28 +580,99 @@ impl<'a> Ast<'a> {
 
 impl<'a> Item<'a> {
     fn parse(tokens: &mut Tokenizer<'a>, docs: Docs<'a>) -> Result<Item<'a>> {
+        let attrs = parse_attrs(tokens)?;
         match tokens.clone().next()? {
+            Some((span, Token::Use)) | Some((span, Token::Interface)) if !attrs.is_empty() => {
+                Err(Error {
+                    span,
+                    msg: "attributes are not allowed here".to_string(),
+                }
+                .into())
+            }
             Some((_span, Token::Use)) => Use::parse(tokens, docs).map(Item::Use),
-            Some((_span, Token::Type)) => TypeDef::parse(tokens, docs).map(Item::TypeDef),
-            Some((_span, Token::Flags)) => TypeDef::parse_flags(tokens, docs).map(Item::TypeDef),
-            Some((_span, Token::Enum)) => TypeDef::parse_enum(tokens, docs).map(Item::TypeDef),
+            Some((_span, Token::Type)) => TypeDef::parse(tokens, docs, attrs).map(Item::TypeDef),
+            Some((_span, Token::Flags)) => {
+                TypeDef::parse_flags(tokens, docs, attrs).map(Item::TypeDef)
+            }
+            Some((_span, Token::Enum)) => {
+                TypeDef::parse_enum(tokens, docs, attrs).map(Item::TypeDef)
+            }
             Some((_span, Token::Variant)) => {
-                TypeDef::parse_variant(tokens, docs).map(Item::TypeDef)
+                TypeDef::parse_variant(tokens, docs, attrs).map(Item::TypeDef)
+            }
+            Some((_span, Token::Record)) => {
+                TypeDef::parse_record(tokens, docs, attrs).map(Item::TypeDef)
             }
-            Some((_span, Token::Record)) => TypeDef::parse_record(tokens, docs).map(Item::TypeDef),
-            Some((_span, Token::Union)) => TypeDef::parse_union(tokens, docs).map(Item::TypeDef),
-            Some((_span, Token::Resource)) => Resource::parse(tokens, docs).map(Item::Resource),
+            Some((_span, Token::Union)) => {
+                TypeDef::parse_union(tokens, docs, attrs).map(Item::TypeDef)
+            }
+            Some((_span, Token::Resource)) => {
+                Resource::parse(tokens, docs, attrs).map(Item::Resource)
+            }
+            Some((_span, Token::Const)) => Const::parse(tokens, docs, attrs).map(Item::Const),
             Some((_span, Token::Interface)) => Interface::parse(tokens, docs).map(Item::Interface),
             Some((_span, Token::Id)) | Some((_span, Token::StrLit)) => {
-                Value::parse(tokens, docs).map(Item::Value)
+                Value::parse(tokens, docs, attrs).map(Item::Value)
+            }
+            other => {
+                Err(err_expected(tokens, "`type`, `resource`, `const`, or `fn`", other).into())
+            }
+        }
+    }
+}
+
+/// Skips tokens after a failed item until the start of something which looks
+/// like the next top-level item.
+fn skip_to_next_item(tokens: &mut Tokenizer<'_>) {
+    // Always skip the first token since that's where the failed item started.
+    let _ = tokens.next();
+    let mut depth = 0u32;
+    loop {
+        let token = match tokens.clone().next() {
+            Ok(Some((_span, token))) => token,
+            Ok(None) => break,
+            // Lexer errors always consume at least one character, so skip
+            // over whatever's broken and keep going.
+            Err(_) => {
+                let _ = tokens.next();
+                continue;
+            }
+        };
+        if depth == 0 {
+            match token {
+                Token::Use
+                | Token::Type
+                | Token::Flags
+                | Token::Enum
+                | Token::Variant
+                | Token::Record
+                | Token::Union
+                | Token::Resource
+                | Token::Interface
+                | Token::Const
+                | Token::At => break,
+                // `foo: ...` starts a value
+                Token::Id | Token::StrLit => {
+                    let mut clone = tokens.clone();
+                    let _ = clone.next();
+                    if let Ok(true) = clone.eat(Token::Colon) {
+                        break;
+                    }
+                }
+                _ => {}
             }
-            other => Err(err_expected(tokens, "`type`, `resource`, or `fn`", other).into()),
         }
+        match token {
+            Token::LeftBrace | Token::LeftParen => depth += 1,
+            Token::RightBrace | Token::RightParen => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        let _ = tokens.next();
     }
 }
 
 impl<'a> Use<'a> {
-    fn parse(tokens: &mut Tokenizer<'a>, _docs: Docs<'a>) -> Result<Self> {
+    fn parse(tokens: &mut Tokenizer<'a>, docs: Docs<'a>) -> Result<Self> {
         tokens.expect(Token::Use)?;
         let mut names = None;
         loop {
@@ -494,25 +700,40 @@ impl<'a> Use<'a> {
             tokens.expect(Token::RightBrace)?;
         }
         tokens.expect(Token::From_)?;
-        let mut from = vec![parse_id(tokens)?];
+        let mut from = vec![parse_module_name(tokens)?];
         while tokens.eat(Token::Colon)? {
             tokens.expect_raw(Token::Colon)?;
             from.push(parse_id(tokens)?);
         }
-        Ok(Use { from, names })
+        Ok(Use { docs, from, names })
     }
 }
 
 impl<'a> TypeDef<'a> {
-    fn parse(tokens: &mut Tokenizer<'a>, docs: Docs<'a>) -> Result<Self> {
+    fn parse(
+        tokens: &mut Tokenizer<'a>,
+        docs: Docs<'a>,
+        attrs: Vec<Attribute<'a>>,
+    ) -> Result<Self> {
         tokens.expect(Token::Type)?;
         let name = parse_id(tokens)?;
+        let params = parse_type_params(tokens)?;
         tokens.expect(Token::Equals)?;
         let ty = Type::parse(tokens)?;
-        Ok(TypeDef { docs, name, ty })
+        Ok(TypeDef {
+            docs,
+            attrs,
+            name,
+            params,
+            ty,
+        })
     }
 
-    fn parse_flags(tokens: &mut Tokenizer<'a>, docs: Docs<'a>) -> Result<Self> {
+    fn parse_flags(
+        tokens: &mut Tokenizer<'a>,
+        docs: Docs<'a>,
+        attrs: Vec<Attribute<'a>>,
+    ) -> Result<Self> {
         tokens.expect(Token::Flags)?;
         let name = parse_id(tokens)?;
         let ty = Type::Record(Record {
@@ -523,21 +744,48 @@ impl<'a> TypeDef<'a> {
                 Token::LeftBrace,
                 Token::RightBrace,
                 |docs, tokens| {
+                    let attrs = parse_attrs(tokens)?;
                     let name = parse_id(tokens)?;
+                    let bit = if tokens.eat(Token::Equals)? {
+                        let (span, value) = parse_integer(tokens, "a flag value")?;
+                        if value.count_ones() != 1 {
+                            return Err(Error {
+                                span,
+                                msg: "flag values must have exactly one bit set".to_string(),
+                            }
+                            .into());
+                        }
+                        Some((span, value.trailing_zeros()))
+                    } else {
+                        None
+                    };
                     Ok(Field {
                         docs,
+                        attrs,
                         name,
-                        ty: Type::bool(),
+                        ty: Type::Bool,
+                        bit,
                     })
                 },
             )?,
         });
-        Ok(TypeDef { docs, name, ty })
+        Ok(TypeDef {
+            docs,
+            attrs,
+            name,
+            params: Vec::new(),
+            ty,
+        })
     }
 
-    fn parse_record(tokens: &mut Tokenizer<'a>, docs: Docs<'a>) -> Result<Self> {
+    fn parse_record(
+        tokens: &mut Tokenizer<'a>,
+        docs: Docs<'a>,
+        attrs: Vec<Attribute<'a>>,
+    ) -> Result<Self> {
         tokens.expect(Token::Record)?;
         let name = parse_id(tokens)?;
+        let params = parse_type_params(tokens)?;
         let ty = Type::Record(Record {
             flags_repr: None,
             tuple_hint: false,
@@ -546,19 +794,37 @@ impl<'a> TypeDef<'a> {
                 Token::LeftBrace,
                 Token::RightBrace,
                 |docs, tokens| {
+                    let attrs = parse_attrs(tokens)?;
                     let name = parse_id(tokens)?;
                     tokens.expect(Token::Colon)?;
                     let ty = Type::parse(tokens)?;
-                    Ok(Field { docs, name, ty })
+                    Ok(Field {
+                        docs,
+                        attrs,
+                        name,
+                        ty,
+                        bit: None,
+                    })
                 },
             )?,
         });
-        Ok(TypeDef { docs, name, ty })
+        Ok(TypeDef {
+            docs,
+            attrs,
+            name,
+            params,
+            ty,
+        })
     }
 
-    fn parse_variant(tokens: &mut Tokenizer<'a>, docs: Docs<'a>) -> Result<Self> {
+    fn parse_variant(
+        tokens: &mut Tokenizer<'a>,
+        docs: Docs<'a>,
+        attrs: Vec<Attribute<'a>>,
+    ) -> Result<Self> {
         tokens.expect(Token::Variant)?;
         let name = parse_id(tokens)?;
+        let params = parse_type_params(tokens)?;
         let ty = Type::Variant(Variant {
             tag: None,
             span: name.span,
@@ -567,6 +833,7 @@ impl<'a> TypeDef<'a> {
                 Token::LeftBrace,
                 Token::RightBrace,
                 |docs, tokens| {
+                    let attrs = parse_attrs(tokens)?;
                     let name = parse_id(tokens)?;
                     let ty = if tokens.eat(Token::LeftParen)? {
                         let ty = Type::parse(tokens)?;
@@ -575,16 +842,34 @@ impl<'a> TypeDef<'a> {
                     } else {
                         None
                     };
-                    Ok(Case { docs, name, ty })
+                    let discriminant = parse_discriminant(tokens)?;
+                    Ok(Case {
+                        docs,
+                        attrs,
+                        name,
+                        ty,
+                        discriminant,
+                    })
                 },
             )?,
         });
-        Ok(TypeDef { docs, name, ty })
+        Ok(TypeDef {
+            docs,
+            attrs,
+            name,
+            params,
+            ty,
+        })
     }
 
-    fn parse_union(tokens: &mut Tokenizer<'a>, docs: Docs<'a>) -> Result<Self> {
+    fn parse_union(
+        tokens: &mut Tokenizer<'a>,
+        docs: Docs<'a>,
+        attrs: Vec<Attribute<'a>>,
+    ) -> Result<Self> {
         tokens.expect(Token::Union)?;
         let name = parse_id(tokens)?;
+        let params = parse_type_params(tokens)?;
         let mut i = 0;
         let ty = Type::Variant(Variant {
             tag: None,
@@ -594,20 +879,33 @@ impl<'a> TypeDef<'a> {
                 Token::LeftBrace,
                 Token::RightBrace,
                 |docs, tokens| {
+                    let attrs = parse_attrs(tokens)?;
                     let ty = Type::parse(tokens)?;
                     i += 1;
                     Ok(Case {
                         docs,
+                        attrs,
                         name: (i - 1).to_string().into(),
                         ty: Some(ty),
+                        discriminant: None,
                     })
                 },
             )?,
         });
-        Ok(TypeDef { docs, name, ty })
+        Ok(TypeDef {
+            docs,
+            attrs,
+            name,
+            params,
+            ty,
+        })
     }
 
-    fn parse_enum(tokens: &mut Tokenizer<'a>, docs: Docs<'a>) -> Result<Self> {
+    fn parse_enum(
+        tokens: &mut Tokenizer<'a>,
+        docs: Docs<'a>,
+        attrs: Vec<Attribute<'a>>,
+    ) -> Result<Self> {
         tokens.expect(Token::Enum)?;
         let name = parse_id(tokens)?;
         let ty = Type::Variant(Variant {
@@ -618,21 +916,35 @@ impl<'a> TypeDef<'a> {
                 Token::LeftBrace,
                 Token::RightBrace,
                 |docs, tokens| {
+                    let attrs = parse_attrs(tokens)?;
                     let name = parse_id(tokens)?;
+                    let discriminant = parse_discriminant(tokens)?;
                     Ok(Case {
                         docs,
+                        attrs,
                         name,
                         ty: None,
+                        discriminant,
                     })
                 },
             )?,
         });
-        Ok(TypeDef { docs, name, ty })
+        Ok(TypeDef {
+            docs,
+            attrs,
+            name,
+            params: Vec::new(),
+            ty,
+        })
     }
 }
 
 impl<'a> Resource<'a> {
-    fn parse(tokens: &mut Tokenizer<'a>, docs: Docs<'a>) -> Result<Self> {
+    fn parse(
+        tokens: &mut Tokenizer<'a>,
+        docs: Docs<'a>,
+        attrs: Vec<Attribute<'a>>,
+    ) -> Result<Self> {
         tokens.expect(Token::Resource)?;
         let name = parse_id(tokens)?;
         let mut values = Vec::new();
@@ -642,16 +954,26 @@ impl<'a> Resource<'a> {
                 if tokens.eat(Token::RightBrace)? {
                     break;
                 }
+                let attrs = parse_attrs(tokens)?;
                 let statik = tokens.eat(Token::Static)?;
-                values.push((statik, Value::parse(tokens, docs)?));
+                values.push((statik, Value::parse(tokens, docs, attrs)?));
             }
         }
-        Ok(Resource { docs, name, values })
+        Ok(Resource {
+            docs,
+            attrs,
+            name,
+            values,
+        })
     }
 }
 
 impl<'a> Value<'a> {
-    fn parse(tokens: &mut Tokenizer<'a>, docs: Docs<'a>) -> Result<Self> {
+    fn parse(
+        tokens: &mut Tokenizer<'a>,
+        docs: Docs<'a>,
+        attrs: Vec<Attribute<'a>>,
+    ) -> Result<Self> {
         let name = parse_id(tokens)?;
         tokens.expect(Token::Colon)?;
 
@@ -663,7 +985,12 @@ impl<'a> Value<'a> {
         } else {
             ValueKind::Global(Type::parse(tokens)?)
         };
-        return Ok(Value { docs, name, kind });
+        return Ok(Value {
+            docs,
+            attrs,
+            name,
+            kind,
+        });
 
         fn parse_func<'a>(tokens: &mut Tokenizer<'a>, is_async: bool) -> Result<ValueKind<'a>> {
             let params = parse_list(
@@ -717,6 +1044,31 @@ impl<'a> Value<'a> {
     }
 }
 
+/// Parses a module name, which is a `.`-separated list of identifiers such as
+/// `wasi.io.streams`, into a single `Id`.
+fn parse_module_name<'a>(tokens: &mut Tokenizer<'a>) -> Result<Id<'a>> {
+    let mut id = parse_id(tokens)?;
+    while tokens.eat(Token::Period)? {
+        let next = parse_id(tokens)?;
+        id.name = format!("{}.{}", id.name, next.name).into();
+        id.span.end = next.span.end;
+    }
+    Ok(id)
+}
+
+/// Parses the optional `<T, U, ...>` parameters of a generic type definition.
+fn parse_type_params<'a>(tokens: &mut Tokenizer<'a>) -> Result<Vec<Id<'a>>> {
+    match tokens.clone().next()? {
+        Some((_span, Token::LessThan)) => parse_list(
+            tokens,
+            Token::LessThan,
+            Token::GreaterThan,
+            |_docs, tokens| parse_id(tokens),
+        ),
+        _ => Ok(Vec::new()),
+    }
+}
+
 fn parse_id<'a>(tokens: &mut Tokenizer<'a>) -> Result<Id<'a>> {
     match tokens.next()? {
         Some((span, Token::Id)) => Ok(Id {
@@ -766,6 +1118,105 @@ fn parse_docs<'a>(tokens: &mut Tokenizer<'a>) -> Result<Docs<'a>> {
     Ok(docs)
 }
 
+fn parse_attrs<'a>(tokens: &mut Tokenizer<'a>) -> Result<Vec<Attribute<'a>>> {
+    let mut attrs = Vec::new();
+    while tokens.eat(Token::At)? {
+        let name = parse_id(tokens)?;
+        let mut args = Vec::new();
+        if let Some((_span, Token::LeftParen)) = tokens.clone().next()? {
+            args = parse_list(
+                tokens,
+                Token::LeftParen,
+                Token::RightParen,
+                |_docs, tokens| {
+                    let mut other = tokens.clone();
+                    let name = match parse_opt_id(&mut other)? {
+                        Some(id) if other.eat(Token::Equals)? => {
+                            *tokens = other;
+                            Some(id)
+                        }
+                        _ => None,
+                    };
+                    let value = match tokens.clone().next()? {
+                        Some((span, Token::StrLit)) => {
+                            tokens.next()?;
+                            Id {
+                                name: tokens.parse_str(span).into(),
+                                span,
+                            }
+                        }
+                        _ => parse_bare_value(tokens, "an attribute value")?,
+                    };
+                    Ok(AttributeArg { name, value })
+                },
+            )?;
+        }
+        attrs.push(Attribute { name, args });
+    }
+    Ok(attrs)
+}
+
+/// Parses a bare run of identifiers and periods with no whitespace in
+/// between, such as `1.2` or `x.y`.
+fn parse_bare_value<'a>(tokens: &mut Tokenizer<'a>, expected: &'static str) -> Result<Id<'a>> {
+    let start = match tokens.next()? {
+        Some((span, Token::Id)) | Some((span, Token::Period)) => span,
+        other => return Err(err_expected(tokens, expected, other).into()),
+    };
+    let mut end = start.end;
+    let mut other = tokens.clone();
+    while let Some((span, Token::Id)) | Some((span, Token::Period)) = other.next_raw()? {
+        end = span.end;
+        *tokens = other.clone();
+    }
+    let span = Span {
+        start: start.start,
+        end,
+    };
+    Ok(Id {
+        name: tokens.get_span(span).into(),
+        span,
+    })
+}
+
+/// Parses an unsigned integer in decimal or, with a `0x` prefix, in
+/// hexadecimal.
+fn parse_integer(tokens: &mut Tokenizer<'_>, expected: &'static str) -> Result<(Span, u64)> {
+    let span = match tokens.next()? {
+        Some((span, Token::Id)) => span,
+        other => return Err(err_expected(tokens, expected, other).into()),
+    };
+    let s = tokens.get_span(span);
+    let value = match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    match value {
+        Ok(value) => Ok((span, value)),
+        Err(_) => Err(Error {
+            span,
+            msg: format!("invalid integer `{}`", s),
+        }
+        .into()),
+    }
+}
+
+/// Parses the optional `= N` after the name of a case.
+fn parse_discriminant(tokens: &mut Tokenizer<'_>) -> Result<Option<(Span, u32)>> {
+    if !tokens.eat(Token::Equals)? {
+        return Ok(None);
+    }
+    let (span, value) = parse_integer(tokens, "a discriminant")?;
+    if value > u64::from(u32::MAX) {
+        return Err(Error {
+            span,
+            msg: "discriminants must fit in 32 bits".to_string(),
+        }
+        .into());
+    }
+    Ok(Some((span, value as u32)))
+}
+
 impl<'a> Type<'a> {
     fn parse(tokens: &mut Tokenizer<'a>) -> Result<Self> {
         match tokens.next()? {
@@ -796,8 +1247,10 @@ impl<'a> Type<'a> {
                         i += 1;
                         Ok(Field {
                             docs,
+                            attrs: Vec::new(),
                             name: (i - 1).to_string().into(),
                             ty: Type::parse(tokens)?,
+                            bit: None,
                         })
                     },
                 )?;
@@ -808,8 +1261,8 @@ impl<'a> Type<'a> {
                 }))
             }
 
-            Some((_span, Token::Bool)) => Ok(Type::bool()),
-            Some((_span, Token::String_)) => Ok(Type::List(Box::new(Type::Char))),
+            Some((_span, Token::Bool)) => Ok(Type::Bool),
+            Some((_span, Token::String_)) => Ok(Type::String),
 
             // list<T>
             Some((_span, Token::List)) => {
@@ -819,6 +1272,28 @@ impl<'a> Type<'a> {
                 Ok(Type::List(Box::new(ty)))
             }
 
+            // array<T, N>
+            Some((_span, Token::Array)) => {
+                tokens.expect(Token::LessThan)?;
+                let ty = Type::parse(tokens)?;
+                tokens.expect(Token::Comma)?;
+                let len = match tokens.next()? {
+                    Some((span, Token::Id)) => match tokens.get_span(span).parse() {
+                        Ok(len) if len > 0 => len,
+                        _ => {
+                            return Err(Error {
+                                span,
+                                msg: "invalid array length".to_string(),
+                            }
+                            .into())
+                        }
+                    },
+                    other => return Err(err_expected(tokens, "an array length", other).into()),
+                };
+                tokens.expect(Token::GreaterThan)?;
+                Ok(Type::Array(Box::new(ty), len))
+            }
+
             // option<T>
             Some((span, Token::Option_)) => {
                 tokens.expect(Token::LessThan)?;
@@ -830,13 +1305,17 @@ impl<'a> Type<'a> {
                     cases: vec![
                         Case {
                             docs: Docs::default(),
+                            attrs: Vec::new(),
                             name: "none".into(),
                             ty: None,
+                            discriminant: None,
                         },
                         Case {
                             docs: Docs::default(),
+                            attrs: Vec::new(),
                             name: "some".into(),
                             ty: Some(ty),
+                            discriminant: None,
                         },
                     ],
                 }))
@@ -863,23 +1342,41 @@ impl<'a> Type<'a> {
                     cases: vec![
                         Case {
                             docs: Docs::default(),
+                            attrs: Vec::new(),
                             name: "ok".into(),
                             ty: ok,
+                            discriminant: None,
                         },
                         Case {
                             docs: Docs::default(),
+                            attrs: Vec::new(),
                             name: "err".into(),
                             ty: err,
+                            discriminant: None,
                         },
                     ],
                 }))
             }
 
-            // `foo`
-            Some((span, Token::Id)) => Ok(Type::Name(Id {
-                name: tokens.get_span(span).into(),
-                span,
-            })),
+            // `foo` or `foo<T, U, ...>`
+            Some((span, Token::Id)) => {
+                let name = Id {
+                    name: tokens.get_span(span).into(),
+                    span,
+                };
+                match tokens.clone().next()? {
+                    Some((_span, Token::LessThan)) => {
+                        let args = parse_list(
+                            tokens,
+                            Token::LessThan,
+                            Token::GreaterThan,
+                            |_docs, tokens| Type::parse(tokens),
+                        )?;
+                        Ok(Type::Generic(name, args))
+                    }
+                    _ => Ok(Type::Name(name)),
+                }
+            }
             // `"foo"`
             Some((span, Token::StrLit)) => Ok(Type::Name(Id {
                 name: tokens.parse_str(span).into(),
@@ -905,23 +1402,41 @@ impl<'a> Type<'a> {
             other => Err(err_expected(tokens, "a type", other).into()),
         }
     }
+}
 
-    fn bool() -> Type<'static> {
-        Type::Variant(Variant {
-            tag: None,
-            span: Span { start: 0, end: 0 },
-            cases: vec![
-                Case {
-                    docs: Docs::default(),
-                    name: "false".into(),
-                    ty: None,
-                },
-                Case {
-                    docs: Docs::default(),
-                    name: "true".into(),
-                    ty: None,
-                },
-            ],
+impl<'a> Const<'a> {
+    fn parse(
+        tokens: &mut Tokenizer<'a>,
+        docs: Docs<'a>,
+        attrs: Vec<Attribute<'a>>,
+    ) -> Result<Self> {
+        tokens.expect(Token::Const)?;
+        let name = parse_id(tokens)?;
+        tokens.expect(Token::Colon)?;
+        let ty = Type::parse(tokens)?;
+        tokens.expect(Token::Equals)?;
+        let value = match tokens.clone().next()? {
+            Some((_span, Token::LeftBrace)) => ConstValue::Flags(parse_list(
+                tokens,
+                Token::LeftBrace,
+                Token::RightBrace,
+                |_docs, tokens| parse_id(tokens),
+            )?),
+            Some((span, Token::StrLit)) => {
+                tokens.next()?;
+                ConstValue::String(Id {
+                    name: tokens.parse_str(span).into(),
+                    span,
+                })
+            }
+            _ => ConstValue::Bare(parse_bare_value(tokens, "a constant value")?),
+        };
+        Ok(Const {
+            docs,
+            attrs,
+            name,
+            ty,
+            value,
         })
     }
 }
@@ -1007,6 +1522,25 @@ impl fmt::Display for Error {
 
 impl std::error::Error for Error {}
 
+/// Returns the span and message of an error produced by parsing or
+/// resolution, or `None` if the error doesn't carry location information.
+pub fn error_span(err: &anyhow::Error) -> Option<(Span, String)> {
+    if let Some(err) = err.downcast_ref::<Error>() {
+        return Some((err.span, err.msg.clone()));
+    }
+    if let Some(err) = err.downcast_ref::<lex::Error>() {
+        let pos = u32::try_from(err.position()).unwrap();
+        return Some((
+            Span {
+                start: pos,
+                end: pos,
+            },
+            err.to_string(),
+        ));
+    }
+    None
+}
+
 pub fn rewrite_error(err: &mut anyhow::Error, file: &str, contents: &str) {
     #[cfg(feature = "old-witx-compat")]
     if let Some(err) = err.downcast_mut::<wast::Error>() {
@@ -1028,7 +1562,7 @@ pub fn rewrite_error(err: &mut anyhow::Error, file: &str, contents: &str) {
     *err = anyhow::anyhow!("{}", msg);
 }
 
-fn highlight_err(
+pub(crate) fn highlight_err(
     start: usize,
     end: Option<usize>,
     file: &str,
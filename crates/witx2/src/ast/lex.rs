@@ -42,6 +42,8 @@ pub enum Token {
     GreaterThan,
     RArrow,
     Star,
+    At,
+    Period,
 
     Use,
     Type,
@@ -69,6 +71,7 @@ pub enum Token {
     Option_,
     Expected,
     List,
+    Array,
     Underscore,
     PushBuffer,
     PullBuffer,
@@ -78,6 +81,7 @@ pub enum Token {
     Interface,
     Tuple,
     Async,
+    Const,
 
     Id,
     StrLit,
@@ -191,9 +195,22 @@ impl<'a> Tokenizer<'a> {
             '<' => LessThan,
             '>' => GreaterThan,
             '*' => Star,
+            '@' => At,
+            '.' => Period,
             '-' => {
                 if self.eatc('>') {
                     RArrow
+                } else if let Some((_, '0'..='9')) = self.chars.clone().next() {
+                    // Negative numbers, like all other numbers, are lexed as
+                    // identifiers.
+                    let mut iter = self.chars.clone();
+                    while let Some((_, ch)) = iter.next() {
+                        if !is_keylike(ch) {
+                            break;
+                        }
+                        self.chars = iter.clone();
+                    }
+                    Id
                 } else {
                     return Err(Error::Unexpected(start, '-'));
                 }
@@ -239,6 +256,7 @@ impl<'a> Tokenizer<'a> {
                     "option" => Option_,
                     "expected" => Expected,
                     "list" => List,
+                    "array" => Array,
                     "_" => Underscore,
                     "push-buffer" => PushBuffer,
                     "pull-buffer" => PullBuffer,
@@ -248,6 +266,7 @@ impl<'a> Tokenizer<'a> {
                     "interface" => Interface,
                     "tuple" => Tuple,
                     "async" => Async,
+                    "const" => Const,
                     _ => Id,
                 }
             }
@@ -420,6 +439,7 @@ impl Token {
             Option_ => "keyword `option`",
             Expected => "keyword `expected`",
             List => "keyword `list`",
+            Array => "keyword `array`",
             Underscore => "keyword `_`",
             Id => "an identifier",
             StrLit => "a string",
@@ -427,18 +447,36 @@ impl Token {
             PullBuffer => "keyword `pull-buffer`",
             RArrow => "`->`",
             Star => "`*`",
+            At => "'@'",
+            Period => "'.'",
             As => "keyword `as`",
             From_ => "keyword `from`",
             Static => "keyword `static`",
             Interface => "keyword `interface`",
             Tuple => "keyword `tuple`",
             Async => "keyword `async`",
+            Const => "keyword `const`",
         }
     }
 }
 
 impl std::error::Error for Error {}
 
+impl Error {
+    /// Returns the byte offset into the input at which this error occurred.
+    pub fn position(&self) -> usize {
+        match self {
+            Error::Unexpected(at, _)
+            | Error::UnterminatedComment(at)
+            | Error::Wanted { at, .. }
+            | Error::UnterminatedString(at)
+            | Error::NewlineInString(at)
+            | Error::InvalidCharInString(at, _)
+            | Error::InvalidEscape(at, _) => *at,
+        }
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -460,15 +498,6 @@ pub fn rewrite_error(err: &mut anyhow::Error, file: &str, contents: &str) {
         Some(err) => err,
         None => return,
     };
-    let pos = match lex {
-        Error::Unexpected(at, _)
-        | Error::UnterminatedComment(at)
-        | Error::Wanted { at, .. }
-        | Error::UnterminatedString(at)
-        | Error::NewlineInString(at)
-        | Error::InvalidCharInString(at, _)
-        | Error::InvalidEscape(at, _) => *at,
-    };
-    let msg = super::highlight_err(pos, None, file, contents, lex);
+    let msg = super::highlight_err(lex.position(), None, file, contents, lex);
     *err = anyhow::anyhow!("{}", msg);
 }
@@ -5,8 +5,12 @@ use std::collections::{HashMap, HashSet};
 use std::mem;
 
 #[derive(Default)]
-pub struct Resolver {
+pub struct Resolver<'a> {
     type_lookup: HashMap<String, TypeId>,
+    generics: HashMap<String, &'a super::TypeDef<'a>>,
+    type_params: HashMap<String, Type>,
+    instances: HashMap<(String, Vec<Type>), Type>,
+    instantiating: HashSet<String>,
     types: Arena<TypeDef>,
     resource_lookup: HashMap<String, ResourceId>,
     resources_copied: HashMap<(String, ResourceId), ResourceId>,
@@ -15,6 +19,8 @@ pub struct Resolver {
     anon_types: HashMap<Key, TypeId>,
     functions: Vec<Function>,
     globals: Vec<Global>,
+    constants: Vec<Constant>,
+    errors: Vec<anyhow::Error>,
 }
 
 #[derive(PartialEq, Eq, Hash)]
@@ -22,34 +28,41 @@ enum Key {
     Variant(Vec<(String, Option<Type>)>),
     Record(Vec<(String, Type)>),
     List(Type),
+    Array(Type, u32),
     PushBuffer(Type),
     PullBuffer(Type),
     Pointer(Type),
     ConstPointer(Type),
 }
 
-impl Resolver {
+impl<'a> Resolver<'a> {
     pub(super) fn resolve(
         &mut self,
         name: &str,
-        fields: &[Item<'_>],
+        fields: &'a [Item<'a>],
         deps: &HashMap<String, Interface>,
-    ) -> Result<Interface> {
+    ) -> (Interface, Vec<anyhow::Error>) {
+        // Errors are recorded in `self.errors` as they're found and resolution
+        // then carries on with the next item, so everything that's wrong with
+        // an interface can be reported at once.
+
         // First pull in any names from our dependencies
-        self.process_use(fields, deps)?;
+        self.process_use(fields, deps);
         // ... then register our own names
-        self.register_names(fields)?;
+        self.register_names(fields);
 
         // With all names registered we can now fully expand and translate all
         // types.
         for field in fields {
             let t = match field {
-                Item::TypeDef(t) => t,
+                Item::TypeDef(t) if t.params.is_empty() => t,
                 _ => continue,
             };
             let id = self.type_lookup[&*t.name.name];
-            let kind = self.resolve_type_def(&t.ty)?;
-            self.types.get_mut(id).unwrap().kind = kind;
+            match self.resolve_type_def(&t.ty) {
+                Ok(kind) => self.types.get_mut(id).unwrap().kind = kind,
+                Err(e) => self.errors.push(e),
+            }
         }
 
         // And finally we can resolve all type references in functions/globals
@@ -58,21 +71,38 @@ impl Resolver {
         let mut visiting = HashSet::new();
         for field in fields {
             match field {
-                Item::Value(v) => self.resolve_value(v)?,
-                Item::Resource(r) => self.resolve_resource(r)?,
-                Item::TypeDef(t) => {
-                    self.validate_type_not_recursive(
+                Item::Value(v) => {
+                    if let Err(e) = self.resolve_value(v) {
+                        self.errors.push(e);
+                    }
+                }
+                Item::Resource(r) => self.resolve_resource(r),
+                Item::Const(c) => {
+                    if let Err(e) = self.resolve_const(c) {
+                        self.errors.push(e);
+                    }
+                }
+                Item::TypeDef(t) if t.params.is_empty() => {
+                    let id = self.type_lookup[&*t.name.name];
+                    let result = self.validate_type_not_recursive(
                         t.name.span,
-                        self.type_lookup[&*t.name.name],
+                        id,
                         &mut visiting,
                         &mut valid_types,
-                    )?;
+                    );
+                    if let Err(e) = result {
+                        self.errors.push(e);
+                        // Break the cycle so the partial interface can still
+                        // be walked without recursing forever.
+                        self.types[id].kind = TypeDefKind::Type(Type::U8);
+                        visiting.clear();
+                    }
                 }
                 _ => continue,
             }
         }
 
-        Ok(Interface {
+        let interface = Interface {
             name: name.to_string(),
             types: mem::take(&mut self.types),
             type_lookup: mem::take(&mut self.type_lookup),
@@ -82,34 +112,47 @@ impl Resolver {
             interfaces: Default::default(),
             functions: mem::take(&mut self.functions),
             globals: mem::take(&mut self.globals),
-        })
+            constants: mem::take(&mut self.constants),
+            sources: Default::default(),
+        };
+        self.validate_sizes(&interface);
+        (interface, mem::take(&mut self.errors))
     }
 
-    fn process_use<'a>(
-        &mut self,
-        fields: &[Item<'a>],
-        deps: &'a HashMap<String, Interface>,
-    ) -> Result<()> {
+    fn process_use<'b>(&mut self, fields: &[Item<'b>], deps: &'b HashMap<String, Interface>) {
         for field in fields {
             let u = match field {
                 Item::Use(u) => u,
                 _ => continue,
             };
-            let mut dep = &deps[&*u.from[0].name];
+            // A dependency which failed to load has already been reported
+            // where it was loaded, so there's nothing more to do here.
+            let mut dep = match deps.get(&*u.from[0].name) {
+                Some(dep) => dep,
+                None => continue,
+            };
             let mut prev = &*u.from[0].name;
+            let mut found_dep = true;
             for name in u.from[1..].iter() {
                 dep = match dep.interface_lookup.get(&*name.name) {
                     Some(i) => &dep.interfaces[*i],
                     None => {
-                        return Err(Error {
-                            span: name.span,
-                            msg: format!("`{}` not defined in `{}`", name.name, prev),
-                        }
-                        .into())
+                        self.errors.push(
+                            Error {
+                                span: name.span,
+                                msg: format!("`{}` not defined in `{}`", name.name, prev),
+                            }
+                            .into(),
+                        );
+                        found_dep = false;
+                        break;
                     }
                 };
                 prev = &*name.name;
             }
+            if !found_dep {
+                continue;
+            }
 
             let mod_name = &u.from[0];
 
@@ -124,40 +167,41 @@ impl Resolver {
 
                         if let Some(id) = dep.resource_lookup.get(&*name.name.name) {
                             let resource = self.copy_resource(&mod_name.name, dep, *id);
-                            self.define_resource(my_name, span, resource)?;
+                            self.define_resource(my_name, span, resource);
                             found = true;
                         }
 
                         if let Some(id) = dep.type_lookup.get(&*name.name.name) {
                             let ty = self.copy_type_def(&mod_name.name, dep, *id);
-                            self.define_type(my_name, span, ty)?;
+                            self.define_type(my_name, span, ty);
                             found = true;
                         }
 
                         if !found {
-                            return Err(Error {
-                                span: name.name.span,
-                                msg: format!("name not defined in submodule"),
-                            }
-                            .into());
+                            self.errors.push(
+                                Error {
+                                    span: name.name.span,
+                                    msg: "name not defined in submodule".to_string(),
+                                }
+                                .into(),
+                            );
                         }
                     }
                 }
                 None => {
                     for (id, resource) in dep.resources.iter() {
                         let id = self.copy_resource(&mod_name.name, dep, id);
-                        self.define_resource(&resource.name, mod_name.span, id)?;
+                        self.define_resource(&resource.name, mod_name.span, id);
                     }
                     let mut names = dep.type_lookup.iter().collect::<Vec<_>>();
                     names.sort(); // produce a stable order by which to add names
                     for (name, id) in names {
                         let ty = self.copy_type_def(&mod_name.name, dep, *id);
-                        self.define_type(name, mod_name.span, ty)?;
+                        self.define_type(name, mod_name.span, ty);
                     }
                 }
             }
         }
-        Ok(())
     }
 
     fn copy_resource(&mut self, dep_name: &str, dep: &Interface, r: ResourceId) -> ResourceId {
@@ -169,7 +213,9 @@ impl Resolver {
                 let r = &dep.resources[r];
                 let resource = Resource {
                     docs: r.docs.clone(),
+                    attrs: r.attrs.clone(),
                     name: r.name.clone(),
+                    span: r.span,
                     foreign_module: Some(r.foreign_module.clone().unwrap_or(dep_name.to_string())),
                 };
                 resources.alloc(resource)
@@ -184,7 +230,9 @@ impl Resolver {
 
         let ty = TypeDef {
             docs: ty.docs.clone(),
+            attrs: ty.attrs.clone(),
             name: ty.name.clone(),
+            span: ty.span,
             foreign_module: Some(ty.foreign_module.clone().unwrap_or(dep_name.to_string())),
             kind: match &ty.kind {
                 TypeDefKind::Type(t) => TypeDefKind::Type(self.copy_type(dep_name, dep, *t)),
@@ -194,8 +242,10 @@ impl Resolver {
                         .iter()
                         .map(|field| Field {
                             docs: field.docs.clone(),
+                            attrs: field.attrs.clone(),
                             name: field.name.clone(),
                             ty: self.copy_type(dep_name, dep, field.ty),
+                            bit: field.bit,
                         })
                         .collect(),
                     kind: r.kind,
@@ -206,13 +256,18 @@ impl Resolver {
                         .iter()
                         .map(|case| Case {
                             docs: case.docs.clone(),
+                            attrs: case.attrs.clone(),
                             name: case.name.clone(),
                             ty: case.ty.map(|t| self.copy_type(dep_name, dep, t)),
+                            discriminant: case.discriminant,
                         })
                         .collect(),
                     tag: v.tag,
                 }),
                 TypeDefKind::List(t) => TypeDefKind::List(self.copy_type(dep_name, dep, *t)),
+                TypeDefKind::Array(t, len) => {
+                    TypeDefKind::Array(self.copy_type(dep_name, dep, *t), *len)
+                }
                 TypeDefKind::PullBuffer(t) => {
                     TypeDefKind::PullBuffer(self.copy_type(dep_name, dep, *t))
                 }
@@ -238,45 +293,70 @@ impl Resolver {
         }
     }
 
-    fn register_names(&mut self, fields: &[Item<'_>]) -> Result<()> {
+    fn register_names(&mut self, fields: &'a [Item<'a>]) {
         let mut values = HashSet::new();
         for field in fields {
             match field {
                 Item::Resource(r) => {
                     let docs = self.docs(&r.docs);
+                    let attrs = self.attrs(&r.attrs);
                     let id = self.resources.alloc(Resource {
                         docs,
+                        attrs,
                         name: r.name.name.to_string(),
+                        span: r.name.span,
                         foreign_module: None,
                     });
-                    self.define_resource(&r.name.name, r.name.span, id)?;
+                    self.define_resource(&r.name.name, r.name.span, id);
                     let type_id = self.types.alloc(TypeDef {
                         docs: Docs::default(),
+                        attrs: Attrs::default(),
                         kind: TypeDefKind::Type(Type::Handle(id)),
                         name: None,
+                        span: r.name.span,
                         foreign_module: None,
                     });
-                    self.define_type(&r.name.name, r.name.span, type_id)?;
+                    self.define_type(&r.name.name, r.name.span, type_id);
+                }
+                // Generic types are only turned into `TypeDef`s when they're
+                // instantiated, see `instantiate`.
+                Item::TypeDef(t) if !t.params.is_empty() => {
+                    if self.type_lookup.contains_key(&*t.name.name)
+                        || self.generics.insert(t.name.name.to_string(), t).is_some()
+                    {
+                        self.errors.push(
+                            Error {
+                                span: t.name.span,
+                                msg: format!("type {:?} defined twice", t.name.name),
+                            }
+                            .into(),
+                        );
+                    }
                 }
                 Item::TypeDef(t) => {
                     let docs = self.docs(&t.docs);
+                    let attrs = self.attrs(&t.attrs);
                     let id = self.types.alloc(TypeDef {
                         docs,
+                        attrs,
                         // a dummy kind is used for now which will get filled in
                         // later with the actual desired contents.
                         kind: TypeDefKind::List(Type::U8),
                         name: Some(t.name.name.to_string()),
+                        span: t.name.span,
                         foreign_module: None,
                     });
-                    self.define_type(&t.name.name, t.name.span, id)?;
+                    self.define_type(&t.name.name, t.name.span, id);
                 }
-                Item::Value(f) => {
-                    if !values.insert(&f.name.name) {
-                        return Err(Error {
-                            span: f.name.span,
-                            msg: format!("{:?} defined twice", f.name.name),
-                        }
-                        .into());
+                Item::Value(Value { name, .. }) | Item::Const(super::Const { name, .. }) => {
+                    if !values.insert(&name.name) {
+                        self.errors.push(
+                            Error {
+                                span: name.span,
+                                msg: format!("{:?} defined twice", name.name),
+                            }
+                            .into(),
+                        );
                     }
                 }
                 Item::Use(_) => {}
@@ -284,31 +364,31 @@ impl Resolver {
                 Item::Interface(_) => unimplemented!(),
             }
         }
-
-        Ok(())
     }
 
-    fn define_resource(&mut self, name: &str, span: Span, id: ResourceId) -> Result<()> {
+    fn define_resource(&mut self, name: &str, span: Span, id: ResourceId) {
         if self.resource_lookup.insert(name.to_string(), id).is_some() {
-            Err(Error {
-                span,
-                msg: format!("resource {:?} defined twice", name),
-            }
-            .into())
-        } else {
-            Ok(())
+            self.errors.push(
+                Error {
+                    span,
+                    msg: format!("resource {:?} defined twice", name),
+                }
+                .into(),
+            );
         }
     }
 
-    fn define_type(&mut self, name: &str, span: Span, id: TypeId) -> Result<()> {
-        if self.type_lookup.insert(name.to_string(), id).is_some() {
-            Err(Error {
-                span,
-                msg: format!("type {:?} defined twice", name),
-            }
-            .into())
-        } else {
-            Ok(())
+    fn define_type(&mut self, name: &str, span: Span, id: TypeId) {
+        if self.type_lookup.insert(name.to_string(), id).is_some()
+            || self.generics.contains_key(name)
+        {
+            self.errors.push(
+                Error {
+                    span,
+                    msg: format!("type {:?} defined twice", name),
+                }
+                .into(),
+            );
         }
     }
 
@@ -327,6 +407,8 @@ impl Resolver {
             super::Type::Char => TypeDefKind::Type(Type::Char),
             super::Type::CChar => TypeDefKind::Type(Type::CChar),
             super::Type::Usize => TypeDefKind::Type(Type::Usize),
+            super::Type::Bool => TypeDefKind::Type(Type::Bool),
+            super::Type::String => TypeDefKind::Type(Type::String),
             super::Type::Handle(resource) => {
                 let id = match self.resource_lookup.get(&*resource.name) {
                     Some(id) => *id,
@@ -341,22 +423,38 @@ impl Resolver {
                 TypeDefKind::Type(Type::Handle(id))
             }
             super::Type::Name(name) => {
+                if let Some(ty) = self.type_params.get(&*name.name) {
+                    return Ok(TypeDefKind::Type(*ty));
+                }
                 let id = match self.type_lookup.get(&*name.name) {
                     Some(id) => *id,
                     None => {
+                        let msg = match self.generics.get(&*name.name) {
+                            Some(def) => format!(
+                                "generic type `{}` needs {} type argument(s)",
+                                name.name,
+                                def.params.len()
+                            ),
+                            None => format!("no type named `{}`", name.name),
+                        };
                         return Err(Error {
                             span: name.span,
-                            msg: format!("no type named `{}`", name.name),
+                            msg,
                         }
-                        .into())
+                        .into());
                     }
                 };
                 TypeDefKind::Type(Type::Id(id))
             }
+            super::Type::Generic(name, args) => TypeDefKind::Type(self.instantiate(name, args)?),
             super::Type::List(list) => {
                 let ty = self.resolve_type(list)?;
                 TypeDefKind::List(ty)
             }
+            super::Type::Array(ty, len) => {
+                let ty = self.resolve_type(ty)?;
+                TypeDefKind::Array(ty, *len)
+            }
             super::Type::Pointer(list) => {
                 let ty = self.resolve_type(list)?;
                 TypeDefKind::Pointer(ty)
@@ -374,14 +472,30 @@ impl Resolver {
                 TypeDefKind::PullBuffer(ty)
             }
             super::Type::Record(record) => {
+                let mut bits = HashSet::new();
+                let mut next = 0;
                 let fields = record
                     .fields
                     .iter()
                     .map(|field| {
+                        let (span, bit) = match field.bit {
+                            Some((span, bit)) => (span, bit),
+                            None => (field.name.span, next),
+                        };
+                        next = bit + 1;
+                        if !bits.insert(bit) {
+                            return Err(Error {
+                                span,
+                                msg: format!("bit {} is used by more than one flag", bit),
+                            }
+                            .into());
+                        }
                         Ok(Field {
                             docs: self.docs(&field.docs),
+                            attrs: self.attrs(&field.attrs),
                             name: field.name.name.to_string(),
                             ty: self.resolve_type(&field.ty)?,
+                            bit,
                         })
                     })
                     .collect::<Result<Vec<_>>>()?;
@@ -410,24 +524,54 @@ impl Resolver {
                     }
                     .into());
                 }
+                let mut discriminants = HashSet::new();
+                let mut next = Some(0u32);
                 let cases = variant
                     .cases
                     .iter()
                     .map(|case| {
+                        let (span, discriminant) = match (case.discriminant, next) {
+                            (Some((span, d)), _) => (span, d),
+                            (None, Some(d)) => (case.name.span, d),
+                            (None, None) => {
+                                return Err(Error {
+                                    span: case.name.span,
+                                    msg: "discriminant of this case doesn't fit in 32 bits"
+                                        .to_string(),
+                                }
+                                .into())
+                            }
+                        };
+                        if !discriminants.insert(discriminant) {
+                            return Err(Error {
+                                span,
+                                msg: format!(
+                                    "discriminant {} is used by more than one case",
+                                    discriminant
+                                ),
+                            }
+                            .into());
+                        }
+                        next = discriminant.checked_add(1);
                         Ok(Case {
                             docs: self.docs(&case.docs),
+                            attrs: self.attrs(&case.attrs),
                             name: case.name.name.to_string(),
                             ty: match &case.ty {
                                 Some(ty) => Some(self.resolve_type(ty)?),
                                 None => None,
                             },
+                            discriminant,
                         })
                     })
                     .collect::<Result<Vec<_>>>()?;
+                // The tag must be wide enough for the largest discriminant,
+                // which is one less than the number of cases by default.
+                let max = cases.iter().map(|c| c.discriminant).max().unwrap();
                 TypeDefKind::Variant(Variant {
                     tag: match &variant.tag {
                         Some(ty) => self.get_variant_tag(ty),
-                        None => Variant::infer_tag(cases.len()),
+                        None => Variant::infer_tag(max as usize + 1),
                     },
                     cases,
                 })
@@ -461,12 +605,174 @@ impl Resolver {
         }
     }
 
+    /// Monomorphises the generic type `name` with `args`.
+    ///
+    /// Each distinct instantiation is resolved once. Instantiations which
+    /// generators can refer to without a name, such as a `list<T>` or
+    /// `tuple<T, U>`, become anonymous types. Records, flags and variants need
+    /// a name, so they're named after the generic type and its arguments,
+    /// meaning that `page<user>` becomes a record named `page-user` which can
+    /// also be referred to by that name.
+    fn instantiate(&mut self, name: &super::Id<'_>, args: &[super::Type<'_>]) -> Result<Type> {
+        let def = match self.generics.get(&*name.name) {
+            Some(def) => *def,
+            None => {
+                let msg = if self.type_lookup.contains_key(&*name.name) {
+                    format!("type `{}` is not generic", name.name)
+                } else {
+                    format!("no type named `{}`", name.name)
+                };
+                return Err(Error {
+                    span: name.span,
+                    msg,
+                }
+                .into());
+            }
+        };
+        if args.len() != def.params.len() {
+            return Err(Error {
+                span: name.span,
+                msg: format!(
+                    "generic type `{}` needs {} type argument(s) but {} were given",
+                    name.name,
+                    def.params.len(),
+                    args.len()
+                ),
+            }
+            .into());
+        }
+        let args = args
+            .iter()
+            .map(|arg| self.resolve_type(arg))
+            .collect::<Result<Vec<_>>>()?;
+        let key = (name.name.to_string(), args);
+        if let Some(ty) = self.instances.get(&key) {
+            return Ok(*ty);
+        }
+
+        // Like all other types generic types can't be recursive, and without
+        // this check instantiating them could go on forever.
+        if !self.instantiating.insert(key.0.clone()) {
+            return Err(Error {
+                span: name.span,
+                msg: format!("generic type `{}` refers to itself", name.name),
+            }
+            .into());
+        }
+        let params = def
+            .params
+            .iter()
+            .map(|param| param.name.to_string())
+            .zip(key.1.iter().cloned())
+            .collect();
+        let prev = mem::replace(&mut self.type_params, params);
+        let kind = self.resolve_type_def(&def.ty);
+        self.type_params = prev;
+        self.instantiating.remove(&key.0);
+        let kind = kind?;
+
+        let needs_name = match &kind {
+            TypeDefKind::Record(r) => !r.is_tuple(),
+            TypeDefKind::Variant(v) => v.as_option().is_none() && v.as_expected().is_none(),
+            _ => false,
+        };
+        let ty = if needs_name {
+            let instance = key
+                .1
+                .iter()
+                .fold(key.0.clone(), |name, arg| name + "-" + &self.mangle(*arg));
+            if self.type_lookup.contains_key(&instance) {
+                return Err(Error {
+                    span: name.span,
+                    msg: format!(
+                        "instantiation `{}` conflicts with the type of the same name",
+                        instance
+                    ),
+                }
+                .into());
+            }
+            let docs = self.docs(&def.docs);
+            let attrs = self.attrs(&def.attrs);
+            let id = self.types.alloc(TypeDef {
+                docs,
+                attrs,
+                kind,
+                name: Some(instance.clone()),
+                span: def.name.span,
+                foreign_module: None,
+            });
+            self.type_lookup.insert(instance, id);
+            Type::Id(id)
+        } else {
+            self.anon_type_def(TypeDef {
+                kind,
+                name: None,
+                span: Span { start: 0, end: 0 },
+                docs: Docs::default(),
+                attrs: Attrs::default(),
+                foreign_module: None,
+            })
+        };
+        self.instances.insert(key, ty);
+        Ok(ty)
+    }
+
+    /// Returns a name for `ty` which can be used as part of an identifier.
+    fn mangle(&self, ty: Type) -> String {
+        let id = match ty {
+            Type::U8 => return "u8".to_string(),
+            Type::U16 => return "u16".to_string(),
+            Type::U32 => return "u32".to_string(),
+            Type::U64 => return "u64".to_string(),
+            Type::S8 => return "s8".to_string(),
+            Type::S16 => return "s16".to_string(),
+            Type::S32 => return "s32".to_string(),
+            Type::S64 => return "s64".to_string(),
+            Type::F32 => return "f32".to_string(),
+            Type::F64 => return "f64".to_string(),
+            Type::Char => return "char".to_string(),
+            Type::CChar => return "c-char".to_string(),
+            Type::Usize => return "usize".to_string(),
+            Type::Bool => return "bool".to_string(),
+            Type::String => return "string".to_string(),
+            Type::Handle(r) => return self.resources[r].name.clone(),
+            Type::Id(id) => id,
+        };
+        let ty = &self.types[id];
+        if let Some(name) = &ty.name {
+            return name.clone();
+        }
+        let join = |prefix: &str, tys: Vec<Option<Type>>| {
+            tys.into_iter()
+                .fold(prefix.to_string(), |name, ty| match ty {
+                    Some(ty) => name + "-" + &self.mangle(ty),
+                    None => name + "-unit",
+                })
+        };
+        match &ty.kind {
+            TypeDefKind::Type(t) => self.mangle(*t),
+            TypeDefKind::List(t) => join("list", vec![Some(*t)]),
+            TypeDefKind::Array(t, len) => format!("{}-{}", join("array", vec![Some(*t)]), len),
+            TypeDefKind::PushBuffer(t) => join("push-buffer", vec![Some(*t)]),
+            TypeDefKind::PullBuffer(t) => join("pull-buffer", vec![Some(*t)]),
+            TypeDefKind::Pointer(t) => join("pointer", vec![Some(*t)]),
+            TypeDefKind::ConstPointer(t) => join("const-pointer", vec![Some(*t)]),
+            TypeDefKind::Record(r) => join("tuple", r.fields.iter().map(|f| Some(f.ty)).collect()),
+            TypeDefKind::Variant(v) => match v.as_option() {
+                Some(t) => join("option", vec![Some(*t)]),
+                None => join("expected", v.cases.iter().map(|c| c.ty).collect()),
+            },
+        }
+    }
+
     fn resolve_type(&mut self, ty: &super::Type<'_>) -> Result<Type> {
         let kind = self.resolve_type_def(ty)?;
         Ok(self.anon_type_def(TypeDef {
             kind,
             name: None,
+            span: Span { start: 0, end: 0 },
             docs: Docs::default(),
+            attrs: Attrs::default(),
             foreign_module: None,
         }))
     }
@@ -487,6 +793,7 @@ impl Resolver {
                     .collect::<Vec<_>>(),
             ),
             TypeDefKind::List(ty) => Key::List(*ty),
+            TypeDefKind::Array(ty, len) => Key::Array(*ty, *len),
             TypeDefKind::Pointer(ty) => Key::Pointer(*ty),
             TypeDefKind::ConstPointer(ty) => Key::ConstPointer(*ty),
             TypeDefKind::PushBuffer(ty) => Key::PushBuffer(*ty),
@@ -502,7 +809,7 @@ impl Resolver {
 
     fn docs(&mut self, doc: &super::Docs<'_>) -> Docs {
         if doc.docs.is_empty() {
-            return Docs { contents: None };
+            return Docs::default();
         }
         let mut docs = String::new();
         for doc in doc.docs.iter() {
@@ -518,13 +825,26 @@ impl Resolver {
                 }
             }
         }
-        Docs {
-            contents: Some(docs),
+        Docs::new(docs)
+    }
+
+    fn attrs(&self, attrs: &[super::Attribute<'_>]) -> Attrs {
+        let mut ret = Attrs::default();
+        for attr in attrs {
+            ret.attrs
+                .entry(attr.name.name.to_string())
+                .or_insert_with(Vec::new)
+                .extend(attr.args.iter().map(|arg| AttrArg {
+                    name: arg.name.as_ref().map(|n| n.name.to_string()),
+                    value: arg.value.name.to_string(),
+                }));
         }
+        ret
     }
 
     fn resolve_value(&mut self, value: &Value<'_>) -> Result<()> {
         let docs = self.docs(&value.docs);
+        let attrs = self.attrs(&value.attrs);
         match &value.kind {
             ValueKind::Function {
                 is_async,
@@ -534,16 +854,18 @@ impl Resolver {
             } => {
                 let params = params
                     .iter()
-                    .map(|(name, ty)| Ok((name.name.to_string(), self.resolve_type(&ty)?)))
+                    .map(|(name, ty)| Ok((name.name.to_string(), self.resolve_type(ty)?)))
                     .collect::<Result<_>>()?;
                 let results = results
                     .iter()
-                    .map(|(name, ty)| Ok((name.name.to_string(), self.resolve_type(&ty)?)))
+                    .map(|(name, ty)| Ok((name.name.to_string(), self.resolve_type(ty)?)))
                     .collect::<Result<_>>()?;
                 self.functions.push(Function {
                     abi: *abi,
                     docs,
+                    attrs,
                     name: value.name.name.to_string(),
+                    span: value.name.span,
                     kind: FunctionKind::Freestanding,
                     params,
                     results,
@@ -554,7 +876,9 @@ impl Resolver {
                 let ty = self.resolve_type(ty)?;
                 self.globals.push(Global {
                     docs,
+                    attrs,
                     name: value.name.name.to_string(),
+                    span: value.name.span,
                     ty,
                 });
             }
@@ -562,64 +886,246 @@ impl Resolver {
         Ok(())
     }
 
-    fn resolve_resource(&mut self, resource: &super::Resource<'_>) -> Result<()> {
+    fn resolve_const(&mut self, c: &super::Const<'_>) -> Result<()> {
+        let ty = self.resolve_type(&c.ty)?;
+        let value = self.resolve_const_value(ty, &c.value, c.name.span)?;
+        let docs = self.docs(&c.docs);
+        let attrs = self.attrs(&c.attrs);
+        self.constants.push(Constant {
+            docs,
+            attrs,
+            name: c.name.name.to_string(),
+            span: c.name.span,
+            ty,
+            value,
+        });
+        Ok(())
+    }
+
+    fn resolve_const_value(
+        &self,
+        ty: Type,
+        value: &super::ConstValue<'_>,
+        name_span: Span,
+    ) -> Result<ConstValue> {
+        let (span, bare, string) = match value {
+            super::ConstValue::Bare(v) => (v.span, Some(&*v.name), None),
+            super::ConstValue::String(v) => (v.span, None, Some(&*v.name)),
+            super::ConstValue::Flags(_) => (name_span, None, None),
+        };
+        let invalid = |what: &str| -> anyhow::Error {
+            Error {
+                span,
+                msg: format!("expected {} for this constant", what),
+            }
+            .into()
+        };
+        let unsigned = |max: u64, what: &str| match bare.map(|v| v.parse::<u64>()) {
+            Some(Ok(n)) if n <= max => Ok(ConstValue::Unsigned(n)),
+            _ => Err(invalid(what)),
+        };
+        let signed = |min: i64, max: i64, what: &str| match bare.map(|v| v.parse::<i64>()) {
+            Some(Ok(n)) if min <= n && n <= max => Ok(ConstValue::Signed(n)),
+            _ => Err(invalid(what)),
+        };
+        let float = |max: f64| match bare.map(|v| v.parse::<f64>()) {
+            Some(Ok(n)) if n.is_finite() && n.abs() <= max => Ok(ConstValue::Float(n)),
+            _ => Err(invalid("a finite floating-point number")),
+        };
+
+        // Look through aliases to find what sort of value is needed.
+        let mut ty = ty;
+        while let Type::Id(id) = ty {
+            match &self.types[id].kind {
+                TypeDefKind::Type(t) => ty = *t,
+                _ => break,
+            }
+        }
+        match ty {
+            Type::U8 => unsigned(u8::MAX.into(), "a `u8`"),
+            Type::U16 => unsigned(u16::MAX.into(), "a `u16`"),
+            Type::U32 => unsigned(u32::MAX.into(), "a `u32`"),
+            Type::U64 => unsigned(u64::MAX, "a `u64`"),
+            Type::S8 => signed(i8::MIN.into(), i8::MAX.into(), "an `s8`"),
+            Type::S16 => signed(i16::MIN.into(), i16::MAX.into(), "an `s16`"),
+            Type::S32 => signed(i32::MIN.into(), i32::MAX.into(), "an `s32`"),
+            Type::S64 => signed(i64::MIN, i64::MAX, "an `s64`"),
+            Type::F32 => float(f32::MAX.into()),
+            Type::F64 => float(f64::MAX),
+            Type::Char => {
+                let mut chars = string.unwrap_or("").chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Ok(ConstValue::Char(c)),
+                    _ => Err(invalid("a string of one character")),
+                }
+            }
+            Type::Bool => match bare {
+                Some("true") => Ok(ConstValue::Bool(true)),
+                Some("false") => Ok(ConstValue::Bool(false)),
+                _ => Err(invalid("`true` or `false`")),
+            },
+            Type::String => match string {
+                Some(s) => Ok(ConstValue::String(s.to_string())),
+                None => Err(invalid("a string")),
+            },
+            Type::Id(id) => match &self.types[id].kind {
+                // Cases are written like identifiers, so may be quoted.
+                TypeDefKind::Variant(v) if v.is_enum() => match bare.or(string) {
+                    Some(case) if v.cases.iter().any(|c| c.name == case) => Ok(ConstValue::Enum {
+                        ty: id,
+                        case: case.to_string(),
+                    }),
+                    _ => Err(invalid("a case of this enum")),
+                },
+                TypeDefKind::Record(r) if r.is_flags() => {
+                    let names = match value {
+                        super::ConstValue::Flags(names) => names,
+                        _ => return Err(invalid("a set of flags like `{ a, b }`")),
+                    };
+                    let mut flags = Vec::new();
+                    for name in names {
+                        if !r.fields.iter().any(|f| f.name == name.name) {
+                            return Err(Error {
+                                span: name.span,
+                                msg: format!("no flag named `{}`", name.name),
+                            }
+                            .into());
+                        }
+                        flags.push(name.name.to_string());
+                    }
+                    Ok(ConstValue::Flags { ty: id, flags })
+                }
+                _ => Err(Error {
+                    span: name_span,
+                    msg: "constants of this type are not supported".to_string(),
+                }
+                .into()),
+            },
+            Type::Handle(_) | Type::CChar | Type::Usize => Err(Error {
+                span: name_span,
+                msg: "constants of this type are not supported".to_string(),
+            }
+            .into()),
+        }
+    }
+
+    fn resolve_resource(&mut self, resource: &super::Resource<'_>) {
         let mut names = HashSet::new();
         let id = self.resource_lookup[&*resource.name.name];
         for (statik, value) in resource.values.iter() {
-            let (abi, is_async, params, results) = match &value.kind {
-                ValueKind::Function {
-                    abi,
-                    is_async,
-                    params,
-                    results,
-                } => (*abi, *is_async, params, results),
-                ValueKind::Global(_) => {
-                    return Err(Error {
+            if !names.insert(&value.name.name) {
+                self.errors.push(
+                    Error {
                         span: value.name.span,
-                        msg: format!("globals not allowed in resources"),
+                        msg: format!("{:?} defined twice in this resource", value.name.name),
                     }
-                    .into());
-                }
-            };
-            if !names.insert(&value.name.name) {
+                    .into(),
+                );
+                continue;
+            }
+            if let Err(e) = self.resolve_resource_function(resource, id, *statik, value) {
+                self.errors.push(e);
+            }
+        }
+    }
+
+    fn resolve_resource_function(
+        &mut self,
+        resource: &super::Resource<'_>,
+        id: ResourceId,
+        statik: bool,
+        value: &Value<'_>,
+    ) -> Result<()> {
+        let (abi, is_async, params, results) = match &value.kind {
+            ValueKind::Function {
+                abi,
+                is_async,
+                params,
+                results,
+            } => (*abi, *is_async, params, results),
+            ValueKind::Global(_) => {
                 return Err(Error {
                     span: value.name.span,
-                    msg: format!("{:?} defined twice in this resource", value.name.name),
+                    msg: "globals not allowed in resources".to_string(),
                 }
                 .into());
             }
-            let docs = self.docs(&value.docs);
-            let mut params = params
-                .iter()
-                .map(|(name, ty)| Ok((name.name.to_string(), self.resolve_type(&ty)?)))
-                .collect::<Result<Vec<_>>>()?;
-            let results = results
-                .iter()
-                .map(|(name, ty)| Ok((name.name.to_string(), self.resolve_type(&ty)?)))
-                .collect::<Result<_>>()?;
-            let kind = if *statik {
-                FunctionKind::Static {
-                    resource: id,
-                    name: value.name.name.to_string(),
-                }
-            } else {
-                params.insert(0, ("self".to_string(), Type::Handle(id)));
-                FunctionKind::Method {
-                    resource: id,
-                    name: value.name.name.to_string(),
+        };
+        let docs = self.docs(&value.docs);
+        let attrs = self.attrs(&value.attrs);
+        let mut params = params
+            .iter()
+            .map(|(name, ty)| Ok((name.name.to_string(), self.resolve_type(ty)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let results = results
+            .iter()
+            .map(|(name, ty)| Ok((name.name.to_string(), self.resolve_type(ty)?)))
+            .collect::<Result<_>>()?;
+        let kind = if statik {
+            FunctionKind::Static {
+                resource: id,
+                name: value.name.name.to_string(),
+            }
+        } else {
+            params.insert(0, ("self".to_string(), Type::Handle(id)));
+            FunctionKind::Method {
+                resource: id,
+                name: value.name.name.to_string(),
+            }
+        };
+        self.functions.push(Function {
+            abi,
+            is_async,
+            docs,
+            attrs,
+            name: format!("{}::{}", resource.name.name, value.name.name),
+            span: value.name.span,
+            kind,
+            params,
+            results,
+        });
+        Ok(())
+    }
+
+    /// Reports types which are too large to lay out in a 32-bit memory, which
+    /// only large arrays can be.
+    ///
+    /// Named types are reported where they're defined, and anonymous types
+    /// where they're used by a function or global.
+    fn validate_sizes(&mut self, iface: &Interface) {
+        let mut sizes = SizeAlign::default();
+        sizes.fill(abi::Direction::Import, iface);
+        let too_large = |ty: &Type| sizes.size(ty) > u32::MAX as usize;
+        let anonymous = |ty: &Type| match ty {
+            Type::Id(id) => iface.types[*id].name.is_none(),
+            _ => true,
+        };
+        let mut spans = Vec::new();
+        for (id, ty) in iface.types.iter() {
+            if ty.name.is_some() && ty.foreign_module.is_none() && too_large(&Type::Id(id)) {
+                spans.push(ty.span);
+            }
+        }
+        for func in iface.functions.iter() {
+            let mut types = func.params.iter().chain(&func.results).map(|(_, ty)| ty);
+            if types.any(|ty| anonymous(ty) && too_large(ty)) {
+                spans.push(func.span);
+            }
+        }
+        for global in iface.globals.iter() {
+            if anonymous(&global.ty) && too_large(&global.ty) {
+                spans.push(global.span);
+            }
+        }
+        for span in spans {
+            self.errors.push(
+                Error {
+                    span,
+                    msg: "type is too large to fit in memory".to_string(),
                 }
-            };
-            self.functions.push(Function {
-                abi,
-                is_async,
-                docs,
-                name: format!("{}::{}", resource.name.name, value.name.name),
-                kind,
-                params,
-                results,
-            });
+                .into(),
+            );
         }
-        Ok(())
     }
 
     fn validate_type_not_recursive(
@@ -642,6 +1148,7 @@ impl Resolver {
 
         match &self.types[ty].kind {
             TypeDefKind::List(Type::Id(id))
+            | TypeDefKind::Array(Type::Id(id), _)
             | TypeDefKind::Pointer(Type::Id(id))
             | TypeDefKind::ConstPointer(Type::Id(id))
             | TypeDefKind::PushBuffer(Type::Id(id))
@@ -665,6 +1172,7 @@ impl Resolver {
             }
 
             TypeDefKind::List(_)
+            | TypeDefKind::Array(..)
             | TypeDefKind::Pointer(_)
             | TypeDefKind::ConstPointer(_)
             | TypeDefKind::PushBuffer(_)
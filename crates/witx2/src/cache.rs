@@ -0,0 +1,284 @@
+use crate::*;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// A cache of resolved interfaces, so that parsing the same modules again is
+/// free.
+///
+/// Interfaces are keyed by a hash of the path and contents of the file they
+/// were parsed from and of every module that file uses, directly or
+/// indirectly. Changing any of those files therefore changes the key, and the
+/// interface is parsed again the next time it's requested. The path is part
+/// of the key because interfaces remember the files they came from, see
+/// [`Interface::sources`]. Only interfaces which
+/// parse and resolve successfully are cached.
+#[derive(Clone, Default)]
+pub struct ParseCache {
+    /// The modules used by a file, keyed by the hash of its contents.
+    uses: HashMap<u64, Vec<String>>,
+    /// Resolved interfaces, keyed by the result of [`ParseCache::key`].
+    interfaces: HashMap<u64, Interface>,
+}
+
+impl ParseCache {
+    pub fn new() -> ParseCache {
+        ParseCache::default()
+    }
+
+    /// Same as [`Interface::parse_file_with`], except that any interfaces
+    /// already in this cache are reused.
+    pub fn parse_file(
+        &mut self,
+        path: impl AsRef<Path>,
+        resolver: &mut dyn Resolver,
+    ) -> Result<Interface> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read: {}", path.display()))?;
+        self.parse_with_resolver(path, &contents, resolver)
+    }
+
+    /// Same as [`Interface::parse_with_resolver`], except that any interfaces
+    /// already in this cache are reused.
+    pub fn parse_with_resolver(
+        &mut self,
+        filename: impl AsRef<Path>,
+        contents: &str,
+        resolver: &mut dyn Resolver,
+    ) -> Result<Interface> {
+        let mut visiting = HashSet::new();
+        let mut deps = HashMap::new();
+        let (key, ast) = self.key_and_ast(
+            filename.as_ref(),
+            contents,
+            resolver,
+            &mut visiting,
+            &mut deps,
+        )?;
+        self.resolve(filename.as_ref(), contents, key, ast, &deps)
+    }
+
+    /// Returns the key that the interface in `contents` is cached under.
+    ///
+    /// This loads every module which `contents` uses through `resolver`, but
+    /// doesn't resolve any of them. The key is the same in every process for
+    /// the same files, so it's also suitable as a key for caching things
+    /// derived from the interface, such as generated code, on disk.
+    pub fn key(
+        &mut self,
+        filename: impl AsRef<Path>,
+        contents: &str,
+        resolver: &mut dyn Resolver,
+    ) -> Result<u64> {
+        let mut visiting = HashSet::new();
+        let mut deps = HashMap::new();
+        let (key, _) = self.key_and_ast(
+            filename.as_ref(),
+            contents,
+            resolver,
+            &mut visiting,
+            &mut deps,
+        )?;
+        Ok(key)
+    }
+
+    /// Returns the number of interfaces in this cache.
+    pub fn len(&self) -> usize {
+        self.interfaces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.interfaces.is_empty()
+    }
+
+    /// Computes the key of the interface in `contents`, loading the modules
+    /// it uses into `deps`.
+    ///
+    /// The AST of `contents` is also returned if it had to be parsed to find
+    /// the modules it uses.
+    fn key_and_ast<'a>(
+        &mut self,
+        filename: &Path,
+        contents: &'a str,
+        load: &mut dyn Resolver,
+        visiting: &mut HashSet<PathBuf>,
+        deps: &mut HashMap<String, Dep>,
+    ) -> Result<(u64, Option<ast::Ast<'a>>)> {
+        let mut hasher = DefaultHasher::new();
+        contents.hash(&mut hasher);
+        let (uses, ast) = match self.uses.get(&hasher.finish()) {
+            Some(uses) => (uses.clone(), None),
+            None => {
+                let ast = parse_ast(filename, contents)?;
+                let uses = ast
+                    .items
+                    .iter()
+                    .filter_map(|item| match item {
+                        ast::Item::Use(u) => Some(u.from[0].name.to_string()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                self.uses.insert(hasher.finish(), uses.clone());
+                (uses, Some(ast))
+            }
+        };
+
+        // Mix the key of every module used into this interface's key, loading
+        // any which haven't been loaded yet.
+        if !visiting.insert(filename.to_path_buf()) {
+            bail!("file `{}` recursively imports itself", filename.display())
+        }
+        // Files which don't exist, such as those of a `MemoryResolver`, are
+        // keyed by the path they're given with.
+        match std::fs::canonicalize(filename) {
+            Ok(path) => path.hash(&mut hasher),
+            Err(_) => filename.hash(&mut hasher),
+        }
+        for name in uses {
+            if !deps.contains_key(&name) {
+                let (filename, contents) = load
+                    .load(&name)
+                    .with_context(|| format!("failed to load module `{}`", name))?;
+                // The AST borrows from `contents`, so it's parsed again if the
+                // module needs to be resolved.
+                let (key, _) = self.key_and_ast(&filename, &contents, load, visiting, deps)?;
+                let dep = Dep {
+                    filename,
+                    contents,
+                    key,
+                };
+                deps.insert(name.clone(), dep);
+            }
+            name.hash(&mut hasher);
+            deps[&name].key.hash(&mut hasher);
+        }
+        visiting.remove(filename);
+        Ok((hasher.finish(), ast))
+    }
+
+    /// Returns the interface with the `key` given, resolving it and any of
+    /// the modules in `deps` that it uses if they aren't cached.
+    fn resolve(
+        &mut self,
+        filename: &Path,
+        contents: &str,
+        key: u64,
+        ast: Option<ast::Ast<'_>>,
+        deps: &HashMap<String, Dep>,
+    ) -> Result<Interface> {
+        if let Some(iface) = self.interfaces.get(&key) {
+            return Ok(iface.clone());
+        }
+        let ast = match ast {
+            Some(ast) => ast,
+            None => parse_ast(filename, contents)?,
+        };
+        let mut map = HashMap::new();
+        for item in ast.items.iter() {
+            let name = match item {
+                ast::Item::Use(u) => &*u.from[0].name,
+                _ => continue,
+            };
+            if map.contains_key(name) {
+                continue;
+            }
+            let dep = &deps[name];
+            let iface = self.resolve(&dep.filename, &dep.contents, dep.key, None, deps)?;
+            map.insert(name.to_string(), iface);
+        }
+        let iface = match ast.resolve(module_name(filename), &map) {
+            Ok(mut i) => {
+                i.add_sources(filename, contents, &ast, &map);
+                i
+            }
+            Err(mut e) => {
+                let file = filename.display().to_string();
+                ast::rewrite_error(&mut e, &file, contents);
+                return Err(e);
+            }
+        };
+        self.interfaces.insert(key, iface.clone());
+        Ok(iface)
+    }
+}
+
+struct Dep {
+    filename: PathBuf,
+    contents: String,
+    key: u64,
+}
+
+fn parse_ast<'a>(filename: &Path, contents: &'a str) -> Result<ast::Ast<'a>> {
+    match ast::Ast::parse(contents) {
+        Ok(ast) => Ok(ast),
+        Err(mut e) => {
+            let file = filename.display().to_string();
+            ast::rewrite_error(&mut e, &file, contents);
+            Err(e)
+        }
+    }
+}
+
+fn module_name(filename: &Path) -> &str {
+    filename.file_stem().unwrap().to_str().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuses_interfaces() -> Result<()> {
+        let mut resolver = MemoryResolver::new();
+        resolver.insert("streams", "resource input-stream");
+        let mut cache = ParseCache::new();
+        let src = "use { input-stream } from streams\nread: function(s: input-stream)";
+        let iface = cache.parse_with_resolver("foo.witx", src, &mut resolver)?;
+        assert_eq!(iface.functions.len(), 1);
+        assert_eq!(cache.len(), 2);
+
+        // Parsing the same files again, from any module, resolves nothing new.
+        let key = cache.key("foo.witx", src, &mut resolver)?;
+        cache.parse_with_resolver("foo.witx", src, &mut resolver)?;
+        cache.parse_with_resolver("bar.witx", "use * from streams", &mut resolver)?;
+        assert_eq!(cache.len(), 3);
+
+        // Changing a module which is used changes the key.
+        resolver.insert("streams", "resource input-stream\nresource output-stream");
+        assert_ne!(cache.key("foo.witx", src, &mut resolver)?, key);
+        let iface = cache.parse_with_resolver("bar.witx", "use * from streams", &mut resolver)?;
+        assert_eq!(iface.resources.len(), 2);
+        assert_eq!(cache.len(), 5);
+        Ok(())
+    }
+
+    #[test]
+    fn same_contents_at_different_paths() -> Result<()> {
+        let mut resolver = MemoryResolver::new();
+        let mut cache = ParseCache::new();
+        let src = "foo: function()";
+        let a = cache.parse_with_resolver("a/foo.witx", src, &mut resolver)?;
+        let b = cache.parse_with_resolver("b/foo.witx", src, &mut resolver)?;
+        assert_eq!(cache.len(), 2);
+        assert_eq!(a.sources["foo"].path, Path::new("a/foo.witx"));
+        assert_eq!(b.sources["foo"].path, Path::new("b/foo.witx"));
+        Ok(())
+    }
+
+    #[test]
+    fn errors() {
+        let mut resolver = MemoryResolver::new();
+        resolver.insert("a", "use * from b");
+        resolver.insert("b", "use * from a");
+        let mut cache = ParseCache::new();
+        let err = match cache.parse_with_resolver("a.witx", "use * from b", &mut resolver) {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => format!("{:#}", e),
+        };
+        assert!(err.contains("recursively imports itself"));
+        assert!(cache
+            .parse_with_resolver("foo.witx", "foo: function(x: bar)", &mut resolver)
+            .is_err());
+        assert!(cache.is_empty());
+    }
+}
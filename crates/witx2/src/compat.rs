@@ -0,0 +1,852 @@
+//! Comparing two versions of an interface for backwards compatibility.
+//!
+//! [`diff`] walks the items of two resolved interfaces, matching them up by
+//! name, and reports each difference along with whether it's compatible with
+//! code built against the old version of the interface or not.
+
+use crate::abi::{Direction, MultiValue};
+use crate::printer::Names;
+use crate::*;
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+
+/// A single difference between two versions of an interface.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Change {
+    pub compat: Compat,
+    /// The item that changed, such as ``function `foo` `` or ``type `bar` ``.
+    pub item: String,
+    pub message: String,
+}
+
+/// Whether a [`Change`] can break code built against the old interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compat {
+    Compatible,
+    Breaking,
+}
+
+impl Change {
+    pub fn is_breaking(&self) -> bool {
+        self.compat == Compat::Breaking
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let compat = match self.compat {
+            Compat::Compatible => "compatible",
+            Compat::Breaking => "breaking",
+        };
+        write!(f, "{}: {}: {}", compat, self.item, self.message)
+    }
+}
+
+/// Returns all differences between `old` and `new`.
+///
+/// Types, resources, functions, globals and constants are matched up by
+/// name. Types
+/// referred to by name are compared by name, and each named type is then
+/// compared on its own, including its size and alignment in both directions.
+/// Functions are additionally compared by their WebAssembly signature, so
+/// changes which don't alter anything at the source level but do alter the
+/// ABI are still reported. Cases added to a variant are only compatible if
+/// no function result or global can hold the variant, since code built
+/// against the old interface traps on discriminants it doesn't know.
+pub fn diff(old: &Interface, new: &Interface) -> Vec<Change> {
+    let mut cx = Diff {
+        old,
+        new,
+        old_names: Names::new(old),
+        new_names: Names::new(new),
+        returned: returned(new),
+        old_sizes: sizes(old),
+        new_sizes: sizes(new),
+        changes: Vec::new(),
+    };
+    cx.resources();
+    cx.types();
+    cx.functions();
+    cx.globals();
+    cx.constants();
+    cx.changes
+}
+
+fn sizes(iface: &Interface) -> [SizeAlign; 2] {
+    let mut import = SizeAlign::default();
+    import.fill(Direction::Import, iface);
+    let mut export = SizeAlign::default();
+    export.fill(Direction::Export, iface);
+    [import, export]
+}
+
+const DIRECTIONS: [(Direction, &str); 2] = [
+    (Direction::Import, "imported"),
+    (Direction::Export, "exported"),
+];
+
+struct Diff<'a> {
+    old: &'a Interface,
+    new: &'a Interface,
+    old_names: Names<'a>,
+    new_names: Names<'a>,
+    /// Types of `new` which are reachable from function results or globals,
+    /// and so can be handed to code which only knows the old interface.
+    returned: HashSet<TypeId>,
+    old_sizes: [SizeAlign; 2],
+    new_sizes: [SizeAlign; 2],
+    changes: Vec<Change>,
+}
+
+impl Diff<'_> {
+    fn push(&mut self, compat: Compat, item: &str, message: impl Into<String>) {
+        self.changes.push(Change {
+            compat,
+            item: item.to_string(),
+            message: message.into(),
+        });
+    }
+
+    fn resources(&mut self) {
+        let names = names(&self.old.resource_lookup, &self.new.resource_lookup);
+        for name in names {
+            let item = format!("resource `{}`", name);
+            match (
+                self.old.resource_lookup.get(name),
+                self.new.resource_lookup.get(name),
+            ) {
+                (Some(_), None) => self.push(Compat::Breaking, &item, "removed"),
+                (None, Some(_)) => self.push(Compat::Compatible, &item, "added"),
+                _ => {}
+            }
+        }
+    }
+
+    fn types(&mut self) {
+        let names = names(&self.old.type_lookup, &self.new.type_lookup);
+        for name in names {
+            // Resources also have an entry in `type_lookup` for their
+            // handle type, but they're compared separately.
+            if self.old.resource_lookup.contains_key(name)
+                || self.new.resource_lookup.contains_key(name)
+            {
+                continue;
+            }
+            let item = format!("type `{}`", name);
+            let (old, new) = match (
+                self.old.type_lookup.get(name),
+                self.new.type_lookup.get(name),
+            ) {
+                (Some(old), Some(new)) => (*old, *new),
+                (Some(_), None) => {
+                    self.push(Compat::Breaking, &item, "removed");
+                    continue;
+                }
+                (None, Some(_)) => {
+                    self.push(Compat::Compatible, &item, "added");
+                    continue;
+                }
+                (None, None) => unreachable!(),
+            };
+            self.typedef(&item, old, new);
+        }
+    }
+
+    fn typedef(&mut self, item: &str, old_id: TypeId, new_id: TypeId) {
+        let changes = self.changes.len();
+        let old = &self.old.types[old_id];
+        let new = &self.new.types[new_id];
+        match (&old.kind, &new.kind) {
+            (TypeDefKind::Record(a), TypeDefKind::Record(b))
+                if a.is_flags() == b.is_flags() && a.is_tuple() == b.is_tuple() =>
+            {
+                self.record(item, a, b)
+            }
+            (TypeDefKind::Variant(a), TypeDefKind::Variant(b)) if !self.is_sugar(a, b) => {
+                let returned = self.returned.contains(&new_id);
+                self.variant(item, a, b, returned)
+            }
+            _ => {
+                let a = self.old_names.kind(&old.kind);
+                let b = self.new_names.kind(&new.kind);
+                if a != b {
+                    self.push(
+                        Compat::Breaking,
+                        item,
+                        format!("changed from `{}` to `{}`", a, b),
+                    );
+                }
+            }
+        }
+
+        // Layout changes are almost always a consequence of the changes
+        // reported above, but they're also what actually breaks existing
+        // compiled code so they're always reported.
+        let mut layouts = Vec::new();
+        for (i, (_, dir)) in DIRECTIONS.iter().enumerate() {
+            let a = (
+                self.old_sizes[i].size(&Type::Id(old_id)),
+                self.old_sizes[i].align(&Type::Id(old_id)),
+            );
+            let b = (
+                self.new_sizes[i].size(&Type::Id(new_id)),
+                self.new_sizes[i].align(&Type::Id(new_id)),
+            );
+            if a != b {
+                layouts.push((*dir, a, b));
+            }
+        }
+        let same_in_both =
+            layouts.len() == 2 && layouts[0].1 == layouts[1].1 && layouts[0].2 == layouts[1].2;
+        if same_in_both {
+            layouts.truncate(1);
+        }
+        let layout_changed = !layouts.is_empty();
+        for (dir, (old_size, old_align), (new_size, new_align)) in layouts {
+            let when = if same_in_both {
+                String::new()
+            } else {
+                format!(" when {}", dir)
+            };
+            self.push(
+                Compat::Breaking,
+                item,
+                format!(
+                    "layout{} changed from size {} align {} to size {} align {}",
+                    when, old_size, old_align, new_size, new_align
+                ),
+            );
+        }
+
+        // Anything which changed in a compatible way but also changed the
+        // layout of the type isn't actually compatible.
+        if layout_changed {
+            for change in self.changes[changes..].iter_mut() {
+                change.compat = Compat::Breaking;
+            }
+        }
+    }
+
+    /// Returns whether either variant is written with syntax sugar such as
+    /// `option<T>`, in which case they're compared as a whole.
+    fn is_sugar(&self, a: &Variant, b: &Variant) -> bool {
+        let sugar = |v: &Variant| v.as_option().is_some() || v.as_expected().is_some();
+        sugar(a) || sugar(b)
+    }
+
+    fn record(&mut self, item: &str, old: &Record, new: &Record) {
+        if old.is_tuple() {
+            let a = self.old_names.record(old);
+            let b = self.new_names.record(new);
+            if a != b {
+                self.push(
+                    Compat::Breaking,
+                    item,
+                    format!("changed from `{}` to `{}`", a, b),
+                );
+            }
+            return;
+        }
+
+        let what = if old.is_flags() { "flag" } else { "field" };
+        let mut removed = false;
+        let mut renumbered = false;
+        for field in old.fields.iter() {
+            match new.fields.iter().find(|f| f.name == field.name) {
+                Some(f) => {
+                    if old.is_flags() && field.bit != f.bit {
+                        renumbered = true;
+                        self.push(
+                            Compat::Breaking,
+                            item,
+                            format!(
+                                "bit of flag `{}` changed from {} to {}",
+                                field.name, field.bit, f.bit
+                            ),
+                        );
+                    }
+                    let a = self.old_names.ty(&field.ty);
+                    let b = self.new_names.ty(&f.ty);
+                    if a != b {
+                        self.push(
+                            Compat::Breaking,
+                            item,
+                            format!(
+                                "type of field `{}` changed from `{}` to `{}`",
+                                field.name, a, b
+                            ),
+                        );
+                    }
+                }
+                None => {
+                    removed = true;
+                    self.push(
+                        Compat::Breaking,
+                        item,
+                        format!("{} `{}` removed", what, field.name),
+                    );
+                }
+            }
+        }
+        for field in new.fields.iter() {
+            if old.fields.iter().any(|f| f.name == field.name) {
+                continue;
+            }
+            // New flags with unused bits don't change the meaning of existing
+            // bits, but new record fields always change the layout.
+            let compat = if old.is_flags() && old.fields.iter().all(|f| f.bit != field.bit) {
+                Compat::Compatible
+            } else {
+                Compat::Breaking
+            };
+            self.push(compat, item, format!("{} `{}` added", what, field.name));
+        }
+        if !removed && !renumbered && !in_order(&old.fields, &new.fields, |f| &f.name) {
+            // Flags are identified by their bits rather than their order.
+            let compat = if old.is_flags() {
+                Compat::Compatible
+            } else {
+                Compat::Breaking
+            };
+            self.push(compat, item, format!("{}s reordered", what));
+        }
+    }
+
+    fn variant(&mut self, item: &str, old: &Variant, new: &Variant, returned: bool) {
+        if old.tag != new.tag {
+            let int = |i: Int| format!("{:?}", i).to_lowercase();
+            self.push(
+                Compat::Breaking,
+                item,
+                format!("tag changed from {} to {}", int(old.tag), int(new.tag)),
+            );
+        }
+        let mut removed = false;
+        let mut renumbered = false;
+        for case in old.cases.iter() {
+            match new.cases.iter().find(|c| c.name == case.name) {
+                Some(c) => {
+                    if case.discriminant != c.discriminant {
+                        renumbered = true;
+                        self.push(
+                            Compat::Breaking,
+                            item,
+                            format!(
+                                "discriminant of case `{}` changed from {} to {}",
+                                case.name, case.discriminant, c.discriminant
+                            ),
+                        );
+                    }
+                    let a = case.ty.as_ref().map(|t| self.old_names.ty(t));
+                    let b = c.ty.as_ref().map(|t| self.new_names.ty(t));
+                    if a != b {
+                        let ty = |t: Option<String>| t.unwrap_or_else(|| "_".to_string());
+                        self.push(
+                            Compat::Breaking,
+                            item,
+                            format!(
+                                "payload of case `{}` changed from `{}` to `{}`",
+                                case.name,
+                                ty(a),
+                                ty(b)
+                            ),
+                        );
+                    }
+                }
+                None => {
+                    removed = true;
+                    self.push(
+                        Compat::Breaking,
+                        item,
+                        format!("case `{}` removed", case.name),
+                    );
+                }
+            }
+        }
+        for case in new.cases.iter() {
+            if old.cases.iter().any(|c| c.name == case.name) {
+                continue;
+            }
+            // Cases with an unused discriminant don't change the meaning of
+            // any existing values, but old code receiving one still traps.
+            let reused = old
+                .cases
+                .iter()
+                .any(|c| c.discriminant == case.discriminant);
+            if reused || old.tag != new.tag {
+                self.push(
+                    Compat::Breaking,
+                    item,
+                    format!("case `{}` added", case.name),
+                );
+            } else if returned {
+                self.push(
+                    Compat::Breaking,
+                    item,
+                    format!("case `{}` added to a type which is returned", case.name),
+                );
+            } else {
+                self.push(
+                    Compat::Compatible,
+                    item,
+                    format!("case `{}` added", case.name),
+                );
+            }
+        }
+        if !removed && !renumbered && !in_order(&old.cases, &new.cases, |c| &c.name) {
+            // Cases are identified by their discriminants rather than their
+            // order, which is only visible in generated code.
+            self.push(Compat::Compatible, item, "cases reordered");
+        }
+    }
+
+    fn functions(&mut self) {
+        let mut names = BTreeSet::new();
+        names.extend(self.old.functions.iter().map(|f| &f.name));
+        names.extend(self.new.functions.iter().map(|f| &f.name));
+        for name in names {
+            let item = format!("function `{}`", name);
+            let old = self.old.functions.iter().find(|f| f.name == *name);
+            let new = self.new.functions.iter().find(|f| f.name == *name);
+            match (old, new) {
+                (Some(old), Some(new)) => self.function(&item, old, new),
+                (Some(_), None) => self.push(Compat::Breaking, &item, "removed"),
+                (None, Some(_)) => self.push(Compat::Compatible, &item, "added"),
+                (None, None) => unreachable!(),
+            }
+        }
+    }
+
+    fn function(&mut self, item: &str, old: &Function, new: &Function) {
+        let kind = |f: &Function| match f.kind {
+            FunctionKind::Freestanding => "a freestanding function",
+            FunctionKind::Static { .. } => "a static function",
+            FunctionKind::Method { .. } => "a method",
+        };
+        if kind(old) != kind(new) {
+            self.push(
+                Compat::Breaking,
+                item,
+                format!("changed from {} to {}", kind(old), kind(new)),
+            );
+        }
+        if old.abi != new.abi {
+            self.push(
+                Compat::Breaking,
+                item,
+                format!("abi changed from {:?} to {:?}", old.abi, new.abi),
+            );
+        }
+        if old.is_async != new.is_async {
+            let msg = if new.is_async {
+                "made async"
+            } else {
+                "no longer async"
+            };
+            self.push(Compat::Breaking, item, msg);
+        }
+        self.values(item, "parameter", &old.params, &new.params);
+        self.values(item, "result", &old.results, &new.results);
+
+        for (dir, name) in DIRECTIONS.iter() {
+            let a = self.old.wasm_signature(*dir, MultiValue::Disabled, old);
+            let b = self.new.wasm_signature(*dir, MultiValue::Disabled, new);
+            if a != b {
+                self.push(
+                    Compat::Breaking,
+                    item,
+                    format!(
+                        "wasm signature when {} changed from `{}` to `{}`",
+                        name,
+                        signature(&a),
+                        signature(&b)
+                    ),
+                );
+            }
+        }
+    }
+
+    fn values(&mut self, item: &str, what: &str, old: &[(String, Type)], new: &[(String, Type)]) {
+        if old.len() != new.len() {
+            self.push(
+                Compat::Breaking,
+                item,
+                format!(
+                    "number of {}s changed from {} to {}",
+                    what,
+                    old.len(),
+                    new.len()
+                ),
+            );
+            return;
+        }
+        for (i, ((a_name, a), (b_name, b))) in old.iter().zip(new).enumerate() {
+            let a = self.old_names.ty(a);
+            let b = self.new_names.ty(b);
+            if a != b {
+                self.push(
+                    Compat::Breaking,
+                    item,
+                    format!("type of {} {} changed from `{}` to `{}`", what, i, a, b),
+                );
+            }
+            // Names of parameters and results aren't part of the ABI.
+            if a_name != b_name {
+                self.push(
+                    Compat::Compatible,
+                    item,
+                    format!("{} {} renamed from `{}` to `{}`", what, i, a_name, b_name),
+                );
+            }
+        }
+    }
+
+    fn globals(&mut self) {
+        let mut names = BTreeSet::new();
+        names.extend(self.old.globals.iter().map(|g| &g.name));
+        names.extend(self.new.globals.iter().map(|g| &g.name));
+        for name in names {
+            let item = format!("global `{}`", name);
+            let old = self.old.globals.iter().find(|g| g.name == *name);
+            let new = self.new.globals.iter().find(|g| g.name == *name);
+            match (old, new) {
+                (Some(old), Some(new)) => {
+                    let a = self.old_names.ty(&old.ty);
+                    let b = self.new_names.ty(&new.ty);
+                    if a != b {
+                        self.push(
+                            Compat::Breaking,
+                            &item,
+                            format!("type changed from `{}` to `{}`", a, b),
+                        );
+                    }
+                }
+                (Some(_), None) => self.push(Compat::Breaking, &item, "removed"),
+                (None, Some(_)) => self.push(Compat::Compatible, &item, "added"),
+                (None, None) => unreachable!(),
+            }
+        }
+    }
+
+    fn constants(&mut self) {
+        let mut names = BTreeSet::new();
+        names.extend(self.old.constants.iter().map(|c| &c.name));
+        names.extend(self.new.constants.iter().map(|c| &c.name));
+        for name in names {
+            let item = format!("const `{}`", name);
+            let old = self.old.constants.iter().find(|c| c.name == *name);
+            let new = self.new.constants.iter().find(|c| c.name == *name);
+            match (old, new) {
+                (Some(old), Some(new)) => {
+                    let a = self.old_names.ty(&old.ty);
+                    let b = self.new_names.ty(&new.ty);
+                    if a != b {
+                        self.push(
+                            Compat::Breaking,
+                            &item,
+                            format!("type changed from `{}` to `{}`", a, b),
+                        );
+                        continue;
+                    }
+                    // Constants are baked into code generated from the
+                    // interface, so both sides must agree on their value.
+                    let a = describe_value(&old.value);
+                    let b = describe_value(&new.value);
+                    if a != b {
+                        self.push(
+                            Compat::Breaking,
+                            &item,
+                            format!("value changed from `{}` to `{}`", a, b),
+                        );
+                    }
+                }
+                (Some(_), None) => self.push(Compat::Breaking, &item, "removed"),
+                (None, Some(_)) => self.push(Compat::Compatible, &item, "added"),
+                (None, None) => unreachable!(),
+            }
+        }
+    }
+}
+
+fn describe_value(value: &ConstValue) -> String {
+    match value {
+        ConstValue::Bool(b) => b.to_string(),
+        ConstValue::Unsigned(n) => n.to_string(),
+        ConstValue::Signed(n) => n.to_string(),
+        ConstValue::Float(n) => format!("{:?}", n),
+        ConstValue::Char(c) => format!("{:?}", c),
+        ConstValue::String(s) => format!("{:?}", s),
+        ConstValue::Enum { case, .. } => case.clone(),
+        // The order flags are written in doesn't matter.
+        ConstValue::Flags { flags, .. } => {
+            let flags = flags.iter().collect::<BTreeSet<_>>();
+            let flags = flags.into_iter().map(|f| f.as_str()).collect::<Vec<_>>();
+            format!("{{{}}}", flags.join(", "))
+        }
+    }
+}
+
+fn names<'a, T>(a: &'a HashMap<String, T>, b: &'a HashMap<String, T>) -> BTreeSet<&'a String> {
+    a.keys().chain(b.keys()).collect()
+}
+
+/// Returns the types reachable from the results of functions and from
+/// globals in `iface`.
+fn returned(iface: &Interface) -> HashSet<TypeId> {
+    fn visit(iface: &Interface, ty: &Type, set: &mut HashSet<TypeId>) {
+        let id = match ty {
+            Type::Id(id) => *id,
+            _ => return,
+        };
+        if !set.insert(id) {
+            return;
+        }
+        match &iface.types[id].kind {
+            TypeDefKind::Type(t)
+            | TypeDefKind::List(t)
+            | TypeDefKind::Array(t, _)
+            | TypeDefKind::PushBuffer(t)
+            | TypeDefKind::PullBuffer(t)
+            | TypeDefKind::Pointer(t)
+            | TypeDefKind::ConstPointer(t) => visit(iface, t, set),
+            TypeDefKind::Record(r) => {
+                for field in r.fields.iter() {
+                    visit(iface, &field.ty, set);
+                }
+            }
+            TypeDefKind::Variant(v) => {
+                for case in v.cases.iter() {
+                    if let Some(t) = &case.ty {
+                        visit(iface, t, set);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut set = HashSet::new();
+    for func in iface.functions.iter() {
+        for (_, ty) in func.results.iter() {
+            visit(iface, ty, &mut set);
+        }
+    }
+    for global in iface.globals.iter() {
+        visit(iface, &global.ty, &mut set);
+    }
+    set
+}
+
+/// Returns whether the items common to `old` and `new` appear in the same
+/// order in both.
+fn in_order<T>(old: &[T], new: &[T], name: impl Fn(&T) -> &String) -> bool {
+    let old_common = old
+        .iter()
+        .map(&name)
+        .filter(|n| new.iter().any(|o| name(o) == *n));
+    let new_common = new
+        .iter()
+        .map(&name)
+        .filter(|n| old.iter().any(|o| name(o) == *n));
+    old_common.eq(new_common)
+}
+
+fn signature(sig: &abi::WasmSignature) -> String {
+    let tys = |tys: &[abi::WasmType]| {
+        tys.iter()
+            .map(|t| format!("{:?}", t).to_lowercase())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    format!("({}) -> ({})", tys(&sig.params), tys(&sig.results))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn changes(old: &str, new: &str) -> Vec<String> {
+        let old = Interface::parse("old", old).unwrap();
+        let new = Interface::parse("new", new).unwrap();
+        diff(&old, &new).iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn identical() {
+        let src = "
+            record r { a: u32, b: string }
+            resource x { m: function() -> option<r> }
+            f: function(a: r, b: handle x) -> expected<u32, _>
+            g: u32
+        ";
+        assert!(changes(src, src).is_empty());
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(
+            changes(
+                "a: function()\nb: function(x: u32)",
+                "b: function(y: u32)\nc: function()"
+            ),
+            [
+                "breaking: function `a`: removed",
+                "compatible: function `b`: parameter 0 renamed from `x` to `y`",
+                "compatible: function `c`: added",
+            ]
+        );
+        assert_eq!(
+            changes("a: function(x: u32)", "a: function(x: u64)"),
+            [
+                "breaking: function `a`: type of parameter 0 changed from `u32` to `u64`",
+                "breaking: function `a`: wasm signature when imported changed from `(i32) -> ()` to `(i64) -> ()`",
+                "breaking: function `a`: wasm signature when exported changed from `(i32) -> ()` to `(i64) -> ()`",
+            ]
+        );
+        assert_eq!(
+            changes("a: function()", "a: async function()")[0],
+            "breaking: function `a`: made async"
+        );
+    }
+
+    #[test]
+    fn records() {
+        assert_eq!(
+            changes("record r { a: u32, b: u8 }", "record r { b: u8, a: u32 }"),
+            ["breaking: type `r`: fields reordered"]
+        );
+        assert_eq!(
+            changes("record r { a: u32 }", "record r { a: u32, b: u64 }"),
+            [
+                "breaking: type `r`: field `b` added",
+                "breaking: type `r`: layout changed from size 4 align 4 to size 16 align 8",
+            ]
+        );
+    }
+
+    #[test]
+    fn variants() {
+        assert_eq!(
+            changes("enum e { a, b }", "enum e { a, b, c }"),
+            ["compatible: type `e`: case `c` added"]
+        );
+        assert_eq!(
+            changes("enum e { a, b }", "enum e { c, a, b }"),
+            [
+                "breaking: type `e`: discriminant of case `a` changed from 0 to 1",
+                "breaking: type `e`: discriminant of case `b` changed from 1 to 2",
+                "breaking: type `e`: case `c` added",
+            ]
+        );
+        assert_eq!(
+            changes("enum e { a, b }", "enum e { c = 2, b = 1, a = 0 }"),
+            [
+                "compatible: type `e`: case `c` added",
+                "compatible: type `e`: cases reordered",
+            ]
+        );
+        assert_eq!(
+            changes("variant v { a(u8) }", "variant v { a(u64), b }"),
+            [
+                "breaking: type `v`: payload of case `a` changed from `u8` to `u64`",
+                "breaking: type `v`: case `b` added",
+                "breaking: type `v`: layout changed from size 2 align 1 to size 16 align 8",
+            ]
+        );
+    }
+
+    #[test]
+    fn variant_positions() {
+        assert_eq!(
+            changes(
+                "enum e { a }\nf: function(x: e)",
+                "enum e { a, b }\nf: function(x: e)"
+            ),
+            ["compatible: type `e`: case `b` added"]
+        );
+        assert_eq!(
+            changes(
+                "enum e { a }\nrecord r { x: e }\nf: function() -> list<r>",
+                "enum e { a, b }\nrecord r { x: e }\nf: function() -> list<r>"
+            ),
+            ["breaking: type `e`: case `b` added to a type which is returned"]
+        );
+        assert_eq!(
+            changes("enum e { a }\ng: e", "enum e { a, b }\ng: e"),
+            ["breaking: type `e`: case `b` added to a type which is returned"]
+        );
+    }
+
+    #[test]
+    fn variant_tags() {
+        let cases = |n: usize| (0..n).map(|i| format!("c{}", i)).collect::<Vec<_>>();
+        // The layout stays the same since the payload is more aligned than
+        // either tag.
+        let old = format!("variant v {{ x(u64), {} }}", cases(254).join(", "));
+        let new = format!("variant v {{ x(u64), {} }}", cases(255).join(", "));
+        assert_eq!(
+            changes(&old, &new),
+            [
+                "breaking: type `v`: tag changed from u8 to u16",
+                "breaking: type `v`: case `c254` added",
+            ]
+        );
+    }
+
+    #[test]
+    fn flags() {
+        assert_eq!(
+            changes("flags f { a, b }", "flags f { a, b, c }"),
+            ["compatible: type `f`: flag `c` added"]
+        );
+        assert_eq!(
+            changes("flags f { a }", "flags f { a, b, c, d, e, f, g, h, i }"),
+            [
+                "breaking: type `f`: flag `b` added",
+                "breaking: type `f`: flag `c` added",
+                "breaking: type `f`: flag `d` added",
+                "breaking: type `f`: flag `e` added",
+                "breaking: type `f`: flag `f` added",
+                "breaking: type `f`: flag `g` added",
+                "breaking: type `f`: flag `h` added",
+                "breaking: type `f`: flag `i` added",
+                "breaking: type `f`: layout changed from size 1 align 1 to size 2 align 2",
+            ]
+        );
+        assert_eq!(
+            changes("flags f { a, b }", "flags f { b = 0x2, a = 0x4 }"),
+            ["breaking: type `f`: bit of flag `a` changed from 0 to 2"]
+        );
+    }
+
+    #[test]
+    fn types_resources_and_globals() {
+        assert_eq!(
+            changes(
+                "type a = u32\nresource r\nx: u32",
+                "type a = string\nresource s\nx: s32"
+            ),
+            [
+                "breaking: resource `r`: removed",
+                "compatible: resource `s`: added",
+                "breaking: type `a`: changed from `u32` to `string`",
+                "breaking: type `a`: layout changed from size 4 align 4 to size 8 align 4",
+                "breaking: global `x`: type changed from `u32` to `s32`",
+            ]
+        );
+    }
+
+    #[test]
+    fn constants() {
+        assert_eq!(
+            changes(
+                "flags f { a, b }\nconst x: u32 = 1\nconst y: f = { a, b }\nconst z: u8 = 1",
+                "flags f { a, b }\nconst x: u32 = 2\nconst y: f = { b, a }\nconst z: u16 = 1\nconst w: bool = true"
+            ),
+            [
+                "compatible: const `w`: added",
+                "breaking: const `x`: value changed from `1` to `2`",
+                "breaking: const `z`: type changed from `u8` to `u16`",
+            ]
+        );
+    }
+}
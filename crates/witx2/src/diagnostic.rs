@@ -0,0 +1,85 @@
+use crate::ast::{self, Span};
+use std::path::{Path, PathBuf};
+
+/// A problem found while parsing, resolving or validating an interface.
+///
+/// These are produced by [`Interface::parse_with_diagnostics`] which, unlike
+/// [`Interface::parse_with`], keeps going after the first error, and by
+/// [`Interface::validate`].
+///
+/// [`Interface::parse_with_diagnostics`]: crate::Interface::parse_with_diagnostics
+/// [`Interface::parse_with`]: crate::Interface::parse_with
+/// [`Interface::validate`]: crate::Interface::validate
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The file that `span` points into.
+    pub file: PathBuf,
+    /// The range of bytes within `file` this diagnostic refers to.
+    pub span: Span,
+    pub message: String,
+    /// Extra context for this diagnostic, such as the underlying cause of a
+    /// module failing to load.
+    pub notes: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Diagnostic {
+    pub(crate) fn error(file: &Path, span: Span, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            file: file.to_path_buf(),
+            span,
+            message: message.into(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub(crate) fn warning(file: &Path, span: Span, message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(file, span, message)
+        }
+    }
+
+    pub(crate) fn from_error(file: &Path, err: anyhow::Error) -> Diagnostic {
+        match ast::error_span(&err) {
+            Some((span, message)) => Diagnostic::error(file, span, message),
+            None => {
+                let mut diagnostic =
+                    Diagnostic::error(file, Span { start: 0, end: 0 }, err.to_string());
+                diagnostic.notes = err.chain().skip(1).map(|e| e.to_string()).collect();
+                diagnostic
+            }
+        }
+    }
+
+    /// Renders this diagnostic with a snippet of the source it points at,
+    /// in the same format as the errors returned by
+    /// [`Interface::parse_with`](crate::Interface::parse_with).
+    ///
+    /// The `contents` must be the contents of `self.file`.
+    pub fn render(&self, contents: &str) -> String {
+        let message = match self.severity {
+            Severity::Error => self.message.clone(),
+            Severity::Warning => format!("warning: {}", self.message),
+        };
+        let mut msg = ast::highlight_err(
+            self.span.start as usize,
+            Some(self.span.end as usize),
+            &self.file.display().to_string(),
+            contents,
+            message,
+        );
+        for note in self.notes.iter() {
+            msg.push_str("\n      = note: ");
+            msg.push_str(note);
+        }
+        msg
+    }
+}
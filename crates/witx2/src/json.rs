@@ -0,0 +1,553 @@
+//! A stable, versioned representation of resolved interfaces which can be
+//! serialized with `serde`.
+//!
+//! The types in this module mirror the ones at the root of this crate, but
+//! are meant for tools which don't link against this crate, for example
+//! because they're written in another language. Types and resources refer to
+//! each other by their index in [`Interface::types`] and
+//! [`Interface::resources`], and everything that a code generator would
+//! otherwise have to compute itself, such as the size of types and the
+//! WebAssembly signature of functions, is included.
+//!
+//! Any change to the serialized form of these types is accompanied by a bump
+//! of [`VERSION`].
+
+use crate::abi::{self, Direction, MultiValue};
+use crate::SizeAlign;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The version of the format produced by this module, stored in
+/// [`Interface::version`].
+pub const VERSION: u32 = 6;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Interface {
+    pub version: u32,
+    pub name: String,
+    pub resources: Vec<Resource>,
+    pub types: Vec<TypeDef>,
+    pub functions: Vec<Function>,
+    pub globals: Vec<Global>,
+    pub constants: Vec<Constant>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Resource {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docs: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attrs: Attrs,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub foreign_module: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TypeDef {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docs: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attrs: Attrs,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub foreign_module: Option<String>,
+    pub kind: TypeDefKind,
+    /// The size and alignment of this type in linear memory.
+    pub layout: PerDirection<Layout>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum TypeDefKind {
+    Record(Record),
+    Variant(Variant),
+    List(Type),
+    Array { element: Type, len: u32 },
+    PushBuffer(Type),
+    PullBuffer(Type),
+    Pointer(Type),
+    ConstPointer(Type),
+    Type(Type),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Record {
+    pub kind: RecordKind,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum RecordKind {
+    Other,
+    Flags(Option<Int>),
+    Tuple,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docs: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attrs: Attrs,
+    pub ty: Type,
+    /// The bit position of this field, only present for flags.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bit: Option<u32>,
+}
+
+/// User-defined attributes keyed by name, see [`crate::Attrs`].
+pub type Attrs = BTreeMap<String, Vec<AttrArg>>;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AttrArg {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub tag: Int,
+    pub cases: Vec<Case>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Case {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docs: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attrs: Attrs,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ty: Option<Type>,
+    pub discriminant: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Type {
+    U8,
+    U16,
+    U32,
+    U64,
+    S8,
+    S16,
+    S32,
+    S64,
+    F32,
+    F64,
+    Char,
+    CChar,
+    Usize,
+    Bool,
+    String,
+    /// A handle to the resource at this index in [`Interface::resources`].
+    Handle(usize),
+    /// The type at this index in [`Interface::types`].
+    Id(usize),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Int {
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    pub size: usize,
+    pub align: usize,
+}
+
+/// Information which differs between imported and exported interfaces.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PerDirection<T> {
+    pub import: T,
+    pub export: T,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Function {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docs: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attrs: Attrs,
+    pub kind: FunctionKind,
+    pub abi: Abi,
+    #[serde(rename = "async")]
+    pub is_async: bool,
+    pub params: Vec<Param>,
+    pub results: Vec<Param>,
+    pub wasm_signature: PerDirection<WasmSignature>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum FunctionKind {
+    Freestanding,
+    /// A static function of the resource at index `resource`.
+    Static {
+        resource: usize,
+        name: String,
+    },
+    /// A method of the resource at index `resource`.
+    Method {
+        resource: usize,
+        name: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Abi {
+    Preview1,
+    Canonical,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Param {
+    pub name: String,
+    pub ty: Type,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct WasmSignature {
+    pub params: Vec<WasmType>,
+    pub results: Vec<WasmType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retptr: Option<Vec<WasmType>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WasmType {
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Global {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docs: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attrs: Attrs,
+    pub ty: Type,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Constant {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docs: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attrs: Attrs,
+    pub ty: Type,
+    pub value: ConstValue,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConstValue {
+    Bool(bool),
+    Unsigned(u64),
+    Signed(i64),
+    Float(f64),
+    Char(char),
+    String(String),
+    /// A case of the enum at index `ty` in [`Interface::types`].
+    Enum {
+        ty: usize,
+        case: String,
+    },
+    /// A set of flags of the flags record at index `ty` in
+    /// [`Interface::types`].
+    Flags {
+        ty: usize,
+        flags: Vec<String>,
+    },
+}
+
+impl Interface {
+    pub fn new(iface: &crate::Interface) -> Interface {
+        let mut import = SizeAlign::default();
+        import.fill(Direction::Import, iface);
+        let mut export = SizeAlign::default();
+        export.fill(Direction::Export, iface);
+        let layout = |sizes: &SizeAlign, id| {
+            let ty = crate::Type::Id(id);
+            Layout {
+                size: sizes.size(&ty),
+                align: sizes.align(&ty),
+            }
+        };
+
+        let resources = iface
+            .resources
+            .iter()
+            .map(|(_, r)| Resource {
+                name: r.name.clone(),
+                docs: r.docs.contents.clone(),
+                attrs: attrs(&r.attrs),
+                foreign_module: r.foreign_module.clone(),
+            })
+            .collect();
+        let types = iface
+            .types
+            .iter()
+            .map(|(id, ty)| TypeDef {
+                name: ty.name.clone(),
+                docs: ty.docs.contents.clone(),
+                attrs: attrs(&ty.attrs),
+                foreign_module: ty.foreign_module.clone(),
+                kind: TypeDefKind::new(&ty.kind),
+                layout: PerDirection {
+                    import: layout(&import, id),
+                    export: layout(&export, id),
+                },
+            })
+            .collect();
+        let functions = iface
+            .functions
+            .iter()
+            .map(|f| Function::new(iface, f))
+            .collect();
+        let globals = iface
+            .globals
+            .iter()
+            .map(|g| Global {
+                name: g.name.clone(),
+                docs: g.docs.contents.clone(),
+                attrs: attrs(&g.attrs),
+                ty: Type::new(&g.ty),
+            })
+            .collect();
+        let constants = iface
+            .constants
+            .iter()
+            .map(|c| Constant {
+                name: c.name.clone(),
+                docs: c.docs.contents.clone(),
+                attrs: attrs(&c.attrs),
+                ty: Type::new(&c.ty),
+                value: ConstValue::new(&c.value),
+            })
+            .collect();
+        Interface {
+            version: VERSION,
+            name: iface.name.clone(),
+            resources,
+            types,
+            functions,
+            globals,
+            constants,
+        }
+    }
+}
+
+impl From<&crate::Interface> for Interface {
+    fn from(iface: &crate::Interface) -> Interface {
+        Interface::new(iface)
+    }
+}
+
+impl TypeDefKind {
+    fn new(kind: &crate::TypeDefKind) -> TypeDefKind {
+        match kind {
+            crate::TypeDefKind::Record(r) => TypeDefKind::Record(Record {
+                kind: match r.kind {
+                    crate::RecordKind::Other => RecordKind::Other,
+                    crate::RecordKind::Flags(repr) => RecordKind::Flags(repr.map(Int::new)),
+                    crate::RecordKind::Tuple => RecordKind::Tuple,
+                },
+                fields: r
+                    .fields
+                    .iter()
+                    .map(|f| Field {
+                        name: f.name.clone(),
+                        docs: f.docs.contents.clone(),
+                        attrs: attrs(&f.attrs),
+                        ty: Type::new(&f.ty),
+                        bit: if r.is_flags() { Some(f.bit) } else { None },
+                    })
+                    .collect(),
+            }),
+            crate::TypeDefKind::Variant(v) => TypeDefKind::Variant(Variant {
+                tag: Int::new(v.tag),
+                cases: v
+                    .cases
+                    .iter()
+                    .map(|c| Case {
+                        name: c.name.clone(),
+                        docs: c.docs.contents.clone(),
+                        attrs: attrs(&c.attrs),
+                        ty: c.ty.as_ref().map(Type::new),
+                        discriminant: c.discriminant,
+                    })
+                    .collect(),
+            }),
+            crate::TypeDefKind::List(t) => TypeDefKind::List(Type::new(t)),
+            crate::TypeDefKind::Array(t, len) => TypeDefKind::Array {
+                element: Type::new(t),
+                len: *len,
+            },
+            crate::TypeDefKind::PushBuffer(t) => TypeDefKind::PushBuffer(Type::new(t)),
+            crate::TypeDefKind::PullBuffer(t) => TypeDefKind::PullBuffer(Type::new(t)),
+            crate::TypeDefKind::Pointer(t) => TypeDefKind::Pointer(Type::new(t)),
+            crate::TypeDefKind::ConstPointer(t) => TypeDefKind::ConstPointer(Type::new(t)),
+            crate::TypeDefKind::Type(t) => TypeDefKind::Type(Type::new(t)),
+        }
+    }
+}
+
+impl Type {
+    fn new(ty: &crate::Type) -> Type {
+        match ty {
+            crate::Type::U8 => Type::U8,
+            crate::Type::U16 => Type::U16,
+            crate::Type::U32 => Type::U32,
+            crate::Type::U64 => Type::U64,
+            crate::Type::S8 => Type::S8,
+            crate::Type::S16 => Type::S16,
+            crate::Type::S32 => Type::S32,
+            crate::Type::S64 => Type::S64,
+            crate::Type::F32 => Type::F32,
+            crate::Type::F64 => Type::F64,
+            crate::Type::Char => Type::Char,
+            crate::Type::CChar => Type::CChar,
+            crate::Type::Usize => Type::Usize,
+            crate::Type::Bool => Type::Bool,
+            crate::Type::String => Type::String,
+            crate::Type::Handle(r) => Type::Handle(r.index()),
+            crate::Type::Id(t) => Type::Id(t.index()),
+        }
+    }
+}
+
+impl ConstValue {
+    fn new(value: &crate::ConstValue) -> ConstValue {
+        match value {
+            crate::ConstValue::Bool(b) => ConstValue::Bool(*b),
+            crate::ConstValue::Unsigned(n) => ConstValue::Unsigned(*n),
+            crate::ConstValue::Signed(n) => ConstValue::Signed(*n),
+            crate::ConstValue::Float(n) => ConstValue::Float(*n),
+            crate::ConstValue::Char(c) => ConstValue::Char(*c),
+            crate::ConstValue::String(s) => ConstValue::String(s.clone()),
+            crate::ConstValue::Enum { ty, case } => ConstValue::Enum {
+                ty: ty.index(),
+                case: case.clone(),
+            },
+            crate::ConstValue::Flags { ty, flags } => ConstValue::Flags {
+                ty: ty.index(),
+                flags: flags.clone(),
+            },
+        }
+    }
+}
+
+impl Int {
+    fn new(int: crate::Int) -> Int {
+        match int {
+            crate::Int::U8 => Int::U8,
+            crate::Int::U16 => Int::U16,
+            crate::Int::U32 => Int::U32,
+            crate::Int::U64 => Int::U64,
+        }
+    }
+}
+
+impl Function {
+    fn new(iface: &crate::Interface, func: &crate::Function) -> Function {
+        let params = |params: &[(String, crate::Type)]| {
+            params
+                .iter()
+                .map(|(name, ty)| Param {
+                    name: name.clone(),
+                    ty: Type::new(ty),
+                })
+                .collect()
+        };
+        Function {
+            name: func.name.clone(),
+            docs: func.docs.contents.clone(),
+            attrs: attrs(&func.attrs),
+            kind: match &func.kind {
+                crate::FunctionKind::Freestanding => FunctionKind::Freestanding,
+                crate::FunctionKind::Static { resource, name } => FunctionKind::Static {
+                    resource: resource.index(),
+                    name: name.clone(),
+                },
+                crate::FunctionKind::Method { resource, name } => FunctionKind::Method {
+                    resource: resource.index(),
+                    name: name.clone(),
+                },
+            },
+            abi: match func.abi {
+                abi::Abi::Preview1 => Abi::Preview1,
+                abi::Abi::Canonical => Abi::Canonical,
+            },
+            is_async: func.is_async,
+            params: params(&func.params),
+            results: params(&func.results),
+            wasm_signature: PerDirection {
+                import: WasmSignature::new(&iface.wasm_signature(
+                    Direction::Import,
+                    MultiValue::Disabled,
+                    func,
+                )),
+                export: WasmSignature::new(&iface.wasm_signature(
+                    Direction::Export,
+                    MultiValue::Disabled,
+                    func,
+                )),
+            },
+        }
+    }
+}
+
+impl WasmSignature {
+    fn new(sig: &abi::WasmSignature) -> WasmSignature {
+        let tys = |tys: &[abi::WasmType]| {
+            tys.iter()
+                .map(|ty| match ty {
+                    abi::WasmType::I32 => WasmType::I32,
+                    abi::WasmType::I64 => WasmType::I64,
+                    abi::WasmType::F32 => WasmType::F32,
+                    abi::WasmType::F64 => WasmType::F64,
+                })
+                .collect()
+        };
+        WasmSignature {
+            params: tys(&sig.params),
+            results: tys(&sig.results),
+            retptr: sig.retptr.as_ref().map(|r| tys(r)),
+        }
+    }
+}
+
+fn attrs(attrs: &crate::Attrs) -> Attrs {
+    attrs
+        .attrs
+        .iter()
+        .map(|(name, args)| {
+            let args = args
+                .iter()
+                .map(|arg| AttrArg {
+                    name: arg.name.clone(),
+                    value: arg.value.clone(),
+                })
+                .collect();
+            (name.clone(), args)
+        })
+        .collect()
+}
@@ -1,14 +1,30 @@
 use anyhow::{anyhow, bail, Context, Result};
 use id_arena::{Arena, Id};
-use std::collections::{HashMap, HashSet};
-use std::fs;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 pub mod abi;
-mod ast;
+pub mod ast;
+mod cache;
+pub mod compat;
+mod diagnostic;
+#[cfg(feature = "serde")]
+pub mod json;
+pub mod printer;
+mod resolver;
 mod sizealign;
+mod source;
+mod validate;
+pub use ast::Span;
+pub use cache::*;
+pub use diagnostic::*;
+pub use resolver::*;
 pub use sizealign::*;
+pub use source::*;
+pub use validate::*;
 
+#[derive(Clone)]
 pub struct Interface {
     pub name: String,
     pub types: Arena<TypeDef>,
@@ -19,25 +35,38 @@ pub struct Interface {
     pub interface_lookup: HashMap<String, InterfaceId>,
     pub functions: Vec<Function>,
     pub globals: Vec<Global>,
+    pub constants: Vec<Constant>,
+    /// The files this interface and the modules it uses were parsed from,
+    /// keyed by module name. See [`Interface::source`].
+    pub sources: BTreeMap<String, Arc<SourceFile>>,
 }
 
 pub type TypeId = Id<TypeDef>;
 pub type ResourceId = Id<Resource>;
 pub type InterfaceId = Id<Interface>;
 
+#[derive(Clone)]
 pub struct TypeDef {
     pub docs: Docs,
+    pub attrs: Attrs,
     pub kind: TypeDefKind,
     pub name: Option<String>,
+    /// Where this type's name is in the file which defined it, or an empty
+    /// span at the start of the file for anonymous types.
+    pub span: Span,
     /// `None` if this type is originally declared in this instance or
     /// otherwise `Some` if it was originally defined in a different module.
     pub foreign_module: Option<String>,
 }
 
+#[derive(Clone)]
 pub enum TypeDefKind {
     Record(Record),
     Variant(Variant),
     List(Type),
+    /// A fixed-size array of the given number of elements, which unlike a
+    /// list is stored inline rather than behind a pointer.
+    Array(Type, u32),
     Pointer(Type),
     ConstPointer(Type),
     PushBuffer(Type),
@@ -60,6 +89,8 @@ pub enum Type {
     Char,
     CChar,
     Usize,
+    Bool,
+    String,
     Handle(ResourceId),
     Id(TypeId),
 }
@@ -72,7 +103,7 @@ pub enum Int {
     U64,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Record {
     pub fields: Vec<Field>,
     pub kind: RecordKind,
@@ -85,11 +116,16 @@ pub enum RecordKind {
     Tuple,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Field {
     pub docs: Docs,
+    pub attrs: Attrs,
     pub name: String,
     pub ty: Type,
+    /// The position of this field's bit when the record is represented as
+    /// flags. This is one more than the previous field's bit, starting from
+    /// zero, unless it was given explicitly as in `flags perms { read = 0x4 }`.
+    pub bit: u32,
 }
 
 impl Record {
@@ -107,8 +143,26 @@ impl Record {
         }
     }
 
+    /// Returns the number of bits needed to represent this record as flags,
+    /// which is one more than the highest bit of any field.
+    pub fn num_bits(&self) -> usize {
+        self.fields
+            .iter()
+            .map(|f| f.bit as usize + 1)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Returns whether the bit of each field is its index.
+    pub fn has_default_bits(&self) -> bool {
+        self.fields
+            .iter()
+            .enumerate()
+            .all(|(i, f)| f.bit as usize == i)
+    }
+
     pub fn num_i32s(&self) -> usize {
-        (self.fields.len() + 31) / 32
+        self.num_bits().div_ceil(32)
     }
 }
 
@@ -136,8 +190,8 @@ impl RecordKind {
 
         fn is_bool(t: &Type, types: &Arena<TypeDef>) -> bool {
             match t {
+                Type::Bool => true,
                 Type::Id(v) => match &types[*v].kind {
-                    TypeDefKind::Variant(v) => v.is_bool(),
                     TypeDefKind::Type(t) => is_bool(t, types),
                     _ => false,
                 },
@@ -147,7 +201,7 @@ impl RecordKind {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Variant {
     pub cases: Vec<Case>,
     /// The bit representation of the width of this variant's tag when the
@@ -155,11 +209,16 @@ pub struct Variant {
     pub tag: Int,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Case {
     pub docs: Docs,
+    pub attrs: Attrs,
     pub name: String,
     pub ty: Option<Type>,
+    /// The value identifying this case in the ABI. This is one more than the
+    /// previous case's discriminant, starting from zero, unless it was given
+    /// explicitly as in `enum errno { success = 0, again = 6 }`.
+    pub discriminant: u32,
 }
 
 impl Variant {
@@ -173,12 +232,19 @@ impl Variant {
         }
     }
 
-    pub fn is_bool(&self) -> bool {
-        self.cases.len() == 2
-            && self.cases[0].name == "false"
-            && self.cases[1].name == "true"
-            && self.cases[0].ty.is_none()
-            && self.cases[1].ty.is_none()
+    /// Returns the index of the case with the `discriminant` given.
+    pub fn case_index(&self, discriminant: u32) -> Option<usize> {
+        self.cases
+            .iter()
+            .position(|c| c.discriminant == discriminant)
+    }
+
+    /// Returns whether the discriminant of each case is its index.
+    pub fn has_default_discriminants(&self) -> bool {
+        self.cases
+            .iter()
+            .enumerate()
+            .all(|(i, c)| c.discriminant as usize == i)
     }
 
     pub fn is_enum(&self) -> bool {
@@ -186,7 +252,7 @@ impl Variant {
     }
 
     pub fn as_option(&self) -> Option<&Type> {
-        if self.cases.len() != 2 {
+        if self.cases.len() != 2 || !self.has_default_discriminants() {
             return None;
         }
         if self.cases[0].name != "none" || self.cases[0].ty.is_some() {
@@ -199,7 +265,7 @@ impl Variant {
     }
 
     pub fn as_expected(&self) -> Option<(Option<&Type>, Option<&Type>)> {
-        if self.cases.len() != 2 {
+        if self.cases.len() != 2 || !self.has_default_discriminants() {
             return None;
         }
         if self.cases[0].name != "ok" {
@@ -212,37 +278,238 @@ impl Variant {
     }
 }
 
+/// A doc comment, split into the sections written with tags such as
+/// `@param name ...`.
+///
+/// Each tag is written at the start of a line and its section continues
+/// until the next tag. Lines starting with any other `@word` are treated as
+/// plain text.
 #[derive(Clone, Default, Debug)]
 pub struct Docs {
+    /// The full text of the doc comment, including any tags.
     pub contents: Option<String>,
+    /// The text before the first tag.
+    pub description: Option<String>,
+    /// The `@param name ...` sections, in the order they're written.
+    pub params: Vec<(String, String)>,
+    /// The `@returns ...` section, which can also be written `@return`.
+    pub returns: Option<String>,
+    /// The `@errors ...` section, describing the errors a function returns.
+    pub errors: Option<String>,
+    /// The `@deprecated ...` section, which is empty if no reason is given.
+    pub deprecated: Option<String>,
+    /// The `@since ...` section, typically the version an item was added in.
+    pub since: Option<String>,
+}
+
+impl Docs {
+    /// Parses the sections of the doc comment `contents`.
+    pub fn new(contents: String) -> Docs {
+        enum Section {
+            Description,
+            Param(String),
+            Returns,
+            Errors,
+            Deprecated,
+            Since,
+        }
+
+        let mut sections = vec![(Section::Description, String::new())];
+        for line in contents.lines() {
+            let trimmed = line.trim();
+            let (tag, rest) = match trimmed.find(char::is_whitespace) {
+                Some(i) => (&trimmed[..i], trimmed[i..].trim_start()),
+                None => (trimmed, ""),
+            };
+            let section = match tag {
+                "@param" => {
+                    let (name, rest) = match rest.find(char::is_whitespace) {
+                        Some(i) => (&rest[..i], rest[i..].trim_start()),
+                        None => (rest, ""),
+                    };
+                    // Allow `@param name - text` as written in JSDoc.
+                    let rest = rest.strip_prefix("- ").unwrap_or(rest);
+                    Some((Section::Param(name.to_string()), rest))
+                }
+                "@returns" | "@return" => Some((Section::Returns, rest)),
+                "@errors" => Some((Section::Errors, rest)),
+                "@deprecated" => Some((Section::Deprecated, rest)),
+                "@since" => Some((Section::Since, rest)),
+                _ => None,
+            };
+            match section {
+                Some((section, rest)) => sections.push((section, rest.to_string())),
+                None => {
+                    let in_description = sections.len() == 1;
+                    let text = &mut sections.last_mut().unwrap().1;
+                    if !text.is_empty() {
+                        text.push('\n');
+                    }
+                    // The description keeps its indentation, but the
+                    // continuation lines of tags are typically indented to
+                    // line up with the tag's text.
+                    if in_description {
+                        text.push_str(line.trim_end());
+                    } else {
+                        text.push_str(trimmed);
+                    }
+                }
+            }
+        }
+
+        let mut docs = Docs::default();
+        for (section, text) in sections {
+            let text = text.trim().to_string();
+            match section {
+                Section::Description if text.is_empty() => {}
+                Section::Description => docs.description = Some(text),
+                Section::Param(name) => docs.params.push((name, text)),
+                Section::Returns => docs.returns = Some(text),
+                Section::Errors => docs.errors = Some(text),
+                Section::Deprecated => docs.deprecated = Some(text),
+                Section::Since => docs.since = Some(text),
+            }
+        }
+        docs.contents = Some(contents);
+        docs
+    }
+
+    /// Returns the documentation of the parameter `name`, if any.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, docs)| docs.as_str())
+    }
+}
+
+/// User-defined `@name(...)` attributes attached to an item, for backends to
+/// interpret as they see fit.
+///
+/// Attributes are keyed by name, and repeating an attribute on the same item
+/// concatenates the arguments of each occurrence.
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct Attrs {
+    pub attrs: BTreeMap<String, Vec<AttrArg>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttrArg {
+    /// The `key` in `@name(key = value)`, or `None` for positional arguments.
+    pub name: Option<String>,
+    pub value: String,
+}
+
+impl Attrs {
+    pub fn is_empty(&self) -> bool {
+        self.attrs.is_empty()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.attrs.contains_key(name)
+    }
+
+    /// Returns all arguments of the attribute `name`, if it's present.
+    pub fn get(&self, name: &str) -> Option<&[AttrArg]> {
+        self.attrs.get(name).map(|args| &args[..])
+    }
+
+    /// Returns the first positional argument of the attribute `name`, for
+    /// example `1.2` in `@since(1.2)`.
+    pub fn value(&self, name: &str) -> Option<&str> {
+        self.get(name)?
+            .iter()
+            .find(|arg| arg.name.is_none())
+            .map(|arg| arg.value.as_str())
+    }
+
+    /// Returns the values of all `key = value` arguments of the attribute
+    /// `name`, for example `Hash` in `@rust(derive = "Hash")`.
+    pub fn named<'a>(&'a self, name: &str, key: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.get(name)
+            .unwrap_or(&[])
+            .iter()
+            .filter(move |arg| arg.name.as_deref() == Some(key))
+            .map(|arg| arg.value.as_str())
+    }
 }
 
+#[derive(Clone)]
 pub struct Resource {
     pub docs: Docs,
+    pub attrs: Attrs,
     pub name: String,
+    /// Where this resource's name is in the file which defined it.
+    pub span: Span,
     /// `None` if this resource is defined within the containing instance,
     /// otherwise `Some` if it's defined in an instance named here.
     pub foreign_module: Option<String>,
 }
 
+/// A read-only value provided by the exporter of an interface.
+///
+/// Globals aren't wasm globals at the ABI level since those can only hold
+/// scalars. Instead each global is read through a getter function, see
+/// [`Global::getter`].
+#[derive(Clone)]
 pub struct Global {
     pub docs: Docs,
+    pub attrs: Attrs,
     pub name: String,
+    pub span: Span,
     pub ty: Type,
 }
 
-#[derive(Debug)]
+#[derive(Clone)]
+pub struct Constant {
+    pub docs: Docs,
+    pub attrs: Attrs,
+    pub name: String,
+    pub span: Span,
+    pub ty: Type,
+    pub value: ConstValue,
+}
+
+/// The value of a [`Constant`], which has already been checked to be valid
+/// for the constant's type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstValue {
+    Bool(bool),
+    /// The value of a constant of an unsigned integer type.
+    Unsigned(u64),
+    /// The value of a constant of a signed integer type.
+    Signed(i64),
+    Float(f64),
+    Char(char),
+    String(String),
+    /// A case of the enum `ty`.
+    Enum {
+        ty: TypeId,
+        case: String,
+    },
+    /// A set of flags of the flags record `ty`, in the order they were
+    /// written.
+    Flags {
+        ty: TypeId,
+        flags: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone)]
 pub struct Function {
     pub abi: abi::Abi,
     pub is_async: bool,
     pub docs: Docs,
+    pub attrs: Attrs,
     pub name: String,
+    /// Where this function's name is in the file which defined it.
+    pub span: Span,
     pub kind: FunctionKind,
     pub params: Vec<(String, Type)>,
     pub results: Vec<(String, Type)>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum FunctionKind {
     Freestanding,
     Static { resource: ResourceId, name: String },
@@ -259,6 +526,27 @@ impl Function {
     }
 }
 
+impl Global {
+    /// Returns the function through which this global is read.
+    ///
+    /// The getter takes no parameters, returns the global's value and is
+    /// imported or exported under the name of the global itself, which can't
+    /// clash with any other function in the interface.
+    pub fn getter(&self) -> Function {
+        Function {
+            abi: abi::Abi::Canonical,
+            is_async: false,
+            docs: self.docs.clone(),
+            attrs: self.attrs.clone(),
+            name: self.name.clone(),
+            span: self.span,
+            kind: FunctionKind::Freestanding,
+            params: Vec::new(),
+            results: vec![(String::new(), self.ty)],
+        }
+    }
+}
+
 impl Interface {
     pub fn parse(name: &str, input: &str) -> Result<Interface> {
         Interface::parse_with(name, input, |f| {
@@ -266,23 +554,45 @@ impl Interface {
         })
     }
 
+    /// Parses the interface in the file `path`, loading modules it uses from
+    /// the directory the file is in.
     pub fn parse_file(path: impl AsRef<Path>) -> Result<Interface> {
         let path = path.as_ref();
-        let parent = path.parent().unwrap();
+        let mut resolver = FsResolver::new(vec![path.parent().unwrap().to_path_buf()]);
+        Interface::parse_file_with(path, &mut resolver)
+    }
+
+    /// Same as [`Interface::parse_file`], except that modules are loaded
+    /// through `resolver`.
+    pub fn parse_file_with(
+        path: impl AsRef<Path>,
+        resolver: &mut dyn Resolver,
+    ) -> Result<Interface> {
+        let path = path.as_ref();
         let contents = std::fs::read_to_string(&path)
             .with_context(|| format!("failed to read: {}", path.display()))?;
-        Interface::parse_with(path, &contents, |path| load_fs(parent, path))
+        Interface::parse_with_resolver(path, &contents, resolver)
     }
 
     pub fn parse_with(
         filename: impl AsRef<Path>,
         contents: &str,
         mut load: impl FnMut(&str) -> Result<(PathBuf, String)>,
+    ) -> Result<Interface> {
+        Interface::parse_with_resolver(filename, contents, &mut load)
+    }
+
+    /// Same as [`Interface::parse_with`], except that modules are loaded
+    /// through `resolver`.
+    pub fn parse_with_resolver(
+        filename: impl AsRef<Path>,
+        contents: &str,
+        resolver: &mut dyn Resolver,
     ) -> Result<Interface> {
         Interface::_parse_with(
             filename.as_ref(),
             contents,
-            &mut load,
+            resolver,
             &mut HashSet::new(),
             &mut HashMap::new(),
         )
@@ -291,7 +601,7 @@ impl Interface {
     fn _parse_with(
         filename: &Path,
         contents: &str,
-        load: &mut dyn FnMut(&str) -> Result<(PathBuf, String)>,
+        load: &mut dyn Resolver,
         visiting: &mut HashSet<PathBuf>,
         map: &mut HashMap<String, Interface>,
     ) -> Result<Interface> {
@@ -317,9 +627,9 @@ impl Interface {
             if map.contains_key(&*u.from[0].name) {
                 continue;
             }
-            let (filename, contents) = load(&u.from[0].name)
-                // TODO: insert context here about `u.name.span` and `filename`
-                ?;
+            let (filename, contents) = load
+                .load(&u.from[0].name)
+                .with_context(|| format!("failed to load module `{}`", u.from[0].name))?;
             let instance = Interface::_parse_with(&filename, &contents, load, visiting, map)?;
             map.insert(u.from[0].name.to_string(), instance);
         }
@@ -328,7 +638,10 @@ impl Interface {
         // and finally resolve everything into our final instance
         let name = filename.file_stem().unwrap().to_str().unwrap();
         match ast.resolve(name, map) {
-            Ok(i) => Ok(i),
+            Ok(mut i) => {
+                i.add_sources(filename, contents, &ast, map);
+                Ok(i)
+            }
             Err(mut e) => {
                 let file = filename.display().to_string();
                 ast::rewrite_error(&mut e, &file, contents);
@@ -337,6 +650,164 @@ impl Interface {
         }
     }
 
+    /// Same as [`Interface::parse_file`], except that every problem in the
+    /// file is reported rather than only the first.
+    ///
+    /// See [`Interface::parse_with_diagnostics`] for more information. An
+    /// error is only returned if `path` itself can't be read.
+    pub fn parse_file_with_diagnostics(
+        path: impl AsRef<Path>,
+    ) -> Result<(Interface, Vec<Diagnostic>)> {
+        let path = path.as_ref();
+        let mut resolver = FsResolver::new(vec![path.parent().unwrap().to_path_buf()]);
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read: {}", path.display()))?;
+        Ok(Interface::parse_with_diagnostics(
+            path,
+            &contents,
+            |name: &str| resolver.load(name),
+        ))
+    }
+
+    /// Same as [`Interface::parse_with`], except that parsing and resolution
+    /// recover from errors rather than stopping at the first one.
+    ///
+    /// Returns the parts of the interface that could be parsed and resolved
+    /// alongside a diagnostic for every problem found, including problems in
+    /// any modules loaded through `load`. If the list of diagnostics is empty
+    /// then the interface is the same as the one `parse_with` would return.
+    ///
+    /// Items which fail to parse are omitted from the returned interface, as
+    /// are functions which fail to resolve. Types which fail to resolve are
+    /// still present but have a placeholder definition.
+    pub fn parse_with_diagnostics(
+        filename: impl AsRef<Path>,
+        contents: &str,
+        mut load: impl FnMut(&str) -> Result<(PathBuf, String)>,
+    ) -> (Interface, Vec<Diagnostic>) {
+        let mut diagnostics = Vec::new();
+        let filename = filename.as_ref();
+        let mut visiting = HashSet::new();
+        visiting.insert(filename.to_path_buf());
+        let interface = Interface::_parse_with_diagnostics(
+            filename,
+            contents,
+            &mut load,
+            &mut visiting,
+            &mut HashMap::new(),
+            &mut diagnostics,
+        );
+        (interface, diagnostics)
+    }
+
+    fn _parse_with_diagnostics(
+        filename: &Path,
+        contents: &str,
+        load: &mut dyn Resolver,
+        visiting: &mut HashSet<PathBuf>,
+        map: &mut HashMap<String, Interface>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Interface {
+        let (ast, errors) = ast::Ast::parse_recover(contents);
+        diagnostics.extend(
+            errors
+                .into_iter()
+                .map(|e| Diagnostic::from_error(filename, e)),
+        );
+
+        // Load up any modules into our `map` that have not yet been parsed,
+        // reporting any failures at the `use` which referred to them.
+        for item in ast.items.iter() {
+            let u = match item {
+                ast::Item::Use(u) => u,
+                _ => continue,
+            };
+            if map.contains_key(&*u.from[0].name) {
+                continue;
+            }
+            let (dep_filename, dep_contents) = match load.load(&u.from[0].name) {
+                Ok(pair) => pair,
+                Err(e) => {
+                    let mut diagnostic = Diagnostic::error(
+                        filename,
+                        u.from[0].span,
+                        format!("failed to load module `{}`", u.from[0].name),
+                    );
+                    diagnostic.notes = e.chain().map(|e| e.to_string()).collect();
+                    diagnostics.push(diagnostic);
+                    continue;
+                }
+            };
+            if !visiting.insert(dep_filename.clone()) {
+                diagnostics.push(Diagnostic::error(
+                    filename,
+                    u.from[0].span,
+                    format!(
+                        "file `{}` recursively imports itself",
+                        dep_filename.display()
+                    ),
+                ));
+                continue;
+            }
+            let instance = Interface::_parse_with_diagnostics(
+                &dep_filename,
+                &dep_contents,
+                load,
+                visiting,
+                map,
+                diagnostics,
+            );
+            visiting.remove(&dep_filename);
+            map.insert(u.from[0].name.to_string(), instance);
+        }
+
+        let name = filename.file_stem().unwrap().to_str().unwrap();
+        let (mut interface, errors) = ast.resolve_recover(name, map);
+        diagnostics.extend(
+            errors
+                .into_iter()
+                .map(|e| Diagnostic::from_error(filename, e)),
+        );
+        interface.add_sources(filename, contents, &ast, map);
+        interface
+    }
+
+    /// Records that this interface was parsed from `contents`, along with
+    /// the files of the modules in `deps` which `ast` uses.
+    fn add_sources(
+        &mut self,
+        filename: &Path,
+        contents: &str,
+        ast: &ast::Ast<'_>,
+        deps: &HashMap<String, Interface>,
+    ) {
+        for item in ast.items.iter() {
+            if let ast::Item::Use(u) = item {
+                if let Some(dep) = deps.get(&*u.from[0].name) {
+                    for (name, file) in dep.sources.iter() {
+                        self.sources.insert(name.clone(), file.clone());
+                    }
+                }
+            }
+        }
+        let file = SourceFile {
+            path: filename.to_path_buf(),
+            contents: contents.to_string(),
+        };
+        self.sources.insert(self.name.clone(), Arc::new(file));
+    }
+
+    /// Returns the file that the items of `module` were defined in, or the
+    /// file of this interface itself if `module` is `None`.
+    ///
+    /// This is intended to be called with the `foreign_module` of a type.
+    /// `None` is returned if the file isn't known, for example because this
+    /// interface wasn't parsed from a file.
+    pub fn source(&self, module: Option<&str>) -> Option<&SourceFile> {
+        let module = module.unwrap_or(&self.name);
+        self.sources.get(module).map(|file| &**file)
+    }
+
     pub fn topological_types(&self) -> Vec<TypeId> {
         let mut ret = Vec::new();
         let mut visited = HashSet::new();
@@ -353,6 +824,7 @@ impl Interface {
         match &self.types[id].kind {
             TypeDefKind::Type(t)
             | TypeDefKind::List(t)
+            | TypeDefKind::Array(t, _)
             | TypeDefKind::PushBuffer(t)
             | TypeDefKind::PullBuffer(t)
             | TypeDefKind::Pointer(t)
@@ -394,14 +866,14 @@ impl Interface {
             | Type::CChar
             | Type::Usize => true,
 
-            Type::Char | Type::Handle(_) => false,
+            Type::Char | Type::Bool | Type::String | Type::Handle(_) => false,
 
             Type::Id(id) => match &self.types[*id].kind {
                 TypeDefKind::List(_)
                 | TypeDefKind::Variant(_)
                 | TypeDefKind::PushBuffer(_)
                 | TypeDefKind::PullBuffer(_) => false,
-                TypeDefKind::Type(t) => self.all_bits_valid(t),
+                TypeDefKind::Type(t) | TypeDefKind::Array(t, _) => self.all_bits_valid(t),
                 TypeDefKind::Record(r) => r.fields.iter().all(|f| self.all_bits_valid(&f.ty)),
                 TypeDefKind::Pointer(_) | TypeDefKind::ConstPointer(_) => true,
             },
@@ -411,9 +883,10 @@ impl Interface {
     pub fn has_preview1_pointer(&self, ty: &Type) -> bool {
         match ty {
             Type::Id(id) => match &self.types[*id].kind {
-                TypeDefKind::List(t) | TypeDefKind::PushBuffer(t) | TypeDefKind::PullBuffer(t) => {
-                    self.has_preview1_pointer(t)
-                }
+                TypeDefKind::List(t)
+                | TypeDefKind::Array(t, _)
+                | TypeDefKind::PushBuffer(t)
+                | TypeDefKind::PullBuffer(t) => self.has_preview1_pointer(t),
                 TypeDefKind::Type(t) => self.has_preview1_pointer(t),
                 TypeDefKind::Pointer(_) | TypeDefKind::ConstPointer(_) => true,
                 TypeDefKind::Record(r) => r.fields.iter().any(|f| self.has_preview1_pointer(&f.ty)),
@@ -426,10 +899,3 @@ impl Interface {
         }
     }
 }
-
-fn load_fs(root: &Path, name: &str) -> Result<(PathBuf, String)> {
-    let path = root.join(name).with_extension("witx");
-    let contents =
-        fs::read_to_string(&path).context(format!("failed to read `{}`", path.display()))?;
-    Ok((path, contents))
-}
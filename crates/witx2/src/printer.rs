@@ -0,0 +1,988 @@
+//! Printing of interfaces back into canonical `*.witx` source.
+//!
+//! Both parsed-but-unresolved [`Ast`]s and resolved [`Interface`]s can be
+//! printed. Syntactic sugar such as `bool`, `string`, `option`, `expected`,
+//! `tuple`, `flags`, `enum` and `union` is reconstructed from its desugared
+//! form, so printing and then re-parsing yields the same interface.
+//!
+//! Printing an [`Ast`] panics if it uses features only available in the old
+//! s-expression witx syntax, such as pointers. In an [`Interface`] these are
+//! still described, as in `pointer<u8>`, but the output can't be parsed.
+
+use crate::ast::lex::{Token, Tokenizer};
+use crate::ast::{self, Ast, Item};
+use crate::*;
+use anyhow::{bail, Result};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+/// Formats the `*.witx` source `contents` of `filename` into its canonical
+/// form.
+///
+/// Comments directly preceding items are preserved, but comments anywhere
+/// else have nowhere to live in the AST. Rather than silently dropping them
+/// an error is returned for such files.
+pub fn format(filename: impl AsRef<Path>, contents: &str) -> Result<String> {
+    let file = filename.as_ref().display().to_string();
+    if let Ok(Some((_, Token::Semicolon))) | Ok(Some((_, Token::LeftParen))) =
+        Tokenizer::new(contents).next()
+    {
+        bail!(
+            "{}: files using the old witx syntax can't be formatted",
+            file
+        );
+    }
+    let ast = match Ast::parse(contents) {
+        Ok(ast) => ast,
+        Err(mut e) => {
+            ast::rewrite_error(&mut e, &file, contents);
+            return Err(e);
+        }
+    };
+    let output = print_ast(&ast);
+    if count_comments(contents) != count_comments(&output) {
+        bail!(
+            "{}: contains comments which aren't attached to an item and would be \
+             lost by formatting",
+            file
+        );
+    }
+    Ok(output)
+}
+
+/// Prints `ast` as canonical witx source, preserving the comments attached to
+/// each item.
+pub fn print_ast(ast: &Ast<'_>) -> String {
+    let mut printer = Printer::default();
+    printer.ast_items(&ast.items);
+    printer.out
+}
+
+/// Prints the resolved interface `iface` as canonical witx source.
+///
+/// Types and resources defined in other modules are imported with `use`
+/// from the module that originally defined them. Types are printed in the
+/// order they were defined, followed by constants, functions and then
+/// globals.
+/// Resources with functions are printed alongside their functions so that
+/// the order of `iface.functions` is preserved.
+pub fn print_interface(iface: &Interface) -> String {
+    let mut printer = Printer::default();
+    printer.interface(iface);
+    printer.out
+}
+
+/// Prints `ty` as it's referred to in witx source, such as `list<u8>` or
+/// the name of a named type.
+pub fn print_type(iface: &Interface, ty: &Type) -> String {
+    Names::new(iface).ty(ty)
+}
+
+/// Prints the definition of the type `id`, such as the whole `record` item
+/// of a named record or the `resource` item of a resource's handle type.
+pub fn print_typedef(iface: &Interface, id: TypeId) -> String {
+    let names = Names::new(iface);
+    let mut printer = Printer::default();
+    match (&iface.types[id].name, &iface.types[id].kind) {
+        (Some(name), _) => printer.typedef(&names, &self::id(name), id),
+        (None, TypeDefKind::Type(Type::Handle(resource))) => {
+            printer.resource(&names, iface, *resource)
+        }
+        (None, kind) => return names.kind(kind),
+    }
+    printer.out.pop();
+    printer.out
+}
+
+fn count_comments(input: &str) -> usize {
+    let mut tokens = Tokenizer::new(input);
+    let mut count = 0;
+    while let Ok(Some((_, token))) = tokens.next_raw() {
+        if token == Token::Comment {
+            count += 1;
+        }
+    }
+    count
+}
+
+#[derive(Default)]
+struct Printer {
+    out: String,
+    indent: usize,
+}
+
+impl Printer {
+    fn line(&mut self, line: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str("  ");
+        }
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+
+    /// Prints a `keyword name { ... }` block with one entry per line.
+    fn block(&mut self, keyword: &str, name: &str, entries: Vec<(Vec<String>, String)>) {
+        if entries.is_empty() {
+            self.line(&format!("{} {} {{}}", keyword, name));
+            return;
+        }
+        self.line(&format!("{} {} {{", keyword, name));
+        self.indent += 1;
+        for (docs, entry) in entries {
+            for doc in docs {
+                self.line(&doc);
+            }
+            self.line(&format!("{},", entry));
+        }
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    fn ast_items(&mut self, items: &[Item<'_>]) {
+        let mut prev_use = false;
+        for (i, item) in items.iter().enumerate() {
+            // Consecutive `use` items are grouped together unless they're
+            // documented.
+            let is_use = match item {
+                Item::Use(u) => u.docs.docs.is_empty(),
+                _ => false,
+            };
+            if i > 0 && !(is_use && prev_use) {
+                self.out.push('\n');
+            }
+            prev_use = is_use;
+
+            match item {
+                Item::Use(u) => {
+                    self.ast_docs(&u.docs);
+                    let from = u
+                        .from
+                        .iter()
+                        .enumerate()
+                        .map(|(i, m)| {
+                            if i == 0 {
+                                module_name(&m.name)
+                            } else {
+                                id(&m.name)
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                        .join("::");
+                    match &u.names {
+                        Some(names) => {
+                            let names = names
+                                .iter()
+                                .map(|n| match &n.as_ {
+                                    Some(as_) => {
+                                        format!("{} as {}", id(&n.name.name), id(&as_.name))
+                                    }
+                                    None => id(&n.name.name),
+                                })
+                                .collect::<Vec<_>>();
+                            self.line(&format!("use {{ {} }} from {}", names.join(", "), from));
+                        }
+                        None => self.line(&format!("use * from {}", from)),
+                    }
+                }
+                Item::Resource(r) => {
+                    self.ast_docs(&r.docs);
+                    self.ast_attrs(&r.attrs);
+                    let name = id(&r.name.name);
+                    if r.values.is_empty() {
+                        self.line(&format!("resource {}", name));
+                    } else {
+                        self.line(&format!("resource {} {{", name));
+                        self.indent += 1;
+                        for (statik, value) in r.values.iter() {
+                            self.ast_value(value, *statik);
+                        }
+                        self.indent -= 1;
+                        self.line("}");
+                    }
+                }
+                Item::TypeDef(t) => {
+                    self.ast_docs(&t.docs);
+                    self.ast_attrs(&t.attrs);
+                    let mut name = id(&t.name.name);
+                    if !t.params.is_empty() {
+                        let params = t.params.iter().map(|p| id(&p.name)).collect::<Vec<_>>();
+                        name.push_str(&format!("<{}>", params.join(", ")));
+                    }
+                    self.ast_typedef(&name, &t.ty);
+                }
+                Item::Value(v) => self.ast_value(v, false),
+                Item::Const(c) => {
+                    self.ast_docs(&c.docs);
+                    self.ast_attrs(&c.attrs);
+                    let value = match &c.value {
+                        ast::ConstValue::Bare(v) => v.name.to_string(),
+                        ast::ConstValue::String(v) => quote(&v.name),
+                        ast::ConstValue::Flags(flags) => flag_set(flags.iter().map(|f| &*f.name)),
+                    };
+                    self.line(&format!(
+                        "const {}: {} = {}",
+                        id(&c.name.name),
+                        ast_type(&c.ty),
+                        value
+                    ));
+                }
+                Item::Interface(i) => {
+                    self.ast_docs(&i.docs);
+                    self.line(&format!("interface {} {{", id(&i.name.name)));
+                    self.indent += 1;
+                    self.ast_items(&i.items);
+                    self.indent -= 1;
+                    self.line("}");
+                }
+            }
+        }
+    }
+
+    fn ast_docs(&mut self, docs: &ast::Docs<'_>) {
+        for doc in docs.docs.iter() {
+            self.line(doc.trim_end());
+        }
+    }
+
+    fn ast_attrs(&mut self, attrs: &[ast::Attribute<'_>]) {
+        for attr in ast_attr_lines(attrs) {
+            self.line(&attr);
+        }
+    }
+
+    fn ast_typedef(&mut self, name: &str, ty: &ast::Type<'_>) {
+        let docs = |docs: &ast::Docs<'_>, attrs: &[ast::Attribute<'_>]| {
+            let mut lines = docs
+                .docs
+                .iter()
+                .map(|d| d.trim_end().to_string())
+                .collect::<Vec<_>>();
+            lines.extend(ast_attr_lines(attrs));
+            lines
+        };
+        match ty {
+            ast::Type::Record(r) if !r.tuple_hint => {
+                let is_flags = !r.fields.is_empty() && r.fields.iter().all(|f| ast_is_bool(&f.ty));
+                let fields = r
+                    .fields
+                    .iter()
+                    .map(|f| {
+                        let entry = if is_flags {
+                            match f.bit {
+                                Some((_, bit)) => {
+                                    format!("{} = {:#x}", id(&f.name.name), 1u64 << bit)
+                                }
+                                None => id(&f.name.name),
+                            }
+                        } else {
+                            format!("{}: {}", id(&f.name.name), ast_type(&f.ty))
+                        };
+                        (docs(&f.docs, &f.attrs), entry)
+                    })
+                    .collect();
+                let keyword = if is_flags { "flags" } else { "record" };
+                self.block(keyword, name, fields);
+            }
+            ast::Type::Variant(v) if ast_variant_sugar(v).is_none() => {
+                let is_union = v.cases.iter().enumerate().all(|(i, c)| {
+                    c.ty.is_some()
+                        && c.discriminant.is_none()
+                        && c.name.name.parse::<usize>().ok() == Some(i)
+                });
+                let (keyword, cases) = if v.cases.iter().all(|c| c.ty.is_none()) {
+                    let cases = v.cases.iter().map(|c| id(&c.name.name));
+                    ("enum", cases.collect::<Vec<_>>())
+                } else if is_union {
+                    let cases = v.cases.iter().map(|c| ast_type(c.ty.as_ref().unwrap()));
+                    ("union", cases.collect())
+                } else {
+                    let cases = v.cases.iter().map(|c| match &c.ty {
+                        Some(ty) => format!("{}({})", id(&c.name.name), ast_type(ty)),
+                        None => id(&c.name.name),
+                    });
+                    ("variant", cases.collect())
+                };
+                let cases = v
+                    .cases
+                    .iter()
+                    .zip(cases)
+                    .map(|(c, entry)| {
+                        let entry = match c.discriminant {
+                            Some((_, d)) => format!("{} = {}", entry, d),
+                            None => entry,
+                        };
+                        (docs(&c.docs, &c.attrs), entry)
+                    })
+                    .collect();
+                self.block(keyword, name, cases);
+            }
+            ty => self.line(&format!("type {} = {}", name, ast_type(ty))),
+        }
+    }
+
+    fn ast_value(&mut self, value: &ast::Value<'_>, statik: bool) {
+        self.ast_docs(&value.docs);
+        self.ast_attrs(&value.attrs);
+        let name = id(&value.name.name);
+        let statik = if statik { "static " } else { "" };
+        match &value.kind {
+            ast::ValueKind::Function {
+                is_async,
+                params,
+                results,
+                ..
+            } => {
+                let params = params
+                    .iter()
+                    .map(|(name, ty)| (&*name.name, ast_type(ty)))
+                    .collect();
+                let results = results
+                    .iter()
+                    .map(|(name, ty)| (&*name.name, ast_type(ty)))
+                    .collect();
+                self.line(&format!(
+                    "{}{}: {}",
+                    statik,
+                    name,
+                    function(*is_async, params, results)
+                ));
+            }
+            ast::ValueKind::Global(ty) => {
+                self.line(&format!("{}{}: {}", statik, name, ast_type(ty)));
+            }
+        }
+    }
+
+    fn interface(&mut self, iface: &Interface) {
+        let names = Names::new(iface);
+
+        // Everything defined elsewhere is imported from the module which
+        // originally defined it.
+        let mut imports = BTreeMap::new();
+        for (local, id) in iface.resource_lookup.iter() {
+            let resource = &iface.resources[*id];
+            if let Some(module) = &resource.foreign_module {
+                imports
+                    .entry(module)
+                    .or_insert_with(Vec::new)
+                    .push((resource.name.clone(), local.clone()));
+            }
+        }
+        for (local, id) in iface.type_lookup.iter() {
+            let ty = &iface.types[*id];
+            let module = match &ty.foreign_module {
+                Some(module) => module,
+                None => continue,
+            };
+            let name = match (&ty.name, &ty.kind) {
+                (Some(name), _) => name.clone(),
+                // resources are imported above
+                (None, TypeDefKind::Type(Type::Handle(_))) => continue,
+                (None, _) => unreachable!("anonymous types can't be imported"),
+            };
+            imports
+                .entry(module)
+                .or_insert_with(Vec::new)
+                .push((name, local.clone()));
+        }
+        // Imported types can refer to other types from the same module which
+        // weren't themselves imported, and those need to be nameable too.
+        for (id, resource) in iface.resources.iter() {
+            if let Some(module) = &resource.foreign_module {
+                if !iface.resource_lookup.values().any(|r| *r == id) {
+                    imports
+                        .entry(module)
+                        .or_insert_with(Vec::new)
+                        .push((resource.name.clone(), resource.name.clone()));
+                }
+            }
+        }
+        for (id, ty) in iface.types.iter() {
+            if let (Some(module), Some(name)) = (&ty.foreign_module, &ty.name) {
+                if !iface.type_lookup.values().any(|t| *t == id) {
+                    imports
+                        .entry(module)
+                        .or_insert_with(Vec::new)
+                        .push((name.clone(), name.clone()));
+                }
+            }
+        }
+        for (module, mut names) in imports {
+            names.sort();
+            names.dedup();
+            let names = names
+                .iter()
+                .map(|(name, local)| {
+                    if name == local {
+                        id(name)
+                    } else {
+                        format!("{} as {}", id(name), id(local))
+                    }
+                })
+                .collect::<Vec<_>>();
+            self.line(&format!(
+                "use {{ {} }} from {}",
+                names.join(", "),
+                module_name(module)
+            ));
+        }
+
+        // Local types and resources without any functions are printed in the
+        // order they were defined, which is the order they were allocated in.
+        for (type_id, ty) in iface.types.iter() {
+            if ty.foreign_module.is_some() {
+                continue;
+            }
+            match (&ty.name, &ty.kind) {
+                (Some(name), _) => {
+                    self.separate();
+                    self.docs(&ty.docs);
+                    self.attrs(&ty.attrs);
+                    self.typedef(&names, &id(name), type_id);
+                }
+                // Each resource has an anonymous handle type allocated when
+                // it's defined.
+                (None, TypeDefKind::Type(Type::Handle(resource)))
+                    if resource_functions(iface, *resource).is_empty() =>
+                {
+                    self.separate();
+                    self.resource(&names, iface, *resource);
+                }
+                (None, _) => {}
+            }
+        }
+
+        for c in iface.constants.iter() {
+            self.separate();
+            self.docs(&c.docs);
+            self.attrs(&c.attrs);
+            let value = match &c.value {
+                ConstValue::Bool(b) => b.to_string(),
+                ConstValue::Unsigned(n) => n.to_string(),
+                ConstValue::Signed(n) => n.to_string(),
+                // `Debug` always includes a decimal point or exponent.
+                ConstValue::Float(n) => format!("{:?}", n),
+                ConstValue::Char(c) => quote(&c.to_string()),
+                ConstValue::String(s) => quote(s),
+                ConstValue::Enum { case, .. } => id(case),
+                ConstValue::Flags { flags, .. } => flag_set(flags.iter().map(|f| f.as_str())),
+            };
+            self.line(&format!(
+                "const {}: {} = {}",
+                id(&c.name),
+                names.ty(&c.ty),
+                value
+            ));
+        }
+
+        // Resources with functions are printed where their functions are so
+        // that the order of all functions is preserved.
+        let mut printed = HashSet::new();
+        for func in iface.functions.iter() {
+            match func.kind {
+                FunctionKind::Freestanding => {
+                    self.separate();
+                    self.docs(&func.docs);
+                    self.attrs(&func.attrs);
+                    let sig = self.signature(&names, func, 0);
+                    self.line(&format!("{}: {}", id(&func.name), sig));
+                }
+                FunctionKind::Static { resource, .. } | FunctionKind::Method { resource, .. } => {
+                    if printed.insert(resource) {
+                        self.separate();
+                        self.resource(&names, iface, resource);
+                    }
+                }
+            }
+        }
+
+        for global in iface.globals.iter() {
+            self.separate();
+            self.docs(&global.docs);
+            self.attrs(&global.attrs);
+            self.line(&format!("{}: {}", id(&global.name), names.ty(&global.ty)));
+        }
+    }
+
+    /// Separates the next item from the previous one with a blank line.
+    fn separate(&mut self) {
+        if !self.out.is_empty() {
+            self.out.push('\n');
+        }
+    }
+
+    fn docs(&mut self, docs: &Docs) {
+        for line in doc_lines(docs) {
+            self.line(&line);
+        }
+    }
+
+    fn attrs(&mut self, attrs: &Attrs) {
+        for line in attr_lines(attrs) {
+            self.line(&line);
+        }
+    }
+
+    fn resource(&mut self, names: &Names<'_>, iface: &Interface, resource_id: ResourceId) {
+        let resource = &iface.resources[resource_id];
+        self.docs(&resource.docs);
+        self.attrs(&resource.attrs);
+        let name = names.resource(resource_id);
+        let funcs = resource_functions(iface, resource_id);
+        if funcs.is_empty() {
+            self.line(&format!("resource {}", name));
+            return;
+        }
+        self.line(&format!("resource {} {{", name));
+        self.indent += 1;
+        for func in funcs {
+            self.docs(&func.docs);
+            self.attrs(&func.attrs);
+            let (statik, skip) = match func.kind {
+                FunctionKind::Static { .. } => ("static ", 0),
+                // the implicit `self` parameter isn't written out
+                _ => ("", 1),
+            };
+            let sig = self.signature(names, func, skip);
+            self.line(&format!("{}{}: {}", statik, id(func.item_name()), sig));
+        }
+        self.indent -= 1;
+        self.line("}");
+    }
+
+    fn signature(&self, names: &Names<'_>, func: &Function, skip: usize) -> String {
+        let params = func.params[skip..]
+            .iter()
+            .map(|(name, ty)| (name.as_str(), names.ty(ty)))
+            .collect();
+        let results = func
+            .results
+            .iter()
+            .map(|(name, ty)| (name.as_str(), names.ty(ty)))
+            .collect();
+        function(func.is_async, params, results)
+    }
+
+    fn typedef(&mut self, names: &Names<'_>, name: &str, type_id: TypeId) {
+        let ty = &names.iface.types[type_id];
+        match &ty.kind {
+            TypeDefKind::Record(r) if !r.is_tuple() => {
+                // Records of bools are inferred to be flags, but they can only
+                // be written as `flags` if the bools aren't named types.
+                let is_flags = r.is_flags() && r.fields.iter().all(|f| f.ty == Type::Bool);
+                let mut next = 0;
+                let fields = r
+                    .fields
+                    .iter()
+                    .map(|f| {
+                        let entry = if is_flags {
+                            let implicit = f.bit == next;
+                            next = f.bit + 1;
+                            if implicit {
+                                id(&f.name)
+                            } else {
+                                format!("{} = {:#x}", id(&f.name), 1u64 << f.bit)
+                            }
+                        } else {
+                            format!("{}: {}", id(&f.name), names.ty(&f.ty))
+                        };
+                        (member_lines(&f.docs, &f.attrs), entry)
+                    })
+                    .collect();
+                let keyword = if is_flags { "flags" } else { "record" };
+                self.block(keyword, name, fields);
+            }
+            TypeDefKind::Variant(v) if v.as_option().is_none() && v.as_expected().is_none() => {
+                let is_union = v
+                    .cases
+                    .iter()
+                    .enumerate()
+                    .all(|(i, c)| c.ty.is_some() && c.name.parse::<usize>().ok() == Some(i))
+                    && v.has_default_discriminants();
+                let mut next = 0;
+                let cases = v.cases.iter().map(|c| {
+                    let entry = if v.is_enum() {
+                        id(&c.name)
+                    } else if is_union {
+                        names.ty(c.ty.as_ref().unwrap())
+                    } else {
+                        match &c.ty {
+                            Some(ty) => format!("{}({})", id(&c.name), names.ty(ty)),
+                            None => id(&c.name),
+                        }
+                    };
+                    let entry = if c.discriminant == next {
+                        entry
+                    } else {
+                        format!("{} = {}", entry, c.discriminant)
+                    };
+                    next = c.discriminant.wrapping_add(1);
+                    (member_lines(&c.docs, &c.attrs), entry)
+                });
+                let keyword = if v.is_enum() {
+                    "enum"
+                } else if is_union {
+                    "union"
+                } else {
+                    "variant"
+                };
+                self.block(keyword, name, cases.collect());
+            }
+            kind => self.line(&format!("type {} = {}", name, names.kind(kind))),
+        }
+    }
+}
+
+/// Names by which types and resources can be referred to in an interface.
+pub(crate) struct Names<'a> {
+    iface: &'a Interface,
+    types: HashMap<TypeId, &'a str>,
+    resources: HashMap<ResourceId, &'a str>,
+}
+
+impl<'a> Names<'a> {
+    pub(crate) fn new(iface: &'a Interface) -> Names<'a> {
+        // If something is available under multiple names then the smallest
+        // is picked so the output is deterministic.
+        let mut types = HashMap::new();
+        for (name, id) in iface.type_lookup.iter() {
+            let entry = types.entry(*id).or_insert(name.as_str());
+            if name.as_str() < *entry {
+                *entry = name;
+            }
+        }
+        let mut resources = HashMap::new();
+        for (name, id) in iface.resource_lookup.iter() {
+            let entry = resources.entry(*id).or_insert(name.as_str());
+            if name.as_str() < *entry {
+                *entry = name;
+            }
+        }
+        for (id, ty) in iface.types.iter() {
+            if let Some(name) = &ty.name {
+                types.entry(id).or_insert(name.as_str());
+            }
+        }
+        for (id, resource) in iface.resources.iter() {
+            resources.entry(id).or_insert(resource.name.as_str());
+        }
+        Names {
+            iface,
+            types,
+            resources,
+        }
+    }
+
+    fn resource(&self, id: ResourceId) -> String {
+        self::id(self.resources[&id])
+    }
+
+    pub(crate) fn ty(&self, ty: &Type) -> String {
+        match ty {
+            Type::Id(id) => match self.types.get(id) {
+                Some(name) => self::id(name),
+                None => self.kind(&self.iface.types[*id].kind),
+            },
+            Type::Handle(id) => format!("handle {}", self.resource(*id)),
+            Type::U8 => "u8".to_string(),
+            Type::U16 => "u16".to_string(),
+            Type::U32 => "u32".to_string(),
+            Type::U64 => "u64".to_string(),
+            Type::S8 => "s8".to_string(),
+            Type::S16 => "s16".to_string(),
+            Type::S32 => "s32".to_string(),
+            Type::S64 => "s64".to_string(),
+            Type::F32 => "f32".to_string(),
+            Type::F64 => "f64".to_string(),
+            Type::Char => "char".to_string(),
+            Type::Bool => "bool".to_string(),
+            Type::String => "string".to_string(),
+            Type::CChar => "c-char".to_string(),
+            Type::Usize => "usize".to_string(),
+        }
+    }
+
+    /// Prints an anonymous type definition.
+    pub(crate) fn kind(&self, kind: &TypeDefKind) -> String {
+        let opt = |ty: Option<&Type>| match ty {
+            Some(ty) => self.ty(ty),
+            None => "_".to_string(),
+        };
+        match kind {
+            TypeDefKind::Type(ty) => self.ty(ty),
+            TypeDefKind::List(ty) => format!("list<{}>", self.ty(ty)),
+            TypeDefKind::Array(ty, len) => format!("array<{}, {}>", self.ty(ty), len),
+            TypeDefKind::PushBuffer(ty) => format!("push-buffer<{}>", self.ty(ty)),
+            TypeDefKind::PullBuffer(ty) => format!("pull-buffer<{}>", self.ty(ty)),
+            TypeDefKind::Pointer(ty) => format!("pointer<{}>", self.ty(ty)),
+            TypeDefKind::ConstPointer(ty) => format!("const-pointer<{}>", self.ty(ty)),
+            TypeDefKind::Record(r) => self.record(r),
+            TypeDefKind::Variant(v) => {
+                if let Some(ty) = v.as_option() {
+                    format!("option<{}>", self.ty(ty))
+                } else if let Some((ok, err)) = v.as_expected() {
+                    format!("expected<{}, {}>", opt(ok), opt(err))
+                } else {
+                    let cases = v
+                        .cases
+                        .iter()
+                        .map(|c| match &c.ty {
+                            Some(ty) => format!("{}({})", id(&c.name), self.ty(ty)),
+                            None => id(&c.name),
+                        })
+                        .collect::<Vec<_>>();
+                    let keyword = if v.is_enum() { "enum" } else { "variant" };
+                    format!("{} {{ {} }}", keyword, cases.join(", "))
+                }
+            }
+        }
+    }
+
+    /// Prints an anonymous record, which is only valid syntax for tuples.
+    pub(crate) fn record(&self, r: &Record) -> String {
+        // Empty tuples aren't inferred to be tuples.
+        if r.is_tuple() || r.fields.is_empty() {
+            let tys = r.fields.iter().map(|f| self.ty(&f.ty)).collect::<Vec<_>>();
+            return format!("tuple<{}>", tys.join(", "));
+        }
+        if r.is_flags() {
+            return format!("flags {}", flag_set(r.fields.iter().map(|f| &f.name[..])));
+        }
+        let fields = r
+            .fields
+            .iter()
+            .map(|f| format!("{}: {}", id(&f.name), self.ty(&f.ty)))
+            .collect::<Vec<_>>();
+        format!("record {{ {} }}", fields.join(", "))
+    }
+}
+
+fn ast_type(ty: &ast::Type<'_>) -> String {
+    match ty {
+        ast::Type::U8 => "u8".to_string(),
+        ast::Type::U16 => "u16".to_string(),
+        ast::Type::U32 => "u32".to_string(),
+        ast::Type::U64 => "u64".to_string(),
+        ast::Type::S8 => "s8".to_string(),
+        ast::Type::S16 => "s16".to_string(),
+        ast::Type::S32 => "s32".to_string(),
+        ast::Type::S64 => "s64".to_string(),
+        ast::Type::F32 => "f32".to_string(),
+        ast::Type::F64 => "f64".to_string(),
+        ast::Type::Char => "char".to_string(),
+        ast::Type::Bool => "bool".to_string(),
+        ast::Type::String => "string".to_string(),
+        ast::Type::Handle(name) => format!("handle {}", id(&name.name)),
+        ast::Type::Name(name) => id(&name.name),
+        ast::Type::Generic(name, args) => {
+            let args = args.iter().map(ast_type).collect::<Vec<_>>();
+            format!("{}<{}>", id(&name.name), args.join(", "))
+        }
+        ast::Type::List(ty) => format!("list<{}>", ast_type(ty)),
+        ast::Type::Array(ty, len) => format!("array<{}, {}>", ast_type(ty), len),
+        ast::Type::PushBuffer(ty) => format!("push-buffer<{}>", ast_type(ty)),
+        ast::Type::PullBuffer(ty) => format!("pull-buffer<{}>", ast_type(ty)),
+        ast::Type::Record(r) if r.tuple_hint => {
+            let tys = r.fields.iter().map(|f| ast_type(&f.ty)).collect::<Vec<_>>();
+            format!("tuple<{}>", tys.join(", "))
+        }
+        ast::Type::Variant(v) => match ast_variant_sugar(v) {
+            Some(s) => s,
+            None => panic!("anonymous variants can't be printed"),
+        },
+        ast::Type::Record(_) => panic!("anonymous records can't be printed"),
+        ast::Type::Usize | ast::Type::CChar => panic!("old witx types can't be printed"),
+        ast::Type::Pointer(_) | ast::Type::ConstPointer(_) => panic!("pointers can't be printed"),
+    }
+}
+
+fn ast_is_bool(ty: &ast::Type<'_>) -> bool {
+    matches!(ty, ast::Type::Bool)
+}
+
+/// Returns how `v` is written if it's the desugared form of `option` or
+/// `expected`.
+fn ast_variant_sugar(v: &ast::Variant<'_>) -> Option<String> {
+    if v.cases.len() != 2 || v.cases.iter().any(|c| c.discriminant.is_some()) {
+        return None;
+    }
+    let (a, b) = (&v.cases[0], &v.cases[1]);
+    let opt = |ty: &Option<ast::Type<'_>>| match ty {
+        Some(ty) => ast_type(ty),
+        None => "_".to_string(),
+    };
+    match (&*a.name.name, &*b.name.name) {
+        ("none", "some") if a.ty.is_none() => {
+            let ty = b.ty.as_ref()?;
+            Some(format!("option<{}>", ast_type(ty)))
+        }
+        ("ok", "err") => Some(format!("expected<{}, {}>", opt(&a.ty), opt(&b.ty))),
+        _ => None,
+    }
+}
+
+fn function(is_async: bool, params: Vec<(&str, String)>, results: Vec<(&str, String)>) -> String {
+    let params = params
+        .iter()
+        .map(|(name, ty)| format!("{}: {}", id(name), ty))
+        .collect::<Vec<_>>();
+    let mut ret = format!(
+        "{}function({})",
+        if is_async { "async " } else { "" },
+        params.join(", ")
+    );
+    match results.as_slice() {
+        [] => {}
+        [("", ty)] => ret.push_str(&format!(" -> {}", ty)),
+        results => {
+            let results = results
+                .iter()
+                .map(|(name, ty)| {
+                    if name.is_empty() {
+                        ty.clone()
+                    } else {
+                        format!("{}: {}", id(name), ty)
+                    }
+                })
+                .collect::<Vec<_>>();
+            ret.push_str(&format!(" -> ({})", results.join(", ")));
+        }
+    }
+    ret
+}
+
+fn resource_functions(iface: &Interface, resource: ResourceId) -> Vec<&Function> {
+    iface
+        .functions
+        .iter()
+        .filter(|f| match f.kind {
+            FunctionKind::Static { resource: r, .. } | FunctionKind::Method { resource: r, .. } => {
+                r == resource
+            }
+            FunctionKind::Freestanding => false,
+        })
+        .collect()
+}
+
+fn doc_lines(docs: &Docs) -> Vec<String> {
+    let contents = match &docs.contents {
+        Some(contents) => contents,
+        None => return Vec::new(),
+    };
+    contents
+        .lines()
+        .map(|line| {
+            if line.is_empty() {
+                "///".to_string()
+            } else {
+                format!("/// {}", line)
+            }
+        })
+        .collect()
+}
+
+fn flag_set<'a>(flags: impl Iterator<Item = &'a str>) -> String {
+    let flags = flags.map(id).collect::<Vec<_>>();
+    if flags.is_empty() {
+        "{}".to_string()
+    } else {
+        format!("{{ {} }}", flags.join(", "))
+    }
+}
+
+fn member_lines(docs: &Docs, attrs: &Attrs) -> Vec<String> {
+    let mut lines = doc_lines(docs);
+    lines.extend(attr_lines(attrs));
+    lines
+}
+
+fn attr_lines(attrs: &Attrs) -> Vec<String> {
+    attrs
+        .attrs
+        .iter()
+        .map(|(name, args)| {
+            let args = args
+                .iter()
+                .map(|arg| (arg.name.as_deref(), arg.value.as_str()));
+            attr(name, args)
+        })
+        .collect()
+}
+
+fn ast_attr_lines(attrs: &[ast::Attribute<'_>]) -> Vec<String> {
+    attrs
+        .iter()
+        .map(|a| {
+            let args = a
+                .args
+                .iter()
+                .map(|arg| (arg.name.as_ref().map(|n| &*n.name), &*arg.value.name));
+            attr(&a.name.name, args)
+        })
+        .collect()
+}
+
+fn attr<'a>(name: &str, args: impl ExactSizeIterator<Item = (Option<&'a str>, &'a str)>) -> String {
+    if args.len() == 0 {
+        return format!("@{}", id(name));
+    }
+    let args = args
+        .map(|(name, value)| match name {
+            Some(name) => format!("{} = {}", id(name), attr_value(value)),
+            None => attr_value(value),
+        })
+        .collect::<Vec<_>>();
+    format!("@{}({})", id(name), args.join(", "))
+}
+
+/// Returns `value` as an attribute argument, which can be left bare if it's
+/// made up of identifiers and periods, like `1.2`.
+fn attr_value(value: &str) -> String {
+    let mut tokens = Tokenizer::new(value);
+    let mut end = 0;
+    while let Ok(Some((span, Token::Id))) | Ok(Some((span, Token::Period))) = tokens.next_raw() {
+        if span.start != end {
+            break;
+        }
+        end = span.end;
+    }
+    if end > 0 && end as usize == value.len() {
+        value.to_string()
+    } else {
+        quote(value)
+    }
+}
+
+/// Returns `name` as an identifier, quoting it if it isn't a valid bare
+/// identifier.
+fn id(name: &str) -> String {
+    let mut tokens = Tokenizer::new(name);
+    if let Ok(Some((span, Token::Id))) = tokens.next() {
+        if span.start == 0 && span.end as usize == name.len() {
+            return name.to_string();
+        }
+    }
+    quote(name)
+}
+
+/// Prints a module name such as `wasi.io.streams`, quoting each part of it
+/// as necessary.
+fn module_name(name: &str) -> String {
+    name.split('.').map(id).collect::<Vec<_>>().join(".")
+}
+
+fn quote(name: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in name.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\t' => quoted.push_str("\\t"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
@@ -0,0 +1,160 @@
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+/// Locates the modules referred to by `use` items while parsing an
+/// interface.
+///
+/// Module names are either a single identifier, as in `use * from types`, or
+/// a dotted path, as in `use * from wasi.io.streams`. Closures of the form
+/// `FnMut(&str) -> Result<(PathBuf, String)>` are resolvers as well.
+pub trait Resolver {
+    /// Returns the path and contents of the module `name`.
+    ///
+    /// The path is used in error messages and to detect cycles, so it must
+    /// be the same every time the same module is loaded.
+    fn load(&mut self, name: &str) -> Result<(PathBuf, String)>;
+}
+
+impl<F> Resolver for F
+where
+    F: FnMut(&str) -> Result<(PathBuf, String)>,
+{
+    fn load(&mut self, name: &str) -> Result<(PathBuf, String)> {
+        self(name)
+    }
+}
+
+/// A [`Resolver`] which looks up modules as `*.witx` files in a list of
+/// directories.
+///
+/// The module `wasi.io.streams` is found at `wasi/io/streams.witx` relative
+/// to the first directory which contains it.
+#[derive(Debug, Clone, Default)]
+pub struct FsResolver {
+    search_path: Vec<PathBuf>,
+}
+
+impl FsResolver {
+    /// Creates a resolver which searches `search_path` in order.
+    pub fn new(search_path: Vec<PathBuf>) -> FsResolver {
+        FsResolver { search_path }
+    }
+
+    /// Returns the directories which are searched, in order.
+    pub fn search_path(&self) -> &[PathBuf] {
+        &self.search_path
+    }
+
+    /// Returns every path the module `name` could be at, in the order
+    /// they're tried.
+    pub fn candidates(&self, name: &str) -> Vec<PathBuf> {
+        let mut relative = name.split('.').collect::<PathBuf>();
+        relative.set_extension("witx");
+        self.search_path
+            .iter()
+            .map(|dir| dir.join(&relative))
+            .collect()
+    }
+}
+
+impl Resolver for FsResolver {
+    fn load(&mut self, name: &str) -> Result<(PathBuf, String)> {
+        let candidates = self.candidates(name);
+        for path in candidates.iter() {
+            if !path.is_file() {
+                continue;
+            }
+            let contents = fs::read_to_string(path)
+                .with_context(|| format!("failed to read `{}`", path.display()))?;
+            return Ok((path.clone(), contents));
+        }
+        if candidates.is_empty() {
+            bail!("module `{}` not found, the search path is empty", name);
+        }
+        let mut msg = format!("module `{}` not found, tried:", name);
+        for path in candidates.iter() {
+            msg.push_str(&format!("\n    {}", path.display()));
+        }
+        bail!("{}", msg)
+    }
+}
+
+/// A [`Resolver`] for modules which only exist in memory.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryResolver {
+    modules: HashMap<String, String>,
+}
+
+impl MemoryResolver {
+    pub fn new() -> MemoryResolver {
+        MemoryResolver::default()
+    }
+
+    /// Defines the module `name` to have the source `contents`.
+    pub fn insert(&mut self, name: impl Into<String>, contents: impl Into<String>) {
+        self.modules.insert(name.into(), contents.into());
+    }
+}
+
+impl Resolver for MemoryResolver {
+    fn load(&mut self, name: &str) -> Result<(PathBuf, String)> {
+        match self.modules.get(name) {
+            Some(contents) => Ok((PathBuf::from(format!("{}.witx", name)), contents.clone())),
+            None => bail!("module `{}` not defined", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Interface;
+    use std::path::Path;
+
+    #[test]
+    fn search_path_order() {
+        let mut resolver = FsResolver::new(vec!["tests/ui/parse-fail".into(), "tests/ui".into()]);
+        let (path, _) = resolver.load("nested.streams").unwrap();
+        assert_eq!(path, Path::new("tests/ui/nested/streams.witx"));
+        let (path, _) = resolver.load("import-me").unwrap();
+        assert_eq!(path, Path::new("tests/ui/parse-fail/import-me.witx"));
+    }
+
+    #[test]
+    fn not_found_lists_every_candidate() {
+        let mut resolver = FsResolver::new(vec!["a".into(), "b/c".into()]);
+        let err = resolver.load("x.y").unwrap_err().to_string();
+        let a = Path::new("a").join("x").join("y.witx");
+        let b = Path::new("b/c").join("x").join("y.witx");
+        assert_eq!(
+            err,
+            format!(
+                "module `x.y` not found, tried:\n    {}\n    {}",
+                a.display(),
+                b.display()
+            )
+        );
+    }
+
+    #[test]
+    fn memory() -> Result<()> {
+        let mut resolver = MemoryResolver::new();
+        resolver.insert("wasi.io.streams", "resource input-stream");
+        let iface = Interface::parse_with_resolver(
+            "foo.witx",
+            "use { input-stream } from wasi.io.streams\nread: function(s: input-stream)",
+            &mut resolver,
+        )?;
+        let id = iface.resource_lookup["input-stream"];
+        assert_eq!(
+            iface.resources[id].foreign_module.as_deref(),
+            Some("wasi.io.streams")
+        );
+        assert!(
+            Interface::parse_with_resolver("foo.witx", "use * from other", &mut resolver).is_err()
+        );
+        Ok(())
+    }
+}
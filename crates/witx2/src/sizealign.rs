@@ -19,6 +19,11 @@ impl SizeAlign {
         match &ty.kind {
             TypeDefKind::Type(t) => (self.size(t), self.align(t)),
             TypeDefKind::List(_) => (8, 4),
+            // Sizes saturate rather than overflowing, and types which are too
+            // large are rejected when they're resolved.
+            TypeDefKind::Array(t, len) => {
+                (self.size(t).saturating_mul(*len as usize), self.align(t))
+            }
             TypeDefKind::Pointer(_) | TypeDefKind::ConstPointer(_) => (4, 4),
             TypeDefKind::PushBuffer(_) | TypeDefKind::PullBuffer(_) => match dir {
                 Direction::Import => (12, 4),
@@ -28,10 +33,10 @@ impl SizeAlign {
                 if let RecordKind::Flags(repr) = r.kind {
                     return match repr {
                         Some(i) => int_size_align(i),
-                        None if r.fields.len() <= 8 => (1, 1),
-                        None if r.fields.len() <= 16 => (2, 2),
-                        None if r.fields.len() <= 32 => (4, 4),
-                        None if r.fields.len() <= 64 => (8, 8),
+                        None if r.num_bits() <= 8 => (1, 1),
+                        None if r.num_bits() <= 16 => (2, 2),
+                        None if r.num_bits() <= 32 => (4, 4),
+                        None if r.num_bits() <= 64 => (8, 8),
                         None => (r.num_i32s() * 4, 4),
                     };
                 }
@@ -40,7 +45,7 @@ impl SizeAlign {
                 for f in r.fields.iter() {
                     let field_size = self.size(&f.ty);
                     let field_align = self.align(&f.ty);
-                    size = align_to(size, field_align) + field_size;
+                    size = align_to(size, field_align).saturating_add(field_size);
                     align = align.max(field_align);
                 }
                 (align_to(size, align), align)
@@ -54,7 +59,8 @@ impl SizeAlign {
                         let case_size = self.size(ty);
                         let case_align = self.align(ty);
                         align = align.max(case_align);
-                        size = size.max(align_to(discrim_size, case_align) + case_size);
+                        size =
+                            size.max(align_to(discrim_size, case_align).saturating_add(case_size));
                     }
                 }
                 (size, align)
@@ -64,19 +70,25 @@ impl SizeAlign {
 
     pub fn size(&self, ty: &Type) -> usize {
         match ty {
-            Type::U8 | Type::S8 | Type::CChar => 1,
+            Type::U8 | Type::S8 | Type::CChar | Type::Bool => 1,
             Type::U16 | Type::S16 => 2,
             Type::U32 | Type::S32 | Type::F32 | Type::Char | Type::Handle(_) | Type::Usize => 4,
-            Type::U64 | Type::S64 | Type::F64 => 8,
+            Type::U64 | Type::S64 | Type::F64 | Type::String => 8,
             Type::Id(id) => self.map[id.index()].0,
         }
     }
 
     pub fn align(&self, ty: &Type) -> usize {
         match ty {
-            Type::U8 | Type::S8 | Type::CChar => 1,
+            Type::U8 | Type::S8 | Type::CChar | Type::Bool => 1,
             Type::U16 | Type::S16 => 2,
-            Type::U32 | Type::S32 | Type::F32 | Type::Char | Type::Handle(_) | Type::Usize => 4,
+            Type::U32
+            | Type::S32
+            | Type::F32
+            | Type::Char
+            | Type::Handle(_)
+            | Type::Usize
+            | Type::String => 4,
             Type::U64 | Type::S64 | Type::F64 => 8,
             Type::Id(id) => self.map[id.index()].1,
         }
@@ -117,5 +129,5 @@ fn int_size_align(i: Int) -> (usize, usize) {
 }
 
 fn align_to(val: usize, align: usize) -> usize {
-    (val + align - 1) & !(align - 1)
+    val.saturating_add(align - 1) & !(align - 1)
 }
@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+
+/// A file which an [`Interface`](crate::Interface), or a module it uses, was
+/// parsed from.
+///
+/// This is what [`Span`](crate::Span)s of the items defined in the file
+/// refer to, so it's used to find where an item was defined, for example to
+/// point generated code back at it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+impl SourceFile {
+    /// Returns the 1-based line and column of the byte `offset` in this
+    /// file, with the column counted in characters.
+    pub fn line_col(&self, offset: u32) -> (u32, u32) {
+        let offset = (offset as usize).min(self.contents.len());
+        let before = &self.contents[..offset];
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let line = before.matches('\n').count() + 1;
+        let col = before[line_start..].chars().count() + 1;
+        (line as u32, col as u32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_col() {
+        let file = SourceFile {
+            path: "foo.witx".into(),
+            contents: "a\nbé c\n".to_string(),
+        };
+        assert_eq!(file.line_col(0), (1, 1));
+        assert_eq!(file.line_col(2), (2, 1));
+        assert_eq!(file.line_col(5), (2, 3));
+        assert_eq!(file.line_col(100), (3, 1));
+    }
+}
@@ -0,0 +1,333 @@
+use crate::abi::Abi;
+use crate::*;
+use std::collections::HashMap;
+use std::mem;
+
+/// The features of interfaces that a code generator supports.
+///
+/// Interfaces are checked against these by [`Interface::validate`] so that
+/// unsupported features are reported with the location they're used at,
+/// rather than as a panic while generating code. The default supports
+/// everything.
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    /// Whether functions using `Abi::Preview1` are supported.
+    pub preview1: bool,
+    /// Whether `async` functions are supported.
+    pub async_functions: bool,
+    /// Whether records without a name, other than tuples, are supported.
+    pub anonymous_records: bool,
+    /// Whether variants without a name, other than `option` and `expected`,
+    /// are supported.
+    pub anonymous_variants: bool,
+    /// Whether `push-buffer` and `pull-buffer` types are supported.
+    pub buffers: bool,
+    /// Whether `resource`s, and handles to them, are supported.
+    pub resources: bool,
+}
+
+impl Default for Capabilities {
+    fn default() -> Capabilities {
+        Capabilities {
+            preview1: true,
+            async_functions: true,
+            anonymous_records: true,
+            anonymous_variants: true,
+            buffers: true,
+            resources: true,
+        }
+    }
+}
+
+impl Interface {
+    /// Checks that this interface can be used with a code generator that has
+    /// the `capabilities` given.
+    ///
+    /// This checks the rules of the ABI of each function, that every feature
+    /// used is supported, and that no two names in the same scope become the
+    /// same once converted to the naming conventions of a target language. For
+    /// example `foo-bar` and `foo_bar` are both `FooBar` in Rust.
+    ///
+    /// Returns a diagnostic for every problem found in `file`, which must be
+    /// the file that this interface was parsed from. Only items defined in
+    /// this interface are checked, and problems with types used from other
+    /// modules are reported at the items which use them. Problems with doc
+    /// comments, such as `@param` naming a parameter that doesn't exist, are
+    /// warnings rather than errors.
+    pub fn validate(&self, file: &Path, capabilities: &Capabilities) -> Vec<Diagnostic> {
+        let mut validator = Validator {
+            iface: self,
+            file,
+            caps: capabilities,
+            diagnostics: Vec::new(),
+        };
+        validator.validate();
+        validator.diagnostics.sort_by_key(|d| d.span.start);
+        validator.diagnostics
+    }
+}
+
+struct Validator<'a> {
+    iface: &'a Interface,
+    file: &'a Path,
+    caps: &'a Capabilities,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator<'_> {
+    fn validate(&mut self) {
+        let iface = self.iface;
+
+        for (_, ty) in iface.types.iter() {
+            if ty.name.is_none() || ty.foreign_module.is_some() {
+                continue;
+            }
+            self.type_def_kind(&ty.kind, false, ty.span);
+            match &ty.kind {
+                TypeDefKind::Record(r) if !r.is_tuple() => {
+                    self.names("field", r.fields.iter().map(|f| (&f.name[..], ty.span)));
+                }
+                TypeDefKind::Variant(v) => {
+                    self.names("case", v.cases.iter().map(|c| (&c.name[..], ty.span)));
+                }
+                _ => {}
+            }
+        }
+
+        for (_, resource) in iface.resources.iter() {
+            if resource.foreign_module.is_none() && !self.caps.resources {
+                self.error(
+                    resource.span,
+                    "resources are not supported by this generator",
+                );
+            }
+        }
+
+        for func in iface.functions.iter() {
+            self.function(func);
+        }
+        for global in iface.globals.iter() {
+            self.function(&global.getter());
+        }
+        for constant in iface.constants.iter() {
+            self.ty(&constant.ty, constant.span);
+        }
+
+        let types = iface
+            .types
+            .iter()
+            .filter(|(_, t)| t.foreign_module.is_none())
+            .filter_map(|(_, t)| Some((t.name.as_deref()?, t.span)));
+        let resources = iface
+            .resources
+            .iter()
+            .filter(|(_, r)| r.foreign_module.is_none())
+            .map(|(_, r)| (&r.name[..], r.span));
+        self.names("type", types.chain(resources));
+
+        let mut methods = HashMap::new();
+        let mut values = Vec::new();
+        for func in iface.functions.iter() {
+            match &func.kind {
+                FunctionKind::Freestanding => values.push((&func.name[..], func.span)),
+                FunctionKind::Static { resource, name }
+                | FunctionKind::Method { resource, name } => {
+                    methods
+                        .entry(*resource)
+                        .or_insert_with(Vec::new)
+                        .push((&name[..], func.span));
+                }
+            }
+        }
+        values.extend(iface.globals.iter().map(|g| (&g.name[..], g.span)));
+        values.extend(iface.constants.iter().map(|c| (&c.name[..], c.span)));
+        self.names("value", values);
+        for (_, methods) in methods {
+            self.names("function", methods);
+        }
+    }
+
+    fn function(&mut self, func: &Function) {
+        if let Err(msg) = self.iface.validate_abi(func) {
+            self.error(
+                func.span,
+                format!("invalid function `{}`: {}", func.name, msg),
+            );
+        }
+        if func.abi == Abi::Preview1 && !self.caps.preview1 {
+            self.error(
+                func.span,
+                "functions using the preview1 ABI are not supported by this generator",
+            );
+        }
+        if func.is_async && !self.caps.async_functions {
+            self.error(
+                func.span,
+                "async functions are not supported by this generator",
+            );
+        }
+        for (_, ty) in func.params.iter().chain(func.results.iter()) {
+            self.ty(ty, func.span);
+        }
+        self.names(
+            "parameter",
+            func.params.iter().map(|(name, _)| (&name[..], func.span)),
+        );
+        for (name, _) in func.docs.params.iter() {
+            if !func.params.iter().any(|(p, _)| p == name) {
+                self.warning(
+                    func.span,
+                    format!(
+                        "`@param {}` doesn't document a parameter of `{}`",
+                        name, func.name
+                    ),
+                );
+            }
+        }
+        self.names(
+            "result",
+            func.results
+                .iter()
+                .filter(|(name, _)| !name.is_empty())
+                .map(|(name, _)| (&name[..], func.span)),
+        );
+    }
+
+    /// Checks the parts of `ty` which aren't checked elsewhere, reporting any
+    /// problems at `span`.
+    fn ty(&mut self, ty: &Type, span: Span) {
+        let id = match ty {
+            Type::Id(id) => *id,
+            // Resources of this interface are checked where they're defined.
+            Type::Handle(r) => {
+                if self.iface.resources[*r].foreign_module.is_some() && !self.caps.resources {
+                    self.error(span, "resources are not supported by this generator");
+                }
+                return;
+            }
+            _ => return,
+        };
+        let def = &self.iface.types[id];
+        // Named types of this interface are checked where they're defined.
+        if def.name.is_some() && def.foreign_module.is_none() {
+            return;
+        }
+        self.type_def_kind(&def.kind, def.name.is_none(), span);
+    }
+
+    fn type_def_kind(&mut self, kind: &TypeDefKind, anonymous: bool, span: Span) {
+        match kind {
+            TypeDefKind::Type(t) | TypeDefKind::List(t) | TypeDefKind::Array(t, _) => {
+                self.ty(t, span)
+            }
+            TypeDefKind::Pointer(t) | TypeDefKind::ConstPointer(t) => self.ty(t, span),
+            TypeDefKind::PushBuffer(t) | TypeDefKind::PullBuffer(t) => {
+                if !self.caps.buffers {
+                    self.error(span, "buffers are not supported by this generator");
+                }
+                self.ty(t, span);
+            }
+            TypeDefKind::Record(r) => {
+                if r.is_flags() {
+                    if anonymous {
+                        self.error(span, "flags must be named");
+                    }
+                    if self.iface.flags_repr(r).is_none() {
+                        let msg = if r.num_bits() > r.fields.len() {
+                            format!("flags using bit {} are not supported", r.num_bits() - 1)
+                        } else {
+                            format!("flags with {} members are not supported", r.fields.len())
+                        };
+                        self.error(span, msg);
+                    }
+                } else if anonymous && !r.is_tuple() && !self.caps.anonymous_records {
+                    self.error(
+                        span,
+                        "records without a name are not supported by this generator",
+                    );
+                }
+                for field in r.fields.iter() {
+                    self.ty(&field.ty, span);
+                }
+            }
+            TypeDefKind::Variant(v) => {
+                if anonymous
+                    && v.as_option().is_none()
+                    && v.as_expected().is_none()
+                    && !self.caps.anonymous_variants
+                {
+                    self.error(
+                        span,
+                        "variants without a name are not supported by this generator",
+                    );
+                }
+                for case in v.cases.iter() {
+                    if let Some(ty) = &case.ty {
+                        self.ty(ty, span);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reports names which are the same as an earlier name once converted to
+    /// a naming convention.
+    fn names<'b>(&mut self, what: &str, names: impl IntoIterator<Item = (&'b str, Span)>) {
+        let mut seen = HashMap::new();
+        for (name, span) in names {
+            let prev = *seen.entry(words(name)).or_insert(name);
+            if prev != name {
+                self.error(
+                    span,
+                    format!(
+                        "{} `{}` conflicts with `{}` once converted to another case",
+                        what, name, prev
+                    ),
+                );
+            }
+        }
+    }
+
+    fn error(&mut self, span: Span, msg: impl Into<String>) {
+        self.push(Diagnostic::error(self.file, span, msg));
+    }
+
+    fn warning(&mut self, span: Span, msg: impl Into<String>) {
+        self.push(Diagnostic::warning(self.file, span, msg));
+    }
+
+    fn push(&mut self, diagnostic: Diagnostic) {
+        if !self
+            .diagnostics
+            .iter()
+            .any(|d| d.span == diagnostic.span && d.message == diagnostic.message)
+        {
+            self.diagnostics.push(diagnostic);
+        }
+    }
+}
+
+/// Splits `name` into lowercase words, as case conversions do.
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if c == '-' || c == '_' {
+            if !word.is_empty() {
+                words.push(mem::take(&mut word));
+            }
+            prev_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && prev_lower {
+            words.push(mem::take(&mut word));
+        }
+        prev_lower = c.is_lowercase() || c.is_ascii_digit();
+        word.extend(c.to_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
@@ -0,0 +1,506 @@
+//! Tests of the canonical ABI which execute the glue code of functions with
+//! the reference interpreter.
+
+use anyhow::Result;
+use witx2::abi::interp::{Interpreter, Val};
+use witx2::abi::program::Program;
+use witx2::abi::{Bindgen, Direction, Instruction, LiftLower, MultiValue, StringEncoding};
+use witx2::{Interface, SizeAlign, Type, TypeId};
+
+const TYPES: &str = "
+    record point { x: u32, y: f32 }
+    record labelled { name: string, at: point, z: u8 }
+    flags perms { read, write, exec }
+    enum color { red, green, blue }
+    variant shape { circle(f32), rect(point), empty }
+";
+
+struct Test {
+    iface: Interface,
+    sizes: SizeAlign,
+    dir: Direction,
+    string_encoding: StringEncoding,
+    multi_value: MultiValue,
+    optimize: bool,
+}
+
+impl Test {
+    fn new(funcs: &str) -> Test {
+        let iface = Interface::parse("test", &format!("{}{}", TYPES, funcs)).unwrap();
+        let mut sizes = SizeAlign::default();
+        sizes.fill(Direction::Import, &iface);
+        Test {
+            iface,
+            sizes,
+            dir: Direction::Import,
+            string_encoding: StringEncoding::Utf8,
+            multi_value: MultiValue::Disabled,
+            optimize: false,
+        }
+    }
+
+    fn program(&self, name: &str, lift_lower: LiftLower) -> Program {
+        let func = self
+            .iface
+            .functions
+            .iter()
+            .find(|f| f.name == name)
+            .unwrap();
+        let mut program = Program::new(
+            &self.iface,
+            self.dir,
+            lift_lower,
+            self.string_encoding,
+            self.multi_value,
+            func,
+            &self.sizes,
+        );
+        if self.optimize {
+            program.optimize(&self.iface, &self.sizes);
+        }
+        program
+    }
+
+    /// Calls `name` with `args` through the glue code of both the caller and
+    /// the callee, with `imp` implementing the function.
+    fn call(
+        &self,
+        name: &str,
+        args: &[Val],
+        imp: &mut dyn FnMut(Vec<Val>) -> Vec<Val>,
+    ) -> Result<Vec<Val>> {
+        let caller = self.program(name, LiftLower::LowerArgsLiftResults);
+        let callee = self.program(name, LiftLower::LiftArgsLowerResults);
+        let mut interp = Interpreter::new(&self.iface, &self.sizes);
+        let results = interp.run(&caller, args, &mut |interp, wasm_args| {
+            interp.run(&callee, &wasm_args, &mut |_, args| Ok(imp(args)))
+        })?;
+        assert_eq!(interp.allocations(), 0, "memory was leaked");
+        Ok(results)
+    }
+
+    /// Asserts that the arguments of `name` arrive unchanged, and that the
+    /// results returned by an identity function are unchanged too.
+    fn roundtrip(&self, name: &str, args: &[Val]) {
+        let expected = args.to_vec();
+        let results = self
+            .call(name, args, &mut |args| {
+                assert_eq!(args, expected);
+                args
+            })
+            .unwrap();
+        assert_eq!(results, args);
+    }
+
+    /// Runs `f` with every combination of options.
+    fn each(mut self, f: impl Fn(&Test)) {
+        for dir in [Direction::Import, Direction::Export].iter() {
+            self.dir = *dir;
+            self.sizes.fill(*dir, &self.iface);
+            for optimize in [false, true].iter() {
+                self.optimize = *optimize;
+                for multi_value in
+                    [MultiValue::Disabled, MultiValue::Enabled { max_results: 3 }].iter()
+                {
+                    self.multi_value = *multi_value;
+                    f(&self);
+                }
+            }
+        }
+    }
+}
+
+fn point(x: u32, y: f32) -> Val {
+    Val::Record(vec![Val::U32(x), Val::F32(y)])
+}
+
+fn labelled(name: &str, at: Val, z: u8) -> Val {
+    Val::Record(vec![Val::String(name.to_string()), at, Val::U8(z)])
+}
+
+fn string(s: &str) -> Val {
+    Val::String(s.to_string())
+}
+
+#[test]
+fn scalars() {
+    Test::new(
+        "f: function(a: u8, b: s8, c: u16, d: s16, e: u32, f: s32, g: u64, h: s64, \
+         i: f32, j: f64, k: char, l: bool) -> (a: u8, b: s16, c: s64, d: f32, e: char, f: bool)",
+    )
+    .each(|t| {
+        let args = vec![
+            Val::U8(0xff),
+            Val::S8(-1),
+            Val::U16(0xffff),
+            Val::S16(i16::MIN),
+            Val::U32(u32::MAX),
+            Val::S32(-2),
+            Val::U64(u64::MAX),
+            Val::S64(i64::MIN),
+            Val::F32(1.5),
+            Val::F64(-0.25),
+            Val::Char('☃'),
+            Val::Bool(true),
+        ];
+        let results = vec![
+            Val::U8(7),
+            Val::S16(-7),
+            Val::S64(-1),
+            Val::F32(f32::MAX),
+            Val::Char('\u{10ffff}'),
+            Val::Bool(false),
+        ];
+        let expected = args.clone();
+        let actual = t
+            .call("f", &args, &mut |args| {
+                assert_eq!(args, expected);
+                results.clone()
+            })
+            .unwrap();
+        assert_eq!(actual, results);
+    });
+}
+
+#[test]
+fn strings() {
+    let mut t =
+        Test::new("f: function(a: string, b: list<string>) -> (a: string, b: list<string>)");
+    for encoding in [
+        StringEncoding::Utf8,
+        StringEncoding::Utf16,
+        StringEncoding::CompactUtf16,
+    ]
+    .iter()
+    {
+        t.string_encoding = *encoding;
+        for s in ["", "hello", "héllo", "☃ 𝄞"].iter() {
+            t.roundtrip(
+                "f",
+                &[
+                    string(s),
+                    Val::List(vec![string(s), string("x"), string(s)]),
+                ],
+            );
+        }
+    }
+}
+
+#[test]
+fn lists() {
+    Test::new(
+        "f: function(a: list<u8>, b: list<list<s64>>, c: list<point>, d: list<labelled>) \
+         -> (a: list<u8>, b: list<list<s64>>, c: list<point>, d: list<labelled>)",
+    )
+    .each(|t| {
+        t.roundtrip(
+            "f",
+            &[
+                Val::List(vec![Val::U8(1), Val::U8(2), Val::U8(3)]),
+                Val::List(vec![
+                    Val::List(vec![]),
+                    Val::List(vec![Val::S64(-1), Val::S64(i64::MAX)]),
+                ]),
+                Val::List(vec![point(1, 2.0), point(3, -4.0)]),
+                Val::List(vec![
+                    labelled("a", point(5, 6.5), 7),
+                    labelled("", point(0, 0.0), 0),
+                ]),
+            ],
+        );
+        t.roundtrip(
+            "f",
+            &[
+                Val::List(vec![]),
+                Val::List(vec![]),
+                Val::List(vec![]),
+                Val::List(vec![]),
+            ],
+        );
+    });
+}
+
+#[test]
+fn arrays() {
+    Test::new(
+        "f: function(a: array<u8, 4>, b: array<point, 2>, c: list<array<string, 2>>) \
+         -> (a: array<u8, 4>, b: array<point, 2>, c: list<array<string, 2>>)",
+    )
+    .each(|t| {
+        t.roundtrip(
+            "f",
+            &[
+                Val::Array(vec![Val::U8(1), Val::U8(2), Val::U8(3), Val::U8(4)]),
+                Val::Array(vec![point(1, 2.0), point(3, -4.0)]),
+                Val::List(vec![
+                    Val::Array(vec![string("a"), string("")]),
+                    Val::Array(vec![string("bc"), string("d")]),
+                ]),
+            ],
+        );
+    });
+
+    // Arrays which flatten to too many values are passed through memory.
+    Test::new(
+        "f: function(a: array<u32, 16>, b: array<point, 9>, c: array<array<string, 2>, 5>) \
+         -> (a: array<u32, 16>, b: array<point, 9>, c: array<array<string, 2>, 5>)",
+    )
+    .each(|t| {
+        t.roundtrip(
+            "f",
+            &[
+                Val::Array((0..16).map(Val::U32).collect()),
+                Val::Array((0..9).map(|i| point(i, -(i as f32))).collect()),
+                Val::Array(
+                    (0..5)
+                        .map(|i| Val::Array(vec![string(&i.to_string()), string("x")]))
+                        .collect(),
+                ),
+            ],
+        );
+    });
+
+    let t = Test::new("f: function(a: array<u32, 16>, b: array<u32, 17>)");
+    let sig = t
+        .iface
+        .wasm_signature(t.dir, t.multi_value, &t.iface.functions[0]);
+    assert_eq!(sig.params.len(), 16 + 1);
+
+    let t = Test::new("f: function(a: array<u32, 2>)");
+    let err = t
+        .call("f", &[Val::Array(vec![Val::U32(1)])], &mut |args| args)
+        .unwrap_err();
+    assert!(err.to_string().contains("array of 2 elements"), "{}", err);
+}
+
+#[test]
+fn variants() {
+    Test::new(
+        "f: function(a: option<point>, b: expected<string, u32>, c: color, d: perms, \
+         e: list<shape>) -> (a: option<point>, b: expected<string, u32>, e: list<shape>)",
+    )
+    .each(|t| {
+        let shapes = Val::List(vec![
+            Val::Variant(0, Some(Box::new(Val::F32(1.0)))),
+            Val::Variant(1, Some(Box::new(point(1, 2.0)))),
+            Val::Variant(2, None),
+        ]);
+        let results = t
+            .call(
+                "f",
+                &[
+                    Val::Variant(1, Some(Box::new(point(1, 2.0)))),
+                    Val::Variant(0, Some(Box::new(string("ok")))),
+                    Val::Variant(2, None),
+                    Val::Flags(vec![true, false, true]),
+                    shapes.clone(),
+                ],
+                &mut |args| {
+                    assert_eq!(args[2], Val::Variant(2, None));
+                    assert_eq!(args[3], Val::Flags(vec![true, false, true]));
+                    vec![
+                        Val::Variant(0, None),
+                        Val::Variant(1, Some(Box::new(Val::U32(3)))),
+                        args[4].clone(),
+                    ]
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            results,
+            [
+                Val::Variant(0, None),
+                Val::Variant(1, Some(Box::new(Val::U32(3)))),
+                shapes,
+            ]
+        );
+    });
+}
+
+#[test]
+fn discriminants() {
+    let t = Test::new(
+        "enum errno { success = 0, again = 6, busy }
+         flags mode { x = 0x4, y = 0x1 }
+         variant event { none = 10, key(u8) = 2 }
+         f: function(a: errno, b: mode, c: event) -> (a: errno, b: mode, c: event)
+         g: function(a: errno, b: mode, c: event)",
+    );
+    let args = [
+        Val::Variant(2, None),
+        Val::Flags(vec![true, true]),
+        Val::Variant(1, Some(Box::new(Val::U8(7)))),
+    ];
+
+    // The core wasm values are the explicit discriminants and bits.
+    let mut interp = Interpreter::new(&t.iface, &t.sizes);
+    let caller = t.program("g", LiftLower::LowerArgsLiftResults);
+    interp
+        .run(&caller, &args, &mut |_, wasm_args| {
+            assert_eq!(
+                wasm_args,
+                [Val::I32(7), Val::I32(5), Val::I32(2), Val::I32(7)]
+            );
+            Ok(vec![])
+        })
+        .unwrap();
+
+    // Values which aren't the discriminant of any case or the bit of any flag
+    // can't be lifted, even if they're valid case or field indices.
+    let callee = t.program("g", LiftLower::LiftArgsLowerResults);
+    let mut run = |wasm_args: &[i32]| {
+        let wasm_args = wasm_args.iter().map(|i| Val::I32(*i)).collect::<Vec<_>>();
+        interp
+            .run(&callee, &wasm_args, &mut |_, _| Ok(vec![]))
+            .unwrap_err()
+            .to_string()
+    };
+    let err = run(&[1, 5, 2, 7]);
+    assert!(err.contains("invalid variant discriminant 1"), "{}", err);
+    let err = run(&[7, 2, 2, 7]);
+    assert!(err.contains("bit 1 is set"), "{}", err);
+    let err = run(&[7, 5, 0, 7]);
+    assert!(err.contains("invalid variant discriminant 0"), "{}", err);
+
+    t.each(|t| t.roundtrip("f", &args));
+}
+
+#[test]
+fn invalid_values() {
+    let t = Test::new("f: function() -> bool\ng: function() -> list<char>");
+    let mut interp = Interpreter::new(&t.iface, &t.sizes);
+    let caller = t.program("f", LiftLower::LowerArgsLiftResults);
+    let err = interp
+        .run(&caller, &[], &mut |_, _| Ok(vec![Val::I32(2)]))
+        .unwrap_err();
+    assert!(err.to_string().contains("BoolFromI32"), "{}", err);
+
+    // The callee stores a list of one invalid char in the return area.
+    let caller = t.program("g", LiftLower::LowerArgsLiftResults);
+    let err = interp
+        .run(&caller, &[], &mut |interp, args| {
+            let ptr = interp.realloc(0, 0, 4, 4)?;
+            interp.memory[ptr as usize..][..4].copy_from_slice(&0xd800u32.to_le_bytes());
+            let retptr = match args[..] {
+                [Val::I32(retptr)] => retptr as usize,
+                _ => unreachable!(),
+            };
+            interp.memory[retptr..][..4].copy_from_slice(&ptr.to_le_bytes());
+            interp.memory[retptr + 8..][..4].copy_from_slice(&1u32.to_le_bytes());
+            Ok(vec![])
+        })
+        .unwrap_err();
+    assert!(err.to_string().contains("not a valid char"), "{}", err);
+}
+
+#[test]
+fn out_of_bounds() {
+    let t = Test::new("f: function(a: list<u32>)");
+    let callee = t.program("f", LiftLower::LiftArgsLowerResults);
+    let mut interp = Interpreter::new(&t.iface, &t.sizes);
+    let err = interp
+        .run(&callee, &[Val::I32(0), Val::I32(1 << 20)], &mut |_, _| {
+            Ok(vec![])
+        })
+        .unwrap_err();
+    assert!(err.to_string().contains("out of bounds"), "{}", err);
+}
+
+/// A backend which records the instructions it's given.
+struct Recorder<'a> {
+    sizes: &'a SizeAlign,
+    canonical: bool,
+    insts: Vec<String>,
+}
+
+impl Bindgen for Recorder<'_> {
+    type Operand = ();
+
+    fn emit(
+        &mut self,
+        _iface: &Interface,
+        inst: &Instruction<'_>,
+        _operands: &mut Vec<()>,
+        results: &mut Vec<()>,
+    ) {
+        self.insts.push(format!("{:?}", inst));
+        results.extend((0..inst.results_len()).map(|_| ()));
+    }
+
+    fn allocate_typed_space(&mut self, _iface: &Interface, _ty: TypeId) {}
+
+    fn i64_return_pointer_area(&mut self, _amt: usize) {}
+
+    fn push_block(&mut self) {}
+
+    fn finish_block(&mut self, _operands: &mut Vec<()>) {}
+
+    fn sizes(&self) -> &SizeAlign {
+        self.sizes
+    }
+
+    fn is_list_canonical(&self, _iface: &Interface, _element: &Type) -> bool {
+        false
+    }
+
+    fn is_record_canonical(&self, _iface: &Interface, _ty: TypeId) -> bool {
+        self.canonical
+    }
+}
+
+fn emit(t: &Test, lift_lower: LiftLower, canonical: bool) -> Vec<String> {
+    let func = &t.iface.functions[0];
+    let mut recorder = Recorder {
+        sizes: &t.sizes,
+        canonical,
+        insts: Vec::new(),
+    };
+    t.program(&func.name, lift_lower)
+        .emit(&t.iface, func, &mut recorder);
+    recorder.insts
+}
+
+fn count(insts: &[String], prefix: &str) -> usize {
+    insts.iter().filter(|i| i.starts_with(prefix)).count()
+}
+
+#[test]
+fn optimize() {
+    let mut t = Test::new(
+        "variant pair { a(tuple<f32, u32>), b(tuple<u32, u32>) }
+         f: function(a: list<labelled>, b: pair) -> list<labelled>",
+    );
+    for lift_lower in [
+        LiftLower::LowerArgsLiftResults,
+        LiftLower::LiftArgsLowerResults,
+    ]
+    .iter()
+    {
+        t.optimize = false;
+        let unoptimized = emit(&t, *lift_lower, true);
+        assert_eq!(count(&unoptimized, "RecordCanon"), 0);
+
+        t.optimize = true;
+        let optimized = emit(&t, *lift_lower, true);
+        assert_eq!(count(&optimized, "RecordCanonLift"), 1);
+        assert_eq!(count(&optimized, "RecordCanonLower"), 1);
+        assert!(optimized.len() < unoptimized.len());
+        let no_op = |insts: &[String]| {
+            insts
+                .iter()
+                .filter(|i| i.starts_with("Bitcasts") && i.contains("None"))
+                .count()
+        };
+        assert!(no_op(&unoptimized) > 0);
+        assert_eq!(no_op(&optimized), 0);
+
+        // Backends which can't copy records as-is see the original
+        // instructions instead.
+        let fallback = emit(&t, *lift_lower, false);
+        assert_eq!(count(&fallback, "RecordCanon"), 0);
+        assert_eq!(
+            count(&fallback, "RecordLift"),
+            count(&unoptimized, "RecordLift")
+        );
+    }
+}
@@ -10,6 +10,7 @@
 use anyhow::{bail, Context, Result};
 use rayon::prelude::*;
 use serde::Serialize;
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
 use std::io;
@@ -97,8 +98,21 @@ impl Runner<'_> {
         let contents = str::from_utf8(contents)?;
 
         let result = witx2::Interface::parse_file(test);
+        let (_, diagnostics) = witx2::Interface::parse_file_with_diagnostics(test)?;
 
-        let result = if contents.contains("// parse-fail") {
+        let result = if contents.contains("// diagnostics") {
+            if result.is_ok() {
+                bail!("expected test to not parse but it did");
+            }
+            let rendered = diagnostics
+                .iter()
+                .map(|d| Ok(d.render(&fs::read_to_string(&d.file)?)))
+                .collect::<Result<Vec<_>>>()?;
+            normalize(test, &rendered.join("\n\n"))
+        } else if contents.contains("// parse-fail") {
+            if diagnostics.is_empty() {
+                bail!("expected diagnostics when recovering but found none");
+            }
             match result {
                 Ok(_) => bail!("expected test to not parse but it did"),
                 Err(mut e) => {
@@ -111,8 +125,24 @@ impl Runner<'_> {
                     normalize(test, &format!("{:?}", e))
                 }
             }
+        } else if let Some(directive) = contents.lines().find_map(|l| l.strip_prefix("// validate"))
+        {
+            let capabilities = capabilities(directive)?;
+            let diagnostics = result?.validate(test, &capabilities);
+            if diagnostics.is_empty() {
+                bail!("expected test to not validate but it did");
+            }
+            let rendered = diagnostics
+                .iter()
+                .map(|d| d.render(contents))
+                .collect::<Vec<_>>();
+            normalize(test, &rendered.join("\n\n"))
         } else {
+            if let Some(d) = diagnostics.first() {
+                bail!("unexpected diagnostic when recovering: {}", d.message);
+            }
             let instance = result?;
+            check_printer(test, contents, &instance)?;
             to_json(&instance)
         };
 
@@ -142,6 +172,7 @@ impl Runner<'_> {
                 &test.display().to_string().replace("\\", "/"),
             )
             .replace("\\parse-fail\\", "/parse-fail/")
+            .replace("\\diagnostics\\", "/diagnostics/")
             .replace("\r\n", "\n")
         }
     }
@@ -151,6 +182,236 @@ impl Runner<'_> {
     }
 }
 
+/// Checks that printing `instance`, both from its AST and after it's been
+/// resolved, and parsing the printed text again yields the same interface.
+/// Returns the capabilities to validate with for a `// validate` test, which
+/// are the defaults without any listed after `without`, as in
+/// `// validate without buffers`.
+fn capabilities(directive: &str) -> Result<witx2::Capabilities> {
+    let mut capabilities = witx2::Capabilities::default();
+    let mut words = directive.split_whitespace();
+    match words.next() {
+        None => return Ok(capabilities),
+        Some("without") => {}
+        Some(word) => bail!("expected `without`, found `{}`", word),
+    }
+    for word in words {
+        match word {
+            "preview1" => capabilities.preview1 = false,
+            "async_functions" => capabilities.async_functions = false,
+            "anonymous_records" => capabilities.anonymous_records = false,
+            "anonymous_variants" => capabilities.anonymous_variants = false,
+            "buffers" => capabilities.buffers = false,
+            "resources" => capabilities.resources = false,
+            _ => bail!("unknown capability `{}`", word),
+        }
+    }
+    Ok(capabilities)
+}
+
+fn check_printer(test: &Path, contents: &str, instance: &witx2::Interface) -> Result<()> {
+    let dir = test.parent().unwrap();
+    let reparse = |printed: &str| {
+        let mut resolver = witx2::FsResolver::new(vec![dir.to_path_buf()]);
+        witx2::Interface::parse_with_resolver(test, printed, &mut resolver)
+            .context(format!("failed to parse printed interface:\n{}", printed))
+    };
+
+    let ast = witx2::ast::Ast::parse(contents)?;
+    let printed = witx2::printer::print_ast(&ast);
+    let reparsed = reparse(&printed)?;
+    if to_json(&reparsed) != to_json(instance) || canonical(&reparsed) != canonical(instance) {
+        bail!("printed AST doesn't round-trip:\n{}", printed);
+    }
+    if witx2::printer::print_ast(&witx2::ast::Ast::parse(&printed)?) != printed {
+        bail!("printing the AST isn't idempotent:\n{}", printed);
+    }
+
+    let printed = witx2::printer::print_interface(instance);
+    let reparsed = reparse(&printed)?;
+    if canonical(&reparsed) != canonical(instance) {
+        bail!("printed interface doesn't round-trip:\n{}", printed);
+    }
+    Ok(())
+}
+
+/// Describes `i` in a way which doesn't depend on the order in which its
+/// types and resources were allocated, unlike `to_json`.
+fn canonical(i: &witx2::Interface) -> String {
+    let mut out = String::new();
+
+    let mut types = i.type_lookup.iter().collect::<Vec<_>>();
+    types.sort();
+    for (name, id) in types {
+        let ty = &i.types[*id];
+        out.push_str(&format!(
+            "type {} ({:?} from {:?}) = {}{}{}\n",
+            name,
+            ty.name,
+            ty.foreign_module,
+            translate_typedef(i, ty),
+            docs(&ty.docs),
+            attrs(&ty.attrs),
+        ));
+    }
+
+    let mut resources = i.resource_lookup.iter().collect::<Vec<_>>();
+    resources.sort();
+    for (name, id) in resources {
+        let r = &i.resources[*id];
+        out.push_str(&format!(
+            "resource {} ({} from {:?}){}{}\n",
+            name,
+            r.name,
+            r.foreign_module,
+            docs(&r.docs),
+            attrs(&r.attrs),
+        ));
+    }
+
+    for f in i.functions.iter() {
+        let kind = match &f.kind {
+            witx2::FunctionKind::Freestanding => "freestanding".to_string(),
+            witx2::FunctionKind::Static { resource, name } => {
+                format!("static {} of {}", name, i.resources[*resource].name)
+            }
+            witx2::FunctionKind::Method { resource, name } => {
+                format!("method {} of {}", name, i.resources[*resource].name)
+            }
+        };
+        let params = f
+            .params
+            .iter()
+            .map(|(name, ty)| format!("{}: {}", name, translate_type(i, ty)))
+            .collect::<Vec<_>>();
+        let results = f
+            .results
+            .iter()
+            .map(|(name, ty)| format!("{}: {}", name, translate_type(i, ty)))
+            .collect::<Vec<_>>();
+        out.push_str(&format!(
+            "function {} ({}, async: {}) ({}) -> ({}){}{}\n",
+            f.name,
+            kind,
+            f.is_async,
+            params.join(", "),
+            results.join(", "),
+            docs(&f.docs),
+            attrs(&f.attrs),
+        ));
+    }
+
+    for g in i.globals.iter() {
+        out.push_str(&format!(
+            "global {}: {}{}{}\n",
+            g.name,
+            translate_type(i, &g.ty),
+            docs(&g.docs),
+            attrs(&g.attrs),
+        ));
+    }
+
+    for c in i.constants.iter() {
+        out.push_str(&format!(
+            "const {}: {} = {:?}{}{}\n",
+            c.name,
+            translate_type(i, &c.ty),
+            c.value,
+            docs(&c.docs),
+            attrs(&c.attrs),
+        ));
+    }
+
+    return out;
+
+    fn docs(docs: &witx2::Docs) -> String {
+        match &docs.contents {
+            Some(contents) => {
+                let lines = contents.lines().map(|l| l.trim()).collect::<Vec<_>>();
+                format!(" {:?}", lines)
+            }
+            None => String::new(),
+        }
+    }
+
+    fn attrs(attrs: &witx2::Attrs) -> String {
+        if attrs.is_empty() {
+            String::new()
+        } else {
+            format!(" {:?}", attrs.attrs)
+        }
+    }
+
+    fn translate_typedef(i: &witx2::Interface, ty: &witx2::TypeDef) -> String {
+        match &ty.kind {
+            witx2::TypeDefKind::Type(t) => translate_type(i, t),
+            witx2::TypeDefKind::Record(r) => {
+                let fields = r
+                    .fields
+                    .iter()
+                    .enumerate()
+                    .map(|(n, f)| {
+                        let ty = translate_type(i, &f.ty);
+                        let bit = if f.bit as usize == n {
+                            String::new()
+                        } else {
+                            format!(" = bit {}", f.bit)
+                        };
+                        let (docs, attrs) = (docs(&f.docs), attrs(&f.attrs));
+                        format!("{}: {}{}{}{}", f.name, ty, bit, docs, attrs)
+                    })
+                    .collect::<Vec<_>>();
+                format!("record {:?} {{ {} }}", r.kind, fields.join(", "))
+            }
+            witx2::TypeDefKind::Variant(v) => {
+                let cases = v
+                    .cases
+                    .iter()
+                    .enumerate()
+                    .map(|(n, c)| {
+                        let ty = c.ty.as_ref().map(|t| translate_type(i, t));
+                        let discriminant = if c.discriminant as usize == n {
+                            String::new()
+                        } else {
+                            format!(" = {}", c.discriminant)
+                        };
+                        let (docs, attrs) = (docs(&c.docs), attrs(&c.attrs));
+                        format!("{}: {:?}{}{}{}", c.name, ty, discriminant, docs, attrs)
+                    })
+                    .collect::<Vec<_>>();
+                format!("variant {:?} {{ {} }}", v.tag, cases.join(", "))
+            }
+            witx2::TypeDefKind::List(t) => format!("list<{}>", translate_type(i, t)),
+            witx2::TypeDefKind::Array(t, len) => {
+                format!("array<{}, {}>", translate_type(i, t), len)
+            }
+            witx2::TypeDefKind::PushBuffer(t) => format!("push-buffer<{}>", translate_type(i, t)),
+            witx2::TypeDefKind::PullBuffer(t) => format!("pull-buffer<{}>", translate_type(i, t)),
+            witx2::TypeDefKind::Pointer(t) => format!("pointer<{}>", translate_type(i, t)),
+            witx2::TypeDefKind::ConstPointer(t) => {
+                format!("const-pointer<{}>", translate_type(i, t))
+            }
+        }
+    }
+
+    fn translate_type(i: &witx2::Interface, ty: &witx2::Type) -> String {
+        match ty {
+            witx2::Type::Id(id) => {
+                let ty = &i.types[*id];
+                match &ty.name {
+                    Some(name) => format!("{} from {:?}", name, ty.foreign_module),
+                    None => translate_typedef(i, ty),
+                }
+            }
+            witx2::Type::Handle(r) => {
+                let r = &i.resources[*r];
+                format!("handle {} from {:?}", r.name, r.foreign_module)
+            }
+            other => format!("{:?}", other),
+        }
+    }
+}
+
 fn to_json(i: &witx2::Interface) -> String {
     #[derive(Serialize)]
     struct Interface {
@@ -162,6 +423,8 @@ fn to_json(i: &witx2::Interface) -> String {
         functions: Vec<Function>,
         #[serde(skip_serializing_if = "Vec::is_empty")]
         globals: Vec<Global>,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        constants: Vec<Constant>,
     }
 
     #[derive(Serialize)]
@@ -169,6 +432,8 @@ fn to_json(i: &witx2::Interface) -> String {
         name: String,
         #[serde(skip_serializing_if = "Option::is_none")]
         foreign_module: Option<String>,
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        attrs: Attrs,
     }
 
     #[derive(Serialize)]
@@ -180,19 +445,30 @@ fn to_json(i: &witx2::Interface) -> String {
         ty: Type,
         #[serde(skip_serializing_if = "Option::is_none")]
         foreign_module: Option<String>,
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        attrs: Attrs,
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        member_attrs: BTreeMap<String, Attrs>,
     }
 
+    type Attrs = BTreeMap<String, Vec<String>>;
+
     #[derive(Serialize)]
     #[serde(rename_all = "kebab-case")]
     enum Type {
         Primitive(String),
         Record {
             fields: Vec<(String, String)>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            bits: Option<Vec<u32>>,
         },
         Variant {
             cases: Vec<(String, Option<String>)>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            discriminants: Option<Vec<u32>>,
         },
         List(String),
+        Array(String, u32),
         PushBuffer(String),
         PullBuffer(String),
         Pointer(String),
@@ -206,12 +482,25 @@ fn to_json(i: &witx2::Interface) -> String {
         is_async: Option<bool>,
         params: Vec<String>,
         results: Vec<String>,
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        attrs: Attrs,
+    }
+
+    #[derive(Serialize)]
+    struct Constant {
+        name: String,
+        ty: String,
+        value: String,
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        attrs: Attrs,
     }
 
     #[derive(Serialize)]
     struct Global {
         name: String,
         ty: String,
+        #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+        attrs: Attrs,
     }
 
     let resources = i
@@ -220,6 +509,7 @@ fn to_json(i: &witx2::Interface) -> String {
         .map(|(_, r)| Resource {
             name: r.name.clone(),
             foreign_module: r.foreign_module.clone(),
+            attrs: translate_attrs(&r.attrs),
         })
         .collect::<Vec<_>>();
 
@@ -231,6 +521,8 @@ fn to_json(i: &witx2::Interface) -> String {
             name: r.name.clone(),
             ty: translate_typedef(r),
             foreign_module: r.foreign_module.clone(),
+            attrs: translate_attrs(&r.attrs),
+            member_attrs: translate_member_attrs(r),
         })
         .collect::<Vec<_>>();
     let functions = i
@@ -241,6 +533,7 @@ fn to_json(i: &witx2::Interface) -> String {
             is_async: if f.is_async { Some(f.is_async) } else { None },
             params: f.params.iter().map(|(_, ty)| translate_type(ty)).collect(),
             results: f.results.iter().map(|(_, ty)| translate_type(ty)).collect(),
+            attrs: translate_attrs(&f.attrs),
         })
         .collect::<Vec<_>>();
     let globals = i
@@ -249,6 +542,24 @@ fn to_json(i: &witx2::Interface) -> String {
         .map(|g| Global {
             name: g.name.clone(),
             ty: translate_type(&g.ty),
+            attrs: translate_attrs(&g.attrs),
+        })
+        .collect::<Vec<_>>();
+
+    let constants = i
+        .constants
+        .iter()
+        .map(|c| Constant {
+            name: c.name.clone(),
+            ty: translate_type(&c.ty),
+            value: match &c.value {
+                witx2::ConstValue::Enum { ty, case } => format!("type-{}::{}", ty.index(), case),
+                witx2::ConstValue::Flags { ty, flags } => {
+                    format!("type-{}::{{{}}}", ty.index(), flags.join(", "))
+                }
+                other => format!("{:?}", other),
+            },
+            attrs: translate_attrs(&c.attrs),
         })
         .collect::<Vec<_>>();
 
@@ -257,9 +568,40 @@ fn to_json(i: &witx2::Interface) -> String {
         types,
         functions,
         globals,
+        constants,
     };
     return serde_json::to_string_pretty(&iface).unwrap();
 
+    fn translate_attrs(attrs: &witx2::Attrs) -> Attrs {
+        attrs
+            .attrs
+            .iter()
+            .map(|(name, args)| {
+                let args = args
+                    .iter()
+                    .map(|arg| match &arg.name {
+                        Some(name) => format!("{} = {}", name, arg.value),
+                        None => arg.value.clone(),
+                    })
+                    .collect();
+                (name.clone(), args)
+            })
+            .collect()
+    }
+
+    fn translate_member_attrs(ty: &witx2::TypeDef) -> BTreeMap<String, Attrs> {
+        let members = match &ty.kind {
+            witx2::TypeDefKind::Record(r) => r.fields.iter().map(|f| (&f.name, &f.attrs)).collect(),
+            witx2::TypeDefKind::Variant(v) => v.cases.iter().map(|c| (&c.name, &c.attrs)).collect(),
+            _ => Vec::new(),
+        };
+        members
+            .into_iter()
+            .filter(|(_, attrs)| !attrs.is_empty())
+            .map(|(name, attrs)| (name.clone(), translate_attrs(attrs)))
+            .collect()
+    }
+
     fn translate_typedef(ty: &witx2::TypeDef) -> Type {
         match &ty.kind {
             witx2::TypeDefKind::Type(t) => Type::Primitive(translate_type(t)),
@@ -269,6 +611,11 @@ fn to_json(i: &witx2::Interface) -> String {
                     .iter()
                     .map(|f| (f.name.clone(), translate_type(&f.ty)))
                     .collect(),
+                bits: if r.has_default_bits() {
+                    None
+                } else {
+                    Some(r.fields.iter().map(|f| f.bit).collect())
+                },
             },
             witx2::TypeDefKind::Variant(v) => Type::Variant {
                 cases: v
@@ -276,10 +623,16 @@ fn to_json(i: &witx2::Interface) -> String {
                     .iter()
                     .map(|f| (f.name.clone(), f.ty.as_ref().map(translate_type)))
                     .collect(),
+                discriminants: if v.has_default_discriminants() {
+                    None
+                } else {
+                    Some(v.cases.iter().map(|c| c.discriminant).collect())
+                },
             },
             witx2::TypeDefKind::PushBuffer(ty) => Type::PushBuffer(translate_type(ty)),
             witx2::TypeDefKind::PullBuffer(ty) => Type::PullBuffer(translate_type(ty)),
             witx2::TypeDefKind::List(ty) => Type::List(translate_type(ty)),
+            witx2::TypeDefKind::Array(ty, len) => Type::Array(translate_type(ty), *len),
             witx2::TypeDefKind::Pointer(ty) => Type::Pointer(translate_type(ty)),
             witx2::TypeDefKind::ConstPointer(ty) => Type::ConstPointer(translate_type(ty)),
         }
@@ -300,6 +653,8 @@ fn to_json(i: &witx2::Interface) -> String {
             witx2::Type::F32 => format!("f32"),
             witx2::Type::F64 => format!("f64"),
             witx2::Type::Char => format!("char"),
+            witx2::Type::Bool => format!("bool"),
+            witx2::Type::String => format!("string"),
             witx2::Type::Handle(resource) => format!("handle-{}", resource.index()),
             witx2::Type::Id(id) => format!("type-{}", id.index()),
         }
@@ -0,0 +1,77 @@
+use witx2::Docs;
+
+#[test]
+fn sections() {
+    let docs = Docs::new(
+        "Reads from a file.\n\
+         \n\
+         Returns early on EOF.\n\
+         @param fd the file\n\
+         @param max-len - the maximum length,\n\
+         \x20 which may be zero\n\
+         @returns the bytes read\n\
+         @errors `again` if nothing is available\n\
+         @deprecated\n\
+         @since 1.2\n\
+         @unknown is part of the `@since` section\n"
+            .to_string(),
+    );
+    assert_eq!(
+        docs.description.as_deref(),
+        Some("Reads from a file.\n\nReturns early on EOF.")
+    );
+    assert_eq!(
+        docs.params,
+        [
+            ("fd".to_string(), "the file".to_string()),
+            (
+                "max-len".to_string(),
+                "the maximum length,\nwhich may be zero".to_string()
+            ),
+        ]
+    );
+    assert_eq!(docs.param("fd"), Some("the file"));
+    assert_eq!(docs.param("len"), None);
+    assert_eq!(docs.returns.as_deref(), Some("the bytes read"));
+    assert_eq!(
+        docs.errors.as_deref(),
+        Some("`again` if nothing is available")
+    );
+    assert_eq!(docs.deprecated.as_deref(), Some(""));
+    assert_eq!(
+        docs.since.as_deref(),
+        Some("1.2\n@unknown is part of the `@since` section")
+    );
+}
+
+#[test]
+fn no_tags() {
+    let docs = Docs::new("  indented\n    more\n".to_string());
+    assert_eq!(docs.description.as_deref(), Some("indented\n    more"));
+    assert!(docs.params.is_empty());
+    assert!(docs.returns.is_none());
+    assert!(docs.deprecated.is_none());
+
+    let docs = Docs::new("@returns nothing\n".to_string());
+    assert!(docs.description.is_none());
+    assert_eq!(docs.returns.as_deref(), Some("nothing"));
+}
+
+#[test]
+fn resolved() {
+    let iface = witx2::Interface::parse(
+        "foo",
+        "
+            /// Does a thing.
+            /// @param x the input
+            /// @deprecated use `g`
+            f: function(x: u32)
+        ",
+    )
+    .unwrap();
+    let docs = &iface.functions[0].docs;
+    assert_eq!(docs.description.as_deref(), Some("Does a thing."));
+    assert_eq!(docs.param("x"), Some("the input"));
+    assert_eq!(docs.deprecated.as_deref(), Some("use `g`"));
+    assert!(docs.contents.as_deref().unwrap().contains("@param x"));
+}
@@ -1,5 +1,6 @@
-use anyhow::{Context, Result};
-use std::path::PathBuf;
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 use witx_bindgen_gen_core::{witx2, Files, Generator};
 
@@ -47,6 +48,19 @@ enum Command {
         #[structopt(flatten)]
         common: Common,
     },
+    Json {
+        #[structopt(flatten)]
+        opts: witx_bindgen_gen_json::Opts,
+        #[structopt(flatten)]
+        common: Common,
+    },
+    /// Renders user-supplied Handlebars templates for each interface
+    Template {
+        #[structopt(flatten)]
+        opts: witx_bindgen_gen_template::Opts,
+        #[structopt(flatten)]
+        common: Common,
+    },
     #[structopt(name = "spidermonkey")]
     SpiderMonkey {
         #[structopt(flatten)]
@@ -54,6 +68,57 @@ enum Command {
         #[structopt(flatten)]
         common: Common,
     },
+    /// Runs an external generator, which is given the JSON description of
+    /// each interface on its stdin and returns the files it generated on its
+    /// stdout
+    Plugin {
+        /// The program to run, either a path or the name of a program in
+        /// `PATH`
+        #[structopt(long)]
+        plugin: PathBuf,
+
+        /// An argument to pass to the plugin. Can be specified multiple
+        /// times.
+        #[structopt(long = "plugin-arg", allow_hyphen_values = true, number_of_values = 1)]
+        plugin_args: Vec<String>,
+
+        #[structopt(flatten)]
+        common: Common,
+    },
+    /// Formats `*.witx` files in place
+    Fmt {
+        /// Don't write any files, instead fail if any aren't formatted
+        #[structopt(long)]
+        check: bool,
+
+        /// The `*.witx` files to format
+        files: Vec<PathBuf>,
+    },
+    /// Lists the differences between two versions of a `*.witx` interface,
+    /// failing if any of them are breaking changes
+    Diff {
+        /// The previous version of the interface
+        old: PathBuf,
+
+        /// The new version of the interface
+        new: PathBuf,
+
+        /// Additional directories to search for modules named in `use`
+        /// items. Can be specified multiple times.
+        #[structopt(long = "witx-path")]
+        witx_path: Vec<PathBuf>,
+    },
+    /// Generates every target described in a `witx-bindgen.toml` manifest
+    Build {
+        /// The manifest to read
+        #[structopt(long, default_value = "witx-bindgen.toml")]
+        manifest: PathBuf,
+
+        /// Don't write any files, instead fail if any target's files are out
+        /// of date
+        #[structopt(long)]
+        check: bool,
+    },
 }
 
 #[derive(Debug, StructOpt)]
@@ -71,43 +136,141 @@ struct Common {
     /// specified multiple times.
     #[structopt(long = "export", short)]
     exports: Vec<PathBuf>,
+
+    /// Additional directories to search for modules named in `use` items,
+    /// after the directory of the file being parsed. Can be specified
+    /// multiple times.
+    #[structopt(long = "witx-path")]
+    witx_path: Vec<PathBuf>,
+
+    /// Don't write any files, instead print a diff and fail if any generated
+    /// file differs from the one already in the output directory
+    #[structopt(long)]
+    check: bool,
+
+    /// Map generated code back to the `*.witx` items it was generated from,
+    /// with source maps written next to generated files or, for C, with
+    /// `#line` directives
+    #[structopt(long = "source-map")]
+    source_map: bool,
 }
 
 fn main() -> Result<()> {
     let opt = Opt::from_args();
-    let (mut generator, common): (Box<dyn Generator>, _) = match opt.command {
+    let mut cache = witx2::ParseCache::new();
+    match opt.command {
+        Command::Fmt { check, files } => fmt(&files, check),
+        Command::Diff {
+            old,
+            new,
+            witx_path,
+        } => diff(&old, &new, &witx_path),
+        Command::Build { manifest, check } => build(&manifest, check),
+        command => {
+            let stale = generate(command, &mut cache)?;
+            if stale > 0 {
+                bail!("{} file(s) are out of date", stale);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Runs the generator selected by `command` over the interfaces it names,
+/// parsing them through `cache`.
+///
+/// Returns the number of files which are out of date when checking them
+/// rather than writing them.
+fn generate(command: Command, cache: &mut witx2::ParseCache) -> Result<usize> {
+    let (mut generator, common): (Box<dyn Generator>, _) = match command {
         Command::RustWasm { opts, common } => (Box::new(opts.build()), common),
         Command::Wasmtime { opts, common } => (Box::new(opts.build()), common),
         Command::WasmtimePy { opts, common } => (Box::new(opts.build()), common),
         Command::Js { opts, common } => (Box::new(opts.build()), common),
         Command::C { opts, common } => (Box::new(opts.build()), common),
         Command::Markdown { opts, common } => (Box::new(opts.build()), common),
+        Command::Json { opts, common } => (Box::new(opts.build()), common),
+        Command::Template { opts, common } => (Box::new(opts.build()?), common),
         Command::SpiderMonkey { opts, common } => {
             let js_source = std::fs::read_to_string(&opts.js)
                 .with_context(|| format!("failed to read {}", opts.js.display()))?;
             (Box::new(opts.build(js_source)), common)
         }
+        // Plugins are run directly, rather than through `Generator`, so that
+        // their errors are reported instead of panicking.
+        Command::Plugin {
+            plugin,
+            plugin_args,
+            common,
+        } => {
+            let mut plugin = witx_bindgen_gen_core::plugin::Plugin::new(plugin);
+            for arg in plugin_args {
+                plugin.arg(arg);
+            }
+            let (imports, exports) = interfaces(&common, cache, &plugin.capabilities())?;
+            let files = plugin.run(&imports, &exports)?;
+            return write(&common, &files);
+        }
+        Command::Fmt { .. } | Command::Diff { .. } | Command::Build { .. } => {
+            bail!("subcommand is not a generator")
+        }
     };
 
+    let (imports, exports) = interfaces(&common, cache, &generator.capabilities())?;
+    let mut files = Files::default();
+    if common.source_map {
+        files.enable_source_maps();
+    }
+    generator.generate_all(&imports, &exports, &mut files);
+    write(&common, &files)
+}
+
+/// Parses and validates the interfaces named in `common`, returning the
+/// imports and the exports.
+fn interfaces(
+    common: &Common,
+    cache: &mut witx2::ParseCache,
+    capabilities: &witx2::Capabilities,
+) -> Result<(Vec<witx2::Interface>, Vec<witx2::Interface>)> {
     let imports = common
         .imports
         .iter()
-        .map(|witx| witx2::Interface::parse_file(witx))
+        .map(|witx| parse(cache, witx, &common.witx_path))
         .collect::<Result<Vec<_>>>()?;
     let exports = common
         .exports
         .iter()
-        .map(|witx| witx2::Interface::parse_file(witx))
+        .map(|witx| parse(cache, witx, &common.witx_path))
         .collect::<Result<Vec<_>>>()?;
+    validate(
+        common.imports.iter().zip(&imports),
+        common.exports.iter().zip(&exports),
+        capabilities,
+    )?;
+    Ok((imports, exports))
+}
 
-    let mut files = Files::default();
-    generator.generate_all(&imports, &exports, &mut files);
-
+/// Writes `files` to the output directory in `common`, or checks them against
+/// it, returning the number of files which are out of date.
+fn write(common: &Common, files: &Files) -> Result<usize> {
+    let mut stale = 0;
     for (name, contents) in files.iter() {
         let dst = match &common.out_dir {
             Some(path) => path.join(name),
             None => name.into(),
         };
+        // Leave files which haven't changed alone so that their timestamps
+        // don't trigger rebuilds of anything which depends on them.
+        let prev = std::fs::read(&dst).ok();
+        if prev.as_deref() == Some(contents) {
+            continue;
+        }
+        if common.check {
+            println!("{} is out of date", dst.display());
+            print_diff(&dst, prev.as_deref(), contents);
+            stale += 1;
+            continue;
+        }
         println!("Generating {:?}", dst);
         if let Some(parent) = dst.parent() {
             std::fs::create_dir_all(parent)
@@ -116,5 +279,288 @@ fn main() -> Result<()> {
         std::fs::write(&dst, contents).with_context(|| format!("failed to write {:?}", dst))?;
     }
 
+    Ok(stale)
+}
+
+/// Prints a unified diff from the `old` contents of `path`, if it exists, to
+/// the `new` contents that were generated for it.
+fn print_diff(path: &Path, old: Option<&[u8]>, new: &[u8]) {
+    let old = String::from_utf8_lossy(old.unwrap_or_default());
+    let new = String::from_utf8_lossy(new);
+    let path = path.display().to_string();
+    let diff = similar::TextDiff::from_lines(&*old, &*new);
+    print!("{}", diff.unified_diff().header(&path, &path));
+}
+
+/// The contents of a `witx-bindgen.toml` manifest.
+///
+/// ```toml
+/// # Directories searched for modules named in `use` items by every target.
+/// witx-path = ["witx/deps"]
+///
+/// [[target]]
+/// generator = "rust-wasm"
+/// out-dir = "src/bindings"
+/// import = ["witx/host.witx"]
+/// export = ["witx/guest.witx"]
+/// options = { rustfmt = true, string-encoding = "utf8" }
+/// ```
+///
+/// Each target is run as though `generator` was given as a subcommand, with
+/// every entry in `options` passed as the long flag of the same name: `true`
+/// passes just the flag, `false` omits it, arrays pass it once per element
+/// and anything else passes it with the value given. Relative paths are
+/// relative to the directory containing the manifest.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct Manifest {
+    #[serde(default)]
+    witx_path: Vec<PathBuf>,
+    #[serde(default, rename = "target")]
+    targets: Vec<Target>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+struct Target {
+    generator: String,
+    out_dir: Option<PathBuf>,
+    #[serde(default)]
+    import: Vec<PathBuf>,
+    #[serde(default)]
+    export: Vec<PathBuf>,
+    #[serde(default)]
+    options: toml::value::Table,
+}
+
+fn build(manifest: &Path, check: bool) -> Result<()> {
+    let contents = std::fs::read_to_string(manifest)
+        .with_context(|| format!("failed to read {:?}", manifest))?;
+    let manifest_dir = manifest
+        .parent()
+        .with_context(|| format!("{:?} isn't a file", manifest))?;
+    let manifest: Manifest =
+        toml::from_str(&contents).with_context(|| format!("failed to parse {:?}", manifest))?;
+    if !manifest_dir.as_os_str().is_empty() {
+        std::env::set_current_dir(manifest_dir)
+            .with_context(|| format!("failed to change directory to {:?}", manifest_dir))?;
+    }
+
+    // Interfaces used by more than one target are only parsed once.
+    let mut cache = witx2::ParseCache::new();
+    let mut stale = 0;
+    for (i, target) in manifest.targets.iter().enumerate() {
+        stale += target_args(target, &manifest.witx_path)
+            .and_then(|mut args| {
+                if check {
+                    args.push("--check".to_string());
+                }
+                Ok(Opt::from_iter_safe(args)?.command)
+            })
+            .and_then(|command| generate(command, &mut cache))
+            .with_context(|| format!("failed to build target {} (`{}`)", i, target.generator))?;
+    }
+    if stale > 0 {
+        bail!("{} file(s) are out of date", stale);
+    }
+    Ok(())
+}
+
+/// Returns the command line which runs `target`.
+fn target_args(target: &Target, witx_path: &[PathBuf]) -> Result<Vec<String>> {
+    let mut args = vec!["witx-bindgen".to_string(), target.generator.clone()];
+    let mut flag = |name: &str, value: Option<String>| {
+        args.push(format!("--{}", name));
+        args.extend(value);
+    };
+    let path = |p: &PathBuf| Some(p.display().to_string());
+    if let Some(dir) = &target.out_dir {
+        flag("out-dir", path(dir));
+    }
+    for file in target.import.iter() {
+        flag("import", path(file));
+    }
+    for file in target.export.iter() {
+        flag("export", path(file));
+    }
+    for dir in witx_path {
+        flag("witx-path", path(dir));
+    }
+    for (name, value) in target.options.iter() {
+        let values = match value {
+            toml::Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            match value {
+                toml::Value::Boolean(true) => flag(name, None),
+                toml::Value::Boolean(false) => {}
+                toml::Value::String(s) => flag(name, Some(s.clone())),
+                toml::Value::Integer(i) => flag(name, Some(i.to_string())),
+                value => bail!("unsupported value for option `{}`: {}", name, value),
+            }
+        }
+    }
+    Ok(args)
+}
+
+fn fmt(files: &[PathBuf], check: bool) -> Result<()> {
+    let mut unformatted = 0;
+    for file in files {
+        let contents =
+            std::fs::read_to_string(file).with_context(|| format!("failed to read {:?}", file))?;
+        let formatted = witx2::printer::format(file, &contents)?;
+        if formatted == contents {
+            continue;
+        }
+        if check {
+            println!("{} is not formatted", file.display());
+            unformatted += 1;
+        } else {
+            println!("Formatting {:?}", file);
+            std::fs::write(file, formatted)
+                .with_context(|| format!("failed to write {:?}", file))?;
+        }
+    }
+    if unformatted > 0 {
+        bail!("{} file(s) are not formatted", unformatted);
+    }
+    Ok(())
+}
+
+fn parse(
+    cache: &mut witx2::ParseCache,
+    file: &Path,
+    witx_path: &[PathBuf],
+) -> Result<witx2::Interface> {
+    let mut search_path = vec![file.parent().unwrap().to_path_buf()];
+    search_path.extend(witx_path.iter().cloned());
+    let mut resolver = witx2::FsResolver::new(search_path);
+    cache.parse_file(file, &mut resolver)
+}
+
+fn validate<'a>(
+    imports: impl Iterator<Item = (&'a PathBuf, &'a witx2::Interface)>,
+    exports: impl Iterator<Item = (&'a PathBuf, &'a witx2::Interface)>,
+    capabilities: &witx2::Capabilities,
+) -> Result<()> {
+    let mut errors = 0;
+    for (file, iface) in imports.chain(exports) {
+        let diagnostics = iface.validate(file, capabilities);
+        if diagnostics.is_empty() {
+            continue;
+        }
+        let contents =
+            std::fs::read_to_string(file).with_context(|| format!("failed to read {:?}", file))?;
+        for diagnostic in diagnostics.iter() {
+            eprintln!("{}", diagnostic.render(&contents));
+        }
+        errors += diagnostics
+            .iter()
+            .filter(|d| d.severity == witx2::Severity::Error)
+            .count();
+    }
+    if errors > 0 {
+        bail!("{} error(s) found while validating interfaces", errors);
+    }
+    Ok(())
+}
+
+fn diff(old: &Path, new: &Path, witx_path: &[PathBuf]) -> Result<()> {
+    let mut cache = witx2::ParseCache::new();
+    let old = parse(&mut cache, old, witx_path)?;
+    let new = parse(&mut cache, new, witx_path)?;
+    let changes = witx2::compat::diff(&old, &new);
+    for change in changes.iter() {
+        println!("{}", change);
+    }
+    let breaking = changes.iter().filter(|c| c.is_breaking()).count();
+    if breaking > 0 {
+        bail!("{} breaking change(s) found", breaking);
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args_for(target: &str) -> Result<Vec<String>> {
+        let target: Target = toml::from_str(target)?;
+        target_args(&target, &[PathBuf::from("deps")])
+    }
+
+    #[test]
+    fn target_paths() -> Result<()> {
+        let args = args_for(
+            r#"
+                generator = "js"
+                out-dir = "out"
+                import = ["a.witx", "b.witx"]
+                export = ["c.witx"]
+            "#,
+        )?;
+        assert_eq!(
+            args,
+            [
+                "witx-bindgen",
+                "js",
+                "--out-dir",
+                "out",
+                "--import",
+                "a.witx",
+                "--import",
+                "b.witx",
+                "--export",
+                "c.witx",
+                "--witx-path",
+                "deps",
+            ]
+        );
+        Opt::from_iter_safe(args)?;
+        Ok(())
+    }
+
+    #[test]
+    fn target_options() -> Result<()> {
+        let args = args_for(
+            r#"
+                generator = "js"
+                options = { no-typescript = true, multi-value = 2, string-encoding = "utf16" }
+            "#,
+        )?;
+        assert_eq!(
+            args[4..],
+            [
+                "--multi-value",
+                "2",
+                "--no-typescript",
+                "--string-encoding",
+                "utf16",
+            ]
+        );
+        match Opt::from_iter_safe(args)?.command {
+            Command::Js { opts, .. } => assert!(opts.no_typescript),
+            command => panic!("unexpected command {:?}", command),
+        }
+
+        let args = args_for(
+            r#"
+                generator = "template"
+                options = { template = ["a.hbs", "b.hbs"], check = false }
+            "#,
+        )?;
+        assert_eq!(args[4..], ["--template", "a.hbs", "--template", "b.hbs"]);
+        Ok(())
+    }
+
+    #[test]
+    fn target_errors() {
+        let err = args_for("generator = 'js'\noptions = { multi-value = 2.5 }").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("unsupported value for option `multi-value`"));
+        assert!(args_for("generator = 'js'\nunknown = 1").is_err());
+        assert!(build(Path::new("/"), false).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+//! Runs the `witx-bindgen` binary over the fixtures in `tests/cli`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Returns an empty scratch directory for the test `name`.
+fn scratch(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    drop(fs::remove_dir_all(&dir));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn copy_dir(src: &Path, dst: &Path) {
+    fs::create_dir_all(dst).unwrap();
+    for entry in fs::read_dir(src).unwrap() {
+        let entry = entry.unwrap();
+        let dst = dst.join(entry.file_name());
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &dst);
+        } else {
+            fs::copy(entry.path(), dst).unwrap();
+        }
+    }
+}
+
+/// Runs `witx-bindgen` in `cwd`, returning its output and whether it
+/// succeeded.
+fn run(cwd: &Path, args: &[&str]) -> (bool, String, String) {
+    let Output {
+        status,
+        stdout,
+        stderr,
+    } = Command::new(env!("CARGO_BIN_EXE_witx-bindgen"))
+        .current_dir(cwd)
+        .args(args)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(stdout).unwrap();
+    let stderr = String::from_utf8(stderr).unwrap();
+    (status.success(), stdout, stderr)
+}
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/cli")
+        .join(name)
+}
+
+#[test]
+fn build_manifest() {
+    let dir = scratch("build_manifest");
+    copy_dir(&fixture("build"), &dir.join("build"));
+
+    // Paths in the manifest are relative to it, not to where it's run from.
+    let (ok, stdout, stderr) = run(&dir, &["build", "--manifest", "build/witx-bindgen.toml"]);
+    assert!(ok, "{}\n{}", stdout, stderr);
+    let out = dir.join("build/out");
+    assert!(out.join("js/shapes.js").exists());
+    assert!(!out.join("js/shapes.d.ts").exists());
+    assert!(out.join("markdown/bindings.md").exists());
+
+    let (ok, stdout, stderr) = run(
+        &dir,
+        &["build", "--manifest", "build/witx-bindgen.toml", "--check"],
+    );
+    assert!(ok, "{}\n{}", stdout, stderr);
+}
+
+#[test]
+fn build_rejects_non_generators() {
+    let dir = scratch("build_rejects_non_generators");
+    fs::write(
+        dir.join("witx-bindgen.toml"),
+        "[[target]]\ngenerator = \"fmt\"\n",
+    )
+    .unwrap();
+    let (ok, _, stderr) = run(&dir, &["build"]);
+    assert!(!ok);
+    assert!(
+        stderr.contains("failed to build target 0 (`fmt`)"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("subcommand is not a generator"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn check_reports_stale_files() {
+    let dir = scratch("check_reports_stale_files");
+    let witx = fixture("build/witx");
+    let shapes_witx = witx.join("shapes.witx");
+    let deps = witx.join("deps");
+    let args = [
+        "js",
+        "--import",
+        shapes_witx.to_str().unwrap(),
+        "--witx-path",
+        deps.to_str().unwrap(),
+        "--out-dir",
+        "out",
+    ];
+    let (ok, stdout, stderr) = run(&dir, &args);
+    assert!(ok, "{}\n{}", stdout, stderr);
+    let (ok, stdout, stderr) = run(&dir, &[&args[..], &["--check"]].concat());
+    assert!(ok, "{}\n{}", stdout, stderr);
+
+    let shapes = dir.join("out/shapes.js");
+    let edited = format!("// edited\n{}", fs::read_to_string(&shapes).unwrap());
+    fs::write(&shapes, &edited).unwrap();
+    let (ok, stdout, stderr) = run(&dir, &[&args[..], &["--check"]].concat());
+    assert!(!ok);
+    assert!(
+        stdout.contains("out/shapes.js is out of date"),
+        "{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("--- out/shapes.js\n+++ out/shapes.js\n"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("\n-// edited\n"), "{}", stdout);
+    assert!(
+        !stdout.contains("intrinsics.js is out of date"),
+        "{}",
+        stdout
+    );
+    assert!(stderr.contains("1 file(s) are out of date"), "{}", stderr);
+    assert_eq!(fs::read_to_string(&shapes).unwrap(), edited);
+}
@@ -0,0 +1,66 @@
+from exports.bindings import Exports
+from imports.bindings import add_imports_to_linker, Imports
+from typing import List, Optional
+import exports.bindings as e
+import imports.bindings as i
+import sys
+import wasmtime
+
+class MyImports:
+    def roundtrip_small(self, a: bytes) -> bytes:
+        assert(a == b'\x01\x02\x03\x04')
+        return a
+
+    def roundtrip_large(self, a: List[int]) -> List[int]:
+        assert(a[16] == 0xffffffff)
+        return a
+
+    def roundtrip_strings(self, a: List[str]) -> List[str]:
+        assert(a[6] == '\U0001f600')
+        return a
+
+    def roundtrip_points(self, a: List[i.Point]) -> List[i.Point]:
+        return a
+
+    def roundtrip_option(self, a: Optional[List[int]]) -> Optional[List[int]]:
+        return a
+
+def run(wasm_file: str) -> None:
+    store = wasmtime.Store()
+    module = wasmtime.Module.from_file(store.engine, wasm_file)
+    linker = wasmtime.Linker(store.engine)
+    linker.define_wasi()
+    wasi = wasmtime.WasiConfig()
+    wasi.inherit_stdout()
+    wasi.inherit_stderr()
+    store.set_wasi(wasi)
+
+    imports = MyImports()
+    add_imports_to_linker(linker, store, imports)
+    wasm = Exports(store, linker, module)
+
+    wasm.test_imports(store)
+
+    assert(wasm.roundtrip_small(store, b'\x05\x06\x07\x08') == b'\x05\x06\x07\x08')
+
+    large = [x * 3 for x in range(17)]
+    assert(wasm.roundtrip_large(store, large) == large)
+
+    strings = ['', 'a', 'b', 'cd', 'efg', '\U0001f600', 'h', '', 'ij']
+    assert(wasm.roundtrip_strings(store, strings) == strings)
+
+    points = [e.Point(x * 2, -2147483648 + x) for x in range(9)]
+    assert(wasm.roundtrip_points(store, points) == points)
+
+    values = [0xffff] * 32
+    assert(wasm.roundtrip_option(store, values) == values)
+    assert(wasm.roundtrip_option(store, None) is None)
+
+    try:
+        wasm.roundtrip_large(store, [1, 2, 3])
+        raise RuntimeError('expected exception')
+    except ValueError:
+        pass
+
+if __name__ == '__main__':
+    run(sys.argv[1])
@@ -0,0 +1,81 @@
+use anyhow::Result;
+
+witx_bindgen_wasmtime::import!("./tests/runtime/arrays/imports.witx");
+
+use imports::*;
+
+#[derive(Default)]
+pub struct MyImports;
+
+impl Imports for MyImports {
+    fn roundtrip_small(&mut self, a: [u8; 4]) -> [u8; 4] {
+        assert_eq!(a, [1, 2, 3, 4]);
+        a
+    }
+
+    fn roundtrip_large(&mut self, a: [u32; 17]) -> [u32; 17] {
+        assert_eq!(a[16], u32::MAX);
+        a
+    }
+
+    fn roundtrip_strings(&mut self, a: [&str; 9]) -> [String; 9] {
+        assert_eq!(a[6], "\u{1f600}");
+        a.map(|s| s.to_string())
+    }
+
+    fn roundtrip_points(&mut self, a: [Point; 9]) -> [Point; 9] {
+        drop(format!("{:?}", a));
+        a
+    }
+
+    fn roundtrip_option(&mut self, a: Option<[u16; 32]>) -> Option<[u16; 32]> {
+        a
+    }
+}
+
+witx_bindgen_wasmtime::export!("./tests/runtime/arrays/exports.witx");
+
+fn run(wasm: &str) -> Result<()> {
+    use exports::*;
+
+    let (exports, mut store) = crate::instantiate(
+        wasm,
+        |linker| imports::add_imports_to_linker(linker, |cx| -> &mut MyImports { &mut cx.imports }),
+        |store, module, linker| Exports::instantiate(store, module, linker, |cx| &mut cx.exports),
+    )?;
+
+    exports.test_imports(&mut store)?;
+
+    assert_eq!(
+        exports.roundtrip_small(&mut store, [5, 6, 7, 8])?,
+        [5, 6, 7, 8]
+    );
+
+    let mut large = [0; 17];
+    for (i, x) in large.iter_mut().enumerate() {
+        *x = i as u32 * 3;
+    }
+    assert_eq!(exports.roundtrip_large(&mut store, large)?, large);
+
+    let strings = ["", "a", "b", "cd", "efg", "\u{1f600}", "h", "", "ij"];
+    assert_eq!(exports.roundtrip_strings(&mut store, strings)?, strings);
+
+    let mut points = [Point { x: 0, y: 0 }; 9];
+    for (i, p) in points.iter_mut().enumerate() {
+        p.x = i as u32 * 2;
+        p.y = i32::MIN + i as i32;
+    }
+    let result = exports.roundtrip_points(&mut store, points)?;
+    for (a, b) in points.iter().zip(result.iter()) {
+        assert_eq!(a.x, b.x);
+        assert_eq!(a.y, b.y);
+    }
+
+    let values = [u16::MAX; 32];
+    assert_eq!(
+        exports.roundtrip_option(&mut store, Some(values))?,
+        Some(values)
+    );
+    assert_eq!(exports.roundtrip_option(&mut store, None)?, None);
+    Ok(())
+}
@@ -0,0 +1,54 @@
+import { addImportsToImports, Imports } from "./imports.js";
+import { Exports } from "./exports.js";
+import { getWasm, addWasiToImports } from "./helpers.js";
+// @ts-ignore
+import * as assert from 'assert';
+
+async function run() {
+  const importObj = {};
+  const imports: Imports = {
+    roundtripSmall(a) {
+      assert.deepStrictEqual(a, new Uint8Array([1, 2, 3, 4]));
+      return a;
+    },
+    roundtripLarge(a) {
+      assert.strictEqual(a[16], 0xffffffff);
+      return a;
+    },
+    roundtripStrings(a) {
+      assert.strictEqual(a[6], '\u{1f600}');
+      return a;
+    },
+    roundtripPoints(a) { return a; },
+    roundtripOption(a) { return a; },
+  };
+  let instance: WebAssembly.Instance;
+  addImportsToImports(importObj, imports, name => instance.exports[name]);
+  const wasi = addWasiToImports(importObj);
+
+  const wasm = new Exports();
+  await wasm.instantiate(getWasm(), importObj);
+  wasi.start(wasm.instance);
+  instance = wasm.instance;
+
+  wasm.testImports();
+
+  assert.deepStrictEqual(wasm.roundtripSmall(new Uint8Array([5, 6, 7, 8])), new Uint8Array([5, 6, 7, 8]));
+
+  const large = new Uint32Array(17).map((_, i) => i * 3);
+  assert.deepStrictEqual(wasm.roundtripLarge(large), large);
+
+  const strings = ['', 'a', 'b', 'cd', 'efg', '\u{1f600}', 'h', '', 'ij'];
+  assert.deepStrictEqual(wasm.roundtripStrings(strings), strings);
+
+  const points = [...Array(9).keys()].map(i => ({ x: i * 2, y: -2147483648 + i }));
+  assert.deepStrictEqual(wasm.roundtripPoints(points), points);
+
+  const values = new Uint16Array(32).fill(0xffff);
+  assert.deepStrictEqual(wasm.roundtripOption(values), values);
+  assert.deepStrictEqual(wasm.roundtripOption(null), null);
+
+  assert.throws(() => wasm.roundtripLarge(new Uint32Array(3)), RangeError);
+}
+
+await run()
@@ -0,0 +1,88 @@
+#include <assert.h>
+#include <imports.h>
+#include <exports.h>
+#include <string.h>
+
+void exports_test_imports() {
+  {
+    imports_array4_u8_t a, b;
+    for (int i = 0; i < 4; i++)
+      a.data[i] = i + 1;
+    imports_roundtrip_small(&a, &b);
+    for (int i = 0; i < 4; i++)
+      assert(b.data[i] == i + 1);
+  }
+
+  {
+    imports_array17_u32_t a, b;
+    for (int i = 0; i < 17; i++)
+      a.data[i] = i;
+    a.data[16] = UINT32_MAX;
+    imports_roundtrip_large(&a, &b);
+    for (int i = 0; i < 16; i++)
+      assert(b.data[i] == i);
+    assert(b.data[16] == UINT32_MAX);
+  }
+
+  {
+    const char *strings[9] = {"a", "", "bc", "def", "x", "", "\xf0\x9f\x98\x80", "y", "z"};
+    imports_array9_string_t a, b;
+    for (int i = 0; i < 9; i++)
+      imports_string_set(&a.data[i], strings[i]);
+    imports_roundtrip_strings(&a, &b);
+    for (int i = 0; i < 9; i++) {
+      assert(b.data[i].len == strlen(strings[i]));
+      assert(memcmp(b.data[i].ptr, strings[i], b.data[i].len) == 0);
+    }
+    imports_array9_string_free(&b);
+  }
+
+  {
+    imports_array9_point_t a, b;
+    for (int i = 0; i < 9; i++) {
+      a.data[i].x = i;
+      a.data[i].y = -i;
+    }
+    imports_roundtrip_points(&a, &b);
+    for (int i = 0; i < 9; i++) {
+      assert(b.data[i].x == i);
+      assert(b.data[i].y == -i);
+    }
+  }
+
+  {
+    imports_option_array32_u16_t a;
+    imports_array32_u16_t b;
+    a.tag = true;
+    for (int i = 0; i < 32; i++)
+      a.val.data[i] = i * 1000;
+    assert(imports_roundtrip_option(&a, &b));
+    for (int i = 0; i < 32; i++)
+      assert(b.data[i] == i * 1000);
+
+    a.tag = false;
+    assert(!imports_roundtrip_option(&a, &b));
+  }
+}
+
+void exports_roundtrip_small(exports_array4_u8_t *a, exports_array4_u8_t *ret0) {
+  *ret0 = *a;
+}
+
+void exports_roundtrip_large(exports_array17_u32_t *a, exports_array17_u32_t *ret0) {
+  *ret0 = *a;
+}
+
+void exports_roundtrip_strings(exports_array9_string_t *a, exports_array9_string_t *ret0) {
+  *ret0 = *a;
+}
+
+void exports_roundtrip_points(exports_array9_point_t *a, exports_array9_point_t *ret0) {
+  *ret0 = *a;
+}
+
+bool exports_roundtrip_option(exports_option_array32_u16_t *a, exports_array32_u16_t *ret0) {
+  if (a->tag)
+    *ret0 = a->val;
+  return a->tag;
+}
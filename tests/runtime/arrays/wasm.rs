@@ -0,0 +1,61 @@
+witx_bindgen_rust::import!("./tests/runtime/arrays/imports.witx");
+witx_bindgen_rust::export!("./tests/runtime/arrays/exports.witx");
+
+use exports::*;
+
+struct Exports;
+
+impl exports::Exports for Exports {
+    fn test_imports() {
+        use imports::*;
+
+        assert_eq!(roundtrip_small([1, 2, 3, 4]), [1, 2, 3, 4]);
+
+        let mut large = [u32::MAX; 17];
+        for (i, x) in large.iter_mut().take(16).enumerate() {
+            *x = i as u32;
+        }
+        assert_eq!(roundtrip_large(large), large);
+
+        let strings = ["a", "", "bc", "def", "x", "", "\u{1f600}", "y", "z"];
+        assert_eq!(roundtrip_strings(strings), strings);
+
+        let mut points = [Point { x: 0, y: 0 }; 9];
+        for (i, p) in points.iter_mut().enumerate() {
+            p.x = i as u32;
+            p.y = -(i as i32);
+        }
+        let result = roundtrip_points(points);
+        for (a, b) in points.iter().zip(result.iter()) {
+            assert_eq!(a.x, b.x);
+            assert_eq!(a.y, b.y);
+        }
+
+        let mut values = [0; 32];
+        for (i, v) in values.iter_mut().enumerate() {
+            *v = i as u16 * 1000;
+        }
+        assert_eq!(roundtrip_option(Some(values)), Some(values));
+        assert_eq!(roundtrip_option(None), None);
+    }
+
+    fn roundtrip_small(a: [u8; 4]) -> [u8; 4] {
+        a
+    }
+
+    fn roundtrip_large(a: [u32; 17]) -> [u32; 17] {
+        a
+    }
+
+    fn roundtrip_strings(a: [String; 9]) -> [String; 9] {
+        a
+    }
+
+    fn roundtrip_points(a: [Point; 9]) -> [Point; 9] {
+        a
+    }
+
+    fn roundtrip_option(a: Option<[u16; 32]>) -> Option<[u16; 32]> {
+        a
+    }
+}
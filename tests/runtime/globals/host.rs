@@ -0,0 +1,42 @@
+use anyhow::Result;
+
+witx_bindgen_wasmtime::import!("./tests/runtime/globals/imports.witx");
+
+use imports::*;
+
+#[derive(Default)]
+pub struct MyImports;
+
+impl Imports for MyImports {
+    fn answer(&mut self) -> u32 {
+        42
+    }
+
+    fn greeting(&mut self) -> String {
+        "hello from the host".to_string()
+    }
+
+    fn origin(&mut self) -> Point {
+        Point { x: 1, y: -2 }
+    }
+}
+
+witx_bindgen_wasmtime::export!("./tests/runtime/globals/exports.witx");
+
+fn run(wasm: &str) -> Result<()> {
+    use exports::*;
+
+    let (exports, mut store) = crate::instantiate(
+        wasm,
+        |linker| imports::add_imports_to_linker(linker, |cx| -> &mut MyImports { &mut cx.imports }),
+        |store, module, linker| Exports::instantiate(store, module, linker, |cx| &mut cx.exports),
+    )?;
+
+    exports.test_imports(&mut store)?;
+    assert_eq!(exports.answer(&mut store)?, 7);
+    assert_eq!(exports.greeting(&mut store)?, "hello from wasm");
+    let p = exports.origin(&mut store)?;
+    assert_eq!(p.x, 3);
+    assert_eq!(p.y, -4);
+    Ok(())
+}
@@ -0,0 +1,29 @@
+import { addImportsToImports, Imports } from "./imports.js";
+import { Exports } from "./exports.js";
+import { getWasm, addWasiToImports } from "./helpers.js";
+// @ts-ignore
+import * as assert from 'assert';
+
+async function run() {
+  const importObj = {};
+  const imports: Imports = {
+    answer() { return 42; },
+    greeting() { return "hello from the host"; },
+    origin() { return { x: 1, y: -2 }; },
+  };
+  let instance: WebAssembly.Instance;
+  addImportsToImports(importObj, imports, name => instance.exports[name]);
+  const wasi = addWasiToImports(importObj);
+
+  const wasm = new Exports();
+  await wasm.instantiate(getWasm(), importObj);
+  wasi.start(wasm.instance);
+  instance = wasm.instance;
+
+  wasm.testImports();
+  assert.strictEqual(wasm.answer(), 7);
+  assert.strictEqual(wasm.greeting(), "hello from wasm");
+  assert.deepStrictEqual(wasm.origin(), { x: 3, y: -4 });
+}
+
+await run()
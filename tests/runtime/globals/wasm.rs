@@ -0,0 +1,30 @@
+witx_bindgen_rust::import!("./tests/runtime/globals/imports.witx");
+witx_bindgen_rust::export!("./tests/runtime/globals/exports.witx");
+
+use exports::*;
+
+struct Exports;
+
+impl exports::Exports for Exports {
+    fn test_imports() {
+        use imports::*;
+
+        assert_eq!(answer(), 42);
+        assert_eq!(greeting(), "hello from the host");
+        let p = origin();
+        assert_eq!(p.x, 1);
+        assert_eq!(p.y, -2);
+    }
+
+    fn answer() -> u32 {
+        7
+    }
+
+    fn greeting() -> String {
+        "hello from wasm".to_string()
+    }
+
+    fn origin() -> Point {
+        Point { x: 3, y: -4 }
+    }
+}
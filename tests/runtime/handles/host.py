@@ -171,10 +171,10 @@ def run(wasm_file: str) -> None:
     s.drop(store)
 
     md = e.Markdown.create(store, wasm)
-    if md:
-        md.append(store, "red is the best color")
-        assert(md.render(store) == "green is the best color")
-        md.drop(store)
+    assert(md is not None)
+    md.append(store, "red is the best color")
+    assert(md.render(store) == "green is the best color")
+    md.drop(store)
 
 if __name__ == '__main__':
     run(sys.argv[1])
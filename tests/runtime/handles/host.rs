@@ -152,5 +152,13 @@ fn run(wasm: &str) -> Result<()> {
     for s in exports.wasm_state2_result_list(&mut store)? {
         exports.drop_wasm_state2(&mut store, s)?;
     }
+
+    let md = exports.markdown_create(&mut store)?.unwrap();
+    exports.markdown_append(&mut store, &md, "red is the best color")?;
+    assert_eq!(
+        exports.markdown_render(&mut store, &md)?,
+        "green is the best color"
+    );
+    exports.drop_markdown(&mut store, md)?;
     Ok(())
 }
@@ -210,14 +210,47 @@ void exports_wasm_state2_result_list(exports_list_wasm_state2_t *ret0) {
   ret0->ptr[1] = exports_wasm_state2_new((void*) 888);
 }
 
+typedef struct {
+  char *buf;
+  size_t len;
+} markdown_t;
+
 bool exports_markdown_create(exports_markdown_t *md) {
-  return false;
+  markdown_t *m = malloc(sizeof(markdown_t));
+  m->buf = NULL;
+  m->len = 0;
+  *md = exports_markdown_new(m);
+  return true;
 }
 
 void exports_markdown_append(exports_markdown_t md, exports_string_t *s) {
-  abort();
+  markdown_t *m = exports_markdown_get(&md);
+  m->buf = realloc(m->buf, m->len + s->len);
+  memcpy(m->buf + m->len, s->ptr, s->len);
+  m->len += s->len;
+  exports_string_free(s);
+  exports_markdown_free(&md);
 }
 
 void exports_markdown_render(exports_markdown_t md, exports_string_t *ret) {
-  abort();
+  markdown_t *m = exports_markdown_get(&md);
+  // Every "red" becomes "green", which is 2 bytes longer.
+  ret->ptr = malloc(m->len / 3 * 5 + m->len % 3);
+  ret->len = 0;
+  for (size_t i = 0; i < m->len; ) {
+    if (m->len - i >= 3 && memcmp(m->buf + i, "red", 3) == 0) {
+      memcpy(ret->ptr + ret->len, "green", 5);
+      ret->len += 5;
+      i += 3;
+    } else {
+      ret->ptr[ret->len++] = m->buf[i++];
+    }
+  }
+  exports_markdown_free(&md);
+}
+
+void exports_markdown_dtor(void *data) {
+  markdown_t *m = data;
+  free(m->buf);
+  free(m);
 }
@@ -0,0 +1,11 @@
+// Same as `host.rs`, but with strings encoded as Latin-1 or UTF-16.
+witx_bindgen_wasmtime::import!({
+    paths: ["./tests/runtime/lists/imports.witx"],
+    string_encoding: "latin1+utf16",
+});
+witx_bindgen_wasmtime::export!({
+    paths: ["./tests/runtime/lists/exports.witx"],
+    string_encoding: "latin1+utf16",
+});
+
+include!("host-impl.rs");
@@ -0,0 +1,11 @@
+// Same as `host.rs`, but with results returned with wasm multi-value.
+witx_bindgen_wasmtime::import!({
+    paths: ["./tests/runtime/lists/imports.witx"],
+    multi_value: 16,
+});
+witx_bindgen_wasmtime::export!({
+    paths: ["./tests/runtime/lists/exports.witx"],
+    multi_value: 16,
+});
+
+include!("host-impl.rs");
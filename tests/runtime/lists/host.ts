@@ -123,6 +123,8 @@ async function run() {
   assert.deepStrictEqual(wasm.stringRoundtrip("x"), "x");
   assert.deepStrictEqual(wasm.stringRoundtrip(""), "");
   assert.deepStrictEqual(wasm.stringRoundtrip("hello ⚑ world"), "hello ⚑ world");
+  assert.deepStrictEqual(wasm.stringRoundtrip("caf\u{e9}"), "caf\u{e9}");
+  assert.deepStrictEqual(wasm.stringRoundtrip("\u{1f600}"), "\u{1f600}");
 
   // Ensure that we properly called `free` everywhere in all the glue that we
   // needed to.
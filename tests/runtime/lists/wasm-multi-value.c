@@ -0,0 +1,2 @@
+// Same as `wasm.c`, but compiled with wasm multi-value returns.
+#include "wasm.c"
@@ -0,0 +1,11 @@
+// Same as `wasm.rs`, but with results returned with wasm multi-value.
+witx_bindgen_rust::import!({
+    paths: ["./tests/runtime/lists/imports.witx"],
+    multi_value: 16,
+});
+witx_bindgen_rust::export!({
+    paths: ["./tests/runtime/lists/exports.witx"],
+    multi_value: 16,
+});
+
+include!("wasm-impl.rs");
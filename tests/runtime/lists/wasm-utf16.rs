@@ -0,0 +1,11 @@
+// Same as `wasm.rs`, but with strings encoded as UTF-16.
+witx_bindgen_rust::import!({
+    paths: ["./tests/runtime/lists/imports.witx"],
+    string_encoding: "utf16",
+});
+witx_bindgen_rust::export!({
+    paths: ["./tests/runtime/lists/exports.witx"],
+    string_encoding: "utf16",
+});
+
+include!("wasm-impl.rs");